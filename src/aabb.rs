@@ -1,4 +1,5 @@
 use crate::{
+    counters::{self, Test},
     interval::Interval,
     ray::Ray,
     vec3::{Point3, Vec3},
@@ -50,7 +51,14 @@ impl Aabb {
         self.2.grow(&other.2);
     }
 
-    pub fn hit(&self, ray: &Ray, mut time_interval: Interval) -> bool {
+    #[inline]
+    pub fn contains(&self, point: Point3) -> bool {
+        self.0.contains(point.x()) && self.1.contains(point.y()) && self.2.contains(point.z())
+    }
+
+    /// Distances at which `ray` enters and leaves the box within `time_interval`, if it crosses it.
+    pub fn hit(&self, ray: &Ray, mut time_interval: Interval) -> Option<(f64, f64)> {
+        counters::count(Test::Aabb);
         let origin = ray.origin();
         let direction = ray.direction();
 
@@ -76,11 +84,11 @@ impl Aabb {
             }
 
             if time_interval.end <= time_interval.start {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some((time_interval.start, time_interval.end))
     }
 
     pub fn x(&self) -> Interval {
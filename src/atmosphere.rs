@@ -0,0 +1,249 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord, Inscattered},
+    epsilon,
+    interval::Interval,
+    material::{Material, Reflected},
+    ray::{Ray, RayKind},
+    vec3::{Color, Point3, Vec3},
+};
+use std::f64::consts::PI;
+
+// Earth's radius in meters, which the default atmosphere is scaled from
+const EARTH_RADIUS: f64 = 6.36e6;
+// Earth's sea-level scattering coefficients per meter, for red, green and blue light
+const RAYLEIGH: [f64; 3] = [5.8e-6, 13.5e-6, 33.1e-6];
+const MIE: f64 = 21e-6;
+// share of the light a haze particle stops that it scatters rather than absorbs
+const MIE_ALBEDO: f64 = 0.9;
+// heights in meters over which the density of air and of haze falls by a factor of e
+const RAYLEIGH_SCALE_HEIGHT: f64 = 8000.0;
+const MIE_SCALE_HEIGHT: f64 = 1200.0;
+// height of the atmosphere's top in meters, above which it is thin enough to leave out
+const THICKNESS: f64 = 60000.0;
+// steps along the ray and from each step towards the sun
+const VIEW_STEPS: u32 = 16;
+const SUN_STEPS: u32 = 8;
+
+/// Sunlit air around a planet, shaded with single scattering: Rayleigh scattering off the air,
+/// which turns the sky blue and the limb and sunsets red, and Mie scattering off haze, which
+/// brightens the sky around the sun. Air and haze thin out exponentially with height. The
+/// defaults are Earth's atmosphere scaled to the planet's radius.
+///
+/// Rather than scattering rays off particles inside it, the atmosphere integrates the sunlight
+/// scattered towards a ray over the stretch it crosses, and is hit where that stretch ends,
+/// letting the ray carry on dimmed by the air it went through. Shadow rays pass through it, so
+/// the sunlight reaching the ground isn't reddened.
+#[derive(Debug, Clone)]
+pub struct Atmosphere {
+    center: Point3,
+    /// Radius of the planet's surface.
+    radius: f64,
+    /// Radius of the atmosphere's top.
+    top: f64,
+    /// Scattering coefficients at the surface, per scene unit.
+    rayleigh: Color,
+    mie: f64,
+    rayleigh_scale_height: f64,
+    mie_scale_height: f64,
+    /// Asymmetry of the haze's scattering; towards 1 it scatters forwards, into a glow around
+    /// the sun.
+    mie_anisotropy: f64,
+    /// Unit vector towards the sun.
+    sun_direction: Vec3,
+    sun_irradiance: Color,
+    bounding_box: Aabb,
+    material: PassThrough,
+}
+
+impl Atmosphere {
+    /// Atmosphere around a planet of `radius`, as dense as `density` times Earth's air, with
+    /// `haze` times Earth's haze.
+    pub fn new(
+        center: Point3,
+        radius: f64,
+        density: f64,
+        haze: f64,
+        mie_anisotropy: f64,
+        sun_direction: Vec3,
+        sun_irradiance: Color,
+    ) -> Self {
+        // meters of Earth per scene unit
+        let scale = EARTH_RADIUS / radius;
+        let top = radius + THICKNESS / scale;
+        let extent = Vec3::new(top, top, top);
+        Self {
+            center,
+            radius,
+            top,
+            rayleigh: density * scale * Color::from(RAYLEIGH),
+            mie: haze * scale * MIE,
+            rayleigh_scale_height: RAYLEIGH_SCALE_HEIGHT / scale,
+            mie_scale_height: MIE_SCALE_HEIGHT / scale,
+            mie_anisotropy,
+            sun_direction: sun_direction.unit(),
+            sun_irradiance,
+            bounding_box: Aabb::new_from_points(center - extent, center + extent),
+            material: PassThrough,
+        }
+    }
+
+    /// Distances along the unit vector `direction` from `origin` at which it crosses the
+    /// sphere of `radius`, nearest first.
+    fn crossings(&self, origin: Point3, direction: Vec3, radius: f64) -> Option<(f64, f64)> {
+        let oc = origin - self.center;
+        let half_b = oc.dot(direction);
+        let discriminant = half_b * half_b - (oc.length_sq() - radius * radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrt_d = discriminant.sqrt();
+        Some((-half_b - sqrt_d, -half_b + sqrt_d))
+    }
+
+    /// Air and haze densities relative to the surface's at `point`.
+    fn densities(&self, point: Point3) -> (f64, f64) {
+        let height = ((point - self.center).length() - self.radius).max(0.0);
+        (
+            (-height / self.rayleigh_scale_height).exp(),
+            (-height / self.mie_scale_height).exp(),
+        )
+    }
+
+    /// Air and haze in the way of sunlight reaching `point`, as lengths of surface-density
+    /// air and haze; `None` where the planet shades it.
+    fn sun_depths(&self, point: Point3) -> Option<(f64, f64)> {
+        if let Some((near, _)) = self.crossings(point, self.sun_direction, self.radius) {
+            if near > 0.0 {
+                return None;
+            }
+        }
+        let (_, far) = self.crossings(point, self.sun_direction, self.top)?;
+        let step = far.max(0.0) / SUN_STEPS as f64;
+        Some((0..SUN_STEPS).fold((0.0, 0.0), |(air, haze), index| {
+            let sample = point + (index as f64 + 0.5) * step * self.sun_direction;
+            let (air_density, haze_density) = self.densities(sample);
+            (air + air_density * step, haze + haze_density * step)
+        }))
+    }
+
+    fn extinction(&self, air: f64, haze: f64) -> Color {
+        let depth =
+            air * self.rayleigh + Color::new(1.0, 1.0, 1.0) * (haze * self.mie / MIE_ALBEDO);
+        Color::new((-depth.x()).exp(), (-depth.y()).exp(), (-depth.z()).exp())
+    }
+
+    /// Sunlight scattered towards the viewer along the unit vector `direction` between
+    /// distances `start` and `end` from `origin`, and the share of light from beyond that
+    /// makes it through.
+    fn integrate(&self, origin: Point3, direction: Vec3, start: f64, end: f64) -> Inscattered {
+        let step = (end - start) / VIEW_STEPS as f64;
+        // jittered so the steps average out rather than band
+        let jitter = fastrand::f64();
+        let (mut air, mut haze) = (0.0, 0.0);
+        let (mut air_light, mut haze_light) = (Color::default(), Color::default());
+        for index in 0..VIEW_STEPS {
+            let point = origin + (start + (index as f64 + jitter) * step) * direction;
+            let (air_density, haze_density) = self.densities(point);
+            let (air_step, haze_step) = (air_density * step, haze_density * step);
+            let (air_before, haze_before) = (air + 0.5 * air_step, haze + 0.5 * haze_step);
+            air += air_step;
+            haze += haze_step;
+            let Some((sun_air, sun_haze)) = self.sun_depths(point) else {
+                continue;
+            };
+            let transmittance = self.extinction(air_before + sun_air, haze_before + sun_haze);
+            air_light += air_step * transmittance;
+            haze_light += haze_step * transmittance;
+        }
+
+        let cos_theta = direction.dot(self.sun_direction);
+        let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
+        let g = self.mie_anisotropy;
+        // Cornette-Shanks phase function
+        let mie_phase = 3.0 / (8.0 * PI) * (1.0 - g * g) * (1.0 + cos_theta * cos_theta)
+            / ((2.0 + g * g) * (1.0 + g * g - 2.0 * g * cos_theta).powf(1.5));
+        Inscattered {
+            radiance: self.sun_irradiance
+                * (rayleigh_phase * self.rayleigh * air_light + mie_phase * self.mie * haze_light),
+            transmittance: self.extinction(air, haze),
+        }
+    }
+}
+
+impl Entity for Atmosphere {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        if ray.kind() == RayKind::Shadow {
+            return None;
+        }
+        let length = ray.direction().length();
+        let direction = *ray.direction() / length;
+        let origin = *ray.origin();
+        // distances along the ray, from where it enters the air to where it leaves it or
+        // meets the ground
+        let (enter, mut exit) = self.crossings(origin, direction, self.top)?;
+        if let Some((ground, _)) = self.crossings(origin, direction, self.radius) {
+            if ground > 0.0 {
+                exit = exit.min(ground);
+            }
+        }
+        let epsilon = epsilon::scene_epsilon();
+        let start = enter.max(time_interval.start * length);
+        // stopping short of the ground, so the ray carried on still finds it
+        let end = (exit - 2.0 * epsilon).min(time_interval.end * length);
+        // the ray carried on from a stretch's end starts too close to it to find another
+        if end - start <= 4.0 * epsilon {
+            return None;
+        }
+
+        let point = origin + end * direction;
+        let mut hit_record = HitRecord::new(
+            point,
+            ray,
+            -direction,
+            end / length,
+            0.0,
+            0.0,
+            &self.material,
+        );
+        hit_record.inscattered = Some(self.integrate(origin, direction, start, end));
+        Some(hit_record)
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
+
+/// Shades a hit with the light the entity found along the ray, sending the ray on unbent.
+#[derive(Debug, Clone, Copy)]
+struct PassThrough;
+
+impl Material for PassThrough {
+    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+        let inscattered = hit_record.inscattered?;
+        let scattered = Ray::new(
+            hit_record.hit_point,
+            *incoming.direction(),
+            *incoming.time(),
+        )
+        .with_media(incoming.media())
+        .with_kind(incoming.kind())
+        .with_differentials(incoming.differentials().copied());
+        Some(Reflected {
+            attenuation: inscattered.transmittance,
+            scattered,
+        })
+    }
+
+    fn emit(&self, hit_record: &HitRecord) -> Color {
+        hit_record
+            .inscattered
+            .map_or(Color::default(), |inscattered| inscattered.radiance)
+    }
+
+    fn is_opaque(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{
+    bvh::BVHNode,
+    camera::Camera,
+    entity::Entity,
+    output,
+    progress::{self, status},
+    ray::{Ray, RayKind},
+    vec3::{Color, Vec3},
+};
+use indicatif::ParallelProgressIterator;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{error::Error, sync::Arc, time::Instant};
+
+/// Renders the light arriving at one entity's surface into its UV space, producing a lightmap
+/// instead of a camera image.
+#[derive(Debug, Clone)]
+pub struct Bake {
+    pub entity: Arc<dyn Entity>,
+    pub width: u32,
+    pub height: u32,
+    pub samples: u16,
+    /// Bakes the side opposite the primitive's normal.
+    pub flip_normal: bool,
+}
+
+impl Bake {
+    /// Cosine-weighted average of the radiance reaching texel (x, y), i.e. what a white
+    /// diffuse surface there would reflect.
+    fn bake_texel(&self, camera: &Camera, world: &BVHNode, x: u32, y: u32) -> Color {
+        let mut sum = Color::default();
+        for _ in 0..self.samples {
+            let u = (x as f64 + fastrand::f64()) / self.width as f64;
+            let v = 1.0 - (y as f64 + fastrand::f64()) / self.height as f64;
+            let Some((point, mut normal)) = self.entity.surface_point(u, v) else {
+                continue;
+            };
+            if self.flip_normal {
+                normal = -normal;
+            }
+            let mut direction = normal + Vec3::random_unit_vector();
+            if direction.near_zero() {
+                direction = normal;
+            }
+            let ray = Ray::new(point, direction, fastrand::f64()).with_kind(RayKind::Diffuse);
+            sum += camera.incident_radiance(world, &ray);
+        }
+        sum / self.samples.max(1) as f64
+    }
+
+    pub fn render(
+        &self,
+        camera: &Camera,
+        world: &BVHNode,
+        scene_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let texel_count = self.width as u64 * self.height as u64;
+        let texels: Vec<Color> = (0..self.width * self.height)
+            .into_par_iter()
+            .progress_with(progress::progress_bar(texel_count))
+            .map(|index| self.bake_texel(camera, world, index % self.width, index / self.width))
+            .collect();
+
+        let png_path = output::result_path(scene_name, "_lightmap", "png")?;
+        let raw: Vec<u8> = texels.iter().copied().flat_map(Vec3::to_rgb8).collect();
+        output::write_png(&png_path, self.width, self.height, &raw)?;
+
+        let exr_path = output::result_path(scene_name, "_lightmap", "exr")?;
+        let raw: Vec<f32> = texels
+            .iter()
+            .flat_map(|c| [c.x() as f32, c.y() as f32, c.z() as f32])
+            .collect();
+        output::write_exr(&exr_path, self.width, self.height, raw)?;
+
+        status!("Finished");
+        status!("Bake Time: {:.3}s", start.elapsed().as_secs_f64());
+        status!("Lightmap: {png_path}");
+        status!("HDR Lightmap: {exr_path}");
+        status!("Resolution: {} x {}", self.width, self.height);
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BVHNode,
+    entity::{Entity, HitRecord},
+    epsilon,
+    interval::Interval,
+    material::Material,
+    memory::{self, Category},
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+use std::{error::Error, fs, mem::size_of, sync::Arc};
+
+// deepest a patch is split; 4^6 leaves is plenty for any patch worth modelling as one
+const MAX_DEPTH: u32 = 6;
+// how far a leaf's control points may stray from its corners' bilinear patch, relative to
+// the leaf's size, before it is split further
+const FLATNESS: f64 = 0.05;
+// Newton steps from a leaf's center before giving up on the ray
+const NEWTON_STEPS: u32 = 8;
+// distance between ray and surface taken as a hit, as a fraction of the scene epsilon
+const CONVERGENCE: f64 = 1e-3;
+// how far outside its own parameter range a leaf accepts a converged hit, so none are lost
+// at the seams between leaves
+const SEAM_MARGIN: f64 = 1e-3;
+
+/// Cubic Bernstein weights at `t` and their derivatives.
+fn bernstein(t: f64) -> ([f64; 4], [f64; 4]) {
+    let s = 1.0 - t;
+    (
+        [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t],
+        [
+            -3.0 * s * s,
+            3.0 * s * s - 6.0 * t * s,
+            6.0 * t * s - 3.0 * t * t,
+            3.0 * t * t,
+        ],
+    )
+}
+
+/// Splits a cubic's control points at its midpoint, de Casteljau style.
+fn split_curve([p0, p1, p2, p3]: [Point3; 4]) -> ([Point3; 4], [Point3; 4]) {
+    let mid = |a: Point3, b: Point3| 0.5 * (a + b);
+    let (p01, p12, p23) = (mid(p0, p1), mid(p1, p2), mid(p2, p3));
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let center = mid(p012, p123);
+    ([p0, p01, p012, center], [center, p123, p23, p3])
+}
+
+/// Control points of a bicubic patch, row by row: `u` runs along a row and `v` down the rows.
+type ControlPoints = [Point3; 16];
+
+fn split_u(points: &ControlPoints) -> (ControlPoints, ControlPoints) {
+    let (mut low, mut high) = (*points, *points);
+    for row in 0..4 {
+        let curve = [0, 1, 2, 3].map(|column| points[4 * row + column]);
+        let (a, b) = split_curve(curve);
+        low[4 * row..4 * row + 4].copy_from_slice(&a);
+        high[4 * row..4 * row + 4].copy_from_slice(&b);
+    }
+    (low, high)
+}
+
+fn split_v(points: &ControlPoints) -> (ControlPoints, ControlPoints) {
+    let (mut low, mut high) = (*points, *points);
+    for column in 0..4 {
+        let curve = [0, 1, 2, 3].map(|row| points[4 * row + column]);
+        let (a, b) = split_curve(curve);
+        for row in 0..4 {
+            low[4 * row + column] = a[row];
+            high[4 * row + column] = b[row];
+        }
+    }
+    (low, high)
+}
+
+/// Box around the control points, which by the convex hull property holds the surface too.
+fn hull(points: &ControlPoints) -> Aabb {
+    points
+        .iter()
+        .fold(Aabb::new_from_points(points[0], points[0]), |b_box, &p| {
+            Aabb::enclose(&b_box, &Aabb::new_from_points(p, p))
+        })
+}
+
+/// Largest distance of a control point from the bilinear patch through the corners, as a
+/// fraction of the points' extent.
+fn deviation(points: &ControlPoints) -> f64 {
+    let [c00, c10, c01, c11] = [points[0], points[3], points[12], points[15]];
+    let b_box = hull(points);
+    let size = Vec3::new(b_box.x().size(), b_box.y().size(), b_box.z().size()).length();
+    let worst = (0..16)
+        .map(|i| {
+            let (u, v) = ((i % 4) as f64 / 3.0, (i / 4) as f64 / 3.0);
+            let bilinear =
+                (1.0 - v) * ((1.0 - u) * c00 + u * c10) + v * ((1.0 - u) * c01 + u * c11);
+            (points[i] - bilinear).length()
+        })
+        .fold(0.0, f64::max);
+    if size > 0.0 {
+        worst / size
+    } else {
+        0.0
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    points: ControlPoints,
+    material: Arc<dyn Material>,
+}
+
+impl Shared {
+    /// Point on the patch and its derivatives along `u` and `v`.
+    fn evaluate(&self, u: f64, v: f64) -> (Point3, Vec3, Vec3) {
+        let (bu, du) = bernstein(u);
+        let (bv, dv) = bernstein(v);
+        let mut point = Point3::default();
+        let (mut dp_du, mut dp_dv) = (Vec3::default(), Vec3::default());
+        for row in 0..4 {
+            for column in 0..4 {
+                let p = self.points[4 * row + column];
+                point += bu[column] * bv[row] * p;
+                dp_du += du[column] * bv[row] * p;
+                dp_dv += bu[column] * dv[row] * p;
+            }
+        }
+        (point, dp_du, dp_dv)
+    }
+}
+
+/// Piece of a patch flat enough for Newton's method to converge from its center.
+#[derive(Debug)]
+struct Leaf {
+    patch: Arc<Shared>,
+    u: Interval,
+    v: Interval,
+    bounding_box: Aabb,
+}
+
+impl Entity for Leaf {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let (enter, exit) = self.bounding_box.hit(ray, time_interval)?;
+        let direction = *ray.direction();
+        let (mut u, mut v) = (
+            0.5 * (self.u.start + self.u.end),
+            0.5 * (self.v.start + self.v.end),
+        );
+        let mut time = 0.5 * (enter + exit);
+        let tolerance = CONVERGENCE * epsilon::scene_epsilon();
+
+        for _ in 0..NEWTON_STEPS {
+            let (point, dp_du, dp_dv) = self.patch.evaluate(u, v);
+            let residual = ray.at(time) - point;
+            if residual.length() < tolerance {
+                let margin_u = SEAM_MARGIN * self.u.size();
+                let margin_v = SEAM_MARGIN * self.v.size();
+                let inside = u >= self.u.start - margin_u
+                    && u <= self.u.end + margin_u
+                    && v >= self.v.start - margin_v
+                    && v <= self.v.end + margin_v;
+                if !inside || !time_interval.surrounds(time) {
+                    return None;
+                }
+                let mut normal = dp_du.cross(dp_dv);
+                if normal.near_zero() {
+                    // collapsed edge, like the teapot's lid tip; nudge inward for a direction
+                    let (_, du, dv) = self
+                        .patch
+                        .evaluate(u.clamp(0.01, 0.99), v.clamp(0.01, 0.99));
+                    normal = du.cross(dv);
+                }
+                let mut hit_record =
+                    HitRecord::new(point, ray, normal.unit(), time, u, v, &*self.patch.material);
+                hit_record.set_tangent(dp_du);
+                hit_record.dp_du = dp_du;
+                hit_record.dp_dv = dp_dv;
+                return Some(hit_record);
+            }
+            // solve dp_du·Δu + dp_dv·Δv − direction·Δt = residual by Cramer's rule
+            let determinant = dp_du.dot(dp_dv.cross(-direction));
+            if determinant.abs() < 1e-300 {
+                return None;
+            }
+            u += residual.dot(dp_dv.cross(-direction)) / determinant;
+            v += dp_du.dot(residual.cross(-direction)) / determinant;
+            time += dp_du.dot(dp_dv.cross(residual)) / determinant;
+            u = u.clamp(0.0, 1.0);
+            v = v.clamp(0.0, 1.0);
+        }
+        None
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
+
+/// Bicubic Bézier patch, intersected directly rather than tessellated: it is split into
+/// nearly flat pieces under a BVH, and a ray reaching a piece refines its hit on the true
+/// surface with Newton's method.
+#[derive(Debug)]
+pub struct BezierPatch {
+    bvh: BVHNode,
+}
+
+impl BezierPatch {
+    pub fn new(points: ControlPoints, material: Arc<dyn Material>) -> Self {
+        let patch = Arc::new(Shared { points, material });
+        let mut leaves = Vec::new();
+        Self::subdivide(
+            &patch,
+            &points,
+            Interval::new(0.0, 1.0),
+            Interval::new(0.0, 1.0),
+            0,
+            &mut leaves,
+        );
+        memory::track(Category::Meshes, leaves.len() * size_of::<Leaf>());
+        let bvh = BVHNode::new(&mut leaves);
+        Self { bvh }
+    }
+
+    fn subdivide(
+        patch: &Arc<Shared>,
+        points: &ControlPoints,
+        u: Interval,
+        v: Interval,
+        depth: u32,
+        leaves: &mut Vec<Arc<dyn Entity>>,
+    ) {
+        if depth == MAX_DEPTH || deviation(points) < FLATNESS {
+            leaves.push(Arc::new(Leaf {
+                patch: patch.clone(),
+                u,
+                v,
+                bounding_box: hull(points),
+            }));
+            return;
+        }
+        let (mid_u, mid_v) = (0.5 * (u.start + u.end), 0.5 * (v.start + v.end));
+        let (low_u, high_u) = split_u(points);
+        for (half, u) in [
+            (low_u, Interval::new(u.start, mid_u)),
+            (high_u, Interval::new(mid_u, u.end)),
+        ] {
+            let (low_v, high_v) = split_v(&half);
+            Self::subdivide(
+                patch,
+                &low_v,
+                u,
+                Interval::new(v.start, mid_v),
+                depth + 1,
+                leaves,
+            );
+            Self::subdivide(
+                patch,
+                &high_v,
+                u,
+                Interval::new(mid_v, v.end),
+                depth + 1,
+                leaves,
+            );
+        }
+    }
+}
+
+impl Entity for BezierPatch {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, time_interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+/// Reads patches in the plain text `.bpt` format the Utah teapot is usually shared in: the
+/// patch count, then per patch its degrees ("3 3") and its 16 control points, one per line.
+pub fn load(path: &str) -> Result<Vec<ControlPoints>, Box<dyn Error>> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read '{}' : {}", path, e))?;
+    let mut numbers = text.split_whitespace().map(|field| {
+        field
+            .parse::<f64>()
+            .map_err(|_| format!("'{path}' : Invalid number '{field}'"))
+    });
+    let mut next = || {
+        numbers
+            .next()
+            .unwrap_or_else(|| Err(format!("'{path}' ends early")))
+    };
+    let count = next()? as usize;
+    let mut patches = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (degree_u, degree_v) = (next()?, next()?);
+        if (degree_u, degree_v) != (3.0, 3.0) {
+            return Err(format!("'{path}' : Only bicubic patches are supported").into());
+        }
+        let mut points = [Point3::default(); 16];
+        for point in &mut points {
+            *point = Point3::new(next()?, next()?, next()?);
+        }
+        patches.push(points);
+    }
+    if patches.is_empty() {
+        return Err(format!("'{path}' has no patches").into());
+    }
+    Ok(patches)
+}
@@ -0,0 +1,81 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    memory::{self, Category},
+    quad::Quad,
+    ray::Ray,
+    vec3::Color,
+};
+use std::error::Error;
+
+/// Opacity of each texel of an image, read from its alpha channel.
+#[derive(Debug, Clone)]
+pub struct AlphaMask {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    /// Mean opacity over the image.
+    coverage: f64,
+}
+
+impl AlphaMask {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let image = image::open(path).map_err(|e| format!("Failed to open '{path}': {e}"))?;
+        if !image.color().has_alpha() {
+            return Err(format!("Alpha mask '{path}' has no alpha channel").into());
+        }
+        let image = image.to_luma_alpha8();
+        let (width, height) = image.dimensions();
+        let data: Vec<u8> = image.pixels().map(|pixel| pixel[1]).collect();
+        memory::track(Category::Textures, data.len());
+        let coverage = data.iter().map(|&alpha| alpha as f64).sum::<f64>()
+            / (255.0 * data.len().max(1) as f64);
+        Ok(Self {
+            data,
+            width,
+            height,
+            coverage,
+        })
+    }
+
+    /// Opacity of the texel at (u, v), with v running up the image like image textures.
+    pub fn opacity(&self, u: f64, v: f64) -> f64 {
+        let x = ((u * self.width as f64) as usize).min(self.width as usize - 1);
+        let y = (((1.0 - v) * self.height as f64) as usize).min(self.height as usize - 1);
+        self.data[x + y * self.width as usize] as f64 / 255.0
+    }
+}
+
+/// Quad cut out by an alpha mask, such as a card of leaves or grass. Rays pass through each
+/// texel with the chance it is transparent, so partly transparent edges average out to soft
+/// ones and the cutout casts a matching shadow.
+#[derive(Debug, Clone)]
+pub struct Billboard {
+    quad: Quad,
+    mask: AlphaMask,
+}
+
+impl Billboard {
+    pub fn new(quad: Quad, mask: AlphaMask) -> Self {
+        Self { quad, mask }
+    }
+}
+
+impl Entity for Billboard {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let hit_record = self.quad.hit(ray, time_interval)?;
+        let opacity = self.mask.opacity(hit_record.u, hit_record.v);
+        // the flat card has no other crossing to fall back to
+        (fastrand::f64() < opacity).then_some(hit_record)
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.quad.bounding_box()
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.mask.coverage * self.quad.emitted_power()
+    }
+}
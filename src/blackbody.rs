@@ -0,0 +1,52 @@
+use crate::vec3::Color;
+
+// speed of light (m/s), Planck's constant (J s) and Boltzmann's constant (J/K)
+const C: f64 = 299_792_458.0;
+const H: f64 = 6.626_070_15e-34;
+const K: f64 = 1.380_649e-23;
+
+/// Spectral radiance of a blackbody at `kelvin` for a wavelength in nanometers, up to a constant.
+fn planck(wavelength: f64, kelvin: f64) -> f64 {
+    let lambda = wavelength * 1e-9;
+    1.0 / (lambda.powi(5) * ((H * C / (lambda * K * kelvin)).exp() - 1.0))
+}
+
+fn lobe(x: f64, mean: f64, low: f64, high: f64) -> f64 {
+    let t = (x - mean) * if x < mean { low } else { high };
+    (-0.5 * t * t).exp()
+}
+
+/// CIE 1931 color matching functions, using the multi-lobe Gaussian fit of Wyman, Sloan and
+/// Shirley (2013).
+fn color_matching(wavelength: f64) -> (f64, f64, f64) {
+    let x = 1.056 * lobe(wavelength, 599.8, 0.0264, 0.0323)
+        + 0.362 * lobe(wavelength, 442.0, 0.0624, 0.0374)
+        - 0.065 * lobe(wavelength, 501.1, 0.0490, 0.0382);
+    let y = 0.821 * lobe(wavelength, 568.8, 0.0213, 0.0247)
+        + 0.286 * lobe(wavelength, 530.9, 0.0613, 0.0322);
+    let z = 1.217 * lobe(wavelength, 437.0, 0.0845, 0.0278)
+        + 0.681 * lobe(wavelength, 459.0, 0.0385, 0.0725);
+    (x, y, z)
+}
+
+/// Linear sRGB color of a blackbody radiator at `kelvin`, scaled to unit luminance.
+pub fn blackbody(kelvin: f64) -> Color {
+    let kelvin = kelvin.max(1.0);
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+    for wavelength in (380..=780).step_by(5) {
+        let wavelength = wavelength as f64;
+        let radiance = planck(wavelength, kelvin);
+        let (cx, cy, cz) = color_matching(wavelength);
+        x += radiance * cx;
+        y += radiance * cy;
+        z += radiance * cz;
+    }
+    let (x, y, z) = (x / y, 1.0, z / y);
+
+    // XYZ to linear sRGB; colors outside the gamut are clipped
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    let color = Color::new(r.max(0.0), g.max(0.0), b.max(0.0));
+    color / color.luminance()
+}
@@ -0,0 +1,51 @@
+use std::error::Error;
+
+/// Aperture shape read from a grayscale image, so out-of-focus highlights take its shape
+/// instead of a disk's.
+#[derive(Debug, Clone)]
+pub struct Aperture {
+    width: u32,
+    height: u32,
+    /// Running total of pixel brightness, normalized to end at 1.
+    cdf: Vec<f64>,
+}
+
+impl Aperture {
+    /// Loads the shape at `image_path`, stretched over the square bounding the lens disk;
+    /// brighter pixels let through more light.
+    pub fn load(image_path: &str) -> Result<Self, Box<dyn Error>> {
+        let img = image::open(image_path)
+            .map_err(|e| format!("Failed to open bokeh image {image_path}: {e}"))?
+            .to_luma32f();
+        let (width, height) = img.dimensions();
+        let mut total = 0.0;
+        let mut cdf: Vec<f64> = img
+            .into_raw()
+            .into_iter()
+            .map(|value| {
+                total += value.max(0.0) as f64;
+                total
+            })
+            .collect();
+        if total <= 0.0 {
+            return Err(format!("Bokeh image {image_path} is completely black").into());
+        }
+        cdf.iter_mut().for_each(|value| *value /= total);
+        Ok(Self { width, height, cdf })
+    }
+
+    /// Point on the aperture in [-1, 1]², drawn in proportion to the image's brightness.
+    pub fn sample(&self) -> (f64, f64) {
+        let pick = fastrand::f64();
+        let index = self
+            .cdf
+            .partition_point(|&value| value <= pick)
+            .min(self.cdf.len() - 1);
+        let (px, py) = (index as u32 % self.width, index as u32 / self.width);
+        let x = (px as f64 + fastrand::f64()) / self.width as f64;
+        let y = (py as f64 + fastrand::f64()) / self.height as f64;
+        // a lens offset shows up mirrored in highlights behind the focal plane, so the shape is
+        // flipped both ways to have those appear the way the image looks
+        (1.0 - 2.0 * x, 2.0 * y - 1.0)
+    }
+}
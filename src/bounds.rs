@@ -0,0 +1,80 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+};
+use std::sync::Arc;
+
+/// Debug outline of a bounding box: lines along its twelve edges, with the faces between them
+/// left open so the box's contents and far edges show through.
+#[derive(Debug, Clone)]
+pub struct BoxOutline {
+    bounding_box: Aabb,
+    /// Line width in pixels.
+    width: f64,
+    /// Fraction of the rays crossing a line that stop at it; the rest pass on, which averages
+    /// out to a translucent line.
+    opacity: f64,
+    material: Arc<dyn Material>,
+}
+
+impl BoxOutline {
+    pub fn new(bounding_box: Aabb, width: f64, opacity: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            bounding_box,
+            width,
+            opacity,
+            material,
+        }
+    }
+
+    /// Distance from a point on the box's surface to its nearest edge.
+    fn edge_distance(&self, hit_record: &HitRecord) -> f64 {
+        let mut distances = [0, 1, 2].map(|axis| {
+            let interval = self.bounding_box[axis];
+            let coordinate = hit_record.hit_point[axis];
+            (coordinate - interval.start)
+                .min(interval.end - coordinate)
+                .max(0.0)
+        });
+        distances.sort_by(f64::total_cmp);
+        // the smallest is across the face the point lies on
+        distances[1]
+    }
+}
+
+impl Entity for BoxOutline {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        // the unclipped crossings, so a ray starting inside still finds the faces around it
+        let (enter, exit) = self
+            .bounding_box
+            .hit(ray, Interval::new(f64::NEG_INFINITY, f64::INFINITY))?;
+        [enter, exit]
+            .into_iter()
+            .filter(|&time| time_interval.surrounds(time))
+            .find_map(|time| {
+                let mut hit_record = HitRecord::new(
+                    ray.at(time),
+                    ray,
+                    -*ray.direction(),
+                    time,
+                    0.0,
+                    0.0,
+                    &*self.material,
+                );
+                hit_record.apply_differentials(ray);
+                let pixel = hit_record.dp_dx.length().max(hit_record.dp_dy.length());
+                hit_record.edge_distance = self.edge_distance(&hit_record);
+                (hit_record.edge_distance <= 0.5 * self.width * pixel
+                    && fastrand::f64() < self.opacity)
+                    .then_some(hit_record)
+            })
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
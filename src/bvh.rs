@@ -1,12 +1,18 @@
 use crate::{
     aabb::Aabb,
     entity::{Entity, HitRecord},
+    epsilon,
     interval::Interval,
+    memory::{self, Category},
     ray::Ray,
+    vec3::{Color, Point3},
 };
 use std::sync::Arc;
 
-#[derive(Debug)]
+/// See-through surfaces a shadow ray crosses before it is given up as blocked.
+const MAX_SHADOW_CROSSINGS: u32 = 16;
+
+#[derive(Debug, Clone)]
 pub struct BVHNode {
     bounding_box: Aabb,
     left: Arc<dyn Entity>,
@@ -14,19 +20,15 @@ pub struct BVHNode {
 }
 
 impl BVHNode {
+    /// Builds the hierarchy by halving `entities` at the median centroid along the axis where
+    /// their centroids spread the most.
     pub fn new(entities: &mut [Arc<dyn Entity>]) -> Self {
-        let axis = fastrand::u8(0..=2);
         let entity_span = entities.len();
         let (left, right) = match entity_span {
             1 => (entities[0].clone(), entities[0].clone()),
             2 => (entities[0].clone(), entities[1].clone()),
             _ => {
-                entities.sort_by(|a, b| {
-                    let x = a.bounding_box()[axis].start;
-                    let y = b.bounding_box()[axis].start;
-                    x.partial_cmp(&y).unwrap()
-                });
-                let mid = entity_span / 2;
+                let mid = Self::partition(entities);
                 let left = Arc::new(BVHNode::new(&mut entities[..mid])) as Arc<dyn Entity>;
                 let right = Arc::new(BVHNode::new(&mut entities[mid..])) as Arc<dyn Entity>;
                 (left, right)
@@ -34,6 +36,7 @@ impl BVHNode {
         };
 
         let bounding_box = Aabb::enclose(&left.bounding_box(), &right.bounding_box());
+        memory::track(Category::Bvh, std::mem::size_of::<Self>());
 
         Self {
             bounding_box,
@@ -41,26 +44,147 @@ impl BVHNode {
             right,
         }
     }
+
+    /// Share of the light along the shadow `ray` over `time_interval` that gets through: zero
+    /// once an opaque surface is in the way, and dimmed by each surface that lets shadow rays
+    /// pass, like tinted glass.
+    pub fn transmittance(&self, ray: &Ray, time_interval: Interval) -> Color {
+        let mut transmittance = Color::new(1.0, 1.0, 1.0);
+        let mut start = time_interval.start;
+        let offset = epsilon::ray_interval(ray).start;
+        for _ in 0..MAX_SHADOW_CROSSINGS {
+            let Some(hit_record) = self.hit(ray, Interval::new(start, time_interval.end)) else {
+                return transmittance;
+            };
+            let Some(crossing) = hit_record.material.shadow_transmittance(ray, &hit_record) else {
+                return Color::default();
+            };
+            transmittance *= crossing;
+            if transmittance.near_zero() {
+                return Color::default();
+            }
+            start = hit_record.time + offset;
+        }
+        Color::default()
+    }
+
+    /// Bounding boxes of the nodes `new` builds over `entities`, from the root down to `depth`
+    /// levels below it.
+    pub fn node_bounds(entities: &mut [Arc<dyn Entity>], depth: u32) -> Vec<Aabb> {
+        let bounding_box = entities
+            .iter()
+            .map(|entity| entity.bounding_box())
+            .reduce(|a, b| Aabb::enclose(&a, &b));
+        let Some(bounding_box) = bounding_box else {
+            return Vec::new();
+        };
+        let mut bounds = vec![bounding_box];
+        if depth > 0 && entities.len() > 2 {
+            let mid = Self::partition(entities);
+            bounds.extend(Self::node_bounds(&mut entities[..mid], depth - 1));
+            bounds.extend(Self::node_bounds(&mut entities[mid..], depth - 1));
+        }
+        bounds
+    }
+
+    /// Moves the entities with the lower centroids along the split axis to the front half and
+    /// returns where the back half starts.
+    fn partition(entities: &mut [Arc<dyn Entity>]) -> usize {
+        let mid = entities.len() / 2;
+        // coincident centroids leave no axis to sort along; any halving is as good
+        if let Some(axis) = Self::split_axis(entities) {
+            entities.select_nth_unstable_by(mid, |a, b| {
+                centroid(&a.bounding_box(), axis).total_cmp(&centroid(&b.bounding_box(), axis))
+            });
+        }
+        mid
+    }
+
+    /// Longest axis of the bounds of the entities' centroids, `None` if they all coincide.
+    fn split_axis(entities: &[Arc<dyn Entity>]) -> Option<u8> {
+        let mut extents = [(f64::INFINITY, f64::NEG_INFINITY); 3];
+        for entity in entities {
+            let bounding_box = entity.bounding_box();
+            for (axis, (low, high)) in (0..3).zip(extents.iter_mut()) {
+                let c = centroid(&bounding_box, axis);
+                *low = low.min(c);
+                *high = high.max(c);
+            }
+        }
+        let (axis, size) = (0..3)
+            .zip(extents.map(|(low, high)| high - low))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        (size > 0.0).then_some(axis)
+    }
+}
+
+#[inline]
+fn centroid(bounding_box: &Aabb, axis: u8) -> f64 {
+    let interval = bounding_box[axis];
+    0.5 * (interval.start + interval.end)
 }
 
 impl Entity for BVHNode {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
-        if !self.bounding_box.hit(ray, time_interval) {
-            return None;
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        self.bounding_box.hit(ray, time_interval)?;
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return self.left.hit(ray, time_interval);
+        }
+
+        // visit the child the ray enters first, then the other only if it starts before the best hit
+        let enter = |child: &Arc<dyn Entity>| {
+            child
+                .bounding_box()
+                .hit(ray, time_interval)
+                .map(|(t_enter, _)| t_enter)
+        };
+        let mut children = [
+            (enter(&self.left), &self.left),
+            (enter(&self.right), &self.right),
+        ];
+        if children[1].0.unwrap_or(f64::INFINITY) < children[0].0.unwrap_or(f64::INFINITY) {
+            children.swap(0, 1);
         }
-        let hit_left = self.left.hit(ray, time_interval);
-        let hit_right = self.right.hit(ray, time_interval);
-
-        match (hit_left, hit_right) {
-            (Some(hl), Some(hr)) => Some(if hl.time < hr.time { hl } else { hr }),
-            (Some(hl), None) => Some(hl),
-            (None, Some(hr)) => Some(hr),
-            (None, None) => None,
+
+        let mut closest = None;
+        let mut interval = time_interval;
+        for (t_enter, child) in children {
+            let Some(t_enter) = t_enter else {
+                continue;
+            };
+            if t_enter > interval.end {
+                break;
+            }
+            if let Some(hit_record) = child.hit(ray, interval) {
+                interval.end = hit_record.time;
+                closest = Some(hit_record);
+            }
         }
+        closest
     }
 
     #[inline]
     fn bounding_box(&self) -> Aabb {
         self.bounding_box
     }
+
+    fn emitted_power(&self) -> Color {
+        // single-entity leaves store the same entity on both sides
+        if Arc::ptr_eq(&self.left, &self.right) {
+            self.left.emitted_power()
+        } else {
+            self.left.emitted_power() + self.right.emitted_power()
+        }
+    }
+
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        if !self.bounding_box.contains(point) {
+            return 0.0;
+        }
+        if Arc::ptr_eq(&self.left, &self.right) {
+            self.left.density(point, time)
+        } else {
+            self.left.density(point, time) + self.right.density(point, time)
+        }
+    }
 }
@@ -1,36 +1,187 @@
 use crate::{
+    bokeh::Aperture,
     bvh::BVHNode,
-    entity::Entity,
+    clip::Clipping,
+    counters,
+    entity::{Entity, HitRecord},
+    environment::Environment,
+    epsilon,
+    film::{Film, PixelAccumulator},
+    filter::PixelFilter,
+    focus_map::FocusMap,
+    guide::PathGuide,
+    integrator::Integrator,
     interval::Interval,
-    ray::Ray,
+    irradiance_cache::IrradianceCache,
+    lens::Lens,
+    light_sampler::LightSampler,
+    mat3::Mat3,
+    material::{Material, Reflected},
+    output::{self, BitDepth, Output},
+    progress::{self, status},
+    ray::{Ray, RayDifferentials, RayKind},
+    restir::Restir,
+    starfield::Starfield,
+    sun::Sun,
+    tile::{Tile, TileSink, Tiling},
+    toon::Toon,
     vec3::{Color, Point3, Vec3},
+    wavefront,
+    wireframe::{Wireframe, WireframeMode},
+};
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
 };
-use image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder};
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::{
     error::Error,
-    fs::{create_dir_all, File},
-    io::BufWriter,
-    path::Path,
-    time::Instant,
+    f64::consts::FRAC_1_PI,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Renderer {
+    #[default]
+    Recursive,
+    Wavefront,
+}
+
+/// Limits that can stop sampling before `samples_per_pixel` is reached.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Budget {
+    /// Time for the whole render, shared between the eyes of a stereo pair.
+    pub max_time: Option<Duration>,
+    pub max_samples: Option<u16>,
+}
+
+/// Bookkeeping carried along a path traced by `Camera::path_color`.
+#[derive(Debug, Clone, Copy)]
+struct PathState {
+    /// Bounces left before the path is cut off.
+    depth: u16,
+    /// Index of the surface interaction the current ray leads to, starting at 1.
+    interaction: u16,
+    /// Set for camera rays, whose first hit can reuse light samples across pixels.
+    pixel: Option<usize>,
+    /// Cleared after a hit whose direct light was already sampled, so the sampled lights'
+    /// emission isn't counted twice.
+    emission: bool,
+    /// Whether a material has already granted this path extra bounces.
+    extended: bool,
+    /// Linked lights the surface the ray left doesn't receive, whose emission is skipped.
+    unlinked: u64,
+    /// Product of the attenuations and roulette weights so far, which russian roulette bases
+    /// the path's survival on.
+    throughput: Color,
+}
+
+impl PathState {
+    /// State of the ray scattered off `material`; extra depth is granted once per path.
+    fn next(self, material: &dyn Material, emission: bool) -> Self {
+        let extra = if self.extended {
+            0
+        } else {
+            material.extra_depth()
+        };
+        Self {
+            depth: (self.depth - 1).saturating_add(extra),
+            interaction: self.interaction + 1,
+            pixel: None,
+            emission,
+            extended: self.extended || extra > 0,
+            unlinked: material.unlinked_lights(),
+            throughput: self.throughput,
+        }
+    }
+}
+
+/// Seeds each pixel's random stream from its position, so a frame renders the same way every
+/// time and neighboring frames of a sequence get related noise.
+#[derive(Debug, Clone, Copy)]
+pub struct Seeding {
+    pub seed: u64,
+    /// Index of the frame in a sequence.
+    pub frame: u32,
+    /// Whether each frame draws fresh noise; without it every frame repeats one noise pattern,
+    /// which doesn't flicker but looks like dirt stuck to the lens.
+    pub decorrelate_frames: bool,
+}
+
+impl Seeding {
+    fn pixel_seed(&self, pixel: usize, first_sample: u16) -> u64 {
+        let frame = if self.decorrelate_frames {
+            self.frame as u64
+        } else {
+            0
+        };
+        [pixel as u64, first_sample as u64, frame]
+            .into_iter()
+            .fold(self.seed, |state, value| splitmix(state ^ value))
+    }
+}
+
+/// SplitMix64 finalizer, scrambling the bits of `value`.
+pub fn splitmix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// What camera rays that miss the scene see in place of the background, which still lights
+/// the scene and shows in reflections, for compositing a render over a plate.
+#[derive(Debug, Clone, Copy)]
+pub enum Backplate {
+    Color(Color),
+    /// Nothing, leaving those pixels see-through in the image's alpha channel.
+    Transparent,
+}
+
 #[derive(Debug, Clone)]
 pub struct Camera {
     image_width: u32,
     image_height: u32,
     samples_per_pixel: u16,
     max_depth: u16,
-    pixel_sample_scale: f64,
     center: Point3,
     background: Color,
+    environment: Option<Arc<Environment>>,
+    starfield: Option<Arc<Starfield>>,
+    sun: Option<Sun>,
+    backplate: Option<Backplate>,
     defocus_angle: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    aperture: Option<Arc<Aperture>>,
+    lens: Option<Arc<Lens>>,
+    focus_distance: f64,
+    focus_map: Option<Arc<FocusMap>>,
+    eye_separation: Option<f64>,
+    exposure: Color,
+    seeding: Option<Seeding>,
     pixel_00: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
+    renderer: Renderer,
+    output: Output,
+    budget: Budget,
+    tiling: Tiling,
+    filter: PixelFilter,
+    integrator: Integrator,
+    cull_backfaces: bool,
+    clipping: Clipping,
+    forward: Vec3,
+    wireframe: Option<Wireframe>,
+    toon: Option<Arc<Toon>>,
+    irradiance_cache: Option<Arc<IrradianceCache>>,
+    restir: Option<Arc<Restir>>,
+    light_sampler: Option<Arc<LightSampler>>,
+    path_guide: Option<Arc<PathGuide>>,
+    tile_sink: Option<TileSink>,
 }
 
 impl Camera {
@@ -47,11 +198,10 @@ impl Camera {
         background: Color,
         defocus_angle: f64,
         focus_distance: f64,
+        renderer: Renderer,
     ) -> Self {
         let image_height = 1.max((image_width as f64 / aspect_ratio).round() as u32);
 
-        let pixel_sample_scale = 1.0 / samples_per_pixel as f64;
-
         let center = look_from;
 
         let theta = vertical_fov.to_radians();
@@ -82,123 +232,1108 @@ impl Camera {
             image_height,
             samples_per_pixel,
             max_depth,
-            pixel_sample_scale,
             center,
             background,
+            environment: None,
+            starfield: None,
+            sun: None,
+            backplate: None,
             defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            focus_distance,
+            aperture: None,
+            lens: None,
+            focus_map: None,
+            eye_separation: None,
+            exposure: Color::default(),
+            seeding: None,
             pixel_00,
             pixel_delta_u,
             pixel_delta_v,
+            renderer,
+            output: Output::default(),
+            budget: Budget::default(),
+            tiling: Tiling::default(),
+            filter: PixelFilter::default(),
+            integrator: Integrator::default(),
+            cull_backfaces: false,
+            clipping: Clipping::default(),
+            forward: -w,
+            wireframe: None,
+            toon: None,
+            irradiance_cache: None,
+            restir: None,
+            light_sampler: None,
+            path_guide: None,
+            tile_sink: None,
         }
     }
 
-    fn sample_square() -> Vec3 {
-        Vec3::new(
-            fastrand_contrib::f64_range(-0.5..0.5),
-            fastrand_contrib::f64_range(-0.5..0.5),
-            0.0,
-        )
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Lets camera rays pass through the back of opaque surfaces, e.g. to look into a closed room.
+    pub fn with_backface_culling(mut self, cull_backfaces: bool) -> Self {
+        self.cull_backfaces = cull_backfaces;
+        self
+    }
+
+    pub fn with_clipping(mut self, clipping: Clipping) -> Self {
+        self.clipping = clipping;
+        self
+    }
+
+    /// Focuses each part of the frame at the distance the map gives there instead of the
+    /// camera's single focus distance.
+    pub fn with_focus_map(mut self, focus_map: Option<FocusMap>) -> Self {
+        self.focus_map = focus_map.map(Arc::new);
+        self
+    }
+
+    /// Shapes the lens aperture after `aperture` rather than a disk.
+    pub fn with_aperture(mut self, aperture: Option<Aperture>) -> Self {
+        self.aperture = aperture.map(Arc::new);
+        self
+    }
+
+    /// Traces camera rays through `lens` in place of the thin lens, whose defocus settings,
+    /// aperture shape and focus map it ignores.
+    pub fn with_lens(mut self, lens: Option<Lens>) -> Self {
+        self.lens = lens.map(Arc::new);
+        self
+    }
+
+    pub fn with_environment(mut self, environment: Option<Arc<Environment>>) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    pub fn with_starfield(mut self, starfield: Option<Starfield>) -> Self {
+        self.starfield = starfield.map(Arc::new);
+        self
+    }
+
+    pub fn with_sun(mut self, sun: Option<Sun>) -> Self {
+        self.sun = sun;
+        self
+    }
+
+    pub fn with_backplate(mut self, backplate: Option<Backplate>) -> Self {
+        self.backplate = backplate;
+        self
+    }
+
+    /// Brightens the image by `exposure` stops, e.g. to bring physically bright lights into range;
+    /// each channel has its own, so a color cast can be corrected too.
+    pub fn with_exposure(mut self, exposure: Color) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Deterministic per-pixel sampling; used by the recursive renderer only.
+    pub fn with_seeding(mut self, seeding: Option<Seeding>) -> Self {
+        self.seeding = seeding;
+        self
+    }
+
+    /// Renders a left and a right eye `eye_separation` apart, converging on the focus plane.
+    pub fn with_stereo(mut self, eye_separation: Option<f64>) -> Self {
+        self.eye_separation = eye_separation;
+        self
+    }
+
+    /// Copy of the camera moved `offset` along its right axis, keeping the same image plane so
+    /// points at the focus distance show no parallax.
+    fn eye(&self, offset: f64) -> Self {
+        let mut eye = self.clone();
+        eye.center += offset * self.pixel_delta_u.unit();
+        eye
+    }
+
+    /// The same camera carried `angle` degrees around `pivot`, turning about `axis`, still
+    /// looking the same way relative to the pivot.
+    pub fn orbit(&self, pivot: Point3, axis: Vec3, angle: f64) -> Self {
+        let rotation = Mat3::rotation_about(axis.unit(), angle);
+        let mut orbit = self.clone();
+        orbit.center = pivot + rotation * (self.center - pivot);
+        orbit.pixel_00 = pivot + rotation * (self.pixel_00 - pivot);
+        orbit.pixel_delta_u = rotation * self.pixel_delta_u;
+        orbit.pixel_delta_v = rotation * self.pixel_delta_v;
+        orbit.defocus_disk_u = rotation * self.defocus_disk_u;
+        orbit.defocus_disk_v = rotation * self.defocus_disk_v;
+        orbit.forward = rotation * self.forward;
+        orbit
+    }
+
+    pub fn with_wireframe(mut self, wireframe: Option<Wireframe>) -> Self {
+        self.wireframe = wireframe;
+        self
+    }
+
+    pub fn with_toon(mut self, toon: Option<Toon>) -> Self {
+        self.toon = toon.map(Arc::new);
+        self
+    }
+
+    pub fn with_irradiance_cache(mut self, irradiance_cache: Option<IrradianceCache>) -> Self {
+        self.irradiance_cache = irradiance_cache.map(Arc::new);
+        self
+    }
+
+    /// Resampled direct lighting for diffuse camera hits; used by the recursive renderer only.
+    pub fn with_restir(mut self, restir: Option<Restir>) -> Self {
+        self.restir = restir.map(Arc::new);
+        self
+    }
+
+    /// Lights sampled directly at irradiance-cached hits, whose cache records hold only the
+    /// indirect light.
+    pub fn with_light_sampler(mut self, light_sampler: Option<Arc<LightSampler>>) -> Self {
+        self.light_sampler = light_sampler;
+        self
+    }
+
+    /// Learned sampling of diffuse bounces; used by the recursive renderer only.
+    pub fn with_path_guide(mut self, path_guide: Option<PathGuide>) -> Self {
+        self.path_guide = path_guide.map(Arc::new);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: PixelFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_tiling(mut self, tiling: Tiling) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    /// Sends each tile into `tile_sink` as soon as a pass over it is done.
+    pub fn with_tile_sink(mut self, tile_sink: Option<TileSink>) -> Self {
+        self.tile_sink = tile_sink;
+        self
+    }
+
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    #[inline]
+    pub fn image_width(&self) -> u32 {
+        self.image_width
+    }
+
+    #[inline]
+    pub fn image_height(&self) -> u32 {
+        self.image_height
+    }
+
+    #[inline]
+    pub fn samples_per_pixel(&self) -> u16 {
+        self.budget
+            .max_samples
+            .map_or(self.samples_per_pixel, |max| {
+                max.min(self.samples_per_pixel)
+            })
+    }
+
+    #[inline]
+    pub fn tile_sink(&self) -> Option<&TileSink> {
+        self.tile_sink.as_ref()
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    #[inline]
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    #[inline]
+    pub fn max_depth(&self) -> u16 {
+        self.max_depth
+    }
+
+    /// Light arriving along `ray` from beyond the scene.
+    #[inline]
+    pub fn background(&self, ray: &Ray) -> Color {
+        let direction = *ray.direction();
+        let sky = self
+            .environment
+            .as_ref()
+            .map_or(self.background, |environment| {
+                environment.radiance(direction)
+            });
+        sky + self.stars(ray)
+            + self
+                .sun
+                .map_or(Color::default(), |sun| sun.radiance(direction))
+    }
+
+    /// The starfield as seen by `ray`. Diffuse rays don't see it, as stars light the scene
+    /// too little to be worth the fireflies they'd leave where a bounce finds one.
+    #[inline]
+    fn stars(&self, ray: &Ray) -> Color {
+        match &self.starfield {
+            Some(starfield) if ray.kind() != RayKind::Diffuse => {
+                starfield.radiance(*ray.direction())
+            }
+            _ => Color::default(),
+        }
+    }
+
+    /// What a camera ray sees when it misses the scene; `None` where the backplate is
+    /// transparent.
+    pub fn primary_background(&self, ray: &Ray) -> Option<Color> {
+        match self.backplate {
+            None => Some(self.background(ray)),
+            Some(Backplate::Color(color)) => Some(color),
+            Some(Backplate::Transparent) => None,
+        }
+    }
+
+    /// Coverage of a camera sample, which only a transparent backplate lets fall below one.
+    pub fn coverage(&self, hit: bool) -> f64 {
+        match self.backplate {
+            Some(Backplate::Transparent) if !hit => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    /// The part of the background that isn't sampled as direct light: all of it but the
+    /// environment and sun.
+    fn unsampled_background(&self, ray: &Ray) -> Color {
+        let sky = match self.environment {
+            Some(_) => Color::default(),
+            None => self.background,
+        };
+        sky + self.stars(ray)
+    }
+
+    #[inline]
+    pub fn integrator(&self) -> &Integrator {
+        &self.integrator
+    }
+
+    /// Closest hit along a ray after clipping; camera rays (`primary`) also get the near/far
+    /// limits and backface culling.
+    pub fn trace<'a>(
+        &'a self,
+        world: &'a BVHNode,
+        ray: &Ray,
+        primary: bool,
+    ) -> Option<HitRecord<'a>> {
+        progress::count_ray();
+        let base = epsilon::ray_interval(ray);
+        let mut interval = base;
+        if primary {
+            let depth_per_t = ray.direction().dot(self.forward);
+            if let Some(near) = self.clipping.near {
+                interval.start = interval.start.max(near / depth_per_t);
+            }
+            if let Some(far) = self.clipping.far {
+                interval.end = far / depth_per_t;
+            }
+        }
+
+        // solids entered through clipped-away faces; a back face hit while inside one means
+        // the ray is looking through the cut
+        let mut inside = 0;
+        loop {
+            let hit_record = world.hit(ray, interval)?;
+            let culled = primary
+                && self.cull_backfaces
+                && !hit_record.front
+                && hit_record.material.is_opaque();
+            let clipped = self.clipping.clips(hit_record.hit_point);
+            if clipped {
+                inside += if hit_record.front { 1 } else { -1 };
+            }
+            if culled || clipped {
+                interval.start = hit_record.time + base.start;
+                continue;
+            }
+            if inside > 0 && !hit_record.front {
+                let before_hit = Interval::new(base.start, hit_record.time);
+                if let Some(cap) = self.clipping.cap(ray, before_hit) {
+                    return Some(cap);
+                }
+            }
+            return Some(hit_record);
+        }
+    }
+
+    /// Color of a camera ray in wireframe mode given its first hit, or `None` if it should be
+    /// shaded normally.
+    pub fn wireframe_color(
+        &self,
+        world: &BVHNode,
+        ray: &Ray,
+        hit_record: Option<&HitRecord>,
+    ) -> Option<Color> {
+        let wireframe = self.wireframe?;
+        match wireframe.mode {
+            WireframeMode::Overlay => hit_record
+                .filter(|hit_record| wireframe.is_edge(hit_record))
+                .map(|_| wireframe.color),
+            WireframeMode::Only => {
+                let base = epsilon::ray_interval(ray);
+                let mut interval = base;
+                while let Some(mut hit_record) = world.hit(ray, interval) {
+                    hit_record.apply_differentials(ray);
+                    if wireframe.is_edge(&hit_record) {
+                        return Some(wireframe.color);
+                    }
+                    interval.start = hit_record.time + base.start;
+                }
+                Some(self.primary_background(ray).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Color of a camera ray in toon mode given its first hit, or `None` if it should be shaded
+    /// normally.
+    pub fn toon_color(
+        &self,
+        world: &BVHNode,
+        ray: &Ray,
+        hit_record: Option<&HitRecord>,
+    ) -> Option<Color> {
+        let toon = self.toon.as_deref()?;
+        if let Some(outline) = toon.outline {
+            // rays half a line width to either side of this one, across and down the image
+            let scale =
+                0.5 * outline.width * ray.direction().dot(self.forward) / self.focus_distance;
+            let offsets = [
+                self.pixel_delta_u,
+                -self.pixel_delta_u,
+                self.pixel_delta_v,
+                -self.pixel_delta_v,
+            ];
+            let inked = offsets.into_iter().any(|offset| {
+                let beside = Ray::new(
+                    *ray.origin(),
+                    *ray.direction() + scale * offset,
+                    *ray.time(),
+                );
+                outline.separates(hit_record, self.trace(world, &beside, true).as_ref())
+            });
+            if inked {
+                return Some(outline.color);
+            }
+        }
+
+        let Some(hit_record) = hit_record else {
+            return Some(self.primary_background(ray).unwrap_or_default());
+        };
+        let material = hit_record.material;
+        if material.is_emissive() {
+            return Some(material.emit_towards(hit_record, hit_record.view));
+        }
+        // surfaces that aren't diffuse are banded in the color they reflect or transmit
+        let albedo = material
+            .diffuse_albedo(hit_record)
+            .or_else(|| {
+                material
+                    .scatter(ray, hit_record)
+                    .map(|reflected| reflected.attenuation)
+            })
+            .unwrap_or(Color::new(1.0, 1.0, 1.0));
+        Some(albedo * toon.shade(toon.lit(world, hit_record)))
+    }
+
+    /// Radiance arriving along a ray that doesn't start at the camera.
+    pub fn incident_radiance(&self, world: &BVHNode, ray: &Ray) -> Color {
+        let state = PathState {
+            depth: self.max_depth.saturating_sub(1),
+            interaction: 2,
+            pixel: None,
+            emission: true,
+            extended: false,
+            unlinked: 0,
+            throughput: Color::new(1.0, 1.0, 1.0),
+        };
+        self.path_color(ray, world, state)
+    }
+
+    /// Light reflected from a diffuse camera-ray hit, the indirect part interpolated from the
+    /// irradiance cache (computing a new record if none is close enough) and the direct part
+    /// sampled afresh, resampled through `pixel` when ReSTIR is on; `None` when the cache
+    /// doesn't apply.
+    pub fn cached_diffuse(
+        &self,
+        world: &BVHNode,
+        hit_record: &HitRecord,
+        pixel: Option<usize>,
+    ) -> Option<Color> {
+        let cache = self.irradiance_cache.as_deref()?;
+        let albedo = hit_record.material.diffuse_albedo(hit_record)?;
+        let (point, normal) = (hit_record.hit_point, hit_record.normal);
+        let indirect = match cache.lookup(point, normal) {
+            Some(irradiance) => irradiance,
+            None => self.cache_record(world, cache, point, normal),
+        };
+        let direct = match (pixel, self.restir.as_deref(), self.light_sampler.as_deref()) {
+            (Some(pixel), Some(restir), _) => restir.direct_light(world, hit_record, albedo, pixel),
+            (_, _, Some(sampler)) => sampler.direct_light(world, hit_record, albedo),
+            _ => Color::default(),
+        };
+        Some(albedo * indirect + direct)
+    }
+
+    /// Traces the hemisphere above `point` for a new cache record of the indirect light there,
+    /// leaving out the light sources that are sampled directly.
+    fn cache_record(
+        &self,
+        world: &BVHNode,
+        cache: &IrradianceCache,
+        point: Point3,
+        normal: Vec3,
+    ) -> Color {
+        let state = PathState {
+            depth: self.max_depth.saturating_sub(1),
+            interaction: 2,
+            pixel: None,
+            emission: false,
+            extended: false,
+            unlinked: 0,
+            throughput: Color::new(1.0, 1.0, 1.0),
+        };
+        if state.depth == 0 {
+            return Color::default();
+        }
+        let mut sum = Color::default();
+        let mut inverse_distances = 0.0;
+        for _ in 0..cache.samples {
+            let mut direction = normal + Vec3::random_unit_vector();
+            if direction.near_zero() {
+                direction = normal;
+            }
+            let ray = Ray::new(point, direction, fastrand::f64()).with_kind(RayKind::Diffuse);
+            let hit = self.trace(world, &ray, false);
+            if let Some(hit) = &hit {
+                inverse_distances += 1.0 / (hit.time * ray.direction().length());
+            }
+            sum += self.hit_color(&ray, world, state, hit);
+        }
+        let samples = cache.samples.max(1) as f64;
+        let irradiance = sum / samples;
+        cache.insert(point, normal, irradiance, samples / inverse_distances);
+        irradiance
+    }
+
+    /// Diffuse bounce that picks between cosine and learned sampling with equal odds, weighted
+    /// by the combined density, which is returned alongside.
+    fn guided_scatter(
+        guide: &PathGuide,
+        incoming: &Ray,
+        hit_record: &HitRecord,
+        albedo: Color,
+    ) -> Option<(Reflected, f64)> {
+        let (point, normal) = (hit_record.hit_point, hit_record.normal);
+        let learned = fastrand::bool()
+            .then(|| guide.sample(point))
+            .flatten()
+            .map(|(direction, _)| direction);
+        let direction = learned.unwrap_or_else(|| {
+            let direction = normal + Vec3::random_unit_vector();
+            if direction.near_zero() {
+                normal
+            } else {
+                direction
+            }
+        });
+        let cosine_pdf = normal.dot(direction.unit()) * FRAC_1_PI;
+        if cosine_pdf <= 0.0 {
+            return None;
+        }
+        let pdf = match guide.pdf(point, direction) {
+            Some(guide_pdf) => 0.5 * (guide_pdf + cosine_pdf),
+            None => cosine_pdf,
+        };
+        let reflected = Reflected {
+            attenuation: albedo * (cosine_pdf / pdf),
+            scattered: Ray::new(point, direction, *incoming.time())
+                .with_media(incoming.media())
+                .with_kind(RayKind::Diffuse),
+        };
+        Some((reflected, pdf))
     }
 
     fn defocus_disk_sample(&self) -> Point3 {
-        let p = Point3::random_in_unit_disk();
-        self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
+        let (x, y) = match &self.aperture {
+            Some(aperture) => aperture.sample(),
+            None => {
+                let p = Point3::random_in_unit_disk();
+                (p.x(), p.y())
+            }
+        };
+        self.center + (x * self.defocus_disk_u) + (y * self.defocus_disk_v)
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let offset = Self::sample_square();
-        let pixel_sample = self.pixel_00
-            + (i as f64 + offset.x()) * self.pixel_delta_u
-            + (j as f64 + offset.y()) * self.pixel_delta_v;
+    /// World space ray leaving `lens` from film point `film` through `rear` on its rear element.
+    fn lens_ray(&self, lens: &Lens, film: (f64, f64), rear: (f64, f64)) -> Option<(Point3, Vec3)> {
+        let (origin, direction) = lens.trace(film, rear)?;
+        let (right, up, back) = (
+            self.pixel_delta_u.unit(),
+            -self.pixel_delta_v.unit(),
+            -self.forward,
+        );
+        let to_world = |v: Vec3| v.x() * right + v.y() * up + v.z() * back;
+        Some((self.center + to_world(origin), to_world(direction)))
+    }
+
+    /// Camera ray through image point (x, y) of a lens, with the share of light it carries.
+    fn lens_camera_ray(&self, lens: &Lens, x: f64, y: f64) -> Option<(Ray, f64)> {
+        let film_at = |x: f64, y: f64| {
+            lens.film_point(
+                (x + 0.5) / self.image_width as f64,
+                (y + 0.5) / self.image_height as f64,
+            )
+        };
+        let film = film_at(x, y);
+        let (rear, transmission) = lens.sample(film)?;
+        let (origin, direction) = self.lens_ray(lens, film, rear)?;
+        // the neighboring pixels' rays through the same point of the rear element
+        let differentials = self
+            .lens_ray(lens, film_at(x + 1.0, y), rear)
+            .zip(self.lens_ray(lens, film_at(x, y + 1.0), rear))
+            .map(
+                |((rx_origin, rx_direction), (ry_origin, ry_direction))| RayDifferentials {
+                    rx_origin,
+                    rx_direction,
+                    ry_origin,
+                    ry_direction,
+                },
+            );
+        let ray = Ray::new(origin, direction, fastrand::f64()).with_differentials(differentials);
+        Some((ray, transmission))
+    }
+
+    /// Samples a camera ray through pixel (i, j), returning it with the share of light the lens
+    /// lets through along it, or `None` if the lens blocks it, and its reconstruction filter
+    /// weight.
+    pub fn get_ray(&self, i: u32, j: u32) -> (Option<(Ray, f64)>, f64) {
+        let (offset_x, offset_y, weight) = self.filter.sample();
+        let (x, y) = (i as f64 + offset_x, j as f64 + offset_y);
+        if let Some(lens) = &self.lens {
+            return (self.lens_camera_ray(lens, x, y), weight);
+        }
+        let mut pixel_sample = self.pixel_00 + x * self.pixel_delta_u + y * self.pixel_delta_v;
+        let (mut delta_u, mut delta_v) = (self.pixel_delta_u, self.pixel_delta_v);
+        if let Some(focus_map) = &self.focus_map {
+            // moving the sample along its line of sight puts the plane of focus at that distance
+            let distance = focus_map.distance(
+                (x + 0.5) / self.image_width as f64,
+                (y + 0.5) / self.image_height as f64,
+            );
+            let scale = distance / self.focus_distance;
+            pixel_sample = self.center + scale * (pixel_sample - self.center);
+            delta_u = scale * delta_u;
+            delta_v = scale * delta_v;
+        }
         let origin = match self.defocus_angle {
             ..=0.0 => self.center,
             _ => self.defocus_disk_sample(),
         };
         let time = fastrand::f64();
-        Ray::new(origin, pixel_sample - origin, time)
+        let differentials = RayDifferentials {
+            rx_origin: origin,
+            rx_direction: pixel_sample + delta_u - origin,
+            ry_origin: origin,
+            ry_direction: pixel_sample + delta_v - origin,
+        };
+        let ray =
+            Ray::new(origin, pixel_sample - origin, time).with_differentials(Some(differentials));
+        (Some((ray, 1.0)), weight)
+    }
+
+    /// Continuous pixel coordinates of `point`, or `None` if it lies behind the camera.
+    pub fn project(&self, point: Point3) -> Option<(f64, f64)> {
+        let normal = self.pixel_delta_u.cross(self.pixel_delta_v);
+        let direction = point - self.center;
+        let denominator = normal.dot(direction);
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = normal.dot(self.pixel_00 - self.center) / denominator;
+        if t <= 0.0 {
+            return None;
+        }
+        let offset = self.center + t * direction - self.pixel_00;
+        Some((
+            offset.dot(self.pixel_delta_u) / self.pixel_delta_u.length_sq(),
+            offset.dot(self.pixel_delta_v) / self.pixel_delta_v.length_sq(),
+        ))
+    }
+
+    /// Pinhole ray through the center of pixel (i, j) at mid-shutter.
+    pub fn center_ray(&self, i: u32, j: u32) -> Ray {
+        let pixel_center =
+            self.pixel_00 + i as f64 * self.pixel_delta_u + j as f64 * self.pixel_delta_v;
+        Ray::new(self.center, pixel_center - self.center, 0.5)
+    }
+
+    /// Screen-space motion over the shutter interval of the surface seen through each pixel center.
+    fn motion_vectors(&self, world: &BVHNode) -> Vec<[f32; 3]> {
+        (0..self.image_width * self.image_height)
+            .into_par_iter()
+            .map(|index| {
+                let ray = self.center_ray(index % self.image_width, index / self.image_width);
+                let Some(hit_record) = self.trace(world, &ray, true) else {
+                    return [0.0; 3];
+                };
+                let time = *ray.time();
+                let start = hit_record.hit_point - time * hit_record.velocity;
+                let end = hit_record.hit_point + (1.0 - time) * hit_record.velocity;
+                match (self.project(start), self.project(end)) {
+                    (Some(a), Some(b)) => [(b.0 - a.0) as f32, (b.1 - a.1) as f32, 0.0],
+                    _ => [0.0; 3],
+                }
+            })
+            .collect()
     }
 
-    fn ray_color(&self, ray: &Ray, world: &BVHNode, depth: u16) -> Color {
-        if depth == 0 {
+    /// Radiance along a path, with `state` describing where on the path `ray` starts.
+    fn path_color(&self, ray: &Ray, world: &BVHNode, state: PathState) -> Color {
+        if state.depth == 0 {
             return Color::default();
         }
+        let hit = self.trace(world, ray, state.interaction == 1);
+        self.hit_color(ray, world, state, hit)
+    }
 
-        if let Some(hit_record) = world.hit(ray, Interval::new(0.001, f64::INFINITY)) {
-            let emitted_color =
-                hit_record
-                    .material
-                    .emit(hit_record.u, hit_record.v, &hit_record.hit_point);
-            if let Some(reflected) = hit_record.material.scatter(ray, &hit_record) {
-                let scattered_color =
-                    reflected.attenuation * self.ray_color(&reflected.scattered, world, depth - 1);
-                emitted_color + scattered_color
-            } else {
-                emitted_color
+    /// Radiance along a path whose first ray `ray` has already been traced to `hit`.
+    fn hit_color(
+        &self,
+        ray: &Ray,
+        world: &BVHNode,
+        state: PathState,
+        mut hit: Option<HitRecord>,
+    ) -> Color {
+        let primary = state.interaction == 1;
+        if let Some(hit_record) = hit.as_mut() {
+            hit_record.apply_differentials(ray);
+            hit_record.set_view(ray);
+        }
+        if primary {
+            if let Some(color) = self.wireframe_color(world, ray, hit.as_ref()) {
+                return color;
+            }
+            if let Some(color) = self.toon_color(world, ray, hit.as_ref()) {
+                return color;
+            }
+        }
+
+        let Some(hit_record) = hit else {
+            if primary {
+                return self.primary_background(ray).unwrap_or_default();
             }
+            // the environment and sun were already sampled as direct light at the previous hit
+            if !state.emission {
+                return self.unsampled_background(ray);
+            }
+            return self.background(ray);
+        };
+        let linked = hit_record.material.light_link() & state.unlinked == 0;
+        let sampled = !state.emission
+            && self
+                .light_sampler
+                .as_deref()
+                .is_some_and(|sampler| sampler.samples(hit_record.material));
+        let mut emitted_color = if linked && !sampled {
+            hit_record
+                .material
+                .emit_towards(&hit_record, -ray.direction().unit())
+        } else {
+            Color::default()
+        };
+        if primary {
+            if let Some(reflected) = self.cached_diffuse(world, &hit_record, state.pixel) {
+                return emitted_color + reflected;
+            }
+        }
+        let direct = state
+            .pixel
+            .zip(self.restir.as_deref())
+            .and_then(|(pixel, restir)| {
+                let albedo = hit_record.material.diffuse_albedo(&hit_record)?;
+                Some(restir.direct_light(world, &hit_record, albedo, pixel))
+            });
+        if let Some(direct) = direct {
+            emitted_color += direct;
+        }
+        let guide = self.path_guide.as_deref().and_then(|guide| {
+            let albedo = hit_record.material.diffuse_albedo(&hit_record)?;
+            Some((guide, albedo))
+        });
+        let next = state.next(hit_record.material, direct.is_none());
+        let splits = if primary {
+            hit_record.material.splitting().max(1)
         } else {
-            self.background
+            1
+        };
+
+        let mut reflected_color = Color::default();
+        for _ in 0..splits {
+            let mut guide_pdf = None;
+            let scattered = match guide {
+                Some((guide, albedo)) => {
+                    Self::guided_scatter(guide, ray, &hit_record, albedo).map(|(reflected, pdf)| {
+                        guide_pdf = Some((guide, pdf));
+                        reflected
+                    })
+                }
+                None => hit_record.material.scatter(ray, &hit_record),
+            };
+            let continued = scattered.and_then(|reflected| {
+                // each split carries its share of the path's weight
+                let throughput = state.throughput * reflected.attenuation / f64::from(splits);
+                let scale = self.integrator.roulette(state.interaction, throughput)?;
+                Some((reflected, scale, throughput))
+            });
+            if let Some((reflected, scale, throughput)) = continued {
+                let next = PathState {
+                    throughput: scale * throughput,
+                    ..next
+                };
+                let incident = self.path_color(&reflected.scattered, world, next);
+                if let Some((guide, pdf)) = guide_pdf {
+                    guide.record(
+                        hit_record.hit_point,
+                        *reflected.scattered.direction(),
+                        incident.luminance() / pdf,
+                    );
+                }
+                reflected_color += scale * reflected.attenuation * incident;
+            }
+        }
+        emitted_color + reflected_color / splits as f64
+    }
+
+    /// Traces one camera sample through pixel (i, j), returning its clamped radiance, coverage
+    /// and filter weight.
+    fn sample_pixel(&self, world: &BVHNode, i: u32, j: u32) -> (Color, f64, f64) {
+        let (ray, weight) = self.get_ray(i, j);
+        let Some((ray, transmission)) = ray else {
+            return (Color::default(), 1.0, weight);
+        };
+        let pixel = (j * self.image_width + i) as usize;
+        let state = PathState {
+            depth: self.max_depth,
+            interaction: 1,
+            pixel: Some(pixel),
+            emission: true,
+            extended: false,
+            unlinked: 0,
+            throughput: Color::new(1.0, 1.0, 1.0) * transmission,
+        };
+        if self.max_depth == 0 {
+            return (Color::default(), 1.0, weight);
         }
+        let hit = self.trace(world, &ray, true);
+        let coverage = self.coverage(hit.is_some());
+        let radiance = self.hit_color(&ray, world, state, hit);
+        (
+            self.integrator.clamp(transmission * radiance),
+            coverage,
+            weight,
+        )
     }
 
-    fn render_image(&self, world: &BVHNode) -> Vec<Color> {
-        let progress_bar = ProgressBar::new(self.image_height as u64);
-        let progress_style = ProgressStyle::default_bar()
-            .template("Render Progress: [{bar:40.green}] {percent_precise}%\nElapsed: {elapsed} | Remaining: {eta}").unwrap()
-            .progress_chars("=> ");
-        progress_bar.set_style(progress_style);
+    // samples are taken in passes over the whole image so a time budget can stop between them
+    const SAMPLES_PER_PASS: u16 = 4;
 
-        (0..self.image_height)
-            .into_par_iter()
-            .progress_with(progress_bar)
-            .flat_map(|j| {
-                (0..self.image_width).into_par_iter().map(move |i| {
-                    // this iterator returns one pixel by averaging samples
-                    (0..self.samples_per_pixel)
-                        .into_par_iter()
-                        .map(|_| self.ray_color(&self.get_ray(i, j), world, self.max_depth))
-                        .sum::<Color>()
-                        * self.pixel_sample_scale
-                })
+    // block sizes of the coarse preview levels, from coarsest to finest
+    const PREVIEW_STRIDES: [u32; 3] = [8, 4, 2];
+
+    /// Takes one sample per `stride` block, skipping pixels already sampled at a coarser level.
+    fn render_coarse_level(&self, world: &BVHNode, film: &mut Film, stride: u32) {
+        let coarser = 2 * stride;
+        let first_level = stride == Self::PREVIEW_STRIDES[0];
+
+        film.pixels_mut()
+            .par_chunks_mut(self.image_width as usize)
+            .enumerate()
+            .filter(|(j, _)| (*j as u32).is_multiple_of(stride))
+            .for_each(|(j, row)| {
+                let j = j as u32;
+                for i in (0..self.image_width).step_by(stride as usize) {
+                    if !first_level && i.is_multiple_of(coarser) && j.is_multiple_of(coarser) {
+                        continue;
+                    }
+                    if let Some(seeding) = &self.seeding {
+                        // preview samples get streams of their own, apart from the passes'
+                        let pixel = (j * self.image_width + i) as usize;
+                        fastrand::seed(seeding.pixel_seed(pixel, u16::MAX - stride as u16));
+                    }
+                    let start = Instant::now();
+                    let (sample, coverage, weight) = self.sample_pixel(world, i, j);
+                    row[i as usize].add_sample(sample, coverage, weight);
+                    row[i as usize].add_cost(start.elapsed().as_nanos() as f64);
+                }
+            });
+    }
+
+    /// Renders progressively finer low-resolution levels, writing a preview after each one.
+    fn render_preview_levels(
+        &self,
+        world: &BVHNode,
+        film: &mut Film,
+        name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        for stride in Self::PREVIEW_STRIDES {
+            self.render_coarse_level(world, film, stride);
+            output::save_preview(film, stride, name)?;
+        }
+        Ok(())
+    }
+
+    fn render_tile(
+        &self,
+        world: &BVHNode,
+        tile: &Tile,
+        first_sample: u16,
+        samples: u16,
+    ) -> Vec<PixelAccumulator> {
+        tile.pixels()
+            .map(|(i, j)| {
+                if let Some(seeding) = &self.seeding {
+                    let pixel = (j * self.image_width + i) as usize;
+                    fastrand::seed(seeding.pixel_seed(pixel, first_sample));
+                }
+                let start = Instant::now();
+                let mut pixel = PixelAccumulator::default();
+                for _ in 0..samples {
+                    let (sample, coverage, weight) = self.sample_pixel(world, i, j);
+                    pixel.add_sample(sample, coverage, weight);
+                }
+                pixel.add_cost(start.elapsed().as_nanos() as f64);
+                pixel
             })
             .collect()
     }
 
-    const OUTPUT_DIR: &'static str = "./results";
+    fn render_image(&self, world: &BVHNode, film: &mut Film, deadline: Option<Instant>) {
+        let samples_per_pixel = self.samples_per_pixel();
+        let pixel_count = self.image_width as u64 * self.image_height as u64;
+        let progress_bar = progress::render_progress_bar(pixel_count * samples_per_pixel as u64);
+        let mut tiles = self.tiling.tiles(self.image_width, self.image_height);
 
-    fn save_image(&self, pixels: Vec<Color>, name: &str) -> Result<String, Box<dyn Error>> {
-        if !Path::new(Self::OUTPUT_DIR).exists() {
-            create_dir_all(Self::OUTPUT_DIR)?;
-        }
+        let mut taken = 0;
+        while taken < samples_per_pixel {
+            let pass = Self::SAMPLES_PER_PASS.min(samples_per_pixel - taken);
+            let first_pass = taken == 0;
 
-        let result_path = format!("{}/{}.png", Self::OUTPUT_DIR, name);
-        let image_file = File::create(&result_path)?;
-        let image_buf = BufWriter::new(image_file);
-        let png_encoder = PngEncoder::new(image_buf);
-        let raw: Vec<u8> = pixels.into_iter().flat_map(Vec3::to_rgb8).collect();
+            // workers pull tiles from a shared cursor so they are started in tiling order
+            let cursor = AtomicUsize::new(0);
+            let finished = Mutex::new(Vec::with_capacity(tiles.len()));
+            let so_far: &Film = film;
+            rayon::broadcast(|_| {
+                while let Some(tile) = tiles.get(cursor.fetch_add(1, Ordering::Relaxed)) {
+                    // the first pass always completes so no pixel is left without samples
+                    if !first_pass && past(deadline) {
+                        break;
+                    }
+                    let pixels = self.render_tile(world, tile, taken, pass);
+                    progress_bar.inc(tile.pixel_count() as u64 * pass as u64);
+                    if let Some(sink) = &self.tile_sink {
+                        let samples = (taken + pass) as u32;
+                        sink.send(so_far.finished_tile(tile, Some(&pixels), samples));
+                    }
+                    finished.lock().unwrap().push((*tile, pixels));
+                }
+            });
+            for (tile, pixels) in finished.into_inner().unwrap() {
+                film.add_tile(&tile, &pixels);
+            }
+            tiles = self
+                .tiling
+                .refine(&tiles, rayon::current_num_threads(), |tile| {
+                    film.tile_cost(tile)
+                });
+
+            if let Some(restir) = &self.restir {
+                restir.end_pass();
+            }
+            if let Some(guide) = &self.path_guide {
+                guide.end_pass();
+            }
 
-        png_encoder.write_image(
-            &raw,
-            self.image_width,
-            self.image_height,
-            ExtendedColorType::Rgb8,
-        )?;
+            taken += pass;
+            // the spread of the samples already includes what russian roulette adds, so the
+            // estimate needs no correction for it
+            progress::set_noise(
+                film.mean_std_error(),
+                taken as u32,
+                samples_per_pixel as u32,
+            );
+            if past(deadline) {
+                break;
+            }
+        }
+        progress_bar.finish();
+    }
 
-        Ok(result_path)
+    /// Renders the image into a film, saving previews along the way but not the image itself.
+    pub fn render_film(
+        &self,
+        world: &BVHNode,
+        name: &str,
+        deadline: Option<Instant>,
+    ) -> Result<Film, Box<dyn Error>> {
+        let mut film = Film::new(self.image_width, self.image_height).with_exposure(self.exposure);
+        if self.output.preview {
+            self.render_preview_levels(world, &mut film, name)?;
+        }
+        match self.renderer {
+            Renderer::Recursive => self.render_image(world, &mut film, deadline),
+            Renderer::Wavefront => wavefront::render_image(self, world, &mut film, deadline),
+        }
+        if self.output.preview {
+            output::save_preview(&film, 1, name)?;
+        }
+        Ok(film)
     }
 
-    pub fn render(&self, world: &BVHNode, scene_name: &str) -> Result<(), Box<dyn Error>> {
+    pub fn render(
+        &self,
+        world: &BVHNode,
+        lights: &[Arc<dyn Entity>],
+        scene_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
         let start = Instant::now();
-        let pixels = self.render_image(world);
+        let deadline = self.budget.max_time.map(|max_time| start + max_time);
+        let mut hdr_paths = Vec::new();
+        let mut save_hdr = |film: &Film, name: &str| -> Result<(), Box<dyn Error>> {
+            // float output already writes the main image as `<name>.exr`
+            if self.output.exr && self.output.bit_depth != BitDepth::Float {
+                hdr_paths.push(output::save_hdr(film, name)?);
+            }
+            Ok(())
+        };
+        let (film, result_path, eyes) = match self.eye_separation {
+            None => {
+                let film = self.render_film(world, scene_name, deadline)?;
+                let result_path = output::save_image(&film, scene_name, &self.output)?;
+                save_hdr(&film, scene_name)?;
+                (film, result_path, None)
+            }
+            Some(separation) => {
+                let left_name = format!("{scene_name}_left");
+                let right_name = format!("{scene_name}_right");
+                // the left eye gets half the time, the right whatever is left of it
+                let left_deadline = self.budget.max_time.map(|max_time| start + max_time / 2);
+                let left =
+                    self.eye(-0.5 * separation)
+                        .render_film(world, &left_name, left_deadline)?;
+                let right = self
+                    .eye(0.5 * separation)
+                    .render_film(world, &right_name, deadline)?;
+                let left_path = output::save_image(&left, &left_name, &self.output)?;
+                let right_path = output::save_image(&right, &right_name, &self.output)?;
+                save_hdr(&left, &left_name)?;
+                save_hdr(&right, &right_name)?;
+                let anaglyph_path = if self.output.anaglyph {
+                    Some(output::save_anaglyph(&left, &right, scene_name)?)
+                } else {
+                    None
+                };
+                let right_samples = (right.min_samples(), right.max_samples());
+                (
+                    left,
+                    left_path,
+                    Some((right_path, anaglyph_path, right_samples)),
+                )
+            }
+        };
         let end = Instant::now();
-        let result_path = self.save_image(pixels, scene_name)?;
 
-        println!("Finished");
-        println!("Render Time: {:.3}s", (end - start).as_secs_f64());
-        println!("Output Location: {result_path}");
-        println!("Resolution: {} x {}", self.image_width, self.image_height);
+        status!("Finished");
+        status!("Render Time: {:.3}s", (end - start).as_secs_f64());
+        status!("Output Location: {result_path}");
+        if let Some((right_path, anaglyph_path, _)) = &eyes {
+            status!("Right Eye: {right_path}");
+            if let Some(anaglyph_path) = anaglyph_path {
+                status!("Anaglyph: {anaglyph_path}");
+            }
+        }
+        for hdr_path in hdr_paths {
+            status!("HDR Output: {hdr_path}");
+        }
+        status!("Resolution: {} x {}", self.image_width, self.image_height);
+        status!("Mean Std. Error: {:.5}", film.mean_std_error());
+        status!(
+            "Lights: {} | Emitted Power: {:.1}",
+            lights.len(),
+            lights
+                .iter()
+                .map(|light| light.emitted_power())
+                .sum::<Color>()
+                .luminance()
+        );
+        let samples = (film.min_samples(), film.max_samples());
+        let sampled = match eyes {
+            Some((_, _, right_samples)) => {
+                vec![("Left Eye ", samples), ("Right Eye ", right_samples)]
+            }
+            None => vec![("", samples)],
+        };
+        for (eye, (min_samples, max_samples)) in sampled {
+            if min_samples < self.samples_per_pixel() as u32 {
+                status!(
+                    "{eye}Time limit reached: {min_samples}-{max_samples} of {} samples per pixel",
+                    self.samples_per_pixel()
+                );
+            }
+        }
+
+        if let Some(summary) = counters::summary() {
+            status!("Intersection Tests: {summary}");
+        }
+
+        if let Some(cache) = &self.irradiance_cache {
+            status!("Irradiance Cache: {} records", cache.len());
+        }
+
+        if self.output.variance_heatmap {
+            let heatmap_path = output::save_variance_heatmap(&film, scene_name)?;
+            status!("Variance Heatmap: {heatmap_path}");
+        }
+
+        if self.output.cost_heatmap {
+            let cost_path = output::save_cost_heatmap(&film, scene_name)?;
+            status!("Cost Heatmap: {cost_path}");
+        }
+
+        if self.output.motion_vectors {
+            let motion = self.motion_vectors(world);
+            let motion_path = output::save_motion_vectors(
+                &motion,
+                self.image_width,
+                self.image_height,
+                scene_name,
+            )?;
+            status!("Motion Vectors: {motion_path}");
+        }
 
         Ok(())
     }
 }
+
+#[inline]
+pub fn past(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
@@ -0,0 +1,137 @@
+use crate::vec3::Point3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub frame: f64,
+    pub look_from: Point3,
+    pub look_at: Point3,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Smooth curve through every keyframe.
+    CatmullRom,
+    /// Single Bézier curve using the keyframes as control points; it passes through the first
+    /// and last ones and is pulled towards the rest.
+    Bezier,
+}
+
+/// Remaps progress along the whole path so motion can start and stop gently.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Camera position and target over an animation, given by keyframes at frame numbers.
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+    easing: Easing,
+}
+
+impl CameraPath {
+    /// Keyframes are sorted by frame; `None` if there are none.
+    pub fn new(
+        mut keyframes: Vec<Keyframe>,
+        interpolation: Interpolation,
+        easing: Easing,
+    ) -> Option<Self> {
+        if keyframes.is_empty() {
+            return None;
+        }
+        keyframes.sort_by(|a, b| a.frame.total_cmp(&b.frame));
+        Some(Self {
+            keyframes,
+            interpolation,
+            easing,
+        })
+    }
+
+    /// Camera position and target at `frame`, held at the ends outside the keyframed range.
+    pub fn at(&self, frame: f64) -> (Point3, Point3) {
+        let (first, last) = (
+            self.keyframes[0].frame,
+            self.keyframes[self.keyframes.len() - 1].frame,
+        );
+        if last <= first {
+            return (self.keyframes[0].look_from, self.keyframes[0].look_at);
+        }
+        let progress = self
+            .easing
+            .apply(((frame - first) / (last - first)).clamp(0.0, 1.0));
+        let frame = first + progress * (last - first);
+
+        let from: Vec<Point3> = self.keyframes.iter().map(|k| k.look_from).collect();
+        let at: Vec<Point3> = self.keyframes.iter().map(|k| k.look_at).collect();
+        match self.interpolation {
+            Interpolation::Bezier => (bezier(&from, progress), bezier(&at, progress)),
+            Interpolation::Linear | Interpolation::CatmullRom => {
+                let segment = self
+                    .keyframes
+                    .windows(2)
+                    .position(|pair| frame <= pair[1].frame)
+                    .unwrap_or(self.keyframes.len() - 2);
+                let (start, end) = (
+                    self.keyframes[segment].frame,
+                    self.keyframes[segment + 1].frame,
+                );
+                let t = if end > start {
+                    (frame - start) / (end - start)
+                } else {
+                    0.0
+                };
+                match self.interpolation {
+                    Interpolation::CatmullRom => {
+                        (catmull_rom(&from, segment, t), catmull_rom(&at, segment, t))
+                    }
+                    _ => (
+                        from[segment] + t * (from[segment + 1] - from[segment]),
+                        at[segment] + t * (at[segment + 1] - at[segment]),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Point `t` of the way between `points[segment]` and the next one on a uniform Catmull-Rom
+/// spline, repeating the end points where neighbors are missing.
+fn catmull_rom(points: &[Point3], segment: usize, t: f64) -> Point3 {
+    let p0 = points[segment.saturating_sub(1)];
+    let (p1, p2) = (points[segment], points[segment + 1]);
+    let p3 = points[(segment + 2).min(points.len() - 1)];
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// De Casteljau evaluation of the Bézier curve with control points `points`.
+fn bezier(points: &[Point3], t: f64) -> Point3 {
+    let mut points = points.to_vec();
+    for level in (1..points.len()).rev() {
+        for i in 0..level {
+            points[i] = points[i] + t * (points[i + 1] - points[i]);
+        }
+    }
+    points[0]
+}
@@ -0,0 +1,156 @@
+use std::{error::Error, time::Duration};
+
+use crate::{
+    output::{BitDepth, ColorSpace},
+    progress::ProgressFormat,
+    scene::MaterialOverride,
+};
+
+const USAGE: &str =
+    "Usage: raytracer [inspect] <scene.toml> [--max-time <duration>] [--max-samples <count>] \
+                     [--include-tags <tag,...>] [--exclude-tags <tag,...>] \
+                     [--progress <bar|json>] [--quiet] \
+                     [--bit-depth <8|16|float>] [--color-space <srgb|linear|display-p3>] \
+                     [--override-material <clay>] [--reference] [--watch] [--stream-tiles]";
+
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    pub scene_path: String,
+    /// Prints the scene's entity tree instead of rendering it.
+    pub inspect: bool,
+    pub max_time: Option<Duration>,
+    pub max_samples: Option<u16>,
+    pub include_tags: Vec<String>,
+    pub exclude_tags: Vec<String>,
+    pub progress: ProgressFormat,
+    pub quiet: bool,
+    pub bit_depth: Option<BitDepth>,
+    pub color_space: Option<ColorSpace>,
+    pub override_material: Option<MaterialOverride>,
+    pub reference: bool,
+    /// Renders the scene again each time its file is saved.
+    pub watch: bool,
+    /// Prints each tile on stdout as a line of JSON as soon as a pass over it is done; reports
+    /// and progress go to stderr instead.
+    pub stream_tiles: bool,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, Box<dyn Error>> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut scene_path = None;
+        let mut parsed = Self::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--max-time" => {
+                    let value = args.next().ok_or("--max-time expects a duration")?;
+                    parsed.max_time = Some(parse_duration(&value)?);
+                }
+                "--max-samples" => {
+                    let value = args.next().ok_or("--max-samples expects a count")?;
+                    parsed.max_samples = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid sample count '{value}' : {e}"))?,
+                    );
+                }
+                "--include-tags" => {
+                    let value = args.next().ok_or("--include-tags expects a list of tags")?;
+                    parsed.include_tags.extend(parse_tags(&value));
+                }
+                "--exclude-tags" => {
+                    let value = args.next().ok_or("--exclude-tags expects a list of tags")?;
+                    parsed.exclude_tags.extend(parse_tags(&value));
+                }
+                "--progress" => {
+                    parsed.progress = match args.next().as_deref() {
+                        Some("bar") => ProgressFormat::Bar,
+                        Some("json") => ProgressFormat::Json,
+                        _ => {
+                            return Err(
+                                format!("--progress expects 'bar' or 'json'\n{USAGE}").into()
+                            )
+                        }
+                    };
+                }
+                "--quiet" => parsed.quiet = true,
+                "--bit-depth" => {
+                    parsed.bit_depth = Some(match args.next().as_deref() {
+                        Some("8") => BitDepth::Eight,
+                        Some("16") => BitDepth::Sixteen,
+                        Some("float") => BitDepth::Float,
+                        _ => {
+                            return Err(
+                                format!("--bit-depth expects 8, 16 or float\n{USAGE}").into()
+                            )
+                        }
+                    });
+                }
+                "--color-space" => {
+                    parsed.color_space = Some(match args.next().as_deref() {
+                        Some("srgb") => ColorSpace::Srgb,
+                        Some("linear") => ColorSpace::Linear,
+                        Some("display-p3") => ColorSpace::DisplayP3,
+                        _ => {
+                            return Err(format!(
+                                "--color-space expects srgb, linear or display-p3\n{USAGE}"
+                            )
+                            .into())
+                        }
+                    });
+                }
+                "--override-material" => {
+                    parsed.override_material = Some(match args.next().as_deref() {
+                        Some("clay") => MaterialOverride::Clay,
+                        _ => {
+                            return Err(format!("--override-material expects clay\n{USAGE}").into())
+                        }
+                    });
+                }
+                "--reference" => parsed.reference = true,
+                "--watch" => parsed.watch = true,
+                "--stream-tiles" => parsed.stream_tiles = true,
+                "inspect" if scene_path.is_none() && !parsed.inspect => parsed.inspect = true,
+                flag if flag.starts_with("--") => {
+                    return Err(format!("Unknown option '{flag}'\n{USAGE}").into())
+                }
+                _ if scene_path.is_none() => scene_path = Some(arg),
+                _ => return Err(format!("Unexpected argument '{arg}'\n{USAGE}").into()),
+            }
+        }
+
+        parsed.scene_path = scene_path.ok_or(format!(
+            "Provide a path to the scene configuration as an argument\n{USAGE}"
+        ))?;
+        Ok(parsed)
+    }
+}
+
+/// Splits a comma-separated tag list, ignoring empty entries.
+fn parse_tags(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+}
+
+/// Parses durations such as `600s`, `10m`, `1.5h` or a bare number of seconds.
+fn parse_duration(value: &str) -> Result<Duration, Box<dyn Error>> {
+    let (number, unit_seconds) = match value.as_bytes().last() {
+        Some(b's') => (&value[..value.len() - 1], 1.0),
+        Some(b'm') => (&value[..value.len() - 1], 60.0),
+        Some(b'h') => (&value[..value.len() - 1], 3600.0),
+        _ => (value, 1.0),
+    };
+    let amount: f64 = number
+        .parse()
+        .map_err(|e| format!("Invalid duration '{value}' : {e}"))?;
+    // negative, infinite, NaN and too long durations are all rejected here
+    Duration::try_from_secs_f64(amount * unit_seconds)
+        .map_err(|e| format!("Invalid duration '{value}' : {e}").into())
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use crate::{
+    entity::HitRecord,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+/// Removes all geometry on the side of the plane that `normal` points to.
+#[derive(Debug, Clone)]
+pub struct SectionPlane {
+    point: Point3,
+    normal: Vec3,
+    /// Material for the flat face left where the plane cuts through a closed solid.
+    cap: Option<Arc<dyn Material>>,
+}
+
+impl SectionPlane {
+    pub fn new(point: Point3, normal: Vec3, cap: Option<Arc<dyn Material>>) -> Self {
+        Self {
+            point,
+            normal: normal.unit(),
+            cap,
+        }
+    }
+
+    #[inline]
+    pub fn clips(&self, point: Point3) -> bool {
+        (point - self.point).dot(self.normal) > 0.0
+    }
+
+    /// Ray parameter at which the ray crosses from the removed side into the kept one.
+    fn entry(&self, ray: &Ray) -> Option<f64> {
+        let denominator = self.normal.dot(*ray.direction());
+        if denominator >= 0.0 {
+            return None;
+        }
+        Some((self.point - *ray.origin()).dot(self.normal) / denominator)
+    }
+}
+
+/// Near/far limits for camera rays and section planes applied to every ray.
+#[derive(Debug, Clone, Default)]
+pub struct Clipping {
+    /// Distances along the view direction outside which primary hits are ignored.
+    pub near: Option<f64>,
+    pub far: Option<f64>,
+    pub sections: Vec<SectionPlane>,
+}
+
+impl Clipping {
+    #[inline]
+    pub fn clips(&self, point: Point3) -> bool {
+        self.sections.iter().any(|plane| plane.clips(point))
+    }
+
+    /// Cap face hit by a ray that enters the kept region within `interval`, for rays that
+    /// would otherwise see the inside of a cut solid.
+    pub fn cap(&self, ray: &Ray, interval: Interval) -> Option<HitRecord<'_>> {
+        let (time, plane, material) = self
+            .sections
+            .iter()
+            .filter_map(|plane| {
+                let material = plane.cap.as_deref()?;
+                let time = plane.entry(ray)?;
+                interval.surrounds(time).then_some((time, plane, material))
+            })
+            .filter(|&(time, plane, _)| {
+                let point = ray.at(time);
+                self.sections
+                    .iter()
+                    .all(|other| std::ptr::eq(other, plane) || !other.clips(point))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        Some(HitRecord::new(
+            ray.at(time),
+            ray,
+            plane.normal,
+            time,
+            0.0,
+            0.0,
+            material,
+        ))
+    }
+}
@@ -4,10 +4,11 @@ use std::sync::Arc;
 use crate::{
     aabb::Aabb,
     entity::{Entity, HitRecord},
+    epsilon,
     interval::Interval,
     material::Material,
     ray::Ray,
-    vec3::Vec3,
+    vec3::{Point3, Vec3},
 };
 
 #[derive(Debug, Clone)]
@@ -15,6 +16,9 @@ pub struct ConstantMedium {
     boundary: Arc<dyn Entity>,
     neg_inv_density: f64,
     phase_function: Arc<dyn Material>,
+    /// Boundaries of media that take precedence where they overlap this one, which is absent
+    /// inside them.
+    excluded: Vec<Arc<dyn Entity>>,
 }
 
 impl ConstantMedium {
@@ -23,19 +27,51 @@ impl ConstantMedium {
             boundary,
             neg_inv_density: -1.0 / density,
             phase_function,
+            excluded: Vec::new(),
         }
     }
+
+    /// Leaves the medium out of the volumes enclosed by `excluded`, given in the same space
+    /// as its boundary.
+    pub fn with_excluded(mut self, excluded: Vec<Arc<dyn Entity>>) -> Self {
+        self.excluded = excluded;
+        self
+    }
+
+    fn is_excluded(&self, point: Point3, direction: Vec3, time: f64) -> bool {
+        self.excluded
+            .iter()
+            .any(|boundary| encloses(&**boundary, point, direction, time))
+    }
+}
+
+/// Whether `point` is inside the closed `boundary`: the first surface a probe from it
+/// crosses is then seen from behind.
+fn encloses(boundary: &dyn Entity, point: Point3, direction: Vec3, time: f64) -> bool {
+    if !boundary.bounding_box().contains(point) {
+        return false;
+    }
+    let probe = Ray::new(point, direction, time);
+    boundary
+        .hit(&probe, Interval::new(0.0, f64::INFINITY))
+        .is_some_and(|hit| !hit.front)
 }
 
 impl Entity for ConstantMedium {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
         let mut t1 = self
             .boundary
             .hit(ray, Interval::new(f64::NEG_INFINITY, f64::INFINITY))?
             .time;
         let mut t2 = self
             .boundary
-            .hit(ray, Interval::new(t1 + 0.0001, f64::INFINITY))?
+            .hit(
+                ray,
+                Interval::new(
+                    t1 + epsilon::scene_epsilon() / ray.direction().length(),
+                    f64::INFINITY,
+                ),
+            )?
             .time;
 
         if t1 < time_interval.start {
@@ -55,14 +91,22 @@ impl Entity for ConstantMedium {
         }
 
         let ray_length = ray.direction().length();
-        let distance_inside_boundary = (t2 - t1) * ray_length;
-        let hit_distance = self.neg_inv_density * fastrand::f64().ln();
+        let mut time = t1;
+        loop {
+            let distance_inside_boundary = (t2 - time) * ray_length;
+            let hit_distance = self.neg_inv_density * fastrand::f64().ln();
 
-        if hit_distance > distance_inside_boundary {
-            return None;
-        }
+            if hit_distance > distance_inside_boundary {
+                return None;
+            }
 
-        let time = t1 + hit_distance / ray_length;
+            time += hit_distance / ray_length;
+            // where a medium of higher priority takes over this one has no density, so the
+            // collision is a null one and the flight carries on from it
+            if !self.is_excluded(ray.at(time), *ray.direction(), *ray.time()) {
+                break;
+            }
+        }
 
         Some(HitRecord::raw(
             ray.at(time),
@@ -79,4 +123,14 @@ impl Entity for ConstantMedium {
     fn bounding_box(&self) -> Aabb {
         self.boundary.bounding_box()
     }
+
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        // off the axes, so the probe doesn't run along the edges of boxes
+        let probe = Vec3::new(0.36, 0.8, 0.48);
+        if encloses(&*self.boundary, point, probe, time) && !self.is_excluded(point, probe, time) {
+            -1.0 / self.neg_inv_density
+        } else {
+            0.0
+        }
+    }
 }
@@ -0,0 +1,85 @@
+//! Tallies of primitive intersection tests, compiled in with the `instrumentation` feature so
+//! a change that makes the BVH or a primitive do more work shows up as a count, not just as a
+//! render time that varies from run to run. Without the feature counting is a no-op.
+
+#[cfg(feature = "instrumentation")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Kind of intersection test being counted.
+#[derive(Debug, Clone, Copy)]
+pub enum Test {
+    Sphere,
+    Quad,
+    Triangle,
+    Polygon,
+    Sdf,
+    Aabb,
+}
+
+impl Test {
+    #[cfg(feature = "instrumentation")]
+    const ALL: [Test; 6] = [
+        Test::Sphere,
+        Test::Quad,
+        Test::Triangle,
+        Test::Polygon,
+        Test::Sdf,
+        Test::Aabb,
+    ];
+
+    #[cfg(feature = "instrumentation")]
+    fn name(self) -> &'static str {
+        match self {
+            Test::Sphere => "Sphere",
+            Test::Quad => "Quad",
+            Test::Triangle => "Triangle",
+            Test::Polygon => "Polygon",
+            Test::Sdf => "SDF",
+            Test::Aabb => "AABB",
+        }
+    }
+}
+
+#[cfg(feature = "instrumentation")]
+static COUNTS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+
+#[inline(always)]
+#[allow(unused_variables)]
+pub fn count(test: Test) {
+    #[cfg(feature = "instrumentation")]
+    COUNTS[test as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Tests of kind `test` counted so far, always 0 without the feature.
+#[allow(unused_variables)]
+pub fn total(test: Test) -> u64 {
+    #[cfg(feature = "instrumentation")]
+    return COUNTS[test as usize].load(Ordering::Relaxed);
+    #[cfg(not(feature = "instrumentation"))]
+    0
+}
+
+/// Sets every count back to zero, so the tests one piece of work does can be read alone.
+pub fn reset() {
+    #[cfg(feature = "instrumentation")]
+    for count in &COUNTS {
+        count.store(0, Ordering::Relaxed);
+    }
+}
+
+/// One line listing every count, `None` without the feature.
+pub fn summary() -> Option<String> {
+    #[cfg(feature = "instrumentation")]
+    return Some(
+        Test::ALL
+            .iter()
+            .map(|&test| {
+                let total = COUNTS[test as usize].load(Ordering::Relaxed);
+                format!("{}: {total}", test.name())
+            })
+            .collect::<Vec<_>>()
+            .join(" | "),
+    );
+    #[cfg(not(feature = "instrumentation"))]
+    None
+}
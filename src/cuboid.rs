@@ -5,7 +5,7 @@ use crate::{
     material::Material,
     quad::Quad,
     ray::Ray,
-    vec3::{Point3, Vec3},
+    vec3::{Color, Point3, Vec3},
 };
 use std::sync::Arc;
 
@@ -16,6 +16,13 @@ pub struct Cuboid {
 
 impl Cuboid {
     pub fn new(a: Point3, b: Point3, material: Arc<dyn Material>) -> Self {
+        Self::with_face_materials(a, b, std::array::from_fn(|_| material.clone()))
+    }
+
+    /// Cuboid with a material of its own on each face, in the order front (+z), right (+x),
+    /// back (-z), left (-x), top (+y) and bottom (-y).
+    pub fn with_face_materials(a: Point3, b: Point3, materials: [Arc<dyn Material>; 6]) -> Self {
+        let [front, right, back, left, top, bottom] = materials;
         let mut faces = EntityCluster::new();
         let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
         let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
@@ -28,42 +35,42 @@ impl Cuboid {
             Point3::new(min.x(), min.y(), max.z()),
             dx,
             dy,
-            material.clone(),
+            front,
         ))); // front
 
         faces.push(Arc::new(Quad::new(
             Point3::new(max.x(), min.y(), max.z()),
             -dz,
             dy,
-            material.clone(),
+            right,
         ))); // right
 
         faces.push(Arc::new(Quad::new(
             Point3::new(max.x(), min.y(), min.z()),
             -dx,
             dy,
-            material.clone(),
+            back,
         ))); // back
 
         faces.push(Arc::new(Quad::new(
             Point3::new(min.x(), min.y(), min.z()),
             dz,
             dy,
-            material.clone(),
+            left,
         ))); // left
 
         faces.push(Arc::new(Quad::new(
             Point3::new(min.x(), max.y(), max.z()),
             dx,
             -dz,
-            material.clone(),
+            top,
         ))); // top
 
         faces.push(Arc::new(Quad::new(
             Point3::new(min.x(), min.y(), min.z()),
             dx,
             dz,
-            material,
+            bottom,
         ))); // bottom
 
         Self { faces }
@@ -71,7 +78,7 @@ impl Cuboid {
 }
 
 impl Entity for Cuboid {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
         self.faces.hit(ray, time_interval)
     }
 
@@ -79,4 +86,8 @@ impl Entity for Cuboid {
     fn bounding_box(&self) -> Aabb {
         self.faces.bounding_box()
     }
+
+    fn emitted_power(&self) -> Color {
+        self.faces.emitted_power()
+    }
 }
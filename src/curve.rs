@@ -0,0 +1,177 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BVHNode,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    memory::{self, Category},
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+use std::{mem::size_of, sync::Arc};
+
+/// Cubic Bézier through its four control points at `t`.
+fn bezier([p0, p1, p2, p3]: &[Point3; 4], t: f64) -> Point3 {
+    let s = 1.0 - t;
+    s * s * s * *p0 + 3.0 * s * s * t * *p1 + 3.0 * s * t * t * *p2 + t * t * t * *p3
+}
+
+/// How thick strands are along their length.
+#[derive(Debug, Clone, Copy)]
+pub struct CurveWidth {
+    pub root: f64,
+    pub tip: f64,
+    /// Exponent shaping the taper from root to tip: 1 narrows evenly, larger values keep the
+    /// strand near its root width for longer and smaller ones thin it out early.
+    pub falloff: f64,
+}
+
+impl CurveWidth {
+    fn at(&self, t: f64) -> f64 {
+        self.root + (self.tip - self.root) * t.powf(self.falloff)
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    material: Arc<dyn Material>,
+}
+
+/// Straight piece of a strand, a cone between two radii that is hit as a ribbon turned to
+/// face the ray, with normals bent around it as if it were round.
+#[derive(Debug)]
+struct Segment {
+    shared: Arc<Shared>,
+    start: Point3,
+    end: Point3,
+    radii: (f64, f64),
+    /// Span of the curve parameter covered, used as the texture coordinate along the strand.
+    span: (f64, f64),
+    bounding_box: Aabb,
+}
+
+impl Segment {
+    fn radius(&self, s: f64) -> f64 {
+        self.radii.0 + (self.radii.1 - self.radii.0) * s
+    }
+}
+
+impl Entity for Segment {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let (origin, direction) = (*ray.origin(), *ray.direction());
+        let axis = self.end - self.start;
+        // closest approach between the ray and the segment's line
+        let w = origin - self.start;
+        let (a, b, c) = (
+            direction.dot(direction),
+            direction.dot(axis),
+            axis.dot(axis),
+        );
+        let (d, e) = (direction.dot(w), axis.dot(w));
+        let denominator = a * c - b * b;
+        if denominator <= 1e-12 * a * c {
+            return None;
+        }
+        // rays leaving the strand start inside it and would find it again right away
+        let nearest_to_origin = (e / c).clamp(0.0, 1.0);
+        if (w - nearest_to_origin * axis).length() < self.radius(nearest_to_origin) {
+            return None;
+        }
+        let s = ((a * e - b * d) / denominator).clamp(0.0, 1.0);
+        let on_axis = self.start + s * axis;
+        let radius = self.radius(s);
+        let time = (on_axis - origin).dot(direction) / a;
+        if !time_interval.surrounds(time) {
+            return None;
+        }
+        let hit_point = ray.at(time);
+        let offset = hit_point - on_axis;
+        if offset.length() > radius {
+            return None;
+        }
+
+        let tangent = axis / c.sqrt();
+        let side = offset - offset.dot(tangent) * tangent;
+        let back = -direction.unit();
+        let facing = (back - back.dot(tangent) * tangent).unit();
+        let lift = (radius * radius - side.length_sq()).max(0.0).sqrt();
+        let normal = (side + lift * facing).unit();
+        let across = tangent.cross(facing).dot(side) / radius;
+        let u = self.span.0 + (self.span.1 - self.span.0) * s;
+        let mut hit_record = HitRecord::new(
+            hit_point,
+            ray,
+            normal,
+            time,
+            u,
+            0.5 + 0.5 * across,
+            &*self.shared.material,
+        );
+        hit_record.set_tangent(tangent);
+        hit_record.dp_du = axis / (self.span.1 - self.span.0);
+        Some(hit_record)
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
+
+/// Strands such as hair or grass, each a cubic Bézier curve from root to tip cut into
+/// straight segments under one BVH.
+#[derive(Debug)]
+pub struct Curves {
+    bvh: BVHNode,
+}
+
+impl Curves {
+    /// Builds every curve in `curves`, given by its four control points, from
+    /// `segments_per_curve` pieces; there must be at least one curve and one piece each.
+    pub fn new(
+        curves: &[[Point3; 4]],
+        width: CurveWidth,
+        segments_per_curve: u32,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let shared = Arc::new(Shared { material });
+        let mut segments: Vec<Arc<dyn Entity>> = Vec::new();
+        for curve in curves {
+            for i in 0..segments_per_curve {
+                let span = (
+                    i as f64 / segments_per_curve as f64,
+                    (i + 1) as f64 / segments_per_curve as f64,
+                );
+                let (start, end) = (bezier(curve, span.0), bezier(curve, span.1));
+                let radii = (0.5 * width.at(span.0), 0.5 * width.at(span.1));
+                let padding = radii.0.max(radii.1);
+                let padding = Vec3::new(padding, padding, padding);
+                let bounding_box = Aabb::enclose(
+                    &Aabb::new_from_points(start - padding, start + padding),
+                    &Aabb::new_from_points(end - padding, end + padding),
+                );
+                segments.push(Arc::new(Segment {
+                    shared: shared.clone(),
+                    start,
+                    end,
+                    radii,
+                    span,
+                    bounding_box,
+                }));
+            }
+        }
+        memory::track(Category::Meshes, segments.len() * size_of::<Segment>());
+        let bvh = BVHNode::new(&mut segments);
+        Self { bvh }
+    }
+}
+
+impl Entity for Curves {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, time_interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
@@ -5,18 +5,49 @@ use crate::{
     interval::Interval,
     material::Material,
     ray::Ray,
-    vec3::{Point3, Vec3},
+    vec3::{Color, Point3, Vec3},
 };
 
+/// Light a medium scattered towards a ray up to a hit, and the share of the light from beyond
+/// the hit that made it through the medium.
+#[derive(Debug, Clone, Copy)]
+pub struct Inscattered {
+    pub radiance: Color,
+    pub transmittance: Color,
+}
+
 #[derive(Debug, Clone)]
 pub struct HitRecord<'a> {
     pub hit_point: Point3,
+    /// Hit point in the primitive's own space; instance wrappers leave this untouched.
+    pub object_point: Point3,
     pub normal: Vec3,
+    /// Shading frame completing `normal`; follows increasing `u` where the primitive has one.
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub time: f64,
     pub front: bool,
     pub material: &'a dyn Material,
     pub u: f64,
     pub v: f64,
+    /// World-space displacement of the hit surface over the shutter interval.
+    pub velocity: Vec3,
+    /// Surface derivatives with respect to (u, v); zero when the primitive doesn't provide them.
+    pub dp_du: Vec3,
+    pub dp_dv: Vec3,
+    /// Change in hit point per pixel step, filled in from the ray's differentials.
+    pub dp_dx: Vec3,
+    pub dp_dy: Vec3,
+    /// World-space distance to the nearest primitive edge; infinite for primitives without edges.
+    pub edge_distance: f64,
+    /// Color interpolated from the primitive's vertices, for meshes that carry one.
+    pub vertex_color: Option<Color>,
+    /// For entities that shade the stretch of ray leading up to the hit rather than a surface.
+    pub inscattered: Option<Inscattered>,
+    /// World-space unit direction back along the ray that made the hit, and the distance it
+    /// travelled; zero until `set_view` is called with that ray.
+    pub view: Vec3,
+    pub distance: f64,
 }
 
 impl<'a> HitRecord<'a> {
@@ -35,14 +66,28 @@ impl<'a> HitRecord<'a> {
         } else {
             -outward_normal
         };
+        let (tangent, bitangent) = normal.orthonormal_basis();
         Self {
             hit_point,
+            object_point: hit_point,
             normal,
+            tangent,
+            bitangent,
             time,
             front,
             material,
             u,
             v,
+            velocity: Vec3::default(),
+            dp_du: Vec3::default(),
+            dp_dv: Vec3::default(),
+            dp_dx: Vec3::default(),
+            dp_dy: Vec3::default(),
+            edge_distance: f64::INFINITY,
+            vertex_color: None,
+            inscattered: None,
+            view: Vec3::default(),
+            distance: 0.0,
         }
     }
 
@@ -55,31 +100,101 @@ impl<'a> HitRecord<'a> {
         v: f64,
         material: &'a dyn Material,
     ) -> Self {
+        let (tangent, bitangent) = normal.orthonormal_basis();
         Self {
             hit_point,
+            object_point: hit_point,
             normal,
+            tangent,
+            bitangent,
             time,
             front,
             material,
             u,
             v,
+            velocity: Vec3::default(),
+            dp_du: Vec3::default(),
+            dp_dv: Vec3::default(),
+            dp_dx: Vec3::default(),
+            dp_dy: Vec3::default(),
+            edge_distance: f64::INFINITY,
+            vertex_color: None,
+            inscattered: None,
+            view: Vec3::default(),
+            distance: 0.0,
+        }
+    }
+
+    /// Intersects the ray's offset rays with the tangent plane to estimate the pixel footprint.
+    pub fn apply_differentials(&mut self, ray: &Ray) {
+        let Some(differentials) = ray.differentials() else {
+            return;
+        };
+        let offset = |origin: Point3, direction: Vec3| {
+            let denominator = self.normal.dot(direction);
+            if denominator.abs() < 1e-12 {
+                return Vec3::default();
+            }
+            let t = self.normal.dot(self.hit_point - origin) / denominator;
+            origin + t * direction - self.hit_point
+        };
+        self.dp_dx = offset(differentials.rx_origin, differentials.rx_direction);
+        self.dp_dy = offset(differentials.ry_origin, differentials.ry_direction);
+    }
+
+    /// Records where the hit was seen from, given the world-space ray that made it.
+    pub fn set_view(&mut self, ray: &Ray) {
+        self.view = -ray.direction().unit();
+        self.distance = self.time * ray.direction().length();
+    }
+
+    /// Replaces the default shading frame with one aligned to `tangent`, which is
+    /// re-orthogonalized against the normal.
+    pub fn set_tangent(&mut self, tangent: Vec3) {
+        let projected = tangent - tangent.dot(self.normal) * self.normal;
+        if projected.near_zero() {
+            return;
         }
+        self.tangent = projected.unit();
+        self.bitangent = self.normal.cross(self.tangent);
     }
 }
 
 pub trait Entity: Send + Sync + std::fmt::Debug {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>>;
     fn bounding_box(&self) -> Aabb;
+
+    /// Point and outward normal at texture coordinates (u, v), for entities whose UVs map
+    /// one-to-one onto their surface.
+    fn surface_point(&self, _u: f64, _v: f64) -> Option<(Point3, Vec3)> {
+        None
+    }
+
+    /// Uniformly chosen point on the surface, as a hit record, together with the surface area;
+    /// `None` for entities that can't be sampled as lights.
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        None
+    }
+
+    /// Estimated radiant power leaving the entity's emissive surfaces.
+    fn emitted_power(&self) -> Color {
+        Color::default()
+    }
+
+    /// Density of the participating media at `point`, zero outside any.
+    fn density(&self, _point: Point3, _time: f64) -> f64 {
+        0.0
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct EntityCluster {
     entities: Vec<Arc<dyn Entity>>,
     bounding_box: Aabb,
 }
 
 impl Entity for EntityCluster {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
         let mut closest = time_interval.end;
         let mut result = None;
         for entity in &self.entities {
@@ -95,14 +210,25 @@ impl Entity for EntityCluster {
     fn bounding_box(&self) -> Aabb {
         self.bounding_box
     }
+
+    fn emitted_power(&self) -> Color {
+        self.entities
+            .iter()
+            .map(|entity| entity.emitted_power())
+            .sum()
+    }
+
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        self.entities
+            .iter()
+            .map(|entity| entity.density(point, time))
+            .sum()
+    }
 }
 
 impl EntityCluster {
     pub fn new() -> Self {
-        Self {
-            entities: Vec::new(),
-            bounding_box: Aabb::default(),
-        }
+        Self::default()
     }
 
     pub fn push(&mut self, entity: Arc<dyn Entity>) {
@@ -0,0 +1,134 @@
+use crate::{
+    memory::{self, Category},
+    vec3::{Color, Vec3},
+};
+use std::{
+    error::Error,
+    f64::consts::{PI, TAU},
+};
+
+/// Light arriving from infinitely far away, read from an equirectangular image laid out like
+/// the UVs of a sphere.
+///
+/// Texels are picked in proportion to the light they send, so direct lighting finds a small
+/// bright sun rather than waiting for paths to stumble onto it.
+#[derive(Debug)]
+pub struct Environment {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    /// Cumulative weights of the rows, top first, normalized to end at one.
+    marginal: Vec<f64>,
+    /// Cumulative weights of each row's texels, normalized to end at one within the row.
+    conditional: Vec<f64>,
+    /// Sum of the texel weights, turning a texel's weight into its probability.
+    total: f64,
+}
+
+impl Environment {
+    pub fn load(image_path: &str, intensity: f64) -> Result<Self, Box<dyn Error>> {
+        let img = image::open(image_path)
+            .map_err(|e| format!("Failed to open environment '{image_path}' : {e}"))?
+            .to_rgb32f();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let texels: Vec<Color> = img
+            .pixels()
+            .map(|p| intensity * Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+        memory::track(
+            Category::Textures,
+            texels.len() * std::mem::size_of::<Color>(),
+        );
+
+        // rows near the poles cover less of the sphere, so they're weighted by sin(theta)
+        let mut conditional = Vec::with_capacity(width * height);
+        let mut row_weights = Vec::with_capacity(height);
+        for (y, row) in texels.chunks(width).enumerate() {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            let start = conditional.len();
+            let mut sum = 0.0;
+            for texel in row {
+                sum += texel.luminance().max(0.0) * sin_theta;
+                conditional.push(sum);
+            }
+            if sum > 0.0 {
+                conditional[start..].iter_mut().for_each(|c| *c /= sum);
+            }
+            row_weights.push(sum);
+        }
+        let total: f64 = row_weights.iter().sum();
+        let marginal = row_weights
+            .iter()
+            .scan(0.0, |sum, weight| {
+                *sum += weight / total;
+                Some(*sum)
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            texels,
+            marginal,
+            conditional,
+            total,
+        })
+    }
+
+    /// Direction at the center of texel (x, y) offset by a fraction of a texel.
+    fn direction(&self, x: f64, y: f64) -> Vec3 {
+        // inverse of `texel`, matching the UVs of a sphere
+        let theta = PI * (1.0 - y / self.height as f64);
+        let phi = TAU * x / self.width as f64;
+        Vec3::new(
+            -theta.sin() * phi.cos(),
+            -theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+
+    fn texel(&self, direction: Vec3) -> (usize, usize) {
+        let direction = direction.unit();
+        let theta = (-direction.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-direction.z()).atan2(direction.x()) + PI;
+        let x = (phi / TAU * self.width as f64) as usize;
+        let y = ((1.0 - theta / PI) * self.height as f64) as usize;
+        (x.min(self.width - 1), y.min(self.height - 1))
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        let (x, y) = self.texel(direction);
+        self.texels[y * self.width + x]
+    }
+
+    /// Probability density over solid angle of `sample` returning a direction in texel (x, y).
+    fn texel_pdf(&self, x: usize, y: usize) -> f64 {
+        let sin_theta = (PI * (y as f64 + 0.5) / self.height as f64).sin();
+        if self.total <= 0.0 || sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let weight = self.texels[y * self.width + x].luminance().max(0.0) * sin_theta;
+        // the texel's probability spread over its solid angle of 2 pi^2 sin(theta) / texel count
+        weight / self.total * (self.width * self.height) as f64 / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// Direction picked in proportion to the light arriving from it, with its radiance and
+    /// probability density over solid angle; `None` for a black environment.
+    pub fn sample(&self) -> Option<(Vec3, Color, f64)> {
+        if self.total <= 0.0 {
+            return None;
+        }
+        let (row_target, column_target) = (fastrand::f64(), fastrand::f64());
+        let y = self
+            .marginal
+            .partition_point(|&c| c < row_target)
+            .min(self.height - 1);
+        let row = &self.conditional[y * self.width..(y + 1) * self.width];
+        let x = row
+            .partition_point(|&c| c < column_target)
+            .min(self.width - 1);
+        let direction = self.direction(x as f64 + fastrand::f64(), y as f64 + fastrand::f64());
+        let pdf = self.texel_pdf(x, y);
+        (pdf > 0.0).then(|| (direction, self.texels[y * self.width + x], pdf))
+    }
+}
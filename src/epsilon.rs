@@ -0,0 +1,35 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{aabb::Aabb, interval::Interval, ray::Ray};
+
+// tolerance as a fraction of the scene diagonal
+const RELATIVE_EPSILON: f64 = 1e-6;
+// used until a scene has been built
+const DEFAULT_EPSILON: f64 = 0.001;
+
+static SCENE_EPSILON: AtomicU64 = AtomicU64::new(DEFAULT_EPSILON.to_bits());
+
+/// Derives the intersection tolerance from the world bounds, so tiny and huge scenes get
+/// offsets proportional to their size.
+pub fn set_scene_extent(bounds: &Aabb) {
+    let diagonal =
+        (bounds.x().size().powi(2) + bounds.y().size().powi(2) + bounds.z().size().powi(2)).sqrt();
+    let epsilon = if diagonal.is_finite() && diagonal > 0.0 {
+        RELATIVE_EPSILON * diagonal
+    } else {
+        DEFAULT_EPSILON
+    };
+    SCENE_EPSILON.store(epsilon.to_bits(), Ordering::Relaxed);
+}
+
+/// World-space distance below which a hit is treated as self-intersection.
+#[inline]
+pub fn scene_epsilon() -> f64 {
+    f64::from_bits(SCENE_EPSILON.load(Ordering::Relaxed))
+}
+
+/// Ray parameter range that skips hits closer than the scene epsilon.
+#[inline]
+pub fn ray_interval(ray: &Ray) -> Interval {
+    Interval::new(scene_epsilon() / ray.direction().length(), f64::INFINITY)
+}
@@ -0,0 +1,270 @@
+use crate::{
+    tile::{FinishedTile, Tile},
+    vec3::Color,
+};
+use std::ops::{Add, AddAssign};
+
+/// Running filter-weighted sums for one pixel, from which the mean and its variance can be
+/// recovered at any time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelAccumulator {
+    sum: Color,
+    sum_sq: Color,
+    /// Weighted sum of the samples' coverage, written out as alpha.
+    coverage: f64,
+    weight: f64,
+    count: u32,
+    cost: f64,
+}
+
+impl PixelAccumulator {
+    /// `coverage` is how much of the sample is opaque, one unless a camera ray found nothing
+    /// but a transparent backplate.
+    #[inline]
+    pub fn add_sample(&mut self, sample: Color, coverage: f64, weight: f64) {
+        self.sum += weight * sample;
+        self.sum_sq += weight * sample * sample;
+        self.coverage += weight * coverage;
+        self.weight += weight;
+        self.count += 1;
+    }
+
+    /// Records rendering effort spent on the pixel, in whatever unit the renderer measures.
+    #[inline]
+    pub fn add_cost(&mut self, cost: f64) {
+        self.cost += cost;
+    }
+
+    #[inline]
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    #[inline]
+    pub fn mean(&self) -> Color {
+        if self.weight == 0.0 {
+            Color::default()
+        } else {
+            self.sum / self.weight
+        }
+    }
+
+    /// Fraction of the pixel covered by the scene; `mean` is already multiplied by it.
+    #[inline]
+    pub fn alpha(&self) -> f64 {
+        if self.weight == 0.0 {
+            0.0
+        } else {
+            self.coverage / self.weight
+        }
+    }
+
+    /// Per-channel sample variance, bias-corrected by the number of samples.
+    pub fn variance(&self) -> Color {
+        if self.count < 2 || self.weight == 0.0 {
+            return Color::default();
+        }
+        let n = self.count as f64;
+        let mean = self.mean();
+        let v = (self.sum_sq / self.weight - mean * mean) * (n / (n - 1.0));
+        Color::new(v.x().max(0.0), v.y().max(0.0), v.z().max(0.0))
+    }
+
+    /// Standard error of the pixel mean, averaged over the three channels.
+    pub fn std_error(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        let v = self.variance() / self.count as f64;
+        (v.x().sqrt() + v.y().sqrt() + v.z().sqrt()) / 3.0
+    }
+}
+
+impl Add for PixelAccumulator {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            sum: self.sum + other.sum,
+            sum_sq: self.sum_sq + other.sum_sq,
+            coverage: self.coverage + other.coverage,
+            weight: self.weight + other.weight,
+            count: self.count + other.count,
+            cost: self.cost + other.cost,
+        }
+    }
+}
+
+impl AddAssign for PixelAccumulator {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<PixelAccumulator>,
+    /// Factor applied to each channel of resolved radiance.
+    exposure: Color,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![PixelAccumulator::default(); (width * height) as usize],
+            exposure: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Brightens each channel of the resolved image by its `stops` powers of two.
+    pub fn with_exposure(mut self, stops: Color) -> Self {
+        self.exposure = Color::new(stops.x().exp2(), stops.y().exp2(), stops.z().exp2());
+        self
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn add_sample(&mut self, index: usize, sample: Color, coverage: f64, weight: f64) {
+        self.pixels[index].add_sample(sample, coverage, weight);
+    }
+
+    #[inline]
+    pub fn add_cost(&mut self, index: usize, cost: f64) {
+        self.pixels[index].add_cost(cost);
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &[PixelAccumulator] {
+        &self.pixels
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [PixelAccumulator] {
+        &mut self.pixels
+    }
+
+    /// Merges accumulators rendered for `tile`, given row by row.
+    pub fn add_tile(&mut self, tile: &Tile, pixels: &[PixelAccumulator]) {
+        for ((i, j), pixel) in tile.pixels().zip(pixels) {
+            self.pixels[(j * self.width + i) as usize] += *pixel;
+        }
+    }
+
+    /// `tile`'s pixels resolved as they stand after `samples` per pixel, with `added`, rendered
+    /// for the tile row by row but not yet merged, counted in.
+    pub fn finished_tile(
+        &self,
+        tile: &Tile,
+        added: Option<&[PixelAccumulator]>,
+        samples: u32,
+    ) -> FinishedTile {
+        let pixels: Vec<PixelAccumulator> = tile
+            .pixels()
+            .enumerate()
+            .map(|(index, (i, j))| {
+                let pixel = self.pixels[(j * self.width + i) as usize];
+                added.map_or(pixel, |added| pixel + added[index])
+            })
+            .collect();
+        FinishedTile {
+            tile: *tile,
+            samples,
+            colors: pixels
+                .iter()
+                .map(|pixel| self.exposure * pixel.mean())
+                .collect(),
+            alpha: pixels.iter().map(PixelAccumulator::alpha).collect(),
+        }
+    }
+
+    /// Cost recorded so far for the pixels of `tile`.
+    pub fn tile_cost(&self, tile: &Tile) -> f64 {
+        tile.pixels()
+            .map(|(i, j)| self.pixels[(j * self.width + i) as usize].cost())
+            .sum()
+    }
+
+    pub fn min_samples(&self) -> u32 {
+        self.pixels
+            .iter()
+            .map(PixelAccumulator::count)
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn max_samples(&self) -> u32 {
+        self.pixels
+            .iter()
+            .map(PixelAccumulator::count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Lays `frames` of one size out in a grid `columns` wide, left to right and then top to
+    /// bottom, leaving the cells past the last frame empty.
+    pub fn tiled(frames: &[Film], columns: u32) -> Self {
+        let (width, height) = (frames[0].width, frames[0].height);
+        let rows = (frames.len() as u32).div_ceil(columns);
+        let mut sheet = Self::new(columns * width, rows * height);
+        sheet.exposure = frames[0].exposure;
+        for (index, frame) in frames.iter().enumerate() {
+            let (column, row) = (index as u32 % columns, index as u32 / columns);
+            for (j, line) in frame.pixels.chunks_exact(width as usize).enumerate() {
+                let start = ((row * height + j as u32) * sheet.width + column * width) as usize;
+                sheet.pixels[start..start + width as usize].copy_from_slice(line);
+            }
+        }
+        sheet
+    }
+
+    pub fn resolve(&self) -> Vec<Color> {
+        self.pixels
+            .iter()
+            .map(|pixel| self.exposure * pixel.mean())
+            .collect()
+    }
+
+    pub fn resolve_alpha(&self) -> Vec<f64> {
+        self.pixels.iter().map(PixelAccumulator::alpha).collect()
+    }
+
+    /// Resolves the image using only pixels on a `stride` grid, each filling its whole block.
+    pub fn resolve_blocks(&self, stride: u32) -> Vec<Color> {
+        let width = self.width as usize;
+        let stride = stride as usize;
+        (0..self.pixels.len())
+            .map(|index| {
+                let (i, j) = (index % width, index / width);
+                self.exposure * self.pixels[(j - j % stride) * width + (i - i % stride)].mean()
+            })
+            .collect()
+    }
+
+    pub fn mean_std_error(&self) -> f64 {
+        self.pixels
+            .iter()
+            .map(PixelAccumulator::std_error)
+            .sum::<f64>()
+            / self.pixels.len() as f64
+    }
+}
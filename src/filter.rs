@@ -0,0 +1,89 @@
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum FilterKind {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    BlackmanHarris,
+}
+
+impl FilterKind {
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell | FilterKind::BlackmanHarris => 2.0,
+        }
+    }
+}
+
+/// Separable pixel reconstruction filter; camera samples are spread uniformly over its support
+/// and weighted by it when accumulated.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelFilter {
+    kind: FilterKind,
+    radius: f64,
+}
+
+impl Default for PixelFilter {
+    fn default() -> Self {
+        Self::new(FilterKind::default(), None)
+    }
+}
+
+impl PixelFilter {
+    pub fn new(kind: FilterKind, radius: Option<f64>) -> Self {
+        Self {
+            kind,
+            radius: radius.unwrap_or_else(|| kind.default_radius()),
+        }
+    }
+
+    /// Returns a sub-pixel offset within the filter support and the filter weight at it.
+    pub fn sample(&self) -> (f64, f64, f64) {
+        let x = fastrand_contrib::f64_range(-self.radius..self.radius);
+        let y = fastrand_contrib::f64_range(-self.radius..self.radius);
+        (x, y, self.evaluate(x) * self.evaluate(y))
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        let r = self.radius;
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => (1.0 - x.abs() / r).max(0.0),
+            FilterKind::Gaussian => {
+                const ALPHA: f64 = 2.0;
+                ((-ALPHA * x * x).exp() - (-ALPHA * r * r).exp()).max(0.0)
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x.abs() / r),
+            FilterKind::BlackmanHarris => {
+                const A: [f64; 4] = [0.35875, 0.48829, 0.14128, 0.01168];
+                let t = 2.0 * PI * (x + r) / (2.0 * r);
+                A[0] - A[1] * t.cos() + A[2] * (2.0 * t).cos() - A[3] * (3.0 * t).cos()
+            }
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic with B = C = 1/3, defined on [0, 2].
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * B - 6.0 * C) * x.powi(3)
+            + (-18.0 + 12.0 * B + 6.0 * C) * x.powi(2)
+            + (6.0 - 2.0 * B)
+    } else if x < 2.0 {
+        (-B - 6.0 * C) * x.powi(3)
+            + (6.0 * B + 30.0 * C) * x.powi(2)
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
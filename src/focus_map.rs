@@ -0,0 +1,37 @@
+use std::error::Error;
+
+/// Focus distances read from a grayscale image, stretched over the frame so different regions
+/// can be in focus at different depths (e.g. for a tilt-shift look).
+#[derive(Debug, Clone)]
+pub struct FocusMap {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+    near: f64,
+    far: f64,
+}
+
+impl FocusMap {
+    /// Loads the map at `image_path`; black maps to `near` and white to `far`.
+    pub fn load(image_path: &str, near: f64, far: f64) -> Result<Self, Box<dyn Error>> {
+        let img = image::open(image_path)
+            .map_err(|e| format!("Failed to open focus map {image_path}: {e}"))?
+            .to_luma32f();
+        let (width, height) = img.dimensions();
+        Ok(Self {
+            width,
+            height,
+            values: img.into_raw(),
+            near,
+            far,
+        })
+    }
+
+    /// Focus distance at fractional image coordinates (`x`, `y`), each in [0, 1).
+    pub fn distance(&self, x: f64, y: f64) -> f64 {
+        let px = ((x * self.width as f64) as u32).min(self.width - 1);
+        let py = ((y * self.height as f64) as u32).min(self.height - 1);
+        let value = self.values[(py * self.width + px) as usize].clamp(0.0, 1.0) as f64;
+        self.near + value * (self.far - self.near)
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    f64::consts::PI,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use crate::{
+    aabb::Aabb,
+    vec3::{Point3, Vec3},
+};
+
+// a quadrant holding more than this fraction of a tree's energy is subdivided
+const REFINE_THRESHOLD: f64 = 0.01;
+const MAX_QUAD_DEPTH: u32 = 12;
+// records a spatial leaf collects before it is split in two
+const SPLIT_SAMPLES: u64 = 8000;
+const MAX_SPATIAL_DEPTH: u32 = 24;
+
+/// Maps a unit direction onto the unit square with an area-preserving cylindrical projection.
+fn to_square(direction: Vec3) -> (f64, f64) {
+    let z = direction.z().clamp(-1.0, 1.0);
+    let phi = direction.y().atan2(direction.x());
+    let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+    (
+        ((z + 1.0) / 2.0).min(1.0 - f64::EPSILON),
+        (phi / (2.0 * PI)).min(1.0 - f64::EPSILON),
+    )
+}
+
+fn from_square((x, y): (f64, f64)) -> Vec3 {
+    let z = 2.0 * x - 1.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * y;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+fn with_component(point: Point3, axis: u8, value: f64) -> Point3 {
+    match axis {
+        0 => Point3::new(value, point.y(), point.z()),
+        1 => Point3::new(point.x(), value, point.z()),
+        _ => Point3::new(point.x(), point.y(), value),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct QuadNode {
+    energy: [f64; 4],
+    /// Index of each quadrant's child node; 0 marks a leaf quadrant.
+    children: [usize; 4],
+}
+
+/// Returns the quadrant containing (x, y) and the point's coordinates inside it.
+fn quadrant(x: f64, y: f64) -> (usize, f64, f64) {
+    let (qx, qy) = (usize::from(x >= 0.5), usize::from(y >= 0.5));
+    (qx + 2 * qy, 2.0 * x - qx as f64, 2.0 * y - qy as f64)
+}
+
+/// Adaptive quadtree over the direction square, storing incident energy per quadrant.
+#[derive(Debug, Clone)]
+struct DirectionTree {
+    nodes: Vec<QuadNode>,
+}
+
+impl Default for DirectionTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![QuadNode::default()],
+        }
+    }
+}
+
+impl DirectionTree {
+    fn total(&self) -> f64 {
+        self.nodes[0].energy.iter().sum()
+    }
+
+    fn record(&mut self, (mut x, mut y): (f64, f64), value: f64) {
+        let mut node = 0;
+        loop {
+            let (q, qx, qy) = quadrant(x, y);
+            self.nodes[node].energy[q] += value;
+            (x, y) = (qx, qy);
+            match self.nodes[node].children[q] {
+                0 => break,
+                child => node = child,
+            }
+        }
+    }
+
+    /// Density over the unit square.
+    fn pdf(&self, (mut x, mut y): (f64, f64)) -> f64 {
+        let mut node = 0;
+        let mut density = 1.0;
+        loop {
+            let sum: f64 = self.nodes[node].energy.iter().sum();
+            if sum <= 0.0 {
+                return 0.0;
+            }
+            let (q, qx, qy) = quadrant(x, y);
+            density *= 4.0 * self.nodes[node].energy[q] / sum;
+            (x, y) = (qx, qy);
+            match self.nodes[node].children[q] {
+                0 => return density,
+                child => node = child,
+            }
+        }
+    }
+
+    fn sample(&self) -> (f64, f64) {
+        let (mut origin, mut size) = ((0.0, 0.0), 1.0);
+        let mut node = 0;
+        loop {
+            let energy = self.nodes[node].energy;
+            let mut pick = fastrand::f64() * energy.iter().sum::<f64>();
+            let mut q = 3;
+            for (i, e) in energy.iter().enumerate() {
+                if pick < *e {
+                    q = i;
+                    break;
+                }
+                pick -= e;
+            }
+            size /= 2.0;
+            origin.0 += size * (q % 2) as f64;
+            origin.1 += size * (q / 2) as f64;
+            match self.nodes[node].children[q] {
+                0 => {
+                    return (
+                        origin.0 + size * fastrand::f64(),
+                        origin.1 + size * fastrand::f64(),
+                    )
+                }
+                child => node = child,
+            }
+        }
+    }
+
+    /// Copy of the tree, subdivided where it holds a lot of energy and collapsed where it holds
+    /// little; energies carry over so learning accumulates across passes.
+    fn refined(&self) -> Self {
+        let mut tree = Self::default();
+        tree.nodes[0].energy = self.nodes[0].energy;
+        let total = self.total();
+        if total > 0.0 {
+            self.refine_into(Some(0), 0, 1, total, &mut tree);
+        }
+        tree
+    }
+
+    fn refine_into(&self, old: Option<usize>, new: usize, depth: u32, total: f64, out: &mut Self) {
+        for q in 0..4 {
+            let energy = old.map_or(0.0, |node| self.nodes[node].energy[q]);
+            if energy / total <= REFINE_THRESHOLD || depth >= MAX_QUAD_DEPTH {
+                continue;
+            }
+            // a bright leaf quadrant gains one level per pass, starting from an even split
+            let old_child = old
+                .map(|node| self.nodes[node].children[q])
+                .filter(|&c| c != 0);
+            let child = out.nodes.len();
+            out.nodes.push(QuadNode {
+                energy: old_child.map_or([energy / 4.0; 4], |c| self.nodes[c].energy),
+                children: [0; 4],
+            });
+            out.nodes[new].children[q] = child;
+            if old_child.is_some() {
+                self.refine_into(old_child, child, depth + 1, total, out);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SpatialNode {
+    min: Point3,
+    max: Point3,
+    children: Option<(usize, usize)>,
+    axis: u8,
+    sampling: DirectionTree,
+    recording: Mutex<DirectionTree>,
+    samples: AtomicU64,
+}
+
+impl SpatialNode {
+    fn leaf(min: Point3, max: Point3, sampling: DirectionTree, recording: DirectionTree) -> Self {
+        Self {
+            min,
+            max,
+            children: None,
+            axis: 0,
+            sampling,
+            recording: Mutex::new(recording),
+            samples: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Learned incident radiance over the scene (a spatial binary tree of directional quadtrees)
+/// used to steer diffuse bounces towards bright directions.
+#[derive(Debug)]
+pub struct PathGuide {
+    nodes: RwLock<Vec<SpatialNode>>,
+}
+
+impl PathGuide {
+    pub fn new(bounds: Aabb) -> Self {
+        let min = Point3::new(bounds.x().start, bounds.y().start, bounds.z().start);
+        let max = Point3::new(bounds.x().end, bounds.y().end, bounds.z().end);
+        let root = SpatialNode::leaf(min, max, DirectionTree::default(), DirectionTree::default());
+        Self {
+            nodes: RwLock::new(vec![root]),
+        }
+    }
+
+    fn leaf(nodes: &[SpatialNode], point: Point3) -> &SpatialNode {
+        let mut node = &nodes[0];
+        while let Some((low, high)) = node.children {
+            let axis = node.axis;
+            let middle = 0.5 * (node.min[axis] + node.max[axis]);
+            node = &nodes[if point[axis] < middle { low } else { high }];
+        }
+        node
+    }
+
+    /// Direction drawn from the learned distribution at `point`, with its solid-angle density;
+    /// `None` before anything has been learned there.
+    pub fn sample(&self, point: Point3) -> Option<(Vec3, f64)> {
+        let nodes = self.nodes.read().unwrap();
+        let tree = &Self::leaf(&nodes, point).sampling;
+        if tree.total() <= 0.0 {
+            return None;
+        }
+        let square = tree.sample();
+        Some((from_square(square), tree.pdf(square) / (4.0 * PI)))
+    }
+
+    /// Solid-angle density of guided sampling at `point` in `direction`, if guiding is active.
+    pub fn pdf(&self, point: Point3, direction: Vec3) -> Option<f64> {
+        let nodes = self.nodes.read().unwrap();
+        let tree = &Self::leaf(&nodes, point).sampling;
+        (tree.total() > 0.0).then(|| tree.pdf(to_square(direction.unit())) / (4.0 * PI))
+    }
+
+    /// Adds a Monte Carlo estimate of the radiance arriving at `point` from `direction`.
+    pub fn record(&self, point: Point3, direction: Vec3, value: f64) {
+        if !value.is_finite() || value <= 0.0 {
+            return;
+        }
+        let nodes = self.nodes.read().unwrap();
+        let leaf = Self::leaf(&nodes, point);
+        leaf.recording
+            .lock()
+            .unwrap()
+            .record(to_square(direction.unit()), value);
+        leaf.samples.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Switches every leaf to sampling what it just recorded, splitting busy leaves and
+    /// refining the directional trees for the next pass.
+    pub fn end_pass(&self) {
+        let mut nodes = self.nodes.write().unwrap();
+        let mut depths = vec![0; nodes.len()];
+        for index in 0..nodes.len() {
+            if nodes[index].children.is_some() {
+                for child in [
+                    nodes[index].children.unwrap().0,
+                    nodes[index].children.unwrap().1,
+                ] {
+                    depths[child] = depths[index] + 1;
+                }
+                continue;
+            }
+            let recorded = std::mem::take(nodes[index].recording.get_mut().unwrap());
+            let refined = recorded.refined();
+            let samples = nodes[index].samples.swap(0, Ordering::Relaxed);
+            nodes[index].sampling = recorded;
+            *nodes[index].recording.get_mut().unwrap() = refined;
+
+            Self::split(&mut nodes, &mut depths, index, samples);
+        }
+    }
+
+    /// Halves a leaf along its longest axis until each part is expected to hold at most
+    /// `SPLIT_SAMPLES` records; children start from copies of the parent's trees.
+    fn split(nodes: &mut Vec<SpatialNode>, depths: &mut Vec<u32>, index: usize, samples: u64) {
+        if samples <= SPLIT_SAMPLES || depths[index] >= MAX_SPATIAL_DEPTH {
+            return;
+        }
+        let (min, max) = (nodes[index].min, nodes[index].max);
+        let extent = max - min;
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+        let middle = 0.5 * (min[axis] + max[axis]);
+        let low_max = with_component(max, axis, middle);
+        let high_min = with_component(min, axis, middle);
+
+        let node = &nodes[index];
+        let (sampling, recording) = (
+            node.sampling.clone(),
+            node.recording.lock().unwrap().clone(),
+        );
+        let low = SpatialNode::leaf(min, low_max, sampling.clone(), recording.clone());
+        let high = SpatialNode::leaf(high_min, max, sampling, recording);
+        let first = nodes.len();
+        nodes.push(low);
+        nodes.push(high);
+        depths.extend([depths[index] + 1; 2]);
+        nodes[index].axis = axis;
+        nodes[index].children = Some((first, first + 1));
+        for child in [first, first + 1] {
+            Self::split(nodes, depths, child, samples / 2);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{mesh::MeshData, vec3::Point3};
+use std::error::Error;
+
+/// Builds terrain from the grayscale image at `image_path`: one vertex per pixel, laid out on
+/// the xz plane `horizontal_scale` apart and centered on the origin, raised along y by the
+/// pixel's brightness times `height_scale`. Texture coordinates stretch the image over the
+/// whole grid, so the same file can also color it.
+pub fn load(
+    image_path: &str,
+    horizontal_scale: f64,
+    height_scale: f64,
+) -> Result<MeshData, Box<dyn Error>> {
+    if horizontal_scale <= 0.0 {
+        return Err(format!("Heightfield {image_path} needs a positive horizontal scale").into());
+    }
+    let img = image::open(image_path)
+        .map_err(|e| format!("Failed to open heightfield image {image_path}: {e}"))?
+        .to_luma32f();
+    let (width, height) = img.dimensions();
+    if width < 2 || height < 2 {
+        return Err(format!("Heightfield image {image_path} needs at least 2×2 pixels").into());
+    }
+    let heights = img.into_raw();
+
+    let mut data = MeshData::default();
+    let half_x = 0.5 * (width - 1) as f64;
+    let half_z = 0.5 * (height - 1) as f64;
+    for row in 0..height {
+        for column in 0..width {
+            let elevation = heights[(row * width + column) as usize] as f64;
+            data.positions.push(Point3::new(
+                horizontal_scale * (column as f64 - half_x),
+                height_scale * elevation,
+                horizontal_scale * (row as f64 - half_z),
+            ));
+            // the image's top row is at v = 1, as image textures expect
+            data.uvs.push((
+                column as f64 / (width - 1) as f64,
+                1.0 - row as f64 / (height - 1) as f64,
+            ));
+        }
+    }
+
+    // two triangles per cell, wound so their normals face up
+    for row in 0..height - 1 {
+        for column in 0..width - 1 {
+            let corner = row * width + column;
+            let (right, below) = (corner + 1, corner + width);
+            data.indices
+                .extend([corner, below, right, right, below, below + 1]);
+        }
+    }
+    data.uv_indices = data.indices.clone();
+    Ok(data)
+}
@@ -5,9 +5,9 @@ use crate::{
     aabb::Aabb,
     entity::{Entity, HitRecord},
     interval::Interval,
-    mat3::Mat3,
+    mat4::Mat4,
     ray::Ray,
-    vec3::{Point3, Vec3},
+    vec3::{Color, Point3, Vec3},
 };
 
 #[derive(Debug, Clone)]
@@ -29,8 +29,9 @@ impl Translated {
 }
 
 impl Entity for Translated {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
-        let offset_ray = Ray::new(*ray.origin() - self.offset, *ray.direction(), *ray.time());
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let offset_ray = Ray::new(*ray.origin() - self.offset, *ray.direction(), *ray.time())
+            .with_kind(ray.kind());
         if let Some(mut hit_record) = self.entity.hit(&offset_ray, time_interval) {
             hit_record.hit_point += self.offset;
             Some(hit_record)
@@ -43,80 +44,240 @@ impl Entity for Translated {
     fn bounding_box(&self) -> Aabb {
         self.bounding_box
     }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let (mut hit_record, area) = self.entity.sample_surface()?;
+        hit_record.hit_point += self.offset;
+        Some((hit_record, area))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        let (point, normal) = self.entity.surface_point(u, v)?;
+        Some((point + self.offset, normal))
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.entity.emitted_power()
+    }
+
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        self.entity.density(point - self.offset, time)
+    }
 }
 
+/// Entity stretched by a separate factor along each axis, about the origin.
 #[derive(Debug, Clone)]
-pub struct Rotated {
+pub struct Scaled {
     entity: Arc<dyn Entity>,
+    scale: Vec3,
+    inverse_scale: Vec3,
     bounding_box: Aabb,
-    rotation_matrix: Mat3,
-    inverse_rotation_matrix: Mat3,
 }
 
-impl Rotated {
-    pub fn new(entity: Arc<dyn Entity>, rotation: Vec3) -> Self {
-        let rotation_matrix = Mat3::rotation_x(rotation.x())
-            * Mat3::rotation_y(rotation.y())
-            * Mat3::rotation_z(rotation.z());
-        let inverse_rotation_matrix = rotation_matrix.transpose();
-
-        let b_box = entity.bounding_box();
-        let mut a = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
-        let mut b = Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
-
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
-                    let (i, j, k) = (i as f64, j as f64, k as f64);
-
-                    let x = i * b_box.x().end + (1.0 - i) * b_box.x().start;
-                    let y = j * b_box.y().end + (1.0 - j) * b_box.y().start;
-                    let z = k * b_box.z().end + (1.0 - k) * b_box.z().start;
-
-                    let rotated_point = rotation_matrix * Point3::new(x, y, z);
-
-                    a = Point3::new(
-                        a.x().min(rotated_point.x()),
-                        a.y().min(rotated_point.y()),
-                        a.z().min(rotated_point.z()),
-                    );
-
-                    b = Point3::new(
-                        b.x().max(rotated_point.x()),
-                        b.y().max(rotated_point.y()),
-                        b.z().max(rotated_point.z()),
-                    );
-                }
-            }
+impl Scaled {
+    /// `None` if any factor is zero, which flattens the entity.
+    pub fn new(entity: Arc<dyn Entity>, scale: Vec3) -> Option<Self> {
+        if scale.x() * scale.y() * scale.z() == 0.0 {
+            return None;
         }
+        let inverse_scale = Vec3::new(1.0 / scale.x(), 1.0 / scale.y(), 1.0 / scale.z());
+        let b_box = entity.bounding_box();
+        let bounding_box = Aabb::new_from_points(
+            scale * Point3::new(b_box.x().start, b_box.y().start, b_box.z().start),
+            scale * Point3::new(b_box.x().end, b_box.y().end, b_box.z().end),
+        );
+        Some(Self {
+            entity,
+            scale,
+            inverse_scale,
+            bounding_box,
+        })
+    }
 
-        let bounding_box = Aabb::new_from_points(a, b);
+    /// Normals take the inverse transpose of the scaling, which for a diagonal matrix is the
+    /// inverse, so they stay perpendicular to the stretched surface.
+    #[inline]
+    fn normal(&self, normal: Vec3) -> Vec3 {
+        (self.inverse_scale * normal).unit()
+    }
 
-        Self {
+    /// Factor by which scaling by `scale` stretches surface areas, averaged over orientations.
+    /// Exact for uniform scaling; otherwise this is Knud Thomsen's ellipsoid area formula
+    /// applied to the unit sphere, within about a percent for spheres.
+    pub fn mean_area_scale(scale: Vec3) -> f64 {
+        const P: f64 = 1.6075;
+        let [x, y, z] = [scale.x(), scale.y(), scale.z()].map(f64::abs);
+        let mean = ((x * y).powf(P) + (y * z).powf(P) + (z * x).powf(P)) / 3.0;
+        mean.powf(1.0 / P)
+    }
+
+    /// Factor by which the surface's area is stretched around a point with object space
+    /// `normal`.
+    fn area_scale(&self, normal: Vec3) -> f64 {
+        let determinant = (self.scale.x() * self.scale.y() * self.scale.z()).abs();
+        determinant * (self.inverse_scale * normal).length() / normal.length()
+    }
+}
+
+impl Entity for Scaled {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        // a linear map leaves ray times unchanged
+        let scaled_ray = Ray::new(
+            self.inverse_scale * *ray.origin(),
+            self.inverse_scale * *ray.direction(),
+            *ray.time(),
+        )
+        .with_kind(ray.kind());
+        let mut hit_record = self.entity.hit(&scaled_ray, time_interval)?;
+        let tangent = self.scale * hit_record.tangent;
+        hit_record.hit_point = self.scale * hit_record.hit_point;
+        hit_record.normal = self.normal(hit_record.normal);
+        hit_record.set_tangent(tangent);
+        hit_record.dp_du = self.scale * hit_record.dp_du;
+        hit_record.dp_dv = self.scale * hit_record.dp_dv;
+        hit_record.velocity = self.scale * hit_record.velocity;
+        // the least stretched direction keeps wireframe lines from thinning
+        hit_record.edge_distance *= self
+            .scale
+            .x()
+            .abs()
+            .min(self.scale.y().abs())
+            .min(self.scale.z().abs());
+        Some(hit_record)
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    /// Points picked uniformly on the unscaled surface bunch up where it is stretched least,
+    /// so the area reported with each is the one that gives its actual density.
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let (mut hit_record, area) = self.entity.sample_surface()?;
+        let area = area * self.area_scale(hit_record.normal);
+        hit_record.hit_point = self.scale * hit_record.hit_point;
+        hit_record.normal = self.normal(hit_record.normal);
+        Some((hit_record, area))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        let (point, normal) = self.entity.surface_point(u, v)?;
+        Some((self.scale * point, self.normal(normal)))
+    }
+
+    fn emitted_power(&self) -> Color {
+        Self::mean_area_scale(self.scale) * self.entity.emitted_power()
+    }
+
+    /// Stretching a medium thins it out along the stretched directions; the mean stretch is
+    /// exact for uniform scaling.
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        let determinant = (self.scale.x() * self.scale.y() * self.scale.z()).abs();
+        self.entity.density(self.inverse_scale * point, time) / determinant.cbrt()
+    }
+}
+
+/// Entity under an arbitrary affine transform, such as a shear or a whole placement at once,
+/// moving each ray into the entity's space in a single step.
+#[derive(Debug, Clone)]
+pub struct Transformed {
+    entity: Arc<dyn Entity>,
+    matrix: Mat4,
+    inverse: Mat4,
+    bounding_box: Aabb,
+}
+
+impl Transformed {
+    /// `None` if `matrix` flattens space, as a zero scale does, so rays can't be taken back
+    /// into the entity's space.
+    pub fn new(entity: Arc<dyn Entity>, matrix: Mat4) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        let b_box = entity.bounding_box();
+        let corners = (0..8).map(|corner| {
+            let pick = |interval: Interval, bit: u8| {
+                if corner & bit == 0 {
+                    interval.start
+                } else {
+                    interval.end
+                }
+            };
+            let corner = Point3::new(pick(b_box.x(), 1), pick(b_box.y(), 2), pick(b_box.z(), 4));
+            matrix.transform_point(corner)
+        });
+        let bounding_box = corners
+            .map(|corner| Aabb::new_from_points(corner, corner))
+            .reduce(|a, b| Aabb::enclose(&a, &b))
+            .expect("a box has corners");
+        Some(Self {
             entity,
+            matrix,
+            inverse,
             bounding_box,
-            rotation_matrix,
-            inverse_rotation_matrix,
-        }
+        })
     }
-}
 
-impl Entity for Rotated {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
-        let origin = self.inverse_rotation_matrix * *ray.origin();
-        let direction = self.inverse_rotation_matrix * *ray.direction();
-        let rotated_ray = Ray::new(origin, direction, *ray.time());
+    #[inline]
+    fn normal(&self, normal: Vec3) -> Vec3 {
+        self.inverse.transform_normal(normal).unit()
+    }
 
-        if let Some(mut hit_record) = self.entity.hit(&rotated_ray, time_interval) {
-            hit_record.hit_point = self.rotation_matrix * hit_record.hit_point;
-            hit_record.normal = self.rotation_matrix * hit_record.normal;
-            Some(hit_record)
-        } else {
-            None
-        }
+    /// Factor by which the surface's area is stretched around a point with object space
+    /// `normal`.
+    fn area_scale(&self, normal: Vec3) -> f64 {
+        self.matrix.determinant().abs() * self.inverse.transform_normal(normal).length()
+            / normal.length()
+    }
+}
+
+impl Entity for Transformed {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        // an affine map leaves ray times unchanged
+        let local_ray = Ray::new(
+            self.inverse.transform_point(*ray.origin()),
+            self.inverse.transform_vector(*ray.direction()),
+            *ray.time(),
+        )
+        .with_kind(ray.kind());
+        let mut hit_record = self.entity.hit(&local_ray, time_interval)?;
+        let tangent = self.matrix.transform_vector(hit_record.tangent);
+        hit_record.hit_point = self.matrix.transform_point(hit_record.hit_point);
+        hit_record.normal = self.normal(hit_record.normal);
+        hit_record.set_tangent(tangent);
+        hit_record.dp_du = self.matrix.transform_vector(hit_record.dp_du);
+        hit_record.dp_dv = self.matrix.transform_vector(hit_record.dp_dv);
+        hit_record.velocity = self.matrix.transform_vector(hit_record.velocity);
+        // the mean stretch keeps wireframe lines about as wide as before
+        hit_record.edge_distance *= self.matrix.determinant().abs().cbrt();
+        Some(hit_record)
     }
 
+    #[inline]
     fn bounding_box(&self) -> Aabb {
         self.bounding_box
     }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let (mut hit_record, area) = self.entity.sample_surface()?;
+        let area = area * self.area_scale(hit_record.normal);
+        hit_record.hit_point = self.matrix.transform_point(hit_record.hit_point);
+        hit_record.normal = self.normal(hit_record.normal);
+        Some((hit_record, area))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        let (point, normal) = self.entity.surface_point(u, v)?;
+        Some((self.matrix.transform_point(point), self.normal(normal)))
+    }
+
+    /// Exact for rotations and uniform scaling, which stretch every area alike.
+    fn emitted_power(&self) -> Color {
+        self.matrix.determinant().abs().powf(2.0 / 3.0) * self.entity.emitted_power()
+    }
+
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        self.entity
+            .density(self.inverse.transform_point(point), time)
+            / self.matrix.determinant().abs().cbrt()
+    }
 }
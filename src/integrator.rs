@@ -0,0 +1,51 @@
+use crate::vec3::Color;
+
+// survival probabilities are kept above this so surviving paths don't get huge weights
+const MIN_SURVIVAL: f64 = 0.05;
+
+/// Path termination and clamping controls shared by both renderers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Integrator {
+    /// Bounce from which russian roulette may terminate paths; `None` disables it.
+    pub rr_start_depth: Option<u16>,
+    /// Upper bound on the largest channel of each camera sample; `None` disables clamping.
+    pub max_radiance: Option<f64>,
+    /// Bounces that are always traced before roulette is allowed to terminate a path.
+    pub min_bounces: u16,
+}
+
+impl Integrator {
+    /// Probability of continuing a path after its `depth`-th surface interaction, given the
+    /// cumulative throughput it would carry on, from the camera through this bounce; `None`
+    /// means the path always continues.
+    pub fn survival_probability(&self, depth: u16, throughput: Color) -> Option<f64> {
+        let start = self.rr_start_depth?.max(self.min_bounces);
+        if depth < start {
+            return None;
+        }
+        let max_component = throughput.x().max(throughput.y()).max(throughput.z());
+        Some(max_component.clamp(MIN_SURVIVAL, 1.0))
+    }
+
+    /// Rolls russian roulette, returning the factor to scale the surviving path by.
+    pub fn roulette(&self, depth: u16, throughput: Color) -> Option<f64> {
+        match self.survival_probability(depth, throughput) {
+            None => Some(1.0),
+            Some(p) if fastrand::f64() < p => Some(1.0 / p),
+            Some(_) => None,
+        }
+    }
+
+    /// Scales `sample` down so no channel exceeds `max_radiance`, keeping its hue.
+    pub fn clamp(&self, sample: Color) -> Color {
+        let Some(max_radiance) = self.max_radiance else {
+            return sample;
+        };
+        let max_component = sample.x().max(sample.y()).max(sample.z());
+        if max_component > max_radiance {
+            sample * (max_radiance / max_component)
+        } else {
+            sample
+        }
+    }
+}
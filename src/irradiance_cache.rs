@@ -0,0 +1,109 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::{
+    epsilon,
+    vec3::{Color, Point3, Vec3},
+};
+
+// bounds on a record's validity radius, in multiples of the scene epsilon
+const MIN_RADIUS: f64 = 1e3;
+const MAX_RADIUS: f64 = 1e5;
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    point: Point3,
+    normal: Vec3,
+    /// Cosine-weighted mean indirect radiance, i.e. indirect irradiance over π.
+    irradiance: Color,
+    /// Harmonic mean distance to the surfaces seen from the record.
+    radius: f64,
+}
+
+#[derive(Debug, Default)]
+struct Grid {
+    records: Vec<Record>,
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+}
+
+/// Sparse irradiance samples on diffuse surfaces, interpolated with Ward's error metric so
+/// smooth indirect lighting doesn't have to be path traced at every pixel.
+#[derive(Debug)]
+pub struct IrradianceCache {
+    /// Interpolation error bound; smaller values place records more densely.
+    max_error: f64,
+    /// Hemisphere rays traced per record.
+    pub samples: u16,
+    grid: RwLock<Grid>,
+}
+
+impl IrradianceCache {
+    pub fn new(max_error: f64, samples: u16) -> Self {
+        Self {
+            max_error,
+            samples,
+            grid: RwLock::default(),
+        }
+    }
+
+    fn cell_size() -> f64 {
+        MAX_RADIUS * epsilon::scene_epsilon()
+    }
+
+    fn cell(point: Point3) -> (i64, i64, i64) {
+        let size = Self::cell_size();
+        (
+            (point.x() / size).floor() as i64,
+            (point.y() / size).floor() as i64,
+            (point.z() / size).floor() as i64,
+        )
+    }
+
+    /// Weighted blend of the records valid at `point`, if any.
+    pub fn lookup(&self, point: Point3, normal: Vec3) -> Option<Color> {
+        let grid = self.grid.read().unwrap();
+        let indices = grid.cells.get(&Self::cell(point))?;
+        let (mut sum, mut total) = (Color::default(), 0.0);
+        for record in indices.iter().map(|&i| &grid.records[i]) {
+            let error = (point - record.point).length() / record.radius
+                + (1.0 - normal.dot(record.normal)).max(0.0).sqrt();
+            let weight = 1.0 / error.max(1e-9);
+            if weight > 1.0 / self.max_error {
+                sum += weight * record.irradiance;
+                total += weight;
+            }
+        }
+        (total > 0.0).then(|| sum / total)
+    }
+
+    /// Stores a new record, registering it in every cell its validity region overlaps.
+    pub fn insert(&self, point: Point3, normal: Vec3, irradiance: Color, harmonic_distance: f64) {
+        let epsilon = epsilon::scene_epsilon();
+        let radius = harmonic_distance.clamp(MIN_RADIUS * epsilon, MAX_RADIUS * epsilon);
+        let reach = Vec3::new(1.0, 1.0, 1.0) * (self.max_error * radius);
+        let (low, high) = (Self::cell(point - reach), Self::cell(point + reach));
+
+        let mut grid = self.grid.write().unwrap();
+        let index = grid.records.len();
+        grid.records.push(Record {
+            point,
+            normal,
+            irradiance,
+            radius,
+        });
+        for x in low.0..=high.0 {
+            for y in low.1..=high.1 {
+                for z in low.2..=high.2 {
+                    grid.cells.entry((x, y, z)).or_default().push(index);
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.grid.read().unwrap().records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
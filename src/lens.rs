@@ -0,0 +1,359 @@
+use crate::vec3::Vec3;
+use std::error::Error;
+
+// radial bands of the film with an exit pupil bound each
+const PUPIL_BANDS: usize = 32;
+// film radii tried per band, and grid points per side tried on the rear element, when bounding
+const PUPIL_RADII: usize = 3;
+const PUPIL_GRID: usize = 48;
+
+/// Built-in lens designs, given at unit focal length and scaled to the one asked for.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum LensPreset {
+    /// Single symmetric bi-convex crown glass element.
+    Biconvex,
+    /// Cemented achromatic doublet, a bi-convex crown element against a flint meniscus.
+    #[default]
+    Doublet,
+}
+
+impl LensPreset {
+    /// Glass surfaces from the front, behind an aperture stop one `stop_gap` ahead of them.
+    fn surfaces(self) -> (f64, Vec<Surface>) {
+        let surface = |radius, thickness, eta, aperture| Surface {
+            radius,
+            thickness,
+            eta,
+            aperture,
+        };
+        match self {
+            LensPreset::Biconvex => (
+                0.08,
+                vec![
+                    surface(1.03, 0.05, 1.5168, 0.15),
+                    surface(-1.03, 0.0, 1.0, 0.15),
+                ],
+            ),
+            LensPreset::Doublet => (
+                0.06,
+                vec![
+                    surface(0.628, 0.04, 1.5168, 0.127),
+                    surface(-0.457, 0.025, 1.6727, 0.127),
+                    surface(-1.282, 0.0, 1.0, 0.127),
+                ],
+            ),
+        }
+    }
+}
+
+/// A refracting surface of a lens, or its aperture stop.
+#[derive(Debug, Clone, Copy)]
+struct Surface {
+    /// Signed radius of curvature, positive when the center lies towards the film; zero for
+    /// the flat stop.
+    radius: f64,
+    /// Distance along the axis to the next surface.
+    thickness: f64,
+    /// Refractive index between this surface and the next.
+    eta: f64,
+    /// Rays passing farther from the axis than this are blocked.
+    aperture: f64,
+}
+
+/// Axis-aligned region of the rear element's plane, as (min x, max x, min y, max y).
+type Bounds = [f64; 4];
+
+/// Lens built from spherical elements that camera rays are traced through on their way out,
+/// so images pick up the distortion, vignetting and focus breathing of the design.
+///
+/// Positions are in lens space: the stop sits at the origin on the z axis, which runs back
+/// towards the film, and the film's x runs opposite to the image's, since the lens flips it.
+#[derive(Debug, Clone)]
+pub struct Lens {
+    surfaces: Vec<Surface>,
+    /// z of each surface's vertex.
+    positions: Vec<f64>,
+    film_distance: f64,
+    film_height: f64,
+    aspect_ratio: f64,
+    /// Where rays leaving each band of film radii can get through, before rotating to the
+    /// film point's angle.
+    pupil_bounds: Vec<Option<Bounds>>,
+    /// Transmission of the film's center, which every sample is divided by so the middle of
+    /// the image comes out as bright as with a pinhole.
+    axial_transmission: f64,
+}
+
+impl Lens {
+    /// Scales `preset` to `focal_length` and moves the film until `focus_distance` is sharp,
+    /// sizing it to give `vertical_fov` when focused at infinity. Without `f_stop` the stop is
+    /// as wide as the glass.
+    pub fn new(
+        preset: LensPreset,
+        focal_length: f64,
+        f_stop: Option<f64>,
+        focus_distance: f64,
+        vertical_fov: f64,
+        aspect_ratio: f64,
+    ) -> Result<Self, Box<dyn Error>> {
+        if focal_length <= 0.0 {
+            return Err("Lens focal length must be positive".into());
+        }
+        let (stop_gap, glass) = preset.surfaces();
+        let open = glass[0].aperture;
+        let stop = Surface {
+            radius: 0.0,
+            thickness: stop_gap,
+            eta: 1.0,
+            aperture: open,
+        };
+        let mut lens = Self::unfocused(std::iter::once(stop).chain(glass).collect());
+        let unit_focal_length = lens
+            .focal_length()
+            .ok_or("Lens preset does not converge light")?;
+        let scale = focal_length / unit_focal_length;
+        lens.surfaces.iter_mut().for_each(|surface| {
+            surface.radius *= scale;
+            surface.thickness *= scale;
+            surface.aperture *= scale;
+        });
+        if let Some(f_stop) = f_stop {
+            // nothing sits in front of the stop, so it is the entrance pupil
+            lens.surfaces[0].aperture = (0.5 * focal_length / f_stop).min(open * scale);
+        }
+        lens = Self::unfocused(lens.surfaces);
+
+        lens.film_distance = lens
+            .image_distance(focus_distance)
+            .filter(|&distance| distance > *lens.positions.last().unwrap())
+            .ok_or_else(|| {
+                format!("Lens cannot focus at {focus_distance}, closer than its focal length")
+            })?;
+        lens.film_height = 2.0 * focal_length * (0.5 * vertical_fov.to_radians()).tan();
+        lens.aspect_ratio = aspect_ratio;
+        lens.pupil_bounds = (0..PUPIL_BANDS).map(|band| lens.pupil_band(band)).collect();
+        lens.axial_transmission = lens.transmission_at(0.0);
+        if lens.axial_transmission <= 0.0 {
+            return Err("Lens lets no light through to the film's center".into());
+        }
+        Ok(lens)
+    }
+
+    fn unfocused(surfaces: Vec<Surface>) -> Self {
+        let positions = surfaces
+            .iter()
+            .scan(0.0, |z, surface| {
+                let position = *z;
+                *z += surface.thickness;
+                Some(position)
+            })
+            .collect();
+        Self {
+            surfaces,
+            positions,
+            film_distance: 0.0,
+            film_height: 0.0,
+            aspect_ratio: 1.0,
+            pupil_bounds: Vec::new(),
+            axial_transmission: 1.0,
+        }
+    }
+
+    /// Film point in lens space for a point of the image in [0, 1]², y running down.
+    pub fn film_point(&self, x: f64, y: f64) -> (f64, f64) {
+        let film_width = self.film_height * self.aspect_ratio;
+        ((0.5 - x) * film_width, (y - 0.5) * self.film_height)
+    }
+
+    fn film_radius(&self) -> f64 {
+        0.5 * self.film_height * self.aspect_ratio.hypot(1.0)
+    }
+
+    /// Crossing of the ray with surface `index`, `None` if it misses or is blocked there.
+    fn cross(&self, index: usize, origin: Vec3, direction: Vec3) -> Option<Vec3> {
+        let surface = &self.surfaces[index];
+        let vertex = self.positions[index];
+        let time = if surface.radius == 0.0 {
+            (vertex - origin.z()) / direction.z()
+        } else {
+            let center = Vec3::new(0.0, 0.0, vertex + surface.radius);
+            let oc = origin - center;
+            let half_b = oc.dot(direction);
+            let discriminant = half_b * half_b - (oc.length_sq() - surface.radius.powi(2));
+            if discriminant < 0.0 {
+                return None;
+            }
+            let root = discriminant.sqrt();
+            // of the two crossings of the whole sphere, the lens surface is the one by its vertex
+            [-half_b - root, -half_b + root]
+                .into_iter()
+                .filter(|&time| time > 0.0)
+                .min_by(|&a, &b| {
+                    let offset = |time: f64| (origin.z() + time * direction.z() - vertex).abs();
+                    offset(a).total_cmp(&offset(b))
+                })?
+        };
+        if time <= 0.0 {
+            return None;
+        }
+        let point = origin + time * direction;
+        (point.x().powi(2) + point.y().powi(2) <= surface.aperture.powi(2)).then_some(point)
+    }
+
+    /// Bends a unit `direction` at `point` on surface `index`, going from index `from` to `to`.
+    fn bend(&self, index: usize, point: Vec3, direction: Vec3, from: f64, to: f64) -> Option<Vec3> {
+        let surface = &self.surfaces[index];
+        if surface.radius == 0.0 {
+            return Some(direction);
+        }
+        let center = Vec3::new(0.0, 0.0, self.positions[index] + surface.radius);
+        let mut normal = (point - center).unit();
+        if normal.dot(direction) > 0.0 {
+            normal = -normal;
+        }
+        let ratio = from / to;
+        let cos_in = -direction.dot(normal);
+        let sin_sq_out = ratio * ratio * (1.0 - cos_in * cos_in);
+        // total internal reflection turns the ray back into the glass
+        if sin_sq_out > 1.0 {
+            return None;
+        }
+        Some(ratio * direction + (ratio * cos_in - (1.0 - sin_sq_out).sqrt()) * normal)
+    }
+
+    /// Index of refraction in front of surface `index`.
+    fn eta_before(&self, index: usize) -> f64 {
+        index
+            .checked_sub(1)
+            .map_or(1.0, |previous| self.surfaces[previous].eta)
+    }
+
+    /// Follows a ray from the scene side back through every surface.
+    fn trace_to_film(&self, mut origin: Vec3, direction: Vec3) -> Option<(Vec3, Vec3)> {
+        let mut direction = direction.unit();
+        for index in 0..self.surfaces.len() {
+            origin = self.cross(index, origin, direction)?;
+            let (from, to) = (self.eta_before(index), self.surfaces[index].eta);
+            direction = self.bend(index, origin, direction, from, to)?;
+        }
+        Some((origin, direction))
+    }
+
+    /// Follows a ray from the film out through every surface into the scene.
+    fn trace_from_film(&self, mut origin: Vec3, direction: Vec3) -> Option<(Vec3, Vec3)> {
+        let mut direction = direction.unit();
+        for index in (0..self.surfaces.len()).rev() {
+            origin = self.cross(index, origin, direction)?;
+            let (from, to) = (self.surfaces[index].eta, self.eta_before(index));
+            direction = self.bend(index, origin, direction, from, to)?;
+        }
+        Some((origin, direction))
+    }
+
+    /// Height above the axis at which to trace paraxial rays.
+    fn paraxial_height(&self) -> f64 {
+        1e-3 * self.surfaces[0].aperture
+    }
+
+    /// Effective focal length, from how steeply a ray parallel to the axis leaves the lens.
+    fn focal_length(&self) -> Option<f64> {
+        let height = self.paraxial_height();
+        let (_, direction) =
+            self.trace_to_film(Vec3::new(height, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0))?;
+        let slope = -direction.x() / direction.z();
+        (slope > 0.0).then(|| height / slope)
+    }
+
+    /// z of the image of the axial point `distance` in front of the stop.
+    fn image_distance(&self, distance: f64) -> Option<f64> {
+        let height = self.paraxial_height();
+        let (origin, direction) = self.trace_to_film(
+            Vec3::new(0.0, 0.0, -distance),
+            Vec3::new(height, 0.0, distance),
+        )?;
+        if direction.x() >= 0.0 {
+            return None;
+        }
+        Some(origin.z() - origin.x() / direction.x() * direction.z())
+    }
+
+    /// Ray leaving film point (x, y) towards `rear`, a point on the rear element's plane,
+    /// after it exits the front of the lens.
+    pub fn trace(&self, (x, y): (f64, f64), rear: (f64, f64)) -> Option<(Vec3, Vec3)> {
+        let film = Vec3::new(x, y, self.film_distance);
+        let rear = Vec3::new(rear.0, rear.1, *self.positions.last().unwrap());
+        self.trace_from_film(film, rear - film)
+    }
+
+    /// Rear element points in a grid over its disk, with the area each stands for.
+    fn rear_grid(&self) -> impl Iterator<Item = ((f64, f64), f64)> {
+        let radius = self.surfaces.last().unwrap().aperture;
+        let cell = 2.0 * radius / PUPIL_GRID as f64;
+        (0..PUPIL_GRID * PUPIL_GRID).filter_map(move |index| {
+            let x = -radius + (index % PUPIL_GRID) as f64 * cell + 0.5 * cell;
+            let y = -radius + (index / PUPIL_GRID) as f64 * cell + 0.5 * cell;
+            (x.hypot(y) <= radius).then_some(((x, y), cell * cell))
+        })
+    }
+
+    /// Bounds of the rear element points that light from the film radii of `band` gets through.
+    fn pupil_band(&self, band: usize) -> Option<Bounds> {
+        let band_width = self.film_radius() / PUPIL_BANDS as f64;
+        let cell = 2.0 * self.surfaces.last().unwrap().aperture / PUPIL_GRID as f64;
+        let mut bounds: Option<Bounds> = None;
+        for step in 0..PUPIL_RADII {
+            let radius = (band as f64 + step as f64 / (PUPIL_RADII - 1) as f64) * band_width;
+            for ((x, y), _) in self.rear_grid() {
+                if self.trace((radius, 0.0), (x, y)).is_none() {
+                    continue;
+                }
+                let [min_x, max_x, min_y, max_y] = bounds.get_or_insert([x, x, y, y]);
+                *min_x = min_x.min(x);
+                *max_x = max_x.max(x);
+                *min_y = min_y.min(y);
+                *max_y = max_y.max(y);
+            }
+        }
+        // grown by a grid cell, so points between the tested ones aren't cut off
+        bounds.map(|[min_x, max_x, min_y, max_y]| {
+            [min_x - cell, max_x + cell, min_y - cell, max_y + cell]
+        })
+    }
+
+    /// Light reaching the film at `radius` from the axis, summed over the rear element.
+    fn transmission_at(&self, radius: f64) -> f64 {
+        self.rear_grid()
+            .filter(|&(rear, _)| self.trace((radius, 0.0), rear).is_some())
+            .map(|((x, y), area)| area * Self::cos4(radius - x, -y, self.rear_gap()))
+            .sum()
+    }
+
+    fn rear_gap(&self) -> f64 {
+        self.film_distance - self.positions.last().unwrap()
+    }
+
+    /// Fourth power of the cosine between the axis and the offset (x, y, z): one for the
+    /// ray's slant onto the film and rear element each, two for the falling off with distance.
+    fn cos4(x: f64, y: f64, z: f64) -> f64 {
+        let cos_sq = z * z / (x * x + y * y + z * z);
+        cos_sq * cos_sq
+    }
+
+    /// Point on the rear element to aim a ray from `film` at, and the share of light it
+    /// carries relative to the film's center; `None` where no light gets through.
+    pub fn sample(&self, film: (f64, f64)) -> Option<((f64, f64), f64)> {
+        let radius = film.0.hypot(film.1);
+        let band =
+            ((radius / self.film_radius() * PUPIL_BANDS as f64) as usize).min(PUPIL_BANDS - 1);
+        let [min_x, max_x, min_y, max_y] = self.pupil_bounds[band]?;
+        let x = min_x + fastrand::f64() * (max_x - min_x);
+        let y = min_y + fastrand::f64() * (max_y - min_y);
+        // the bounds were found for film points on the x axis, so they're turned to this one's angle
+        let angle = film.1.atan2(film.0);
+        let (sin, cos) = angle.sin_cos();
+        let rear = (cos * x - sin * y, sin * x + cos * y);
+        let area = (max_x - min_x) * (max_y - min_y);
+        let weight = area * Self::cos4(film.0 - rear.0, film.1 - rear.1, self.rear_gap());
+        Some((rear, weight / self.axial_transmission))
+    }
+}
@@ -0,0 +1,81 @@
+//! Physically based ray tracer rendering TOML scene files, as a library for the
+//! `raytracer` binary and for tests that build scenes and entities directly.
+
+#![allow(clippy::cast_lossless)]
+#![allow(clippy::cast_sign_loss)]
+#![allow(clippy::cast_possible_truncation)]
+
+pub mod aabb;
+pub mod atmosphere;
+pub mod bake;
+pub mod bezier;
+pub mod billboard;
+pub mod blackbody;
+pub mod bokeh;
+pub mod bounds;
+pub mod bvh;
+pub mod camera;
+pub mod camera_path;
+pub mod cli;
+pub mod clip;
+pub mod constant_medium;
+pub mod counters;
+pub mod cuboid;
+pub mod curve;
+pub mod entity;
+pub mod environment;
+pub mod epsilon;
+pub mod film;
+pub mod filter;
+pub mod fnv;
+pub mod focus_map;
+pub mod guide;
+pub mod heightfield;
+pub mod instance;
+pub mod integrator;
+pub mod interval;
+pub mod irradiance_cache;
+pub mod lens;
+pub mod light_sampler;
+pub mod mat3;
+pub mod mat4;
+pub mod material;
+pub mod media_slice;
+pub mod memory;
+pub mod mesh;
+pub mod metaballs;
+pub mod obj;
+pub mod output;
+pub mod perlin;
+pub mod ply;
+pub mod point_cloud;
+pub mod polygon;
+pub mod progress;
+pub mod quad;
+pub mod ray;
+pub mod restir;
+pub mod rounded_cuboid;
+pub mod scene;
+pub mod sdf;
+pub mod shell;
+pub mod sphere;
+pub mod sprite_sheet;
+pub mod starfield;
+pub mod stl;
+pub mod subdivision;
+pub mod sun;
+pub mod texture;
+pub mod texture_bake;
+pub mod texture_cache;
+pub mod tile;
+pub mod toon;
+pub mod triangle;
+pub mod units;
+pub mod vec3;
+pub mod visibility;
+pub mod vox;
+pub mod voxels;
+pub mod watch;
+pub mod wavefront;
+pub mod wireframe;
+pub mod xyz;
@@ -0,0 +1,171 @@
+use std::{f64::consts::FRAC_1_PI, sync::Arc};
+
+use crate::{
+    bvh::BVHNode,
+    entity::{Entity, HitRecord},
+    environment::Environment,
+    epsilon,
+    interval::Interval,
+    material::{EmissionSpread, Material},
+    ray::{Ray, RayKind},
+    sun::Sun,
+    vec3::{Color, Point3, Vec3},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightSample {
+    Surface {
+        point: Point3,
+        normal: Vec3,
+        emitted: Color,
+        spread: EmissionSpread,
+        /// `Material::light_link` of the light.
+        link: u64,
+    },
+    /// Light from the environment or sun, arriving along `direction` from infinitely far away.
+    Distant { direction: Vec3, radiance: Color },
+}
+
+/// The lights, environment and sun that direct lighting picks samples from.
+#[derive(Debug)]
+pub struct LightSampler {
+    lights: Vec<Arc<dyn Entity>>,
+    /// Addresses of the sampled lights' materials, which tell their surfaces apart when a path
+    /// hits one.
+    materials: Vec<usize>,
+    environment: Option<Arc<Environment>>,
+    sun: Option<Sun>,
+}
+
+impl LightSampler {
+    /// Keeps only the lights that can be sampled; returns `None` if there are none and no
+    /// environment or sun either.
+    pub fn new(
+        lights: &[Arc<dyn Entity>],
+        environment: Option<Arc<Environment>>,
+        sun: Option<Sun>,
+    ) -> Option<Self> {
+        let lights: Vec<_> = lights
+            .iter()
+            .filter(|light| light.sample_surface().is_some())
+            .cloned()
+            .collect();
+        if lights.is_empty() && environment.is_none() && sun.is_none() {
+            return None;
+        }
+        let materials = lights
+            .iter()
+            .filter_map(|light| light.sample_surface())
+            .map(|(surface, _)| Self::address(surface.material))
+            .collect();
+        Some(Self {
+            lights,
+            materials,
+            environment,
+            sun,
+        })
+    }
+
+    fn address(material: &dyn Material) -> usize {
+        std::ptr::from_ref(material).cast::<()>() as usize
+    }
+
+    /// Whether the emission of surfaces made of `material` is sampled as direct light, and so
+    /// must be skipped where a path scattered from a directly lit hit runs into them.
+    pub fn samples(&self, material: &dyn Material) -> bool {
+        self.materials.contains(&Self::address(material))
+    }
+
+    /// Unshadowed light reflected towards the viewer by a diffuse surface of `albedo`.
+    pub fn contribution(hit_record: &HitRecord, albedo: Color, sample: &LightSample) -> Color {
+        match *sample {
+            LightSample::Surface {
+                point,
+                normal,
+                emitted,
+                spread,
+                link,
+            } => {
+                if link & hit_record.material.unlinked_lights() != 0 {
+                    return Color::default();
+                }
+                let to_light = point - hit_record.hit_point;
+                let distance_sq = to_light.length_sq();
+                if distance_sq == 0.0 {
+                    return Color::default();
+                }
+                let direction = to_light / distance_sq.sqrt();
+                let cos_surface = hit_record.normal.dot(direction).max(0.0);
+                let cos_light = normal.dot(direction);
+                let spread = spread.factor(cos_light < 0.0, cos_light);
+                FRAC_1_PI
+                    * albedo
+                    * emitted
+                    * (spread * cos_surface * cos_light.abs() / distance_sq)
+            }
+            LightSample::Distant {
+                direction,
+                radiance,
+            } => FRAC_1_PI * albedo * radiance * hit_record.normal.dot(direction).max(0.0),
+        }
+    }
+
+    pub fn target(hit_record: &HitRecord, albedo: Color, sample: &LightSample) -> f64 {
+        Self::contribution(hit_record, albedo, sample).luminance()
+    }
+
+    /// Share of the sample's light that reaches the hit past whatever is in the way.
+    pub fn transmittance(world: &BVHNode, hit_record: &HitRecord, sample: &LightSample) -> Color {
+        let (direction, end) = match *sample {
+            LightSample::Surface { point, .. } => (point - hit_record.hit_point, 1.0),
+            LightSample::Distant { direction, .. } => (direction, f64::INFINITY),
+        };
+        let ray =
+            Ray::new(hit_record.hit_point, direction, fastrand::f64()).with_kind(RayKind::Shadow);
+        let start = epsilon::ray_interval(&ray).start;
+        world.transmittance(&ray, Interval::new(start, end - start))
+    }
+
+    /// One fresh light sample and its probability density: over the light's area for surfaces,
+    /// over solid angle for the environment and sun, which each count as one more light to
+    /// pick from.
+    pub fn candidate(&self) -> Option<(LightSample, f64)> {
+        let choices = self.lights.len()
+            + usize::from(self.environment.is_some())
+            + usize::from(self.sun.is_some());
+        let choice = fastrand::usize(..choices);
+        let Some(light) = self.lights.get(choice) else {
+            let (direction, radiance, pdf) = match (choice - self.lights.len(), &self.environment) {
+                (0, Some(environment)) => environment.sample()?,
+                _ => self.sun?.sample(),
+            };
+            let sample = LightSample::Distant {
+                direction,
+                radiance,
+            };
+            return Some((sample, pdf / choices as f64));
+        };
+        let (surface, area) = light.sample_surface()?;
+        let sample = LightSample::Surface {
+            point: surface.hit_point,
+            normal: surface.normal,
+            emitted: surface.material.emit(&surface),
+            spread: surface.material.emission_spread(),
+            link: surface.material.light_link(),
+        };
+        Some((sample, 1.0 / (choices as f64 * area)))
+    }
+
+    /// Direct light reflected by a diffuse surface of `albedo`, estimated from a single light
+    /// sample.
+    pub fn direct_light(&self, world: &BVHNode, hit_record: &HitRecord, albedo: Color) -> Color {
+        let Some((sample, pdf)) = self.candidate() else {
+            return Color::default();
+        };
+        let contribution = Self::contribution(hit_record, albedo, &sample);
+        if contribution.near_zero() {
+            return Color::default();
+        }
+        Self::transmittance(world, hit_record, &sample) * contribution / pdf
+    }
+}
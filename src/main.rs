@@ -2,35 +2,129 @@
 #![allow(clippy::cast_sign_loss)]
 #![allow(clippy::cast_possible_truncation)]
 
-use std::error::Error;
-
-mod aabb;
-mod bvh;
-mod camera;
-mod constant_medium;
-mod cuboid;
-mod entity;
-mod instance;
-mod interval;
-mod mat3;
-mod material;
-mod perlin;
-mod quad;
-mod ray;
-mod scene;
-mod sphere;
-mod texture;
-mod vec3;
-
-fn main() -> Result<(), Box<dyn Error>> {
-    let scene_path = std::env::args()
-        .nth(1)
-        .ok_or("Provide a path to the scene configuration as an argument")?;
-
-    match scene::create(&scene_path) {
-        Ok((world, camera, scene_name)) => camera.render(&world, &scene_name)?,
-        Err(e) => eprintln!("{e}"),
+use raytracer::{camera, cli, progress, scene, tile, watch};
+use std::{
+    error::Error,
+    io::{self, Write},
+    process::ExitCode,
+};
+
+// exit codes scripts can tell apart
+const EXIT_RENDER_FAILED: u8 = 1;
+const EXIT_INVALID_INPUT: u8 = 2;
+
+fn main() -> ExitCode {
+    let args = match cli::Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(EXIT_INVALID_INPUT);
+        }
+    };
+    progress::set_format(args.progress);
+    progress::set_quiet(args.quiet);
+    // streamed tiles have stdout to themselves
+    progress::set_stdout_reserved(args.stream_tiles);
+    let budget = camera::Budget {
+        max_time: args.max_time,
+        max_samples: args.max_samples,
+    };
+
+    let overrides = scene::Overrides {
+        tags: scene::TagFilter {
+            include: args.include_tags,
+            exclude: args.exclude_tags,
+        },
+        bit_depth: args.bit_depth,
+        color_space: args.color_space,
+        material: args.override_material,
+        reference: args.reference,
+        watch: args.watch,
+    };
+
+    if args.inspect {
+        return match scene::inspect(&args.scene_path, &overrides) {
+            Ok(tree) => {
+                print!("{tree}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::from(EXIT_INVALID_INPUT)
+            }
+        };
     }
 
-    Ok(())
+    let mut modified = watch::modified(&args.scene_path);
+    let mut scene = match scene::create(&args.scene_path, &overrides) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(EXIT_INVALID_INPUT);
+        }
+    };
+    loop {
+        let rendered = render(&scene, budget, args.stream_tiles);
+        if !args.watch {
+            return match rendered {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("{e}");
+                    ExitCode::from(EXIT_RENDER_FAILED)
+                }
+            };
+        }
+        if let Err(e) = rendered {
+            eprintln!("{e}");
+        }
+        // a file saved mid-edit is reported and waited past, keeping the last good scene
+        scene = loop {
+            modified = watch::wait_for_change(&args.scene_path, modified);
+            match scene::reload(&args.scene_path, &overrides, &scene) {
+                Ok(reloaded) => break reloaded,
+                Err(e) => eprintln!("{e}"),
+            }
+        };
+    }
+}
+
+fn render(
+    scene: &scene::Scene,
+    budget: camera::Budget,
+    stream_tiles: bool,
+) -> Result<(), Box<dyn Error>> {
+    match (&scene.bake, &scene.media_slice) {
+        _ if !scene.texture_bakes.is_empty() => scene
+            .texture_bakes
+            .iter()
+            .try_for_each(|texture_bake| texture_bake.render(&scene.name)),
+        (Some(bake), _) => bake.render(&scene.camera, &scene.world, &scene.name),
+        (None, Some(media_slice)) => media_slice.render(&scene.camera, &scene.world, &scene.name),
+        (None, None) => {
+            let camera = scene.camera.clone().with_budget(budget);
+            if !stream_tiles {
+                return render_camera(scene, &camera);
+            }
+            let (rendered, ()) = tile::stream(
+                |sink| render_camera(scene, &camera.with_tile_sink(Some(sink))),
+                |tiles| {
+                    for tile in tiles {
+                        // locked a line at a time, as stderr status lines may still be printed
+                        // by other threads between them
+                        if writeln!(io::stdout().lock(), "{}", tile.to_json()).is_err() {
+                            break;
+                        }
+                    }
+                },
+            );
+            rendered
+        }
+    }
+}
+
+fn render_camera(scene: &scene::Scene, camera: &camera::Camera) -> Result<(), Box<dyn Error>> {
+    match &scene.sprite_sheet {
+        Some(sprite_sheet) => sprite_sheet.render(camera, &scene.world, &scene.name),
+        None => camera.render(&scene.world, &scene.lights, &scene.name),
+    }
 }
@@ -11,17 +11,6 @@ impl Mat3 {
         Self { elements }
     }
 
-    #[allow(clippy::needless_range_loop)]
-    pub fn transpose(&self) -> Self {
-        let mut result = [[0.0; 3]; 3];
-        for i in 0..3 {
-            for j in 0..3 {
-                result[i][j] = self.elements[j][i];
-            }
-        }
-        Self::new(result)
-    }
-
     pub fn rotation_x(angle: f64) -> Self {
         let angle = angle.to_radians();
         let (sin, cos) = angle.sin_cos();
@@ -39,6 +28,37 @@ impl Mat3 {
         let (sin, cos) = angle.sin_cos();
         Self::new([[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]])
     }
+
+    /// Rotation by `angles` degrees about each axis, composed as x · y · z so the rotation
+    /// about z is applied first.
+    pub fn rotation(angles: Vec3) -> Self {
+        Self::rotation_x(angles.x()) * Self::rotation_y(angles.y()) * Self::rotation_z(angles.z())
+    }
+
+    /// Rotation by `angle` degrees about the unit vector `axis`, counter-clockwise when looking
+    /// down the axis towards the origin.
+    pub fn rotation_about(axis: Vec3, angle: f64) -> Self {
+        let angle = angle.to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let (x, y, z) = (axis.x(), axis.y(), axis.z());
+        let t = 1.0 - cos;
+        Self::new([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+        ])
+    }
+
+    /// Rotation of RGB colors about the gray axis, shifting hue by `angle` degrees while
+    /// keeping the channel sum.
+    pub fn hue_rotation(angle: f64) -> Self {
+        let angle = angle.to_radians();
+        let (sin, cos) = angle.sin_cos();
+        let a = cos + (1.0 - cos) / 3.0;
+        let b = (1.0 - cos) / 3.0 - sin / 3.0f64.sqrt();
+        let c = (1.0 - cos) / 3.0 + sin / 3.0f64.sqrt();
+        Self::new([[a, b, c], [c, a, b], [b, c, a]])
+    }
 }
 
 impl Mul for Mat3 {
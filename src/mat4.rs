@@ -0,0 +1,126 @@
+use crate::{
+    mat3::Mat3,
+    vec3::{Point3, Vec3},
+};
+use std::ops::Mul;
+
+/// Affine transform in homogeneous coordinates; the bottom row is always (0, 0, 0, 1).
+#[derive(Debug, Clone, Copy)]
+pub struct Mat4 {
+    elements: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    /// `rows` are the top three rows; the bottom one is implied.
+    pub fn new(rows: [[f64; 4]; 3]) -> Self {
+        Self {
+            elements: [rows[0], rows[1], rows[2], [0.0, 0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+        ])
+    }
+
+    pub fn scaling(scale: Vec3) -> Self {
+        Self::new([
+            [scale.x(), 0.0, 0.0, 0.0],
+            [0.0, scale.y(), 0.0, 0.0],
+            [0.0, 0.0, scale.z(), 0.0],
+        ])
+    }
+
+    /// The linear map `matrix` with no translation.
+    pub fn linear(matrix: Mat3) -> Self {
+        let columns = [
+            matrix * Vec3::new(1.0, 0.0, 0.0),
+            matrix * Vec3::new(0.0, 1.0, 0.0),
+            matrix * Vec3::new(0.0, 0.0, 1.0),
+        ];
+        Self::new([0u8, 1, 2].map(|row| [columns[0][row], columns[1][row], columns[2][row], 0.0]))
+    }
+
+    /// Determinant of the linear part, the factor volumes grow by.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.elements;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// `None` for transforms that flatten space.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-300 {
+            return None;
+        }
+        let m = &self.elements;
+        // adjugate of the linear part over its determinant
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / determinant
+        };
+        let linear = [
+            [
+                cofactor(1, 2, 1, 2),
+                -cofactor(0, 2, 1, 2),
+                cofactor(0, 1, 1, 2),
+            ],
+            [
+                -cofactor(1, 2, 0, 2),
+                cofactor(0, 2, 0, 2),
+                -cofactor(0, 1, 0, 2),
+            ],
+            [
+                cofactor(1, 2, 0, 1),
+                -cofactor(0, 2, 0, 1),
+                cofactor(0, 1, 0, 1),
+            ],
+        ];
+        let offset = Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let row = |i: usize| {
+            let [a, b, c] = linear[i];
+            [a, b, c, -(a * offset.x() + b * offset.y() + c * offset.z())]
+        };
+        Some(Self::new([row(0), row(1), row(2)]))
+    }
+
+    pub fn transform_point(&self, point: Point3) -> Point3 {
+        let m = &self.elements;
+        self.transform_vector(point) + Vec3::new(m[0][3], m[1][3], m[2][3])
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        let m = &self.elements;
+        let row = |i: usize| m[i][0] * vector.x() + m[i][1] * vector.y() + m[i][2] * vector.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    /// Normal transformed by the transform this matrix is the inverse of: the inverse
+    /// transpose keeps it perpendicular to the transformed surface.
+    pub fn transform_normal(&self, normal: Vec3) -> Vec3 {
+        let m = &self.elements;
+        let column = |j: usize| m[0][j] * normal.x() + m[1][j] * normal.y() + m[2][j] * normal.z();
+        Vec3::new(column(0), column(1), column(2))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    #[allow(clippy::needless_range_loop)]
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut result = [[0.0; 4]; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..4 {
+                    result[i][j] += self.elements[i][k] * rhs.elements[k][j];
+                }
+            }
+        }
+        Self { elements: result }
+    }
+}
@@ -1,10 +1,14 @@
 use crate::{
     entity::HitRecord,
-    ray::Ray,
-    texture::Texture,
-    vec3::{Color, Point3, Vec3},
+    mat3::Mat3,
+    ray::{Ray, RayDifferentials, RayKind},
+    texture::{ShadingContext, Texture},
+    vec3::{Color, Vec3},
+};
+use std::{
+    f64::consts::PI,
+    sync::{Arc, RwLock},
 };
-use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Reflected {
@@ -12,14 +16,96 @@ pub struct Reflected {
     pub scattered: Ray,
 }
 
+/// Carries the incoming ray's differentials across a specular bounce, treating the surface
+/// as locally flat; `bend` maps an incoming direction to the outgoing one.
+fn specular_differentials(
+    incoming: &Ray,
+    hit_record: &HitRecord,
+    bend: impl Fn(Vec3) -> Vec3,
+) -> Option<RayDifferentials> {
+    let differentials = incoming.differentials()?;
+    Some(RayDifferentials {
+        rx_origin: hit_record.hit_point + hit_record.dp_dx,
+        rx_direction: bend(differentials.rx_direction.unit()),
+        ry_origin: hit_record.hit_point + hit_record.dp_dy,
+        ry_direction: bend(differentials.ry_direction.unit()),
+    })
+}
+
 pub trait Material: Send + Sync + std::fmt::Debug {
     fn scatter(&self, _incoming: &Ray, _hit_record: &HitRecord) -> Option<Reflected> {
         None
     }
 
-    fn emit(&self, _u: f64, _v: f64, _hit_point: &Point3) -> Color {
+    fn emit(&self, _hit_record: &HitRecord) -> Color {
         Color::new(0.0, 0.0, 0.0)
     }
+
+    /// Directions the emitted light leaves the surface in.
+    fn emission_spread(&self) -> EmissionSpread {
+        EmissionSpread::default()
+    }
+
+    /// Radiance emitted at the hit towards `outgoing`, a unit vector on the side the hit was
+    /// made from.
+    fn emit_towards(&self, hit_record: &HitRecord, outgoing: Vec3) -> Color {
+        let spread = self.emission_spread();
+        if spread.is_uniform() {
+            return self.emit(hit_record);
+        }
+        let factor = spread.factor(hit_record.front, hit_record.normal.dot(outgoing));
+        if factor > 0.0 {
+            factor * self.emit(hit_record)
+        } else {
+            Color::default()
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Reflectance of a purely diffuse surface at the hit, `None` for any other kind.
+    fn diffuse_albedo(&self, _hit_record: &HitRecord) -> Option<Color> {
+        None
+    }
+
+    /// Whether light can pass through the surface; see-through materials are never culled.
+    fn is_opaque(&self) -> bool {
+        true
+    }
+
+    /// Share of the light a shadow ray carries on with through the surface at the hit, standing
+    /// in for light that would reach the far side by refraction; `None` if the surface blocks it.
+    fn shadow_transmittance(&self, _incoming: &Ray, _hit_record: &HitRecord) -> Option<Color> {
+        None
+    }
+
+    /// Average radiance emitted over the surface, used to estimate the power of lights.
+    fn emitted_power(&self) -> Color {
+        Color::default()
+    }
+
+    /// Scattered rays traced and averaged when a camera ray hits the surface.
+    fn splitting(&self) -> u16 {
+        1
+    }
+
+    /// Bounces added to a path's depth the first time it scatters off the surface.
+    fn extra_depth(&self) -> u16 {
+        0
+    }
+
+    /// Bit standing for this light among the lights linked to only some entities; 0 for
+    /// lights that reach everything and for surfaces that don't emit.
+    fn light_link(&self) -> u64 {
+        0
+    }
+
+    /// Linked lights, as `light_link` bits, that the surface doesn't receive light from.
+    fn unlinked_lights(&self) -> u64 {
+        0
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,14 +130,16 @@ impl Material for Lambertian {
             }
         };
         Some(Reflected {
-            attenuation: self.texture.color_value(
-                hit_record.u,
-                hit_record.v,
-                &hit_record.hit_point,
-            ),
-            scattered: Ray::new(hit_record.hit_point, scatter_dir, *incoming.time()),
+            attenuation: self.texture.color_value(&ShadingContext::new(hit_record)),
+            scattered: Ray::new(hit_record.hit_point, scatter_dir, *incoming.time())
+                .with_media(incoming.media())
+                .with_kind(RayKind::Diffuse),
         })
     }
+
+    fn diffuse_albedo(&self, hit_record: &HitRecord) -> Option<Color> {
+        Some(self.texture.color_value(&ShadingContext::new(hit_record)))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,76 +156,227 @@ impl Metal {
 
 impl Material for Metal {
     fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
-        let reflected = incoming.direction().reflect(hit_record.normal).unit()
-            + self.fuzz * Vec3::random_unit_vector();
-        let scattered = Ray::new(hit_record.hit_point, reflected, *incoming.time());
-
-        if scattered.direction().dot(hit_record.normal) > 0.0 {
-            Some(Reflected {
-                attenuation: self.albedo,
-                scattered,
-            })
-        } else {
-            None
-        }
+        let mirror = incoming.direction().reflect(hit_record.normal).unit();
+        // directions below the surface are redrawn from the same lobe rather than absorbed,
+        // so the accepted ones follow the lobe cut off at the surface and rough metal at
+        // grazing angles doesn't lose most of its samples
+        let attempts = if self.fuzz > 0.0 { FUZZ_ATTEMPTS } else { 1 };
+        let reflected = (0..attempts)
+            .map(|_| mirror + self.fuzz * Vec3::random_unit_vector())
+            .find(|direction| direction.dot(hit_record.normal) > 0.0)?;
+        let differentials =
+            specular_differentials(incoming, hit_record, |d| d.reflect(hit_record.normal));
+        let scattered = Ray::new(hit_record.hit_point, reflected, *incoming.time())
+            .with_differentials(differentials)
+            .with_media(incoming.media())
+            .with_kind(RayKind::Specular);
+
+        Some(Reflected {
+            attenuation: self.albedo,
+            scattered,
+        })
     }
 }
 
+/// Draws from a fuzzed metal lobe before a scattered ray is given up as absorbed.
+const FUZZ_ATTEMPTS: u32 = 8;
+
 #[derive(Debug, Clone)]
 pub struct Dielectric {
     refraction_index: f64,
+    /// Color shadow rays are tinted by as they cross the surface, or `None` if it blocks them.
+    shadow_tint: Option<Color>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self {
+            refraction_index,
+            shadow_tint: None,
+        }
     }
 
-    fn reflectance(&self, cosine: f64) -> f64 {
-        let r0 = ((1.0 - self.refraction_index) / (1.0 + self.refraction_index)).powi(2);
+    /// Lets shadow rays through, tinted by `tint` and dimmed by what the surface reflects, so
+    /// the glass casts a colored, lighter shadow instead of a black one where direct light is
+    /// sampled. The caustic it would focus is left out.
+    pub fn with_shadow_tint(mut self, tint: Option<Color>) -> Self {
+        self.shadow_tint = tint;
+        self
+    }
+
+    /// Schlick's approximation for an interface with relative refractive index `ri`.
+    fn reflectance(cosine: f64, ri: f64) -> f64 {
+        let r0 = ((1.0 - ri) / (1.0 + ri)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+    fn is_opaque(&self) -> bool {
+        false
+    }
+
+    fn shadow_transmittance(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Color> {
+        let tint = self.shadow_tint?;
         let ri = if hit_record.front {
             1.0 / self.refraction_index
         } else {
             self.refraction_index
         };
+        let cos_theta = (-incoming.direction().unit())
+            .dot(hit_record.normal)
+            .min(1.0);
+        let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+        if ri * sin_theta > 1.0 {
+            return Some(Color::default());
+        }
+        Some((1.0 - Self::reflectance(cos_theta, ri)) * tint)
+    }
+
+    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+        // the medium on the other side comes from the ray's stack, so glass in water bends
+        // light by their relative index rather than as if surrounded by air
+        let media = incoming.media();
+        let (ri, refracted_media) = if hit_record.front {
+            (
+                media.current_ior() / self.refraction_index,
+                media.entered(self.refraction_index),
+            )
+        } else {
+            let outside = media.exited(self.refraction_index);
+            (self.refraction_index / outside.current_ior(), outside)
+        };
 
         let unit_dir = incoming.direction().unit();
         let cos_theta = (-unit_dir).dot(hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let direction = if ri * sin_theta > 1.0 || self.reflectance(cos_theta) > fastrand::f64() {
-            unit_dir.reflect(hit_record.normal)
-        } else {
-            unit_dir.refract(hit_record.normal, ri)
-        };
+        let normal = hit_record.normal;
+        let (direction, differentials, media) =
+            if ri * sin_theta > 1.0 || Self::reflectance(cos_theta, ri) > fastrand::f64() {
+                (
+                    unit_dir.reflect(normal),
+                    specular_differentials(incoming, hit_record, |d| d.reflect(normal)),
+                    media,
+                )
+            } else {
+                (
+                    unit_dir.refract(normal, ri),
+                    specular_differentials(incoming, hit_record, |d| d.refract(normal, ri)),
+                    refracted_media,
+                )
+            };
 
         Some(Reflected {
             attenuation: Color::new(1.0, 1.0, 1.0),
-            scattered: Ray::new(hit_record.hit_point, direction, *incoming.time()),
+            scattered: Ray::new(hit_record.hit_point, direction, *incoming.time())
+                .with_differentials(differentials)
+                .with_media(media)
+                .with_kind(RayKind::Specular),
         })
     }
 }
 
+/// Limits emission to the front face and/or a cone around the normal, like a light panel
+/// with a honeycomb grid.
+#[derive(Debug, Clone, Copy)]
+pub struct EmissionSpread {
+    pub one_sided: bool,
+    /// Full opening angle of the emission cone in radians; π emits over the whole hemisphere.
+    pub angle: f64,
+}
+
+impl Default for EmissionSpread {
+    fn default() -> Self {
+        Self {
+            one_sided: false,
+            angle: PI,
+        }
+    }
+}
+
+// fraction of the cone's half-angle over which emission fades out, softening the light's edge
+const SPREAD_SOFTNESS: f64 = 0.1;
+
+impl EmissionSpread {
+    fn is_uniform(&self) -> bool {
+        !self.one_sided && self.angle >= PI
+    }
+
+    /// Emission scale on the `front` or back face towards a direction at `cos_theta` to the normal.
+    pub fn factor(&self, front: bool, cos_theta: f64) -> f64 {
+        if self.one_sided && !front {
+            return 0.0;
+        }
+        if self.angle >= PI {
+            return 1.0;
+        }
+        let half_angle = 0.5 * self.angle;
+        let theta = cos_theta.abs().min(1.0).acos();
+        ((half_angle - theta) / (SPREAD_SOFTNESS * half_angle)).clamp(0.0, 1.0)
+    }
+
+    /// Share of a uniform emitter's power that leaves through the spread.
+    fn power_fraction(&self) -> f64 {
+        let side = if self.one_sided { 0.5 } else { 1.0 };
+        side * (0.5 * self.angle.min(PI)).sin().powi(2)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DiffuseLight {
     texture: Arc<dyn Texture>,
+    spread: EmissionSpread,
+    /// Factor on the radiance the texture gives.
+    tint: Color,
+    /// Factor on the tinted radiance, for lights brighter than a color can say.
+    intensity: f64,
 }
 
 impl DiffuseLight {
     pub fn new(texture: Arc<dyn Texture>) -> Self {
-        Self { texture }
+        Self {
+            texture,
+            spread: EmissionSpread::default(),
+            tint: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+        }
+    }
+
+    /// Scales the radiance the texture gives, per channel.
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Scales the radiance in all channels, e.g. 15 for a small white light 15 times as bright
+    /// as its texture.
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_spread(mut self, spread: EmissionSpread) -> Self {
+        self.spread = spread;
+        self
     }
 }
 
 impl Material for DiffuseLight {
-    fn emit(&self, u: f64, v: f64, hit_point: &Point3) -> Color {
-        self.texture.color_value(u, v, hit_point)
+    fn emit(&self, hit_record: &HitRecord) -> Color {
+        self.intensity * self.tint * self.texture.color_value(&ShadingContext::new(hit_record))
+    }
+
+    fn emission_spread(&self) -> EmissionSpread {
+        self.spread
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.spread.power_fraction() * self.intensity * self.tint * self.texture.average_color()
     }
 }
 
@@ -153,16 +392,20 @@ impl Isotropic {
 }
 
 impl Material for Isotropic {
+    fn is_opaque(&self) -> bool {
+        false
+    }
+
     fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
         let scattered = Ray::new(
             hit_record.hit_point,
             Vec3::random_unit_vector(),
             *incoming.time(),
-        );
+        )
+        .with_media(incoming.media())
+        .with_kind(RayKind::Diffuse);
 
-        let attenuation =
-            self.texture
-                .color_value(hit_record.u, hit_record.v, &hit_record.hit_point);
+        let attenuation = self.texture.color_value(&ShadingContext::new(hit_record));
 
         Some(Reflected {
             attenuation,
@@ -170,3 +413,306 @@ impl Material for Isotropic {
         })
     }
 }
+
+/// Wraps a material to spend more of the sample budget on it, e.g. to tame noise behind glass.
+#[derive(Debug, Clone)]
+pub struct Budgeted {
+    material: Arc<dyn Material>,
+    splitting: u16,
+    extra_depth: u16,
+}
+
+impl Budgeted {
+    pub fn new(material: Arc<dyn Material>, splitting: u16, extra_depth: u16) -> Self {
+        Self {
+            material,
+            splitting: splitting.max(1),
+            extra_depth,
+        }
+    }
+}
+
+impl Material for Budgeted {
+    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+        self.material.scatter(incoming, hit_record)
+    }
+
+    fn emit(&self, hit_record: &HitRecord) -> Color {
+        self.material.emit(hit_record)
+    }
+
+    fn emission_spread(&self) -> EmissionSpread {
+        self.material.emission_spread()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn diffuse_albedo(&self, hit_record: &HitRecord) -> Option<Color> {
+        self.material.diffuse_albedo(hit_record)
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.material.is_opaque()
+    }
+
+    fn shadow_transmittance(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Color> {
+        self.material.shadow_transmittance(incoming, hit_record)
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.material.emitted_power()
+    }
+
+    fn splitting(&self) -> u16 {
+        self.splitting
+    }
+
+    fn extra_depth(&self) -> u16 {
+        self.extra_depth
+    }
+
+    fn light_link(&self) -> u64 {
+        self.material.light_link()
+    }
+
+    fn unlinked_lights(&self) -> u64 {
+        self.material.unlinked_lights()
+    }
+}
+
+/// Wraps a material to rotate the hue of everything it reflects or emits, so copies of one
+/// object can vary in color.
+#[derive(Debug, Clone)]
+pub struct HueShifted {
+    material: Arc<dyn Material>,
+    rotation: Mat3,
+}
+
+impl HueShifted {
+    /// `angle` is the hue shift in degrees.
+    pub fn new(material: Arc<dyn Material>, angle: f64) -> Self {
+        Self {
+            material,
+            rotation: Mat3::hue_rotation(angle),
+        }
+    }
+
+    fn shift(&self, color: Color) -> Color {
+        let shifted = self.rotation * color;
+        Color::new(
+            shifted.x().max(0.0),
+            shifted.y().max(0.0),
+            shifted.z().max(0.0),
+        )
+    }
+}
+
+impl Material for HueShifted {
+    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+        let reflected = self.material.scatter(incoming, hit_record)?;
+        Some(Reflected {
+            attenuation: self.shift(reflected.attenuation),
+            scattered: reflected.scattered,
+        })
+    }
+
+    fn emit(&self, hit_record: &HitRecord) -> Color {
+        self.shift(self.material.emit(hit_record))
+    }
+
+    fn emission_spread(&self) -> EmissionSpread {
+        self.material.emission_spread()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn diffuse_albedo(&self, hit_record: &HitRecord) -> Option<Color> {
+        self.material
+            .diffuse_albedo(hit_record)
+            .map(|albedo| self.shift(albedo))
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.material.is_opaque()
+    }
+
+    fn shadow_transmittance(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Color> {
+        self.material
+            .shadow_transmittance(incoming, hit_record)
+            .map(|transmittance| self.shift(transmittance))
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.shift(self.material.emitted_power())
+    }
+
+    fn splitting(&self) -> u16 {
+        self.material.splitting()
+    }
+
+    fn extra_depth(&self) -> u16 {
+        self.material.extra_depth()
+    }
+
+    fn light_link(&self) -> u64 {
+        self.material.light_link()
+    }
+
+    fn unlinked_lights(&self) -> u64 {
+        self.material.unlinked_lights()
+    }
+}
+
+/// Wraps a material to take part in light linking: as a light reaching only some entities,
+/// or as a surface left out of some lights.
+#[derive(Debug, Clone)]
+pub struct LightLinked {
+    material: Arc<dyn Material>,
+    link: u64,
+    unlinked: u64,
+}
+
+impl LightLinked {
+    pub fn new(material: Arc<dyn Material>, link: u64, unlinked: u64) -> Self {
+        Self {
+            material,
+            link,
+            unlinked,
+        }
+    }
+}
+
+impl Material for LightLinked {
+    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+        self.material.scatter(incoming, hit_record)
+    }
+
+    fn emit(&self, hit_record: &HitRecord) -> Color {
+        self.material.emit(hit_record)
+    }
+
+    fn emission_spread(&self) -> EmissionSpread {
+        self.material.emission_spread()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn diffuse_albedo(&self, hit_record: &HitRecord) -> Option<Color> {
+        self.material.diffuse_albedo(hit_record)
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.material.is_opaque()
+    }
+
+    fn shadow_transmittance(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Color> {
+        self.material.shadow_transmittance(incoming, hit_record)
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.material.emitted_power()
+    }
+
+    fn splitting(&self) -> u16 {
+        self.material.splitting()
+    }
+
+    fn extra_depth(&self) -> u16 {
+        self.material.extra_depth()
+    }
+
+    fn light_link(&self) -> u64 {
+        self.link
+    }
+
+    fn unlinked_lights(&self) -> u64 {
+        self.unlinked
+    }
+}
+
+// materials swapped in while watching a scene, indexed by slot
+static SLOTS: RwLock<Vec<Option<Arc<dyn Material>>>> = RwLock::new(Vec::new());
+
+/// Puts `material` in `slot`, replacing what was there for every `Slotted` pointing at it.
+pub fn set_slot(slot: usize, material: Arc<dyn Material>) {
+    let mut slots = SLOTS.write().unwrap();
+    if slots.len() <= slot {
+        slots.resize(slot + 1, None);
+    }
+    slots[slot] = Some(material);
+}
+
+/// Stands in for whichever material is currently in its slot, so a scene's materials can be
+/// edited while its geometry and BVH stay as they are.
+#[derive(Debug, Clone)]
+pub struct Slotted {
+    slot: usize,
+}
+
+impl Slotted {
+    pub fn new(slot: usize) -> Self {
+        Self { slot }
+    }
+
+    fn material(&self) -> Arc<dyn Material> {
+        SLOTS.read().unwrap()[self.slot]
+            .clone()
+            .expect("material slots are filled as they're handed out")
+    }
+}
+
+impl Material for Slotted {
+    fn scatter(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Reflected> {
+        self.material().scatter(incoming, hit_record)
+    }
+
+    fn emit(&self, hit_record: &HitRecord) -> Color {
+        self.material().emit(hit_record)
+    }
+
+    fn emission_spread(&self) -> EmissionSpread {
+        self.material().emission_spread()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material().is_emissive()
+    }
+
+    fn diffuse_albedo(&self, hit_record: &HitRecord) -> Option<Color> {
+        self.material().diffuse_albedo(hit_record)
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.material().is_opaque()
+    }
+
+    fn shadow_transmittance(&self, incoming: &Ray, hit_record: &HitRecord) -> Option<Color> {
+        self.material().shadow_transmittance(incoming, hit_record)
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.material().emitted_power()
+    }
+
+    fn splitting(&self) -> u16 {
+        self.material().splitting()
+    }
+
+    fn extra_depth(&self) -> u16 {
+        self.material().extra_depth()
+    }
+
+    fn light_link(&self) -> u64 {
+        self.material().light_link()
+    }
+
+    fn unlinked_lights(&self) -> u64 {
+        self.material().unlinked_lights()
+    }
+}
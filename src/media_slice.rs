@@ -0,0 +1,73 @@
+use crate::{
+    bvh::BVHNode,
+    camera::Camera,
+    entity::Entity,
+    output,
+    progress::{self, status},
+    vec3::{Point3, Vec3},
+};
+use indicatif::ParallelProgressIterator;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{error::Error, time::Instant};
+
+// shade of pixels whose ray crosses the plane outside any medium
+const EMPTY_SHADE: u8 = 24;
+
+/// Debug view of participating media: each pixel shows, in false color, the density where
+/// its camera ray crosses a plane through the scene, so volumes can be placed and tuned
+/// without rendering them. Surfaces are ignored.
+#[derive(Debug, Clone)]
+pub struct MediaSlice {
+    pub point: Point3,
+    pub normal: Vec3,
+    /// Density at the top of the color ramp; the densest point on the slice if not given.
+    pub max_density: Option<f64>,
+}
+
+impl MediaSlice {
+    /// Density where the camera ray through pixel (i, j) crosses the plane, `None` if it
+    /// doesn't.
+    fn sample(&self, camera: &Camera, world: &BVHNode, i: u32, j: u32) -> Option<f64> {
+        let ray = camera.center_ray(i, j);
+        let denominator = self.normal.dot(*ray.direction());
+        if denominator == 0.0 {
+            return None;
+        }
+        let time = (self.point - *ray.origin()).dot(self.normal) / denominator;
+        (time > 0.0).then(|| world.density(ray.at(time), *ray.time()))
+    }
+
+    pub fn render(
+        &self,
+        camera: &Camera,
+        world: &BVHNode,
+        scene_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let (width, height) = (camera.image_width(), camera.image_height());
+        let densities: Vec<Option<f64>> = (0..width * height)
+            .into_par_iter()
+            .progress_with(progress::progress_bar(width as u64 * height as u64))
+            .map(|index| self.sample(camera, world, index % width, index / width))
+            .collect();
+
+        let peak = densities.iter().flatten().copied().fold(0.0, f64::max);
+        let max_density = self.max_density.unwrap_or(peak);
+        let raw: Vec<u8> = densities
+            .iter()
+            .flat_map(|density| match *density {
+                None => [0; 3],
+                Some(density) if density <= 0.0 => [EMPTY_SHADE; 3],
+                Some(density) => output::false_color(density / max_density),
+            })
+            .collect();
+        let path = output::result_path(scene_name, "_media_slice", "png")?;
+        output::write_png(&path, width, height, &raw)?;
+
+        status!("Finished");
+        status!("Slice Time: {:.3}s", start.elapsed().as_secs_f64());
+        status!("Media Slice: {path}");
+        status!("Peak Density: {peak:.4} | Ramp Top: {max_density:.4}");
+        Ok(())
+    }
+}
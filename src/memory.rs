@@ -0,0 +1,67 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static TEXTURES: AtomicUsize = AtomicUsize::new(0);
+static BVH: AtomicUsize = AtomicUsize::new(0);
+static MESHES: AtomicUsize = AtomicUsize::new(0);
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Category {
+    Textures,
+    Bvh,
+    Meshes,
+}
+
+/// Counts `bytes` against `category`; called by whatever allocates scene data as it's built.
+pub fn track(category: Category, bytes: usize) {
+    let counter = match category {
+        Category::Textures => &TEXTURES,
+        Category::Bvh => &BVH,
+        Category::Meshes => &MESHES,
+    };
+    counter.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Approximate bytes held by the scene's textures, BVH nodes and mesh buffers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    pub textures: usize,
+    pub bvh: usize,
+    pub meshes: usize,
+}
+
+impl MemoryUsage {
+    /// Everything tracked so far.
+    pub fn current() -> Self {
+        Self {
+            textures: TEXTURES.load(Ordering::Relaxed),
+            bvh: BVH.load(Ordering::Relaxed),
+            meshes: MESHES.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.textures + self.bvh + self.meshes
+    }
+
+    pub fn total_mb(&self) -> f64 {
+        self.total() as f64 / BYTES_PER_MB
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} MB (Textures: {:.1} MB | BVH: {:.1} MB | Meshes: {:.1} MB)",
+            self.total_mb(),
+            self.textures as f64 / BYTES_PER_MB,
+            self.bvh as f64 / BYTES_PER_MB,
+            self.meshes as f64 / BYTES_PER_MB
+        )
+    }
+}
@@ -0,0 +1,286 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BVHNode,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    memory::{self, Category},
+    obj, ply,
+    ray::Ray,
+    stl,
+    triangle::{self, Triangle},
+    vec3::{Color, Point3, Vec3},
+};
+use std::{error::Error, mem::size_of, path::Path, sync::Arc};
+
+/// Triangle soup as read from a model file.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub uvs: Vec<(f64, f64)>,
+    /// Three indices into `positions` per triangle.
+    pub indices: Vec<u32>,
+    /// Three indices into `uvs` per triangle, or empty if the mesh has no texture coordinates.
+    pub uv_indices: Vec<u32>,
+    /// One per position, or empty if the file has no vertex colors.
+    pub colors: Vec<Color>,
+    /// One per position, or empty if the file has no vertex normals.
+    pub normals: Vec<Vec3>,
+}
+
+impl MeshData {
+    /// Reads the model at `path`, picking the format from its extension.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let data = match extension.as_deref() {
+            Some("obj") => obj::load(path)?,
+            Some("stl") => stl::load(path)?,
+            Some("ply") => ply::load(path)?,
+            _ => return Err(format!("Unsupported mesh format '{path}'").into()),
+        };
+        if data.indices.is_empty() {
+            return Err(format!("Mesh '{path}' has no faces").into());
+        }
+        Ok(data)
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn vertices(&self, face: usize) -> [Point3; 3] {
+        let corners = &self.indices[3 * face..3 * face + 3];
+        [0, 1, 2].map(|i| self.positions[corners[i] as usize])
+    }
+
+    fn face_uvs(&self, face: usize) -> [(f64, f64); 3] {
+        if self.uv_indices.is_empty() {
+            return Triangle::DEFAULT_UVS;
+        }
+        let corners = &self.uv_indices[3 * face..3 * face + 3];
+        [0, 1, 2].map(|i| self.uvs[corners[i] as usize])
+    }
+
+    /// Vertex color at barycentric coordinates (`beta`, `gamma`) of `face`.
+    fn color(&self, face: usize, beta: f64, gamma: f64) -> Option<Color> {
+        if self.colors.is_empty() {
+            return None;
+        }
+        let corners = &self.indices[3 * face..3 * face + 3];
+        let [c0, c1, c2] = [0, 1, 2].map(|i| self.colors[corners[i] as usize]);
+        Some((1.0 - beta - gamma) * c0 + beta * c1 + gamma * c2)
+    }
+
+    /// Normal at barycentric coordinates (`beta`, `gamma`) of `face`, blended from its
+    /// vertices' normals.
+    fn normal(&self, face: usize, beta: f64, gamma: f64) -> Option<Vec3> {
+        if self.normals.is_empty() {
+            return None;
+        }
+        let corners = &self.indices[3 * face..3 * face + 3];
+        let [n0, n1, n2] = [0, 1, 2].map(|i| self.normals[corners[i] as usize]);
+        let normal = (1.0 - beta - gamma) * n0 + beta * n1 + gamma * n2;
+        (!normal.near_zero()).then(|| normal.unit())
+    }
+
+    /// Gives every position the average of the normals of the faces around it, weighted by
+    /// their areas, for smooth shading of files without vertex normals.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for face in 0..self.triangle_count() {
+            let [a, b, c] = self.vertices(face);
+            // the cross product's length is twice the face's area
+            let normal = (b - a).cross(c - a);
+            for &corner in &self.indices[3 * face..3 * face + 3] {
+                normals[corner as usize] += normal;
+            }
+        }
+        for normal in &mut normals {
+            if !normal.near_zero() {
+                *normal = normal.unit();
+            }
+        }
+        self.normals = normals;
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.positions.len() * size_of::<Point3>()
+            + self.colors.len() * size_of::<Color>()
+            + self.normals.len() * size_of::<Vec3>()
+            + self.uvs.len() * size_of::<(f64, f64)>()
+            + (self.indices.len() + self.uv_indices.len()) * size_of::<u32>()
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    data: MeshData,
+    material: Arc<dyn Material>,
+}
+
+/// One triangle of a mesh, looked up in the shared buffers when hit.
+#[derive(Debug)]
+struct Face {
+    mesh: Arc<Shared>,
+    index: u32,
+}
+
+impl Face {
+    fn edges(&self) -> (Point3, Vec3, Vec3) {
+        let [a, b, c] = self.mesh.data.vertices(self.index as usize);
+        (a, b - a, c - a)
+    }
+}
+
+impl Entity for Face {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let (a, e1, e2) = self.edges();
+        let (time, beta, gamma) = triangle::intersect(a, e1, e2, ray, time_interval)?;
+        let uvs = self.mesh.data.face_uvs(self.index as usize);
+        let (u, v) = triangle::interpolate_uv(uvs, beta, gamma);
+        let n = e1.cross(e2);
+        let mut hit_record = HitRecord::new(
+            ray.at(time),
+            ray,
+            n.unit(),
+            time,
+            u,
+            v,
+            &*self.mesh.material,
+        );
+        if let Some(shading) = self.mesh.data.normal(self.index as usize, beta, gamma) {
+            // turned to the face's own side, so the side a ray hits from doesn't flip
+            let shading = if shading.dot(n) < 0.0 {
+                -shading
+            } else {
+                shading
+            };
+            hit_record.normal = if hit_record.front { shading } else { -shading };
+        }
+        let (dp_du, dp_dv) = triangle::surface_derivatives(e1, e2, uvs);
+        hit_record.set_tangent(dp_du);
+        hit_record.dp_du = dp_du;
+        hit_record.dp_dv = dp_dv;
+        hit_record.edge_distance = triangle::edge_distance(e1, e2, n.length(), beta, gamma);
+        hit_record.vertex_color = self.mesh.data.color(self.index as usize, beta, gamma);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.mesh.data.vertices(self.index as usize);
+        Aabb::enclose(&Aabb::new_from_points(a, b), &Aabb::new_from_points(a, c))
+    }
+}
+
+/// Triangle mesh with its own BVH over the faces.
+#[derive(Debug)]
+pub struct Mesh {
+    mesh: Arc<Shared>,
+    bvh: BVHNode,
+    /// Running total of face areas, for picking a face to sample in proportion to its area.
+    area_cdf: Vec<f64>,
+}
+
+impl Mesh {
+    /// Builds the mesh with every position multiplied by `scale`; faces without area are dropped.
+    pub fn new(
+        mut data: MeshData,
+        scale: f64,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, Box<dyn Error>> {
+        for position in &mut data.positions {
+            *position = scale * *position;
+        }
+        let face_area = |face: usize| {
+            let [a, b, c] = data.vertices(face);
+            0.5 * (b - a).cross(c - a).length()
+        };
+        let kept: Vec<usize> = (0..data.triangle_count())
+            .filter(|&face| face_area(face) > 0.0)
+            .collect();
+        if kept.is_empty() {
+            return Err("Mesh has no faces with any area".into());
+        }
+        let area_cdf = kept
+            .iter()
+            .scan(0.0, |total, &face| {
+                *total += face_area(face);
+                Some(*total)
+            })
+            .collect();
+        let corners = |indices: &[u32]| -> Vec<u32> {
+            kept.iter()
+                .flat_map(|&face| indices[3 * face..3 * face + 3].iter().copied())
+                .collect()
+        };
+        data.indices = corners(&data.indices);
+        if !data.uv_indices.is_empty() {
+            data.uv_indices = corners(&data.uv_indices);
+        }
+        memory::track(
+            Category::Meshes,
+            data.bytes() + kept.len() * (size_of::<Face>() + size_of::<f64>()),
+        );
+
+        let mesh = Arc::new(Shared { data, material });
+        let mut faces: Vec<Arc<dyn Entity>> = (0..kept.len() as u32)
+            .map(|index| {
+                Arc::new(Face {
+                    mesh: mesh.clone(),
+                    index,
+                }) as Arc<dyn Entity>
+            })
+            .collect();
+        let bvh = BVHNode::new(&mut faces);
+        Ok(Self {
+            mesh,
+            bvh,
+            area_cdf,
+        })
+    }
+
+    fn area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or_default()
+    }
+}
+
+impl Entity for Mesh {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, time_interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let area = self.area();
+        let target = fastrand::f64() * area;
+        let face = self
+            .area_cdf
+            .partition_point(|&total| total < target)
+            .min(self.area_cdf.len() - 1);
+        let [a, b, c] = self.mesh.data.vertices(face);
+        let (e1, e2) = (b - a, c - a);
+        let (beta, gamma) = triangle::sample_barycentric();
+        let (u, v) = triangle::interpolate_uv(self.mesh.data.face_uvs(face), beta, gamma);
+        let mut hit_record = HitRecord::raw(
+            a + beta * e1 + gamma * e2,
+            e1.cross(e2).unit(),
+            0.0,
+            true,
+            u,
+            v,
+            &*self.mesh.material,
+        );
+        hit_record.vertex_color = self.mesh.data.color(face, beta, gamma);
+        Some((hit_record, area))
+    }
+
+    fn emitted_power(&self) -> Color {
+        std::f64::consts::PI * self.area() * self.mesh.material.emitted_power()
+    }
+}
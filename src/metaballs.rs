@@ -0,0 +1,156 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    epsilon,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+use std::sync::Arc;
+
+// marching steps before a ray is taken to have missed
+const MAX_STEPS: u32 = 512;
+// halvings of the step that crossed the surface
+const BISECTIONS: u32 = 40;
+// steepest slope of the kernel (1 - x²)³, reached at x = 1/√5
+const KERNEL_SLOPE: f64 = 1.7173;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metaball {
+    pub center: Point3,
+    /// Distance at which the ball's contribution reaches zero.
+    pub radius: f64,
+    pub weight: f64,
+}
+
+impl Metaball {
+    /// Contribution to the field at `p` and its gradient.
+    fn field(&self, p: Point3) -> (f64, Vec3) {
+        let offset = p - self.center;
+        let x2 = offset.length_sq() / (self.radius * self.radius);
+        if x2 >= 1.0 {
+            return (0.0, Vec3::default());
+        }
+        let falloff = 1.0 - x2;
+        let gradient = -6.0 * self.weight * falloff * falloff / (self.radius * self.radius);
+        (self.weight * falloff.powi(3), gradient * offset)
+    }
+}
+
+/// Blobby surface where the summed fields of the balls reach `threshold`, so nearby balls
+/// melt into each other. The field has no distance to step by, so rays march by a bound on
+/// how fast it can change and bisect the step that crosses the surface.
+#[derive(Debug, Clone)]
+pub struct Metaballs {
+    balls: Vec<Metaball>,
+    threshold: f64,
+    /// Upper bound on the field's gradient, turning its gap to the threshold into a distance
+    /// the surface is at least away.
+    lipschitz: f64,
+    material: Arc<dyn Material>,
+    bounding_box: Aabb,
+}
+
+impl Metaballs {
+    pub fn new(balls: Vec<Metaball>, threshold: f64, material: Arc<dyn Material>) -> Self {
+        assert!(!balls.is_empty(), "Metaballs need at least one ball");
+        assert!(threshold > 0.0, "Metaball threshold must be positive");
+        let lipschitz = balls
+            .iter()
+            .map(|ball| KERNEL_SLOPE * ball.weight.abs() / ball.radius)
+            .sum();
+        let bounding_box = balls
+            .iter()
+            .map(|ball| {
+                let extent = Vec3::new(ball.radius, ball.radius, ball.radius);
+                Aabb::new_from_points(ball.center - extent, ball.center + extent)
+            })
+            .reduce(|a, b| Aabb::enclose(&a, &b))
+            .unwrap();
+        Self {
+            balls,
+            threshold,
+            lipschitz,
+            material,
+            bounding_box,
+        }
+    }
+
+    /// Field minus the threshold at `p`, positive inside, and its gradient.
+    fn level(&self, p: Point3) -> (f64, Vec3) {
+        self.balls.iter().fold(
+            (-self.threshold, Vec3::default()),
+            |(value, gradient), ball| {
+                let (v, g) = ball.field(p);
+                (value + v, gradient + g)
+            },
+        )
+    }
+}
+
+impl Entity for Metaballs {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let (start, end) = self.bounding_box.hit(ray, time_interval)?;
+        let speed = ray.direction().length();
+        let epsilon = epsilon::scene_epsilon();
+
+        let (level, gradient) = self.level(ray.at(start));
+        // a ray leaving the surface starts on it, where only its heading tells which side
+        // it is going to
+        let inside = if level.abs() / self.lipschitz < epsilon {
+            ray.direction().dot(gradient) > 0.0
+        } else {
+            level > 0.0
+        };
+        let (mut before, mut time) = (start, start);
+        for _ in 0..MAX_STEPS {
+            let (level, _) = self.level(ray.at(time));
+            if (level > 0.0) != inside {
+                break;
+            }
+            before = time;
+            time += (level.abs() / self.lipschitz).max(epsilon) / speed;
+            if time > end {
+                return None;
+            }
+        }
+        if time == start {
+            return None;
+        }
+
+        let (mut low, mut high) = (before, time);
+        for _ in 0..BISECTIONS {
+            let mid = 0.5 * (low + high);
+            if (self.level(ray.at(mid)).0 > 0.0) == inside {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let time = high;
+        if !time_interval.surrounds(time) {
+            return None;
+        }
+        let point = ray.at(time);
+        // the field grows inwards, so the outward normal runs against its gradient
+        let (_, gradient) = self.level(point);
+        if gradient.near_zero() {
+            return None;
+        }
+        Some(HitRecord::new(
+            point,
+            ray,
+            -gradient.unit(),
+            time,
+            0.0,
+            0.0,
+            &*self.material,
+        ))
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{mesh::MeshData, vec3::Point3};
+use std::{error::Error, fs};
+
+/// Reads the positions, texture coordinates and faces of a Wavefront OBJ file; polygons are
+/// split into fans of triangles and everything else (normals, groups, materials) is ignored.
+pub fn load(path: &str) -> Result<MeshData, Box<dyn Error>> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read '{}' : {}", path, e))?;
+    let mut data = MeshData::default();
+    // texture coordinates are kept only if every face has them
+    let mut textured = true;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let error = |message: &str| format!("{path}:{} : {message}", number + 1);
+        match fields.next() {
+            Some("v") => {
+                let [x, y, z] = parse_floats(fields).ok_or_else(|| error("Invalid vertex"))?;
+                data.positions.push(Point3::new(x, y, z));
+            }
+            Some("vt") => {
+                let [u, v] =
+                    parse_floats(fields).ok_or_else(|| error("Invalid texture coordinate"))?;
+                data.uvs.push((u, v));
+            }
+            Some("f") => {
+                let mut corners = Vec::new();
+                for corner in fields {
+                    let mut indices = corner.split('/');
+                    let position = indices
+                        .next()
+                        .and_then(|i| resolve(i, data.positions.len()))
+                        .ok_or_else(|| error("Invalid face"))?;
+                    let uv = match indices.next() {
+                        Some(i) if !i.is_empty() => {
+                            Some(resolve(i, data.uvs.len()).ok_or_else(|| error("Invalid face"))?)
+                        }
+                        _ => None,
+                    };
+                    corners.push((position, uv));
+                }
+                if corners.len() < 3 {
+                    return Err(error("Face needs at least three corners").into());
+                }
+                textured &= corners.iter().all(|(_, uv)| uv.is_some());
+                for i in 1..corners.len() - 1 {
+                    for (position, uv) in [corners[0], corners[i], corners[i + 1]] {
+                        data.indices.push(position);
+                        data.uv_indices.push(uv.unwrap_or_default());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !textured {
+        data.uvs.clear();
+        data.uv_indices.clear();
+    }
+    Ok(data)
+}
+
+/// Takes exactly `N` numbers, ignoring an optional trailing one (like a `w` component).
+fn parse_floats<'a, const N: usize>(mut fields: impl Iterator<Item = &'a str>) -> Option<[f64; N]> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        *value = fields.next()?.parse().ok()?;
+    }
+    Some(values)
+}
+
+/// Zero-based index for an OBJ index, which counts from one or, if negative, back from the end
+/// of the `count` elements read so far.
+fn resolve(index: &str, count: usize) -> Option<u32> {
+    let index: i64 = index.parse().ok()?;
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => count as i64 + index,
+        0 => return None,
+    };
+    (0..count as i64)
+        .contains(&resolved)
+        .then_some(resolved as u32)
+}
@@ -0,0 +1,312 @@
+use crate::{
+    film::{Film, PixelAccumulator},
+    mat3::Mat3,
+    vec3::Vec3,
+};
+use image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder, Rgb32FImage, Rgba32FImage};
+use std::{
+    error::Error,
+    fs::{create_dir_all, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+pub const OUTPUT_DIR: &str = "./results";
+
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    pub variance_heatmap: bool,
+    pub preview: bool,
+    pub motion_vectors: bool,
+    /// Time spent per pixel by the recursive renderer, or rays traced per pixel by the wavefront one.
+    pub cost_heatmap: bool,
+    /// Linear radiance as `<name>.exr`, alongside the display PNG; float output already
+    /// takes that name, so it's skipped then.
+    pub exr: bool,
+    /// Red/cyan composite of the two eyes of a stereo render.
+    pub anaglyph: bool,
+    pub bit_depth: BitDepth,
+    pub color_space: ColorSpace,
+    /// Lets overexposed colors fade to white in the PNG rather than clip to saturated primaries.
+    pub highlight_rolloff: bool,
+    /// Adds the film's coverage to the main image as an alpha channel.
+    pub alpha: bool,
+}
+
+/// Precision of the main image; `Float` writes it as `<name>.exr` instead of a PNG.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+    Float,
+}
+
+/// Color space the main image is written in and tagged with.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ColorSpace {
+    /// The renderer's own primaries, display-encoded.
+    #[default]
+    Srgb,
+    /// Same primaries, with no transfer curve.
+    Linear,
+    /// Wider-gamut P3 primaries with a D65 white, display-encoded.
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// Converts the renderer's linear sRGB into this space's primaries, still linear.
+    fn conversion(self) -> Option<Mat3> {
+        match self {
+            Self::Srgb | Self::Linear => None,
+            Self::DisplayP3 => Some(Mat3::new([
+                [0.822_462_1, 0.177_538_0, 0.0],
+                [0.033_194_1, 0.966_805_8, 0.0],
+                [0.017_082_7, 0.072_397_4, 0.910_519_9],
+            ])),
+        }
+    }
+
+    /// Display encoding of a linear component, the same curve as `Vec3::to_rgb8`.
+    fn encode(self, component: f64) -> f64 {
+        match self {
+            Self::Linear => component.max(0.0),
+            Self::Srgb | Self::DisplayP3 => component.max(0.0).sqrt(),
+        }
+    }
+
+    fn tag<W: Write>(self, encoder: &mut png::Encoder<W>) {
+        const D65: (f32, f32) = (0.3127, 0.3290);
+        match self {
+            Self::Srgb => encoder.set_srgb(png::SrgbRenderingIntent::Perceptual),
+            Self::Linear => {
+                encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+                encoder.set_source_chromaticities(png::SourceChromaticities::new(
+                    D65,
+                    (0.64, 0.33),
+                    (0.30, 0.60),
+                    (0.15, 0.06),
+                ));
+            }
+            Self::DisplayP3 => {
+                // matches the square-root encoding rather than P3's nominal sRGB curve
+                encoder.set_source_gamma(png::ScaledFloat::new(0.5));
+                encoder.set_source_chromaticities(png::SourceChromaticities::new(
+                    D65,
+                    (0.680, 0.320),
+                    (0.265, 0.690),
+                    (0.150, 0.060),
+                ));
+            }
+        }
+    }
+}
+
+/// Returns `<OUTPUT_DIR>/<name><suffix>.<extension>`, creating the output directory if needed.
+pub fn result_path(name: &str, suffix: &str, extension: &str) -> Result<String, Box<dyn Error>> {
+    if !Path::new(OUTPUT_DIR).exists() {
+        create_dir_all(OUTPUT_DIR)?;
+    }
+    Ok(format!("{OUTPUT_DIR}/{name}{suffix}.{extension}"))
+}
+
+pub fn write_png(path: &str, width: u32, height: u32, raw: &[u8]) -> Result<(), Box<dyn Error>> {
+    let image_file = File::create(path)?;
+    let image_buf = BufWriter::new(image_file);
+    let png_encoder = PngEncoder::new(image_buf);
+    png_encoder.write_image(raw, width, height, ExtendedColorType::Rgb8)?;
+    Ok(())
+}
+
+/// Writes the film as the main image, `<name>.png` or `<name>.exr` for float output, in the
+/// configured bit depth and color space, with its coverage as alpha if asked to.
+pub fn save_image(film: &Film, name: &str, output: &Output) -> Result<String, Box<dyn Error>> {
+    let space = output.color_space;
+    let conversion = space.conversion();
+    let colors = film
+        .resolve()
+        .into_iter()
+        .map(|color| conversion.map_or(color, |matrix| matrix * color));
+    let alpha = output.alpha.then(|| film.resolve_alpha());
+    let channel_count = if alpha.is_some() { 4 } else { 3 };
+    let pixels = colors.enumerate().map(|(index, c)| {
+        let a = alpha.as_ref().map_or(1.0, |alpha| alpha[index]);
+        [c.x(), c.y(), c.z(), a]
+    });
+
+    if output.bit_depth == BitDepth::Float {
+        let path = result_path(name, "", "exr")?;
+        // EXR color stays premultiplied by alpha, as the format expects
+        let raw: Vec<f32> = pixels
+            .flat_map(|pixel| pixel.map(|v| v as f32).into_iter().take(channel_count))
+            .collect();
+        if alpha.is_some() {
+            Rgba32FImage::from_raw(film.width(), film.height(), raw)
+                .ok_or("Image buffer does not match its size")?
+                .save(&path)?;
+        } else {
+            write_exr(&path, film.width(), film.height(), raw)?;
+        }
+        return Ok(path);
+    }
+
+    let path = result_path(name, "", "png")?;
+    let channels = pixels.flat_map(|[r, g, b, a]| {
+        // PNG color isn't premultiplied, so covered color is divided back out
+        let unpremultiply = if a > 0.0 { 1.0 / a } else { 0.0 };
+        let mut color = unpremultiply * Vec3::new(r, g, b);
+        if output.highlight_rolloff {
+            color = color.desaturate_highlights();
+        }
+        [color.x(), color.y(), color.z()]
+            .map(|v| space.encode(v))
+            .into_iter()
+            .chain([a])
+            .take(channel_count)
+    });
+    let (depth, raw): (_, Vec<u8>) = match output.bit_depth {
+        BitDepth::Sixteen => (
+            png::BitDepth::Sixteen,
+            channels
+                .flat_map(|v| ((65535.0 * v.min(1.0)).round() as u16).to_be_bytes())
+                .collect(),
+        ),
+        _ => (
+            png::BitDepth::Eight,
+            channels.map(|v| (256.0 * v.min(0.999)) as u8).collect(),
+        ),
+    };
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(&path)?),
+        film.width(),
+        film.height(),
+    );
+    encoder.set_color(if alpha.is_some() {
+        png::ColorType::Rgba
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_depth(depth);
+    space.tag(&mut encoder);
+    encoder.write_header()?.write_image_data(&raw)?;
+    Ok(path)
+}
+
+pub fn write_exr(path: &str, width: u32, height: u32, raw: Vec<f32>) -> Result<(), Box<dyn Error>> {
+    let image =
+        Rgb32FImage::from_raw(width, height, raw).ok_or("Image buffer does not match its size")?;
+    image.save(path)?;
+    Ok(())
+}
+
+/// Maps `t` in [0, 1] onto a blue → cyan → green → yellow → red ramp.
+pub fn false_color(t: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0],
+    ];
+
+    let scaled = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (scaled as usize).min(STOPS.len() - 2);
+    let frac = scaled - index as f64;
+    let (a, b) = (STOPS[index], STOPS[index + 1]);
+
+    [0, 1, 2].map(|c| (255.0 * (a[c] + (b[c] - a[c]) * frac)).round() as u8)
+}
+
+/// Normalizes `values` by their 99th percentile so a few fireflies don't wash out the map.
+pub fn heatmap(values: &[f64]) -> Vec<u8> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let reference = sorted
+        .get(sorted.len() * 99 / 100)
+        .copied()
+        .unwrap_or_default();
+    let inv_reference = if reference > 0.0 {
+        1.0 / reference
+    } else {
+        0.0
+    };
+
+    values
+        .iter()
+        .flat_map(|v| false_color(v * inv_reference))
+        .collect()
+}
+
+pub fn save_variance_heatmap(film: &Film, name: &str) -> Result<String, Box<dyn Error>> {
+    let path = result_path(name, "_variance", "png")?;
+    let std_errors: Vec<f64> = film
+        .pixels()
+        .iter()
+        .map(PixelAccumulator::std_error)
+        .collect();
+    write_png(&path, film.width(), film.height(), &heatmap(&std_errors))?;
+    Ok(path)
+}
+
+/// Writes `<name>_cost.png`, a false-color map of the effort recorded for each pixel.
+pub fn save_cost_heatmap(film: &Film, name: &str) -> Result<String, Box<dyn Error>> {
+    let path = result_path(name, "_cost", "png")?;
+    let costs: Vec<f64> = film.pixels().iter().map(PixelAccumulator::cost).collect();
+    write_png(&path, film.width(), film.height(), &heatmap(&costs))?;
+    Ok(path)
+}
+
+/// Writes `<name>.exr` with the film's linear radiance, before exposure or tone mapping.
+pub fn save_hdr(film: &Film, name: &str) -> Result<String, Box<dyn Error>> {
+    let path = result_path(name, "", "exr")?;
+    let raw: Vec<f32> = film
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let mean = pixel.mean();
+            [mean.x() as f32, mean.y() as f32, mean.z() as f32]
+        })
+        .collect();
+    write_exr(&path, film.width(), film.height(), raw)?;
+    Ok(path)
+}
+
+/// Writes `<name>_anaglyph.png`, taking red from the left eye and green and blue from the right.
+pub fn save_anaglyph(left: &Film, right: &Film, name: &str) -> Result<String, Box<dyn Error>> {
+    let path = result_path(name, "_anaglyph", "png")?;
+    let raw: Vec<u8> = left
+        .resolve()
+        .into_iter()
+        .zip(right.resolve())
+        .flat_map(|(left, right)| {
+            let (left, right) = (left.to_rgb8(), right.to_rgb8());
+            [left[0], right[1], right[2]]
+        })
+        .collect();
+    write_png(&path, left.width(), left.height(), &raw)?;
+    Ok(path)
+}
+
+/// Overwrites `<name>_preview.png` with the film resolved at block size `stride`.
+pub fn save_preview(film: &Film, stride: u32, name: &str) -> Result<String, Box<dyn Error>> {
+    let path = result_path(name, "_preview", "png")?;
+    let raw: Vec<u8> = film
+        .resolve_blocks(stride)
+        .into_iter()
+        .flat_map(Vec3::to_rgb8)
+        .collect();
+    write_png(&path, film.width(), film.height(), &raw)?;
+    Ok(path)
+}
+
+/// Writes per-pixel motion in pixels as the red (x) and green (y) channels of a float EXR.
+pub fn save_motion_vectors(
+    motion: &[[f32; 3]],
+    width: u32,
+    height: u32,
+    name: &str,
+) -> Result<String, Box<dyn Error>> {
+    let path = result_path(name, "_motion", "exr")?;
+    write_exr(&path, width, height, motion.concat())?;
+    Ok(path)
+}
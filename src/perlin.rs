@@ -29,6 +29,7 @@ impl Perlin {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn noise(&self, point: Point3) -> f64 {
         let limit = (self.point_count - 1) as isize;
 
@@ -0,0 +1,248 @@
+use crate::{
+    mesh::MeshData,
+    vec3::{Color, Point3, Vec3},
+};
+use std::{error::Error, fs};
+
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Largest value of an integer type, which a color channel of that type is divided by.
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    /// Count type, item type.
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Source of property values, either whitespace-separated text or little-endian binary.
+enum Values<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary(&'a [u8]),
+}
+
+impl Values<'_> {
+    fn next(&mut self, scalar: Scalar) -> Option<f64> {
+        match self {
+            Values::Ascii(words) => words.next()?.parse().ok(),
+            Values::Binary(bytes) => {
+                let size = scalar.size();
+                let (value, rest) = (bytes.get(..size)?, &bytes[size..]);
+                *bytes = rest;
+                let value = match scalar {
+                    Scalar::I8 => value[0] as i8 as f64,
+                    Scalar::U8 => value[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes(value.try_into().ok()?) as f64,
+                    Scalar::U16 => u16::from_le_bytes(value.try_into().ok()?) as f64,
+                    Scalar::I32 => i32::from_le_bytes(value.try_into().ok()?) as f64,
+                    Scalar::U32 => u32::from_le_bytes(value.try_into().ok()?) as f64,
+                    Scalar::F32 => f32::from_le_bytes(value.try_into().ok()?) as f64,
+                    Scalar::F64 => f64::from_le_bytes(value.try_into().ok()?),
+                };
+                Some(value)
+            }
+        }
+    }
+}
+
+/// Reads a Stanford PLY file, ASCII or binary little-endian, taking vertex positions, texture
+/// coordinates, colors and normals and the faces' vertex indices; polygons are split into fans of
+/// triangles and other elements are skipped.
+pub fn load(path: &str) -> Result<MeshData, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read '{}' : {}", path, e))?;
+    let invalid = |message: &str| format!("'{path}' : {message}");
+    let header_end = bytes
+        .windows(10)
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| invalid("Missing PLY header"))?;
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |newline| header_end + newline + 1);
+    let header =
+        std::str::from_utf8(&bytes[..header_end]).map_err(|_| invalid("Invalid header"))?;
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid("Not a PLY file").into());
+    }
+    let mut binary = false;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["format", "ascii", _] => binary = false,
+            ["format", "binary_little_endian", _] => binary = true,
+            ["format", format, _] => {
+                return Err(invalid(&format!("Unsupported PLY format '{format}'")).into())
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid("Invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("Stray property"))?;
+                let (Some(count), Some(item)) = (Scalar::parse(count), Scalar::parse(item)) else {
+                    return Err(invalid("Invalid property type").into());
+                };
+                element
+                    .properties
+                    .push(Property::List(name.to_string(), count, item));
+            }
+            ["property", scalar, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("Stray property"))?;
+                let scalar =
+                    Scalar::parse(scalar).ok_or_else(|| invalid("Invalid property type"))?;
+                element
+                    .properties
+                    .push(Property::Scalar(name.to_string(), scalar));
+            }
+            _ => {}
+        }
+    }
+
+    let mut values = if binary {
+        Values::Binary(&bytes[body_start..])
+    } else {
+        let body =
+            std::str::from_utf8(&bytes[body_start..]).map_err(|_| invalid("Invalid body"))?;
+        Values::Ascii(body.split_ascii_whitespace())
+    };
+    let truncated = || invalid("Unexpected end of data");
+    let mut data = MeshData::default();
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut position = [0.0; 3];
+            let mut uv = (0.0, 0.0);
+            let mut color = [1.0; 3];
+            let mut normal = [0.0; 3];
+            for property in &element.properties {
+                match property {
+                    Property::Scalar(name, scalar) => {
+                        let value = values.next(*scalar).ok_or_else(truncated)?;
+                        match name.as_str() {
+                            "x" => position[0] = value,
+                            "y" => position[1] = value,
+                            "z" => position[2] = value,
+                            "u" | "s" | "texture_u" | "texture_s" => uv.0 = value,
+                            "v" | "t" | "texture_v" | "texture_t" => uv.1 = value,
+                            "red" => color[0] = value / scalar.color_scale(),
+                            "green" => color[1] = value / scalar.color_scale(),
+                            "blue" => color[2] = value / scalar.color_scale(),
+                            "nx" => normal[0] = value,
+                            "ny" => normal[1] = value,
+                            "nz" => normal[2] = value,
+                            _ => {}
+                        }
+                    }
+                    Property::List(name, count, item) => {
+                        let count = values.next(*count).ok_or_else(truncated)? as usize;
+                        let mut corners = Vec::with_capacity(count);
+                        for _ in 0..count {
+                            corners.push(values.next(*item).ok_or_else(truncated)? as u32);
+                        }
+                        if element.name == "face"
+                            && matches!(name.as_str(), "vertex_indices" | "vertex_index")
+                        {
+                            for i in 1..corners.len().saturating_sub(1) {
+                                data.indices
+                                    .extend([corners[0], corners[i], corners[i + 1]]);
+                            }
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                data.positions
+                    .push(Point3::new(position[0], position[1], position[2]));
+                data.uvs.push(uv);
+                data.colors.push(Color::new(color[0], color[1], color[2]));
+                data.normals.push(Vec3::from(normal));
+            }
+        }
+    }
+
+    let has = |names: &[&str]| {
+        elements.iter().any(|element| {
+            element.name == "vertex"
+                && element.properties.iter().any(|property| {
+                    matches!(property, Property::Scalar(name, _) if names.contains(&name.as_str()))
+                })
+        })
+    };
+    if has(&["u", "s", "texture_u", "texture_s"]) {
+        data.uv_indices = data.indices.clone();
+    } else {
+        data.uvs.clear();
+    }
+    if !has(&["red", "green", "blue"]) {
+        data.colors.clear();
+    }
+    if !has(&["nx", "ny", "nz"]) {
+        data.normals.clear();
+    }
+    if data
+        .indices
+        .iter()
+        .any(|&index| index as usize >= data.positions.len())
+    {
+        return Err(invalid("Face refers to a missing vertex").into());
+    }
+    Ok(data)
+}
@@ -0,0 +1,195 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BVHNode,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    memory::{self, Category},
+    mesh::MeshData,
+    ply,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+    xyz,
+};
+use std::{error::Error, mem::size_of, path::Path, sync::Arc};
+
+/// Shape each point of a cloud is drawn as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplatShape {
+    Sphere,
+    /// Flat disk across the point's normal, or facing each ray for clouds without normals.
+    Disk,
+}
+
+/// Reads the points at `path`, picking the format from its extension; faces are ignored.
+pub fn load(path: &str) -> Result<MeshData, Box<dyn Error>> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let mut data = match extension.as_deref() {
+        Some("xyz") => xyz::load(path)?,
+        Some("ply") => ply::load(path)?,
+        _ => return Err(format!("Unsupported point cloud format '{path}'").into()),
+    };
+    if data.positions.is_empty() {
+        return Err(format!("Point cloud '{path}' has no points").into());
+    }
+    data.indices.clear();
+    data.uvs.clear();
+    data.uv_indices.clear();
+    Ok(data)
+}
+
+/// Scanned points, such as a LiDAR capture, drawn as small spheres or disks of one radius
+/// with their own BVH. Points carrying colors pass them on as vertex colors.
+#[derive(Debug)]
+pub struct PointCloud {
+    bvh: BVHNode,
+}
+
+impl PointCloud {
+    /// Builds the cloud with every position multiplied by `scale`; `radius` must be positive.
+    pub fn new(
+        mut data: MeshData,
+        scale: f64,
+        radius: f64,
+        shape: SplatShape,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        for position in &mut data.positions {
+            *position = scale * *position;
+        }
+        for normal in &mut data.normals {
+            *normal = if normal.near_zero() {
+                Vec3::default()
+            } else {
+                normal.unit()
+            };
+        }
+        memory::track(
+            Category::Meshes,
+            data.bytes() + data.positions.len() * size_of::<Splat>(),
+        );
+
+        let cloud = Arc::new(Shared {
+            data,
+            radius,
+            shape,
+            material,
+        });
+        let mut splats: Vec<Arc<dyn Entity>> = (0..cloud.data.positions.len() as u32)
+            .map(|index| {
+                Arc::new(Splat {
+                    cloud: cloud.clone(),
+                    index,
+                }) as Arc<dyn Entity>
+            })
+            .collect();
+        Self {
+            bvh: BVHNode::new(&mut splats),
+        }
+    }
+}
+
+impl Entity for PointCloud {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, time_interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    data: MeshData,
+    radius: f64,
+    shape: SplatShape,
+    material: Arc<dyn Material>,
+}
+
+/// One point of a cloud, looked up in the shared buffers when hit.
+#[derive(Debug)]
+struct Splat {
+    cloud: Arc<Shared>,
+    index: u32,
+}
+
+impl Splat {
+    fn center(&self) -> Point3 {
+        self.cloud.data.positions[self.index as usize]
+    }
+
+    /// Time and outward normal of the ray's first hit on the sphere around the point.
+    fn hit_sphere(&self, ray: &Ray, time_interval: Interval) -> Option<(f64, Vec3)> {
+        let radius = self.cloud.radius;
+        let oc = self.center() - *ray.origin();
+        let a = ray.direction().length_sq();
+        let half_b = ray.direction().dot(oc);
+        let c = oc.length_sq() - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrt_d = discriminant.sqrt();
+        let root = [half_b - sqrt_d, half_b + sqrt_d]
+            .into_iter()
+            .map(|root| root / a)
+            .find(|&root| time_interval.surrounds(root))?;
+        Some((root, (ray.at(root) - self.center()) / radius))
+    }
+
+    /// Time and normal of the ray's hit on the disk across `normal`.
+    fn hit_disk(&self, ray: &Ray, time_interval: Interval, normal: Vec3) -> Option<(f64, Vec3)> {
+        let denominator = normal.dot(*ray.direction());
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let time = normal.dot(self.center() - *ray.origin()) / denominator;
+        if !time_interval.surrounds(time) {
+            return None;
+        }
+        let radius = self.cloud.radius;
+        ((ray.at(time) - self.center()).length_sq() <= radius * radius).then_some((time, normal))
+    }
+}
+
+impl Entity for Splat {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let index = self.index as usize;
+        let data = &self.cloud.data;
+        let (time, normal) = match self.cloud.shape {
+            SplatShape::Sphere => self.hit_sphere(ray, time_interval)?,
+            SplatShape::Disk => {
+                let normal = match data.normals.get(index) {
+                    Some(normal) if !normal.near_zero() => *normal,
+                    _ => -ray.direction().unit(),
+                };
+                self.hit_disk(ray, time_interval, normal)?
+            }
+        };
+        let mut hit_record = HitRecord::new(
+            ray.at(time),
+            ray,
+            normal,
+            time,
+            0.0,
+            0.0,
+            &*self.cloud.material,
+        );
+        if self.cloud.shape == SplatShape::Disk {
+            hit_record.edge_distance =
+                self.cloud.radius - (hit_record.hit_point - self.center()).length();
+        }
+        hit_record.vertex_color = data.colors.get(index).copied();
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = self.cloud.radius;
+        let extent = Vec3::new(radius, radius, radius);
+        Aabb::new_from_points(self.center() - extent, self.center() + extent)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    aabb::Aabb,
+    counters::{self, Test},
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    quad::Plane,
+    ray::Ray,
+    triangle,
+    vec3::{Color, Point3, Vec3},
+};
+use std::{error::Error, sync::Arc};
+
+// how far off the polygon's plane a point may lie, relative to the polygon's size
+const PLANARITY_TOLERANCE: f64 = 1e-6;
+
+/// One side of the polygon in its plane, with the interior to its left.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    start: (f64, f64),
+    /// Unit length.
+    direction: (f64, f64),
+}
+
+impl Edge {
+    /// Distance of (x, y) from the edge's line, negative on the outside.
+    #[inline]
+    fn inner_distance(&self, (x, y): (f64, f64)) -> f64 {
+        self.direction.0 * (y - self.start.1) - self.direction.1 * (x - self.start.0)
+    }
+}
+
+/// Flat convex polygon. Its plane is set up over the polygon's bounding rectangle, so the
+/// texture coordinates span that rectangle and a point is inside when it's to the left of
+/// every edge.
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    plane: Plane,
+    /// Corners measured from the plane's origin along unit `u` and `v`, counterclockwise
+    /// around the normal.
+    corners: Vec<(f64, f64)>,
+    edges: Vec<Edge>,
+    /// Running total of the areas of the triangles fanning out from the first corner.
+    area_cdf: Vec<f64>,
+    material: Arc<dyn Material>,
+    bounding_box: Aabb,
+}
+
+impl Polygon {
+    /// `points` are the corners in order; the normal faces the side they wind counterclockwise
+    /// around. Fails unless they are at least three and make a flat convex shape with some area.
+    pub fn new(points: &[Point3], material: Arc<dyn Material>) -> Result<Self, Box<dyn Error>> {
+        if points.len() < 3 {
+            return Err("Polygon needs at least three points".into());
+        }
+        // Newell's method, which stays accurate for nearly collinear corners
+        let origin = points[0];
+        let newell = (0..points.len()).fold(Vec3::default(), |sum, i| {
+            let next = points[(i + 1) % points.len()];
+            sum + (points[i] - origin).cross(next - origin)
+        });
+        if newell.near_zero() {
+            return Err("Polygon has no area".into());
+        }
+        let normal = newell.unit();
+        // a polygon with area has a corner apart from the first
+        let u_axis = points
+            .iter()
+            .map(|&point| point - origin)
+            .find(|offset| !offset.near_zero())
+            .expect("polygon has area")
+            .unit();
+        let v_axis = normal.cross(u_axis);
+
+        let extent = points
+            .iter()
+            .map(|&point| (point - origin).length())
+            .fold(0.0, f64::max);
+        let flat = points
+            .iter()
+            .map(|&point| {
+                let offset = point - origin;
+                if offset.dot(normal).abs() > PLANARITY_TOLERANCE * extent {
+                    return Err("Polygon points are not coplanar");
+                }
+                Ok((offset.dot(u_axis), offset.dot(v_axis)))
+            })
+            .collect::<Result<Vec<(f64, f64)>, _>>()?;
+        let (min_x, max_x, min_y, max_y) = flat.iter().fold(
+            (
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(min_x, max_x, min_y, max_y), &(x, y)| {
+                (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+            },
+        );
+        let plane = Plane::new(
+            origin + min_x * u_axis + min_y * v_axis,
+            (max_x - min_x) * u_axis,
+            (max_y - min_y) * v_axis,
+        );
+        let corners: Vec<(f64, f64)> = flat.iter().map(|&(x, y)| (x - min_x, y - min_y)).collect();
+
+        let edges: Vec<Edge> = (0..corners.len())
+            .filter_map(|i| {
+                let (start, end) = (corners[i], corners[(i + 1) % corners.len()]);
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                let length = dx.hypot(dy);
+                // repeated points add no edge
+                (length > 0.0).then(|| Edge {
+                    start,
+                    direction: (dx / length, dy / length),
+                })
+            })
+            .collect();
+        let convex = corners.iter().all(|&corner| {
+            edges
+                .iter()
+                .all(|edge| edge.inner_distance(corner) >= -PLANARITY_TOLERANCE * extent)
+        });
+        if !convex {
+            return Err("Polygon is not convex".into());
+        }
+
+        let area_cdf = (1..corners.len() - 1)
+            .scan(0.0, |total, i| {
+                *total += Self::fan_area(corners[0], corners[i], corners[i + 1]);
+                Some(*total)
+            })
+            .collect();
+        let bounding_box = points
+            .iter()
+            .map(|&point| Aabb::new_from_points(point, point))
+            .reduce(|a, b| Aabb::enclose(&a, &b))
+            .expect("polygon has points");
+        Ok(Self {
+            plane,
+            corners,
+            edges,
+            area_cdf,
+            material,
+            bounding_box,
+        })
+    }
+
+    fn fan_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+        0.5 * ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0))
+    }
+
+    fn area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or_default()
+    }
+
+    /// Position in the plane, in the units of `corners`, at coordinates (alpha, beta).
+    #[inline]
+    fn flat_point(&self, alpha: f64, beta: f64) -> (f64, f64) {
+        (alpha * self.plane.u.length(), beta * self.plane.v.length())
+    }
+
+    /// Distance from the nearest edge, `None` outside the polygon.
+    fn edge_distance(&self, point: (f64, f64)) -> Option<f64> {
+        self.edges.iter().try_fold(f64::INFINITY, |nearest, edge| {
+            let distance = edge.inner_distance(point);
+            (distance >= 0.0).then_some(nearest.min(distance))
+        })
+    }
+}
+
+impl Entity for Polygon {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        counters::count(Test::Polygon);
+        let crossing = self.plane.intersect(ray, time_interval)?;
+        let (_, _, alpha, beta) = crossing;
+        let edge_distance = self.edge_distance(self.flat_point(alpha, beta))?;
+        let mut hit_record = self.plane.hit_record(ray, crossing, &*self.material);
+        hit_record.edge_distance = edge_distance;
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let area = self.area();
+        let target = fastrand::f64() * area;
+        let fan = self
+            .area_cdf
+            .partition_point(|&total| total < target)
+            .min(self.area_cdf.len() - 1);
+        let (a, b, c) = (
+            self.corners[0],
+            self.corners[fan + 1],
+            self.corners[fan + 2],
+        );
+        let (beta, gamma) = triangle::sample_barycentric();
+        let x = a.0 + beta * (b.0 - a.0) + gamma * (c.0 - a.0);
+        let y = a.1 + beta * (b.1 - a.1) + gamma * (c.1 - a.1);
+        let Plane {
+            q, u, v, normal, ..
+        } = self.plane;
+        let (alpha, beta) = (x / u.length(), y / v.length());
+        let point = q + alpha * u + beta * v;
+        let hit_record = HitRecord::raw(point, normal, 0.0, true, alpha, beta, &*self.material);
+        Some((hit_record, area))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        self.edge_distance(self.flat_point(u, v))?;
+        let plane = &self.plane;
+        Some((plane.q + u * plane.u + v * plane.v, plane.normal))
+    }
+
+    fn emitted_power(&self) -> Color {
+        std::f64::consts::PI * self.area() * self.material.emitted_power()
+    }
+}
@@ -0,0 +1,225 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle, TermLike};
+use std::{
+    cell::Cell,
+    fmt::Write,
+    io,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+// only counted while JSON progress is on, the one place rays per second are shown
+static RAYS: AtomicU64 = AtomicU64::new(0);
+// rays a thread counts before adding them to the shared total
+const RAY_BATCH: u64 = 1024;
+// the render's mean standard error so far and projected at the last sample, as f64 bits;
+// NaN until the first pass is in
+static NOISE: AtomicU64 = AtomicU64::new(u64::MAX);
+static PROJECTED_NOISE: AtomicU64 = AtomicU64::new(u64::MAX);
+
+thread_local! {
+    static UNCOUNTED_RAYS: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ProgressFormat {
+    #[default]
+    Bar,
+    /// One JSON object per line on stdout, about once a second.
+    Json,
+}
+
+pub fn set_format(format: ProgressFormat) {
+    JSON.store(matches!(format, ProgressFormat::Json), Ordering::Relaxed);
+}
+
+/// Silences progress and the reports printed to stdout; errors and warnings still go to stderr.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Moves the reports and JSON progress normally printed to stdout over to stderr, leaving
+/// stdout to another stream such as `--stream-tiles`.
+pub fn set_stdout_reserved(reserved: bool) {
+    STDOUT_RESERVED.store(reserved, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn is_stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// `println!` that prints nothing under `--quiet`, and prints to stderr while stdout is
+/// reserved.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::progress::is_quiet() {
+            if $crate::progress::is_stdout_reserved() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    };
+}
+pub(crate) use status;
+
+/// Counts one ray cast against the scene.
+#[inline]
+pub fn count_ray() {
+    if JSON.load(Ordering::Relaxed) {
+        UNCOUNTED_RAYS.with(|uncounted| {
+            let rays = uncounted.get() + 1;
+            if rays == RAY_BATCH {
+                RAYS.fetch_add(rays, Ordering::Relaxed);
+                uncounted.set(0);
+            } else {
+                uncounted.set(rays);
+            }
+        });
+    }
+}
+
+/// Records how noisy the render is after a pass, as the mean standard error of its pixels,
+/// and the noise expected once all `samples_per_pixel` are in, for the render progress bar.
+pub fn set_noise(noise: f64, samples_taken: u32, samples_per_pixel: u32) {
+    // error falls with the square root of the sample count
+    let projected = noise * (samples_taken as f64 / samples_per_pixel.max(1) as f64).sqrt();
+    NOISE.store(noise.to_bits(), Ordering::Relaxed);
+    PROJECTED_NOISE.store(projected.to_bits(), Ordering::Relaxed);
+}
+
+fn noise() -> Option<(f64, f64)> {
+    let noise = f64::from_bits(NOISE.load(Ordering::Relaxed));
+    let projected = f64::from_bits(PROJECTED_NOISE.load(Ordering::Relaxed));
+    (!noise.is_nan()).then_some((noise, projected))
+}
+
+/// Progress bar over `length` units of work, drawn in the selected format.
+pub fn progress_bar(length: u64) -> ProgressBar {
+    styled_bar(length, false)
+}
+
+/// Progress bar over a render's samples that also shows the noise reported by `set_noise`,
+/// so a progressive render can be stopped once it's clean enough or left to finish.
+pub fn render_progress_bar(length: u64) -> ProgressBar {
+    NOISE.store(u64::MAX, Ordering::Relaxed);
+    PROJECTED_NOISE.store(u64::MAX, Ordering::Relaxed);
+    styled_bar(length, true)
+}
+
+fn styled_bar(length: u64, show_noise: bool) -> ProgressBar {
+    if is_quiet() {
+        return ProgressBar::with_draw_target(Some(length), ProgressDrawTarget::hidden());
+    }
+    if !JSON.load(Ordering::Relaxed) {
+        let progress_bar = ProgressBar::new(length);
+        let progress_style = ProgressStyle::default_bar()
+            .template("Render Progress: [{bar:40.green}] {percent_precise}%\nElapsed: {elapsed} | Remaining: {eta}{noise}").unwrap()
+            .with_key("noise", move |_: &ProgressState, w: &mut dyn Write| {
+                match noise().filter(|_| show_noise) {
+                    Some((noise, projected)) => {
+                        write!(w, " | Noise: {noise:.5} (at finish {projected:.5})").unwrap();
+                    }
+                    None if show_noise => write!(w, " | Noise: -").unwrap(),
+                    None => {}
+                }
+            })
+            .progress_chars("=> ");
+        progress_bar.set_style(progress_style);
+        return progress_bar;
+    }
+
+    let first_ray = RAYS.load(Ordering::Relaxed);
+    let progress_style = ProgressStyle::with_template(
+        "{{\"percent\":{percent_precise},\"elapsed\":{elapsed_seconds},\"eta\":{eta_seconds},\"rays_per_sec\":{rays_per_sec}{noise}}}",
+    )
+    .unwrap()
+    .with_key("elapsed_seconds", |state: &ProgressState, w: &mut dyn Write| {
+        write!(w, "{:.3}", state.elapsed().as_secs_f64()).unwrap();
+    })
+    .with_key("eta_seconds", |state: &ProgressState, w: &mut dyn Write| {
+        write!(w, "{:.3}", state.eta().as_secs_f64()).unwrap();
+    })
+    .with_key(
+        "rays_per_sec",
+        move |state: &ProgressState, w: &mut dyn Write| {
+            let rays = RAYS.load(Ordering::Relaxed) - first_ray;
+            let seconds = state.elapsed().as_secs_f64();
+            let rate = if seconds > 0.0 { rays as f64 / seconds } else { 0.0 };
+            write!(w, "{rate:.0}").unwrap();
+        },
+    )
+    .with_key("noise", move |_: &ProgressState, w: &mut dyn Write| {
+        if show_noise {
+            match noise() {
+                Some((noise, projected)) => {
+                    write!(w, ",\"noise\":{noise:.6},\"projected_noise\":{projected:.6}").unwrap();
+                }
+                None => write!(w, ",\"noise\":null,\"projected_noise\":null").unwrap(),
+            }
+        }
+    });
+    ProgressBar::with_draw_target(
+        Some(length),
+        ProgressDrawTarget::term_like_with_hz(Box::new(JsonLines), 1),
+    )
+    .with_style(progress_style)
+}
+
+/// Draw target printing each rendered progress line as is, ignoring cursor movement.
+#[derive(Debug)]
+struct JsonLines;
+
+impl TermLike for JsonLines {
+    fn width(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn move_cursor_up(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_down(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_right(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_left(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        self.write_str(s)
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        // lines come padded out to the terminal width
+        let line = s.trim_end();
+        if !line.is_empty() {
+            if is_stdout_reserved() {
+                eprintln!("{line}");
+            } else {
+                println!("{line}");
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,30 +1,28 @@
 use crate::{
     aabb::Aabb,
+    counters::{self, Test},
     entity::{Entity, HitRecord},
     interval::Interval,
     material::Material,
     ray::Ray,
-    vec3::{Point3, Vec3},
+    vec3::{Color, Point3, Vec3},
 };
 use std::sync::Arc;
 
-#[derive(Debug, Clone)]
-pub struct Quad {
-    q: Point3,
-    u: Vec3,
-    v: Vec3,
+/// Plane through `q` spanned by `u` and `v`, locating points on it by their coordinates along
+/// `u` and `v`; shared by the flat primitives.
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
     w: Vec3,
-    normal: Vec3,
+    pub normal: Vec3,
     d: f64,
-    material: Arc<dyn Material>,
-    bounding_box: Aabb,
 }
 
-impl Quad {
-    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
-        let d1 = Aabb::new_from_points(q, q + u + v);
-        let d2 = Aabb::new_from_points(q + u, q + v);
-        let bounding_box = Aabb::enclose(&d1, &d2);
+impl Plane {
+    pub fn new(q: Point3, u: Vec3, v: Vec3) -> Self {
         let n = u.cross(v);
         let normal = n.unit();
         let d = normal.dot(q);
@@ -36,16 +34,20 @@ impl Quad {
             w,
             normal,
             d,
-            material,
-            bounding_box,
         }
     }
-}
 
-impl Entity for Quad {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
+    /// Area of the parallelogram spanned by `u` and `v`.
+    #[inline]
+    pub fn area(&self) -> f64 {
+        self.u.cross(self.v).length()
+    }
+
+    /// Time, point and coordinates (alpha, beta) at which `ray` crosses the plane.
+    pub fn intersect(&self, ray: &Ray, time_interval: Interval) -> Option<(f64, Point3, f64, f64)> {
         let denominator = self.normal.dot(*ray.direction());
-        if denominator.abs() < 1e-6 {
+        // compare the cosine rather than the raw dot so the test doesn't depend on scene scale
+        if denominator.abs() < 1e-6 * ray.direction().length() {
             return None;
         }
         let time = (self.d - self.normal.dot(*ray.origin())) / denominator;
@@ -56,22 +58,96 @@ impl Entity for Quad {
         let hit_point_vector = hit_point - self.q;
         let alpha = self.w.dot(hit_point_vector.cross(self.v));
         let beta = self.w.dot(self.u.cross(hit_point_vector));
+        Some((time, hit_point, alpha, beta))
+    }
+
+    /// Hit record for a crossing found by `intersect`, using (alpha, beta) as texture
+    /// coordinates.
+    pub fn hit_record<'a>(
+        &self,
+        ray: &Ray,
+        (time, hit_point, alpha, beta): (f64, Point3, f64, f64),
+        material: &'a dyn Material,
+    ) -> HitRecord<'a> {
+        let mut hit_record =
+            HitRecord::new(hit_point, ray, self.normal, time, alpha, beta, material);
+        hit_record.set_tangent(self.u);
+        hit_record.dp_du = self.u;
+        hit_record.dp_dv = self.v;
+        hit_record
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Quad {
+    plane: Plane,
+    material: Arc<dyn Material>,
+    bounding_box: Aabb,
+    /// Whether rays arriving from behind, along the normal, pass through.
+    single_sided: bool,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let d1 = Aabb::new_from_points(q, q + u + v);
+        let d2 = Aabb::new_from_points(q + u, q + v);
+        let bounding_box = Aabb::enclose(&d1, &d2);
+        Self {
+            plane: Plane::new(q, u, v),
+            material,
+            bounding_box,
+            single_sided: false,
+        }
+    }
+
+    /// Makes the quad visible only from the side `u` × `v` faces, for light panels and open
+    /// boxes the camera looks into.
+    pub fn with_single_sided(mut self, single_sided: bool) -> Self {
+        self.single_sided = single_sided;
+        self
+    }
+}
+
+impl Entity for Quad {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        counters::count(Test::Quad);
+        if self.single_sided && self.plane.normal.dot(*ray.direction()) > 0.0 {
+            return None;
+        }
+        let crossing = self.plane.intersect(ray, time_interval)?;
+        let (_, _, alpha, beta) = crossing;
         let unit_interval = Interval::new(0.0, 1.0);
         if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
             return None;
         }
-        Some(HitRecord::new(
-            hit_point,
-            ray,
-            self.normal,
-            time,
-            alpha,
-            beta,
-            &*self.material,
-        ))
+        let mut hit_record = self.plane.hit_record(ray, crossing, &*self.material);
+        let Plane { u, v, .. } = self.plane;
+        let area = self.plane.area();
+        hit_record.edge_distance = (alpha.min(1.0 - alpha) * area / v.length())
+            .min(beta.min(1.0 - beta) * area / u.length());
+        Some(hit_record)
     }
 
     fn bounding_box(&self) -> Aabb {
         self.bounding_box
     }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let (alpha, beta) = (fastrand::f64(), fastrand::f64());
+        let Plane {
+            q, u, v, normal, ..
+        } = self.plane;
+        let point = q + alpha * u + beta * v;
+        let hit_record = HitRecord::raw(point, normal, 0.0, true, alpha, beta, &*self.material);
+        Some((hit_record, self.plane.area()))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        let plane = &self.plane;
+        Some((plane.q + u * plane.u + v * plane.v, plane.normal))
+    }
+
+    fn emitted_power(&self) -> Color {
+        std::f64::consts::PI * self.plane.area() * self.material.emitted_power()
+    }
 }
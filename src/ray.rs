@@ -1,10 +1,77 @@
 use crate::vec3::{Point3, Vec3};
 
+/// Rays offset by one pixel in x and y, tracked alongside a ray to estimate its footprint.
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferentials {
+    pub rx_origin: Point3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Point3,
+    pub ry_direction: Vec3,
+}
+
+// nesting deeper than this keeps the innermost media and forgets the outermost
+const MAX_MEDIA: usize = 4;
+
+/// Refractive indices of the dielectrics a ray is inside, innermost last; empty means vacuum.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MediumStack {
+    iors: [f64; MAX_MEDIA],
+    len: u8,
+}
+
+impl MediumStack {
+    /// Refractive index around the ray.
+    pub fn current_ior(&self) -> f64 {
+        match self.len {
+            0 => 1.0,
+            len => self.iors[len as usize - 1],
+        }
+    }
+
+    /// Stack after entering a medium of refractive index `ior`.
+    pub fn entered(mut self, ior: f64) -> Self {
+        if self.len as usize == MAX_MEDIA {
+            self.iors.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.iors[self.len as usize] = ior;
+        self.len += 1;
+        self
+    }
+
+    /// Stack after leaving the innermost medium of refractive index `ior`; media entered
+    /// after it (e.g. through an overlapping boundary) stay on the stack.
+    pub fn exited(mut self, ior: f64) -> Self {
+        let len = self.len as usize;
+        if let Some(index) = self.iors[..len].iter().rposition(|&other| other == ior) {
+            self.iors.copy_within(index + 1..len, index);
+            self.len -= 1;
+        }
+        self
+    }
+}
+
+/// What a ray is traced for, so entities can be hidden from some kinds of rays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RayKind {
+    #[default]
+    Camera,
+    /// Diffuse bounces and volume scattering, the rays global illumination is gathered by.
+    Diffuse,
+    /// Reflections and refractions.
+    Specular,
+    /// Rays checking whether a point is blocked, for direct light and occlusion.
+    Shadow,
+}
+
 #[derive(Debug, Clone)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
     time: f64,
+    differentials: Option<RayDifferentials>,
+    media: MediumStack,
+    kind: RayKind,
 }
 
 impl Ray {
@@ -13,9 +80,27 @@ impl Ray {
             origin,
             direction,
             time,
+            differentials: None,
+            media: MediumStack::default(),
+            kind: RayKind::default(),
         }
     }
 
+    pub fn with_differentials(mut self, differentials: Option<RayDifferentials>) -> Self {
+        self.differentials = differentials;
+        self
+    }
+
+    pub fn with_media(mut self, media: MediumStack) -> Self {
+        self.media = media;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     #[inline]
     pub fn origin(&self) -> &Point3 {
         &self.origin
@@ -31,6 +116,22 @@ impl Ray {
         &self.time
     }
 
+    #[inline]
+    pub fn differentials(&self) -> Option<&RayDifferentials> {
+        self.differentials.as_ref()
+    }
+
+    /// Media the ray travels through.
+    #[inline]
+    pub fn media(&self) -> MediumStack {
+        self.media
+    }
+
+    #[inline]
+    pub fn kind(&self) -> RayKind {
+        self.kind
+    }
+
     #[inline]
     pub fn at(&self, t: f64) -> Point3 {
         self.origin + t * self.direction
@@ -0,0 +1,147 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{
+    bvh::BVHNode,
+    entity::HitRecord,
+    light_sampler::{LightSample, LightSampler},
+    vec3::Color,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RestirSettings {
+    /// Fresh light samples drawn per shading point.
+    pub candidates: u16,
+    /// Reservoirs borrowed from nearby pixels of the previous pass.
+    pub spatial_neighbors: u16,
+    /// Pixel radius neighbors are picked from.
+    pub spatial_radius: u32,
+    /// Cap on the sample count a reused reservoir contributes, limiting stale history.
+    pub max_history: f64,
+}
+
+/// Weighted reservoir holding one light sample out of all candidates streamed through it.
+#[derive(Debug, Clone, Copy, Default)]
+struct Reservoir {
+    sample: Option<LightSample>,
+    weight_sum: f64,
+    count: f64,
+    /// Unbiased contribution weight of the kept sample.
+    weight: f64,
+}
+
+impl Reservoir {
+    fn update(&mut self, sample: LightSample, weight: f64, count: f64) {
+        self.weight_sum += weight;
+        self.count += count;
+        if weight > 0.0 && fastrand::f64() * self.weight_sum < weight {
+            self.sample = Some(sample);
+        }
+    }
+}
+
+/// Direct lighting for diffuse camera hits by resampled importance sampling, reusing the
+/// previous pass's reservoirs at the same pixel (temporal) and its neighbors (spatial).
+#[derive(Debug)]
+pub struct Restir {
+    settings: RestirSettings,
+    sampler: Arc<LightSampler>,
+    width: u32,
+    height: u32,
+    previous: RwLock<Vec<Reservoir>>,
+    current: Vec<Mutex<Reservoir>>,
+}
+
+impl Restir {
+    pub fn new(
+        settings: RestirSettings,
+        sampler: Arc<LightSampler>,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let pixel_count = (width * height) as usize;
+        Self {
+            settings,
+            sampler,
+            width,
+            height,
+            previous: RwLock::new(vec![Reservoir::default(); pixel_count]),
+            current: (0..pixel_count)
+                .map(|_| Mutex::new(Reservoir::default()))
+                .collect(),
+        }
+    }
+
+    /// Makes this pass's reservoirs available for reuse by the next one.
+    pub fn end_pass(&self) {
+        let mut previous = self.previous.write().unwrap();
+        for (stored, current) in previous.iter_mut().zip(&self.current) {
+            *stored = *current.lock().unwrap();
+        }
+    }
+
+    fn neighbor(&self, pixel: usize) -> usize {
+        let radius = self.settings.spatial_radius as i64;
+        let (x, y) = (
+            (pixel as u32 % self.width) as i64,
+            (pixel as u32 / self.width) as i64,
+        );
+        let nx = (x + fastrand::i64(-radius..=radius)).clamp(0, self.width as i64 - 1);
+        let ny = (y + fastrand::i64(-radius..=radius)).clamp(0, self.height as i64 - 1);
+        (ny * self.width as i64 + nx) as usize
+    }
+
+    /// Resampled direct light at the hit seen through `pixel`.
+    pub fn direct_light(
+        &self,
+        world: &BVHNode,
+        hit_record: &HitRecord,
+        albedo: Color,
+        pixel: usize,
+    ) -> Color {
+        let mut reservoir = Reservoir::default();
+
+        for _ in 0..self.settings.candidates {
+            let Some((sample, source_pdf)) = self.sampler.candidate() else {
+                continue;
+            };
+            let weight = LightSampler::target(hit_record, albedo, &sample) / source_pdf;
+            reservoir.update(sample, weight, 1.0);
+        }
+
+        {
+            let previous = self.previous.read().unwrap();
+            let reused = std::iter::once(pixel)
+                .chain((0..self.settings.spatial_neighbors).map(|_| self.neighbor(pixel)));
+            for index in reused {
+                let other = previous[index];
+                let Some(sample) = other.sample else {
+                    continue;
+                };
+                let count = other.count.min(self.settings.max_history);
+                let weight =
+                    LightSampler::target(hit_record, albedo, &sample) * other.weight * count;
+                reservoir.update(sample, weight, count);
+            }
+        }
+
+        let Some(sample) = reservoir.sample else {
+            *self.current[pixel].lock().unwrap() = reservoir;
+            return Color::default();
+        };
+        let target = LightSampler::target(hit_record, albedo, &sample);
+        let transmittance = if target > 0.0 {
+            LightSampler::transmittance(world, hit_record, &sample)
+        } else {
+            Color::default()
+        };
+        // occluded samples are kept with zero weight so neighbors don't reuse them
+        reservoir.weight = if transmittance.near_zero() {
+            0.0
+        } else {
+            reservoir.weight_sum / (reservoir.count * target)
+        };
+        *self.current[pixel].lock().unwrap() = reservoir;
+
+        reservoir.weight * transmittance * LightSampler::contribution(hit_record, albedo, &sample)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Color, Point3, Vec3},
+};
+use std::{f64::consts::PI, sync::Arc};
+
+/// Axis-aligned box with its edges and corners rounded off, intersected exactly. Its surface
+/// is everything at `radius` from a smaller inner box, made of 26 pieces: flat faces over the
+/// inner box's faces, quarter cylinders along its edges and eighth spheres on its corners.
+#[derive(Debug, Clone)]
+pub struct RoundedCuboid {
+    center: Point3,
+    /// Half the size of the inner box.
+    inner: Vec3,
+    radius: f64,
+    bounding_box: Aabb,
+    material: Arc<dyn Material>,
+}
+
+impl RoundedCuboid {
+    /// Box with opposite corners `a` and `b`, rounded off by `radius`, which can be at most
+    /// half its smallest side.
+    pub fn new(a: Point3, b: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let half = Vec3::new(
+            (a.x() - b.x()).abs(),
+            (a.y() - b.y()).abs(),
+            (a.z() - b.z()).abs(),
+        ) / 2.0;
+        Self {
+            center: (a + b) / 2.0,
+            inner: half - Vec3::new(radius, radius, radius),
+            radius,
+            bounding_box: Aabb::new_from_points(a, b),
+            material,
+        }
+    }
+
+    /// Surface area of a box of `size` rounded off by `radius`.
+    pub fn area(size: Vec3, radius: f64) -> f64 {
+        let [x, y, z] = [0, 1, 2].map(|axis| size[axis].abs() - 2.0 * radius);
+        // the faces, the quarter cylinders around each group of four parallel edges, and the
+        // corners, which together make up one sphere
+        2.0 * (x * y + y * z + z * x) + 2.0 * PI * radius * (x + y + z) + 4.0 * PI * radius * radius
+    }
+
+    /// Whether `p`, relative to the center, lies over the piece given by `rounded`, the axes
+    /// the piece curves around, and `signs`, the side of each of them it is on.
+    fn on_piece(&self, p: Vec3, rounded: u8, signs: u8) -> bool {
+        (0..3).all(|axis: u8| {
+            let inner = self.inner[axis];
+            if rounded & (1 << axis) == 0 {
+                p[axis].abs() <= inner
+            } else if signs & (1 << axis) == 0 {
+                p[axis] >= inner
+            } else {
+                p[axis] <= -inner
+            }
+        })
+    }
+}
+
+impl Entity for RoundedCuboid {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        self.bounding_box.hit(ray, time_interval)?;
+        let origin = *ray.origin() - self.center;
+        let direction = *ray.direction();
+
+        // every piece is the set of points at `radius` from a corner, an edge line or a face
+        // plane of the inner box, so the same quadratic in the rounded axes finds each one
+        let mut closest = time_interval.end;
+        for rounded in 1..8u8 {
+            for signs in (0..8u8).filter(|signs| signs & !rounded == 0) {
+                let (mut a, mut half_b, mut c) = (0.0, 0.0, -self.radius * self.radius);
+                for axis in (0..3u8).filter(|axis| rounded & (1 << axis) != 0) {
+                    let sign = if signs & (1 << axis) == 0 { 1.0 } else { -1.0 };
+                    let offset = origin[axis] - sign * self.inner[axis];
+                    a += direction[axis] * direction[axis];
+                    half_b += offset * direction[axis];
+                    c += offset * offset;
+                }
+                let discriminant = half_b * half_b - a * c;
+                if a == 0.0 || discriminant < 0.0 {
+                    continue;
+                }
+                let sqrt_d = discriminant.sqrt();
+                let root = [-half_b - sqrt_d, -half_b + sqrt_d]
+                    .into_iter()
+                    .map(|root| root / a)
+                    .filter(|&root| time_interval.start < root && root < closest)
+                    .find(|&root| self.on_piece(origin + root * direction, rounded, signs));
+                if let Some(root) = root {
+                    closest = root;
+                }
+            }
+        }
+        if closest == time_interval.end {
+            return None;
+        }
+
+        let p = origin + closest * direction;
+        // the nearest point of the inner box is the one the surface is rounded around
+        let nearest = Vec3::from(
+            [0, 1, 2].map(|axis: u8| p[axis].clamp(-self.inner[axis], self.inner[axis])),
+        );
+        let outward_normal = (p - nearest).unit();
+        // UVs are projected along the normal's main axis, spanning the box's side
+        let main = (0..3u8)
+            .max_by(|&i, &j| outward_normal[i].abs().total_cmp(&outward_normal[j].abs()))
+            .expect("three axes");
+        let [u, v] = [(main + 1) % 3, (main + 2) % 3]
+            .map(|axis| 0.5 + 0.5 * p[axis] / (self.inner[axis] + self.radius));
+        Some(HitRecord::new(
+            ray.at(closest),
+            ray,
+            outward_normal,
+            closest,
+            u,
+            v,
+            &*self.material,
+        ))
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn emitted_power(&self) -> Color {
+        let size = 2.0 * (self.inner + Vec3::new(self.radius, self.radius, self.radius));
+        PI * Self::area(size, self.radius) * self.material.emitted_power()
+    }
+}
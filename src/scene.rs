@@ -1,267 +1,3378 @@
 use crate::{
+    aabb::Aabb,
+    atmosphere::Atmosphere,
+    bake::Bake,
+    bezier::{self, BezierPatch},
+    billboard::{AlphaMask, Billboard},
+    blackbody::blackbody,
+    bokeh::Aperture,
+    bounds::BoxOutline,
     bvh::BVHNode,
-    camera::Camera,
+    camera::{Backplate, Camera, Renderer, Seeding},
+    camera_path::{CameraPath, Easing, Interpolation, Keyframe},
+    clip::{Clipping, SectionPlane},
     constant_medium::ConstantMedium,
     cuboid::Cuboid,
+    curve::{CurveWidth, Curves},
     entity::{Entity, EntityCluster},
-    instance::{Rotated, Translated},
-    material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal},
+    environment::Environment,
+    epsilon,
+    filter::{FilterKind, PixelFilter},
+    fnv::Fnv1a,
+    focus_map::FocusMap,
+    guide::PathGuide,
+    heightfield,
+    instance::{Scaled, Transformed, Translated},
+    integrator::Integrator,
+    interval::Interval,
+    irradiance_cache::IrradianceCache,
+    lens::{Lens, LensPreset},
+    light_sampler::LightSampler,
+    mat3::Mat3,
+    mat4::Mat4,
+    material::{
+        self, Budgeted, Dielectric, DiffuseLight, EmissionSpread, HueShifted, Isotropic,
+        Lambertian, LightLinked, Material, Metal, Slotted,
+    },
+    media_slice::MediaSlice,
+    memory::MemoryUsage,
+    mesh::{Mesh, MeshData},
+    metaballs::{Metaball, Metaballs},
+    output::{BitDepth, ColorSpace, Output},
+    point_cloud::{self, PointCloud, SplatShape},
+    polygon::Polygon,
+    progress::status,
     quad::Quad,
+    restir::{Restir, RestirSettings},
+    rounded_cuboid::RoundedCuboid,
+    sdf::{Sdf, Shape},
+    shell::Shell,
     sphere::Sphere,
-    texture::{Checker, ImageTex, PerlinTex, Solid, Texture},
+    sprite_sheet::SpriteSheet,
+    starfield::{MilkyWay, Starfield},
+    subdivision,
+    sun::Sun,
+    texture::{
+        self, Cavity, Checker, ImageTex, PerlinTex, Projected, Ramp, RampInput, Solid, Texture,
+        Triplanar, VertexColor,
+    },
+    texture_bake::{BakeDomain, TextureBake},
+    texture_cache::TiledImageTex,
+    tile::{TileOrder, Tiling},
+    toon::{KeyLight, Outline, Toon},
+    triangle::Triangle,
+    units::Unit,
     vec3::{Color, Point3, Vec3},
+    visibility::{Restricted, Visibility},
+    vox,
+    voxels::VoxelGrid,
+    wireframe::{Wireframe, WireframeMode},
 };
 use serde::Deserialize;
-use std::{convert::Into, error::Error, fs, path::Path, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::Into,
+    error::Error,
+    f64::consts::PI,
+    fmt::Write,
+    fs,
+    ops::Range,
+    path::Path,
+    sync::Arc,
+};
 
 #[derive(Debug, Deserialize)]
 struct Config {
+    /// Unit of all lengths in the scene; entities may declare their own to be converted to it.
+    #[serde(default)]
+    units: UnitVariant,
     entity: Vec<EntityConfig>,
+    #[serde(default)]
+    entities: EntitiesConfig,
     camera: CameraConfig,
+    #[serde(default)]
+    output: OutputConfig,
+    #[serde(default)]
+    integrator: IntegratorConfig,
+    #[serde(default)]
+    section: Vec<SectionConfig>,
+    bake: Option<BakeConfig>,
+    /// Plane to show the media's density on; if given the scene isn't rendered.
+    media_slice: Option<MediaSliceConfig>,
+    /// Turns to render into one image in place of the camera's single view.
+    sprite_sheet: Option<SpriteSheetConfig>,
+    /// Outlines of the entities' bounding boxes drawn into the render, for debugging.
+    bounds: Option<BoundsConfig>,
+    /// Textures to write out as images; if any are given the scene isn't rendered.
+    #[serde(default)]
+    texture_bake: Vec<TextureBakeConfig>,
+    #[serde(default)]
+    tags: TagFilter,
+    /// Megabytes of textures and BVH nodes past which a warning is printed at load.
+    memory_budget: Option<f64>,
+    override_material: Option<MaterialOverride>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Settings given on the command line that take precedence over the scene file.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// Applied on top of the scene's own tag filter.
+    pub tags: TagFilter,
+    pub bit_depth: Option<BitDepth>,
+    pub color_space: Option<ColorSpace>,
+    pub material: Option<MaterialOverride>,
+    /// Renders a ground truth to compare against, see `Config::make_reference`.
+    pub reference: bool,
+    /// Builds materials into slots a `reload` can swap edited ones into.
+    pub watch: bool,
+}
+
+/// Stand-in for every material that doesn't emit light, for checking lighting and geometry on
+/// their own.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum MaterialOverride {
+    /// Neutral gray Lambertian; participating media keep scattering, in the same gray.
+    Clay,
+}
+
+// albedo of the clay override
+const CLAY_ALBEDO: [f64; 3] = [0.5, 0.5, 0.5];
+
+// bounces traced by reference renders, deep enough that cutting paths off is never visible
+const REFERENCE_MAX_DEPTH: u16 = 512;
+// roulette start for reference renders of scenes without one, keeping the deep paths affordable
+const REFERENCE_RR_START_DEPTH: u16 = 8;
+
+impl Config {
+    /// Calls `f` on every material config in the scene, in the same order for the same file.
+    fn for_each_material(&mut self, f: &mut impl FnMut(&mut MaterialConfig)) {
+        for entity in &mut self.entity {
+            entity.for_each_material(f);
+        }
+        let mut named: Vec<_> = self.entities.named.iter_mut().collect();
+        named.sort_by(|a, b| a.0.cmp(b.0));
+        for (_, entity) in named {
+            entity.for_each_material(f);
+        }
+    }
+
+    /// Description of everything the built geometry depends on, equal for files differing
+    /// only in their materials. Lights count as geometry, as the lights sampled are picked
+    /// from them when the scene is built.
+    fn geometry_key(&self) -> String {
+        let stripped = |entity: &EntityConfig| {
+            let mut entity = entity.clone();
+            entity.override_material(MaterialOverride::Clay);
+            entity
+        };
+        let entities: Vec<_> = self.entity.iter().map(stripped).collect();
+        let mut named: Vec<_> = self
+            .entities
+            .named
+            .iter()
+            .map(|(name, entity)| (name, stripped(entity)))
+            .collect();
+        named.sort_by(|a, b| a.0.cmp(b.0));
+        format!("{entities:?}{named:?}{:?}{:?}", self.camera, self.bounds)
+    }
+
+    /// Swaps the scene's shortcuts for settings that converge to the exact image: paths run to a
+    /// far greater depth, samples aren't clamped, and every sample is drawn independently,
+    /// without a fixed seed or light, irradiance and guiding data shared between pixels.
+    /// Russian roulette stays on, as it trades time for noise without biasing the image.
+    fn make_reference(&mut self) {
+        self.camera.max_depth = self.camera.max_depth.max(REFERENCE_MAX_DEPTH);
+        self.camera.seed = None;
+        let integrator = &mut self.integrator;
+        integrator.max_radiance = None;
+        integrator
+            .rr_start_depth
+            .get_or_insert(REFERENCE_RR_START_DEPTH);
+        integrator.irradiance_cache = None;
+        integrator.restir = None;
+        integrator.path_guiding = false;
+        integrator.toon = None;
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EntitiesConfig {
+    /// Entities built once and shared by every `Instance` naming them, however many there are.
+    #[serde(default)]
+    named: HashMap<String, EntityConfig>,
+}
+
+/// Which tagged entities make it into the render.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TagFilter {
+    /// If any are given, only entities carrying one of these tags are kept.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Entities carrying any of these tags are dropped, whatever `include` says.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn allows(&self, tags: &[String]) -> bool {
+        (self.include.is_empty() || tags.iter().any(|tag| self.include.contains(tag)))
+            && !tags.iter().any(|tag| self.exclude.contains(tag))
+    }
+
+    /// Combines two filters, so an entity must pass both.
+    fn extended(mut self, other: &Self) -> Self {
+        self.include.extend_from_slice(&other.include);
+        self.exclude.extend_from_slice(&other.exclude);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "variant")]
 enum EntityVariant {
     Sphere(SphereConfig),
     MovingSphere(MovingSphereConfig),
+    Ellipsoid(EllipsoidConfig),
     Quad(QuadConfig),
-    Cuboid(CuboidConfig),
+    Billboard(BillboardConfig),
+    QuadGrid(QuadGridConfig),
+    Triangle(TriangleConfig),
+    Polygon(PolygonConfig),
+    RegularPolygon(RegularPolygonConfig),
+    Mesh(MeshConfig),
+    BezierPatch(BezierPatchConfig),
+    BezierPatches(BezierPatchesConfig),
+    Heightfield(HeightfieldConfig),
+    Curves(CurvesConfig),
+    Sdf(SdfConfig),
+    Metaballs(MetaballsConfig),
+    PointCloud(PointCloudConfig),
+    Voxels(VoxelsConfig),
+    Cuboid(Box<CuboidConfig>),
+    RoundedCuboid(RoundedCuboidConfig),
+    Atmosphere(AtmosphereConfig),
     ConstantMedium(Box<ConstantMediumConfig>),
+    Shell(Box<ShellConfig>),
     EntityCluster(EntityClusterConfig),
+    Lod(LodConfig),
+    Instance(InstanceConfig),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct EntityConfig {
     #[serde(flatten)]
     variant: EntityVariant,
     material: MaterialConfig,
     translation: Option<[f64; 3]>,
     rotation: Option<[f64; 3]>,
+    /// Stretches the entity about its origin before it is rotated, by one factor or one per
+    /// axis.
+    scale: Option<ScaleConfig>,
+    /// Top three rows of an affine matrix applied after scale, rotation and translation, for
+    /// shears and transforms exported from other tools.
+    matrix: Option<[[f64; 4]; 3]>,
+    jitter: Option<JitterConfig>,
+    /// Unit the entity's lengths are given in, if not the scene's.
+    units: Option<UnitVariant>,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Name light links refer to the entity by; the children of a named cluster go by it too.
+    name: Option<String>,
+    /// Light linking resolved for the entity, see `LightLinked`.
+    #[serde(skip)]
+    light_link: u64,
+    #[serde(skip)]
+    unlinked_lights: u64,
+    /// Kinds of rays the entity shows up to, all of them if left out.
+    visibility: Option<VisibilityConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct VisibilityConfig {
+    #[serde(default = "default_visible")]
+    camera: bool,
+    #[serde(default = "default_visible")]
+    diffuse: bool,
+    #[serde(default = "default_visible")]
+    specular: bool,
+    #[serde(default = "default_visible")]
+    shadow: bool,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+impl From<VisibilityConfig> for Visibility {
+    fn from(value: VisibilityConfig) -> Self {
+        Self {
+            camera: value.camera,
+            diffuse: value.diffuse,
+            specular: value.specular,
+            shadow: value.shadow,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum ScaleConfig {
+    Uniform(f64),
+    PerAxis([f64; 3]),
+}
+
+impl ScaleConfig {
+    /// Factor surface areas grow by, averaged over orientations for unequal factors.
+    fn area_factor(self) -> f64 {
+        Scaled::mean_area_scale(self.into())
+    }
+}
+
+impl From<ScaleConfig> for Vec3 {
+    fn from(value: ScaleConfig) -> Self {
+        match value {
+            ScaleConfig::Uniform(factor) => Vec3::new(factor, factor, factor),
+            ScaleConfig::PerAxis(factors) => Vec3::from(factors),
+        }
+    }
+}
+
+/// Transform an entity's config applies to it: scaling, then rotation, then translation, then
+/// the matrix.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    scale: Option<ScaleConfig>,
+    rotation: Option<[f64; 3]>,
+    translation: Option<[f64; 3]>,
+    matrix: Option<[[f64; 4]; 3]>,
+}
+
+impl Placement {
+    /// The whole transform as one matrix; `None` if it leaves the entity as it is.
+    fn matrix(&self) -> Option<Mat4> {
+        let steps = [
+            self.scale.map(|scale| Mat4::scaling(scale.into())),
+            self.rotation
+                .map(|rotation| Mat4::linear(Mat3::rotation(Vec3::from(rotation)))),
+            self.translation
+                .map(|translation| Mat4::translation(Vec3::from(translation))),
+            self.matrix.map(Mat4::new),
+        ];
+        steps
+            .into_iter()
+            .flatten()
+            .reduce(|transform, step| step * transform)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum UnitVariant {
+    #[default]
+    Meters,
+    Centimeters,
+    Millimeters,
+    Kilometers,
+    Inches,
+    Feet,
+}
+
+impl EntityVariant {
+    fn name(&self) -> &'static str {
+        match self {
+            EntityVariant::Sphere(_) => "Sphere",
+            EntityVariant::MovingSphere(_) => "MovingSphere",
+            EntityVariant::Ellipsoid(_) => "Ellipsoid",
+            EntityVariant::Quad(_) => "Quad",
+            EntityVariant::Billboard(_) => "Billboard",
+            EntityVariant::QuadGrid(_) => "QuadGrid",
+            EntityVariant::Triangle(_) => "Triangle",
+            EntityVariant::Polygon(_) => "Polygon",
+            EntityVariant::RegularPolygon(_) => "RegularPolygon",
+            EntityVariant::Mesh(_) => "Mesh",
+            EntityVariant::BezierPatch(_) => "BezierPatch",
+            EntityVariant::BezierPatches(_) => "BezierPatches",
+            EntityVariant::Heightfield(_) => "Heightfield",
+            EntityVariant::Curves(_) => "Curves",
+            EntityVariant::Sdf(_) => "Sdf",
+            EntityVariant::Metaballs(_) => "Metaballs",
+            EntityVariant::PointCloud(_) => "PointCloud",
+            EntityVariant::Voxels(_) => "Voxels",
+            EntityVariant::Cuboid(_) => "Cuboid",
+            EntityVariant::RoundedCuboid(_) => "RoundedCuboid",
+            EntityVariant::Atmosphere(_) => "Atmosphere",
+            EntityVariant::ConstantMedium(_) => "ConstantMedium",
+            EntityVariant::Shell(_) => "Shell",
+            EntityVariant::EntityCluster(_) => "EntityCluster",
+            EntityVariant::Lod(_) => "Lod",
+            EntityVariant::Instance(_) => "Instance",
+        }
+    }
+
+    /// Surface area in scene units, for the shapes it is known for.
+    fn area(&self) -> Option<f64> {
+        match self {
+            EntityVariant::Sphere(sphere) => {
+                Some(4.0 * PI * sphere.radius.powi(2) * sphere.range.area_fraction())
+            }
+            EntityVariant::MovingSphere(moving_sphere) => {
+                Some(4.0 * PI * moving_sphere.radius.powi(2) * moving_sphere.range.area_fraction())
+            }
+            EntityVariant::Ellipsoid(ellipsoid) => {
+                Some(4.0 * PI * Scaled::mean_area_scale(Vec3::from(ellipsoid.radii)))
+            }
+            EntityVariant::Quad(quad) => {
+                Some(Vec3::from(quad.u).cross(Vec3::from(quad.v)).length())
+            }
+            EntityVariant::QuadGrid(grid) => Some(
+                (grid.columns * grid.rows) as f64
+                    * Vec3::from(grid.u).cross(Vec3::from(grid.v)).length(),
+            ),
+            EntityVariant::Triangle(triangle) => {
+                let e1 = Vec3::from(triangle.b) - Vec3::from(triangle.a);
+                let e2 = Vec3::from(triangle.c) - Vec3::from(triangle.a);
+                Some(0.5 * e1.cross(e2).length())
+            }
+            EntityVariant::Polygon(polygon) => {
+                let origin = Vec3::from(polygon.points[0]);
+                let doubled = polygon
+                    .points
+                    .windows(2)
+                    .map(|pair| (Vec3::from(pair[0]) - origin).cross(Vec3::from(pair[1]) - origin))
+                    .fold(Vec3::default(), |sum, cross| sum + cross);
+                Some(0.5 * doubled.length())
+            }
+            EntityVariant::RegularPolygon(polygon) => {
+                let sides = polygon.sides as f64;
+                Some(0.5 * sides * polygon.radius.powi(2) * (2.0 * PI / sides).sin())
+            }
+            // faces with materials of their own don't share the entity's
+            EntityVariant::Cuboid(cuboid) => Some(
+                cuboid
+                    .faces()
+                    .into_iter()
+                    .zip(cuboid.face_areas())
+                    .filter(|(face, _)| face.is_none())
+                    .map(|(_, area)| area)
+                    .sum(),
+            ),
+            EntityVariant::RoundedCuboid(rounded_cuboid) => Some(RoundedCuboid::area(
+                Vec3::from(rounded_cuboid.a) - Vec3::from(rounded_cuboid.b),
+                rounded_cuboid.radius,
+            )),
+            EntityVariant::Mesh(_)
+            | EntityVariant::BezierPatch(_)
+            | EntityVariant::BezierPatches(_)
+            | EntityVariant::Heightfield(_)
+            | EntityVariant::Curves(_)
+            | EntityVariant::Sdf(_)
+            | EntityVariant::Metaballs(_)
+            | EntityVariant::PointCloud(_)
+            | EntityVariant::Voxels(_)
+            | EntityVariant::Atmosphere(_)
+            | EntityVariant::ConstantMedium(_)
+            | EntityVariant::Shell(_)
+            | EntityVariant::EntityCluster(_)
+            | EntityVariant::Lod(_)
+            | EntityVariant::Instance(_) => None,
+            // the cutout's area is only known once the mask is loaded
+            EntityVariant::Billboard(_) => None,
+        }
+    }
+
+    /// Whether the shape encloses a volume, so only its outer side lights the scene.
+    fn is_closed(&self) -> bool {
+        match self {
+            EntityVariant::Sphere(sphere) => !sphere.range.is_partial(),
+            EntityVariant::MovingSphere(moving_sphere) => !moving_sphere.range.is_partial(),
+            _ => !matches!(
+                self,
+                EntityVariant::Quad(_)
+                    | EntityVariant::Billboard(_)
+                    | EntityVariant::QuadGrid(_)
+                    | EntityVariant::Triangle(_)
+                    | EntityVariant::Polygon(_)
+                    | EntityVariant::RegularPolygon(_)
+                    | EntityVariant::Heightfield(_)
+                    | EntityVariant::BezierPatch(_)
+            ),
+        }
+    }
+}
+
+fn scale_point(point: &mut [f64; 3], factor: f64) {
+    point.iter_mut().for_each(|x| *x *= factor);
+}
+
+impl EntityConfig {
+    /// Turns physical emitter strengths into radiance, given the length of a scene unit in meters.
+    fn resolve_light_units(&mut self, meters_per_unit: f64) -> Result<(), Box<dyn Error>> {
+        match &mut self.variant {
+            EntityVariant::ConstantMedium(constant_medium) => {
+                constant_medium
+                    .boundary
+                    .resolve_light_units(meters_per_unit)?;
+            }
+            EntityVariant::Shell(shell) => shell.boundary.resolve_light_units(meters_per_unit)?,
+            EntityVariant::EntityCluster(entity_cluster) => {
+                for child in &mut entity_cluster.children {
+                    child.resolve_light_units(meters_per_unit)?;
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &mut lod.levels {
+                    level.entity.resolve_light_units(meters_per_unit)?;
+                }
+            }
+            _ => {}
+        }
+        let area_scale = self.scale.map_or(1.0, ScaleConfig::area_factor) * meters_per_unit.powi(2);
+        if let EntityVariant::Cuboid(cuboid) = &mut self.variant {
+            let areas = cuboid.face_areas();
+            for (face, area) in cuboid.faces_mut().into_iter().zip(areas) {
+                if let Some(MaterialVariant::DiffuseLight(light)) =
+                    face.as_mut().map(|face| &mut face.material)
+                {
+                    light.resolve_units(Some(area * area_scale), true)?;
+                }
+            }
+        }
+        let MaterialVariant::DiffuseLight(light) = &mut self.material.material else {
+            return Ok(());
+        };
+        // a quad that can't be seen from behind doesn't light what is behind it either
+        if matches!(&self.variant, EntityVariant::Quad(quad) if quad.single_sided) {
+            light.one_sided = true;
+        }
+        let area = self.variant.area().map(|area| area * area_scale);
+        light.resolve_units(area, self.variant.is_closed())
+    }
+
+    /// Applies the scene-wide settings an entity is built with: the tag filter, the material
+    /// override and the scene's units.
+    fn prepare(
+        &mut self,
+        tags: &TagFilter,
+        material_override: Option<MaterialOverride>,
+        units: Unit,
+    ) -> Result<(), Box<dyn Error>> {
+        self.filter_tags(tags);
+        self.validate()?;
+        if let Some(material) = material_override {
+            self.override_material(material);
+        }
+        self.convert_units(units, units);
+        self.resolve_light_units(units.meters())
+    }
+
+    /// Appends a line describing the built entity to `out`, then its children indented below
+    /// it, and returns the entity; children's boxes are in their parent's space, before its
+    /// placement.
+    fn describe(
+        &self,
+        label: &str,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<Arc<dyn Entity>, Box<dyn Error>> {
+        // children are described first and the container built from them, so nothing nested
+        // is built more than once
+        let mut children = String::new();
+        let mut built: Vec<_> = match &self.variant {
+            EntityVariant::ConstantMedium(constant_medium) => {
+                vec![constant_medium
+                    .boundary
+                    .describe("boundary: ", depth + 1, &mut children)?]
+            }
+            EntityVariant::Shell(shell) => {
+                vec![shell
+                    .boundary
+                    .describe("boundary: ", depth + 1, &mut children)?]
+            }
+            EntityVariant::EntityCluster(entity_cluster) => entity_cluster
+                .children
+                .iter()
+                .map(|child| child.describe("", depth + 1, &mut children))
+                .collect::<Result<_, _>>()?,
+            EntityVariant::Lod(lod) => lod
+                .levels
+                .iter()
+                .map(|level| {
+                    let label = format!("from {} px: ", level.min_size);
+                    level.entity.describe(&label, depth + 1, &mut children)
+                })
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        }
+        .into_iter()
+        .map(Some)
+        .collect();
+        let entity = self
+            .clone()
+            .build(&mut |index, _| Ok(built[index].take().expect("built once")))?;
+        let b_box = entity.bounding_box();
+        let light = if matches!(self.material.material, MaterialVariant::DiffuseLight(_)) {
+            " light"
+        } else {
+            ""
+        };
+        let range = |interval: Interval| format!("[{:.3}, {:.3}]", interval.start, interval.end);
+        write!(
+            out,
+            "{}{label}{} ({}){light} x {} y {} z {}",
+            "  ".repeat(depth),
+            self.variant.name(),
+            self.material.material.name(),
+            range(b_box.x()),
+            range(b_box.y()),
+            range(b_box.z()),
+        )
+        .unwrap();
+        if !self.tags.is_empty() {
+            write!(out, " tags: {}", self.tags.join(", ")).unwrap();
+        }
+        out.push('\n');
+        out.push_str(&children);
+        Ok(entity)
+    }
+
+    /// Drops children of clusters (and media boundaries' clusters) that `filter` excludes.
+    fn filter_tags(&mut self, filter: &TagFilter) {
+        match &mut self.variant {
+            EntityVariant::ConstantMedium(constant_medium) => {
+                constant_medium.boundary.filter_tags(filter);
+            }
+            EntityVariant::Shell(shell) => {
+                shell.boundary.filter_tags(filter);
+            }
+            EntityVariant::EntityCluster(entity_cluster) => {
+                entity_cluster
+                    .children
+                    .retain(|child| filter.allows(&child.tags));
+                for child in &mut entity_cluster.children {
+                    child.filter_tags(filter);
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &mut lod.levels {
+                    level.entity.filter_tags(filter);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rejects settings the entity or its children can't be built with.
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        match &self.variant {
+            EntityVariant::Sphere(sphere) => sphere.range.validate(),
+            EntityVariant::MovingSphere(moving_sphere) => moving_sphere.range.validate(),
+            EntityVariant::Metaballs(metaballs) if metaballs.balls.is_empty() => {
+                Err("Metaballs need at least one ball".into())
+            }
+            EntityVariant::Metaballs(metaballs) if metaballs.threshold <= 0.0 => {
+                Err("Metaball threshold must be positive".into())
+            }
+            EntityVariant::Metaballs(metaballs)
+                if metaballs.balls.iter().any(|ball| ball.radius <= 0.0) =>
+            {
+                Err("Metaballs need positive radii".into())
+            }
+            EntityVariant::PointCloud(point_cloud) if point_cloud.radius <= 0.0 => {
+                Err("Point cloud splats need a positive radius".into())
+            }
+            EntityVariant::Polygon(polygon) if polygon.points.len() < 3 => {
+                Err("Polygon needs at least three points".into())
+            }
+            EntityVariant::RegularPolygon(polygon) if polygon.sides < 3 => {
+                Err("A regular polygon needs at least three sides".into())
+            }
+            EntityVariant::RegularPolygon(polygon) if polygon.radius <= 0.0 => {
+                Err("A regular polygon needs a positive radius".into())
+            }
+            EntityVariant::Curves(curves) if curves.curves.is_empty() => {
+                Err("Curves need at least one curve".into())
+            }
+            EntityVariant::Curves(curves) if curves.segments == 0 => {
+                Err("Curves need at least one segment each".into())
+            }
+            EntityVariant::RoundedCuboid(rounded_cuboid) => rounded_cuboid.validate(),
+            EntityVariant::Voxels(voxels) if voxels.voxel_size <= 0.0 => {
+                Err("Voxels need a positive size".into())
+            }
+            EntityVariant::ConstantMedium(constant_medium) => constant_medium.boundary.validate(),
+            EntityVariant::Shell(shell) => shell.boundary.validate(),
+            EntityVariant::EntityCluster(entity_cluster) => entity_cluster
+                .children
+                .iter()
+                .try_for_each(EntityConfig::validate),
+            EntityVariant::Lod(lod) if lod.levels.is_empty() => {
+                Err("Lod needs at least one level".into())
+            }
+            EntityVariant::Lod(lod) => lod
+                .levels
+                .iter()
+                .try_for_each(|level| level.entity.validate()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends the keys of the named entities instanced here and among the children.
+    fn collect_instances<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match &self.variant {
+            EntityVariant::Instance(instance) => names.push(&instance.entity),
+            EntityVariant::ConstantMedium(constant_medium) => {
+                constant_medium.boundary.collect_instances(names);
+            }
+            EntityVariant::Shell(shell) => shell.boundary.collect_instances(names),
+            EntityVariant::EntityCluster(entity_cluster) => {
+                for child in &entity_cluster.children {
+                    child.collect_instances(names);
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &lod.levels {
+                    level.entity.collect_instances(names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Numbers the lights with a link, here and among the children, after those in `links`.
+    fn collect_light_links(&mut self, links: &mut Vec<(u64, LightLinkConfig)>) {
+        if let MaterialVariant::DiffuseLight(light) = &self.material.material {
+            if let Some(link) = &light.link {
+                // bits past the last one are never handed out, as the count is checked after
+                self.light_link = 1u64.checked_shl(links.len() as u32).unwrap_or(0);
+                links.push((self.light_link, link.clone()));
+            }
+        }
+        match &mut self.variant {
+            EntityVariant::ConstantMedium(constant_medium) => {
+                constant_medium.boundary.collect_light_links(links);
+            }
+            EntityVariant::Shell(shell) => shell.boundary.collect_light_links(links),
+            EntityVariant::EntityCluster(entity_cluster) => {
+                for child in &mut entity_cluster.children {
+                    child.collect_light_links(links);
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &mut lod.levels {
+                    level.entity.collect_light_links(links);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Marks the linked lights that don't reach the entity, given the names of its parents.
+    fn unlink_lights<'a>(&'a mut self, links: &[(u64, LightLinkConfig)], names: &mut Vec<&'a str>) {
+        let parents = names.len();
+        names.extend(self.name.as_deref());
+        self.unlinked_lights = links
+            .iter()
+            .filter(|(_, link)| !link.reaches(names))
+            .fold(0, |unlinked, (bit, _)| unlinked | bit);
+        match &mut self.variant {
+            EntityVariant::ConstantMedium(constant_medium) => {
+                constant_medium.boundary.unlink_lights(links, names);
+            }
+            EntityVariant::Shell(shell) => shell.boundary.unlink_lights(links, names),
+            EntityVariant::EntityCluster(entity_cluster) => {
+                for child in &mut entity_cluster.children {
+                    child.unlink_lights(links, names);
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &mut lod.levels {
+                    level.entity.unlink_lights(links, names);
+                }
+            }
+            _ => {}
+        }
+        names.truncate(parents);
+    }
+
+    fn override_material(&mut self, material: MaterialOverride) {
+        self.for_each_material(&mut |config| config.override_with(material));
+    }
+
+    /// Calls `f` on the entity's material, then on those of its faces and children.
+    fn for_each_material(&mut self, f: &mut impl FnMut(&mut MaterialConfig)) {
+        f(&mut self.material);
+        match &mut self.variant {
+            EntityVariant::Cuboid(cuboid) => {
+                for face in cuboid.faces_mut().into_iter().flatten() {
+                    f(face);
+                }
+            }
+            EntityVariant::ConstantMedium(constant_medium) => {
+                constant_medium.boundary.for_each_material(f);
+            }
+            EntityVariant::Shell(shell) => {
+                shell.boundary.for_each_material(f);
+            }
+            EntityVariant::EntityCluster(entity_cluster) => {
+                for child in &mut entity_cluster.children {
+                    child.for_each_material(f);
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &mut lod.levels {
+                    level.entity.for_each_material(f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rewrites every length in scene units, given the unit the enclosing config was in.
+    fn convert_units(&mut self, inherited: Unit, scene: Unit) {
+        let unit = self.units.map_or(inherited, Unit::from);
+        let factor = unit.to(scene);
+        if let Some(translation) = &mut self.translation {
+            scale_point(translation, factor);
+        }
+        if let Some(matrix) = &mut self.matrix {
+            for row in matrix {
+                row[3] *= factor;
+            }
+        }
+        match &mut self.variant {
+            EntityVariant::Sphere(sphere) => {
+                scale_point(&mut sphere.center, factor);
+                sphere.radius *= factor;
+            }
+            EntityVariant::MovingSphere(moving_sphere) => {
+                scale_point(&mut moving_sphere.center1, factor);
+                scale_point(&mut moving_sphere.center2, factor);
+                moving_sphere.radius *= factor;
+            }
+            EntityVariant::Ellipsoid(ellipsoid) => {
+                scale_point(&mut ellipsoid.center, factor);
+                scale_point(&mut ellipsoid.radii, factor);
+            }
+            EntityVariant::Quad(quad) | EntityVariant::Billboard(BillboardConfig { quad, .. }) => {
+                scale_point(&mut quad.q, factor);
+                scale_point(&mut quad.u, factor);
+                scale_point(&mut quad.v, factor);
+            }
+            EntityVariant::QuadGrid(grid) => {
+                scale_point(&mut grid.q, factor);
+                scale_point(&mut grid.u, factor);
+                scale_point(&mut grid.v, factor);
+                grid.spacing.iter_mut().for_each(|gap| *gap *= factor);
+            }
+            EntityVariant::Triangle(triangle) => {
+                scale_point(&mut triangle.a, factor);
+                scale_point(&mut triangle.b, factor);
+                scale_point(&mut triangle.c, factor);
+            }
+            EntityVariant::Polygon(polygon) => {
+                for point in &mut polygon.points {
+                    scale_point(point, factor);
+                }
+            }
+            EntityVariant::RegularPolygon(polygon) => {
+                scale_point(&mut polygon.center, factor);
+                polygon.radius *= factor;
+            }
+            EntityVariant::Mesh(mesh) => {
+                mesh.scale = Some(mesh.scale.unwrap_or(1.0) * factor);
+            }
+            EntityVariant::BezierPatch(patch) => {
+                for point in &mut patch.points {
+                    scale_point(point, factor);
+                }
+            }
+            EntityVariant::BezierPatches(patches) => {
+                patches.scale = Some(patches.scale.unwrap_or(1.0) * factor);
+            }
+            EntityVariant::Heightfield(heightfield) => {
+                heightfield.horizontal_scale *= factor;
+                heightfield.height_scale *= factor;
+            }
+            EntityVariant::Curves(curves) => {
+                for point in curves.curves.iter_mut().flatten() {
+                    scale_point(point, factor);
+                }
+                curves.root_width *= factor;
+                curves.tip_width *= factor;
+            }
+            EntityVariant::Sdf(sdf) => sdf.shape.scale(factor),
+            EntityVariant::Metaballs(metaballs) => {
+                for ball in &mut metaballs.balls {
+                    scale_point(&mut ball.center, factor);
+                    ball.radius *= factor;
+                }
+            }
+            EntityVariant::PointCloud(point_cloud) => {
+                point_cloud.scale = Some(point_cloud.scale.unwrap_or(1.0) * factor);
+                point_cloud.radius *= factor;
+            }
+            EntityVariant::Voxels(voxels) => voxels.voxel_size *= factor,
+            EntityVariant::Cuboid(cuboid) => {
+                scale_point(&mut cuboid.a, factor);
+                scale_point(&mut cuboid.b, factor);
+            }
+            EntityVariant::RoundedCuboid(rounded_cuboid) => {
+                scale_point(&mut rounded_cuboid.a, factor);
+                scale_point(&mut rounded_cuboid.b, factor);
+                rounded_cuboid.radius *= factor;
+            }
+            // the air's density is scaled to the planet's size, so needs no converting
+            EntityVariant::Atmosphere(atmosphere) => {
+                scale_point(&mut atmosphere.center, factor);
+                atmosphere.radius *= factor;
+            }
+            EntityVariant::ConstantMedium(constant_medium) => {
+                // density is per unit length, so it shrinks as lengths grow
+                constant_medium.density /= factor;
+                constant_medium.boundary.convert_units(unit, scene);
+            }
+            EntityVariant::Shell(shell) => {
+                shell.thickness *= factor;
+                shell.boundary.convert_units(unit, scene);
+            }
+            EntityVariant::EntityCluster(entity_cluster) => {
+                for child in &mut entity_cluster.children {
+                    child.convert_units(unit, scene);
+                }
+            }
+            EntityVariant::Lod(lod) => {
+                for level in &mut lod.levels {
+                    level.entity.convert_units(unit, scene);
+                }
+            }
+            // the named entity is in scene units already
+            EntityVariant::Instance(_) => {}
+        }
+    }
+}
+
+/// Random per-entity variation of the material, so repeated copies don't look cloned.
+#[derive(Debug, Clone, Deserialize)]
+struct JitterConfig {
+    /// Largest hue shift in degrees, either way.
+    #[serde(default)]
+    hue: f64,
+    /// Largest change to a metal's fuzz, either way.
+    #[serde(default)]
+    roughness: f64,
+    /// Defaults to a hash of the entity's shape and placement, so identical copies at
+    /// different places still differ but every render of the scene matches.
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 struct SphereConfig {
     center: [f64; 3],
     radius: f64,
+    #[serde(flatten)]
+    range: SphereRangeConfig,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct MovingSphereConfig {
     center1: [f64; 3],
     center2: [f64; 3],
     radius: f64,
+    #[serde(flatten)]
+    range: SphereRangeConfig,
 }
 
-#[derive(Debug, Deserialize)]
+/// Part of a sphere's surface to keep, the whole of it by default.
+#[derive(Debug, Clone, Deserialize)]
+struct SphereRangeConfig {
+    /// Polar angles in degrees from the top pole, [0, 90] leaving a dome.
+    theta: Option<[f64; 2]>,
+    /// Azimuths in degrees, measured like the `u` texture coordinate.
+    phi: Option<[f64; 2]>,
+}
+
+impl SphereRangeConfig {
+    fn is_partial(&self) -> bool {
+        self.theta.is_some() || self.phi.is_some()
+    }
+
+    /// Share of the whole sphere's area kept.
+    fn area_fraction(&self) -> f64 {
+        let [top, bottom] = self.theta.unwrap_or([0.0, 180.0]);
+        let [start, end] = self.phi.unwrap_or([0.0, 360.0]);
+        let band = 0.5
+            * (top.clamp(0.0, 180.0).to_radians().cos()
+                - bottom.clamp(0.0, 180.0).to_radians().cos());
+        band * ((end - start) / 360.0).min(1.0)
+    }
+
+    /// Fails if no part of the surface is left.
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if let Some([top, bottom]) = self.theta {
+            if top.max(0.0) >= bottom.min(180.0) {
+                return Err(format!("Sphere's theta range [{top}, {bottom}] is empty").into());
+            }
+        }
+        if let Some([start, end]) = self.phi {
+            if start >= end {
+                return Err(format!("Sphere's phi range [{start}, {end}] is empty").into());
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, sphere: Sphere) -> Sphere {
+        if !self.is_partial() {
+            return sphere;
+        }
+        let [top, bottom] = self.theta.unwrap_or([0.0, 180.0]);
+        let [start, end] = self.phi.unwrap_or([0.0, 360.0]);
+        sphere.with_angular_range(
+            Interval::new(top.to_radians(), bottom.to_radians()),
+            Interval::new(start.to_radians(), end.to_radians()),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EllipsoidConfig {
+    center: [f64; 3],
+    /// Semi-axes along x, y and z.
+    radii: [f64; 3],
+}
+
+#[derive(Debug, Clone, Deserialize)]
 struct QuadConfig {
     q: [f64; 3],
     u: [f64; 3],
     v: [f64; 3],
+    /// Lets rays through from the side `v` × `u` faces, such as the back of a light panel.
+    #[serde(default)]
+    single_sided: bool,
 }
 
-#[derive(Debug, Deserialize)]
-struct CuboidConfig {
+impl QuadConfig {
+    fn build(&self, material: Arc<dyn Material>) -> Quad {
+        Quad::new(
+            Point3::from(self.q),
+            Vec3::from(self.u),
+            Vec3::from(self.v),
+            material,
+        )
+        .with_single_sided(self.single_sided)
+    }
+}
+
+/// Quad cut out by the alpha channel of an image, for foliage cards; see `Billboard`.
+#[derive(Debug, Clone, Deserialize)]
+struct BillboardConfig {
+    #[serde(flatten)]
+    quad: QuadConfig,
+    /// Image whose alpha channel gives the opacity at each texture coordinate.
+    alpha_mask: String,
+}
+
+/// Array of identical quads, such as the cells of an LED panel or the panes of a window. At
+/// the top level each quad is an entity and a light of its own; a light `power` is shared
+/// between them.
+#[derive(Debug, Clone, Deserialize)]
+struct QuadGridConfig {
+    /// Corner of the first quad.
+    q: [f64; 3],
+    /// Edges of one quad.
+    u: [f64; 3],
+    v: [f64; 3],
+    /// Quads along `u`.
+    columns: usize,
+    /// Quads along `v`.
+    rows: usize,
+    /// Gaps between neighboring quads along `u` and `v`.
+    #[serde(default)]
+    spacing: [f64; 2],
+}
+
+impl QuadGridConfig {
+    fn quads(&self, material: Arc<dyn Material>) -> Vec<Arc<dyn Entity>> {
+        let (u, v) = (Vec3::from(self.u), Vec3::from(self.v));
+        let step_u = u + self.spacing[0] * u.unit();
+        let step_v = v + self.spacing[1] * v.unit();
+        (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let q = Point3::from(self.q) + column as f64 * step_u + row as f64 * step_v;
+                Arc::new(Quad::new(q, u, v, material.clone())) as Arc<dyn Entity>
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TriangleConfig {
     a: [f64; 3],
     b: [f64; 3],
+    c: [f64; 3],
+    /// Texture coordinates of `a`, `b` and `c`.
+    uvs: Option<[[f64; 2]; 3]>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ConstantMediumConfig {
-    boundary: EntityConfig,
-    density: f64,
+#[derive(Debug, Clone, Deserialize)]
+struct PolygonConfig {
+    /// Corners of a flat convex polygon, in order around it.
+    points: Vec<[f64; 3]>,
 }
 
-#[derive(Debug, Deserialize)]
-struct EntityClusterConfig {
-    children: Vec<EntityConfig>,
+/// Polygon with equal sides, such as a hexagonal tile, lying in the xz plane and facing up
+/// until rotated.
+#[derive(Debug, Clone, Deserialize)]
+struct RegularPolygonConfig {
+    center: [f64; 3],
+    /// Distance from the center to each corner.
+    radius: f64,
+    sides: u32,
+}
+
+impl RegularPolygonConfig {
+    /// Corners counterclockwise seen from above, the first on the +x axis.
+    fn corners(&self) -> Vec<Point3> {
+        let center = Point3::from(self.center);
+        (0..self.sides)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f64 / self.sides as f64;
+                center + self.radius * Vec3::new(angle.cos(), 0.0, -angle.sin())
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MeshConfig {
+    /// Model file, OBJ, STL or PLY.
+    path: String,
+    /// Rounds of Loop subdivision applied at load, each splitting every triangle into four.
+    #[serde(default)]
+    subdivision_levels: u32,
+    /// Computes vertex normals from the faces around each vertex when the file has none, so
+    /// the mesh is shaded smooth rather than faceted. Normals in the file are always used.
+    #[serde(default)]
+    smooth_normals: bool,
+    /// Factor taking the file's lengths to scene units.
+    #[serde(skip)]
+    scale: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BezierPatchConfig {
+    /// The 16 control points of a bicubic patch, row by row.
+    points: Vec<[f64; 3]>,
+}
+
+/// Bicubic patches read from a `.bpt` file, such as the Utah teapot.
+#[derive(Debug, Clone, Deserialize)]
+struct BezierPatchesConfig {
+    path: String,
+    /// Factor taking the file's lengths to scene units.
+    #[serde(skip)]
+    scale: Option<f64>,
+}
+
+/// Terrain raised from a grayscale image, centered on the origin with y up.
+#[derive(Debug, Clone, Deserialize)]
+struct HeightfieldConfig {
+    path: String,
+    /// Distance between neighboring pixels' vertices.
+    #[serde(default = "default_heightfield_scale")]
+    horizontal_scale: f64,
+    /// Height of a white pixel.
+    #[serde(default = "default_heightfield_scale")]
+    height_scale: f64,
+}
+
+fn default_heightfield_scale() -> f64 {
+    1.0
+}
+
+/// Strands of hair or grass, each a cubic Bezier curve from root to tip.
+#[derive(Debug, Clone, Deserialize)]
+struct CurvesConfig {
+    /// Four control points per curve, starting at the root.
+    curves: Vec<[[f64; 3]; 4]>,
+    root_width: f64,
+    #[serde(default)]
+    tip_width: f64,
+    /// Exponent shaping the taper; above 1 strands stay thick for longer.
+    #[serde(default = "default_width_falloff")]
+    width_falloff: f64,
+    /// Straight pieces each curve is cut into.
+    #[serde(default = "default_curve_segments")]
+    segments: u32,
+}
+
+fn default_width_falloff() -> f64 {
+    1.0
+}
+
+fn default_curve_segments() -> u32 {
+    8
+}
+
+/// Blobs whose surface is where the balls' summed fields reach the threshold, so balls closer
+/// than their radii merge.
+#[derive(Debug, Clone, Deserialize)]
+struct MetaballsConfig {
+    balls: Vec<MetaballConfig>,
+    /// Field strength on the surface; lower values give fatter blobs.
+    #[serde(default = "default_metaball_threshold")]
+    threshold: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MetaballConfig {
+    center: [f64; 3],
+    /// Distance at which the ball stops contributing to the field.
+    radius: f64,
+    #[serde(default = "default_metaball_weight")]
+    weight: f64,
+}
+
+fn default_metaball_threshold() -> f64 {
+    0.5
+}
+
+fn default_metaball_weight() -> f64 {
+    1.0
+}
+
+/// Points read from an XYZ or PLY file, each drawn as a small sphere or disk.
+#[derive(Debug, Clone, Deserialize)]
+struct PointCloudConfig {
+    path: String,
+    /// Radius of every point's splat.
+    radius: f64,
+    #[serde(default)]
+    shape: SplatShapeVariant,
+    scale: Option<f64>,
+}
+
+/// Model read from a MagicaVoxel `.vox` file; a `VertexColor` texture picks up its palette.
+#[derive(Debug, Clone, Deserialize)]
+struct VoxelsConfig {
+    path: String,
+    #[serde(default = "default_voxel_size")]
+    voxel_size: f64,
+}
+
+fn default_voxel_size() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum SplatShapeVariant {
+    #[default]
+    Sphere,
+    Disk,
+}
+
+impl From<SplatShapeVariant> for SplatShape {
+    fn from(value: SplatShapeVariant) -> Self {
+        match value {
+            SplatShapeVariant::Sphere => SplatShape::Sphere,
+            SplatShapeVariant::Disk => SplatShape::Disk,
+        }
+    }
+}
+
+/// Shape built from a tree of signed distance primitives and the ways of combining them.
+#[derive(Debug, Clone, Deserialize)]
+struct SdfConfig {
+    shape: SdfShapeConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "shape")]
+enum SdfShapeConfig {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+    Box {
+        center: [f64; 3],
+        size: [f64; 3],
+        /// Radius of the rounded edges.
+        #[serde(default)]
+        rounding: f64,
+    },
+    /// Ring lying in the xz plane.
+    Torus {
+        center: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Union {
+        a: Box<SdfShapeConfig>,
+        b: Box<SdfShapeConfig>,
+    },
+    SmoothUnion {
+        a: Box<SdfShapeConfig>,
+        b: Box<SdfShapeConfig>,
+        /// Width of the blend between the two.
+        smoothness: f64,
+    },
+    Intersection {
+        a: Box<SdfShapeConfig>,
+        b: Box<SdfShapeConfig>,
+    },
+    /// `a` with `b` cut out.
+    Subtraction {
+        a: Box<SdfShapeConfig>,
+        b: Box<SdfShapeConfig>,
+    },
+}
+
+impl SdfShapeConfig {
+    fn scale(&mut self, factor: f64) {
+        match self {
+            SdfShapeConfig::Sphere { center, radius } => {
+                scale_point(center, factor);
+                *radius *= factor;
+            }
+            SdfShapeConfig::Box {
+                center,
+                size,
+                rounding,
+            } => {
+                scale_point(center, factor);
+                scale_point(size, factor);
+                *rounding *= factor;
+            }
+            SdfShapeConfig::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                scale_point(center, factor);
+                *major_radius *= factor;
+                *minor_radius *= factor;
+            }
+            SdfShapeConfig::SmoothUnion { a, b, smoothness } => {
+                a.scale(factor);
+                b.scale(factor);
+                *smoothness *= factor;
+            }
+            SdfShapeConfig::Union { a, b }
+            | SdfShapeConfig::Intersection { a, b }
+            | SdfShapeConfig::Subtraction { a, b } => {
+                a.scale(factor);
+                b.scale(factor);
+            }
+        }
+    }
+}
+
+impl From<SdfShapeConfig> for Shape {
+    fn from(value: SdfShapeConfig) -> Self {
+        let operands = |a: Box<SdfShapeConfig>, b: Box<SdfShapeConfig>| {
+            (Box::new((*a).into()), Box::new((*b).into()))
+        };
+        match value {
+            SdfShapeConfig::Sphere { center, radius } => Shape::Sphere {
+                center: Point3::from(center),
+                radius,
+            },
+            SdfShapeConfig::Box {
+                center,
+                size,
+                rounding,
+            } => Shape::Box {
+                center: Point3::from(center),
+                half_size: 0.5 * Vec3::from(size),
+                rounding,
+            },
+            SdfShapeConfig::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => Shape::Torus {
+                center: Point3::from(center),
+                major_radius,
+                minor_radius,
+            },
+            SdfShapeConfig::Union { a, b } => {
+                let (a, b) = operands(a, b);
+                Shape::Union(a, b)
+            }
+            SdfShapeConfig::SmoothUnion { a, b, smoothness } => {
+                let (a, b) = operands(a, b);
+                Shape::SmoothUnion { a, b, smoothness }
+            }
+            SdfShapeConfig::Intersection { a, b } => {
+                let (a, b) = operands(a, b);
+                Shape::Intersection(a, b)
+            }
+            SdfShapeConfig::Subtraction { a, b } => {
+                let (a, b) = operands(a, b);
+                Shape::Subtraction(a, b)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CuboidConfig {
+    a: [f64; 3],
+    b: [f64; 3],
+    /// Materials of single faces, in place of the entity's.
+    #[serde(default)]
+    faces: CuboidFacesConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CuboidFacesConfig {
+    front: Option<MaterialConfig>,
+    right: Option<MaterialConfig>,
+    back: Option<MaterialConfig>,
+    left: Option<MaterialConfig>,
+    top: Option<MaterialConfig>,
+    bottom: Option<MaterialConfig>,
+}
+
+impl CuboidConfig {
+    /// The faces' materials, in the order `Cuboid::with_face_materials` takes them.
+    fn faces(&self) -> [&Option<MaterialConfig>; 6] {
+        let faces = &self.faces;
+        [
+            &faces.front,
+            &faces.right,
+            &faces.back,
+            &faces.left,
+            &faces.top,
+            &faces.bottom,
+        ]
+    }
+
+    fn faces_mut(&mut self) -> [&mut Option<MaterialConfig>; 6] {
+        let faces = &mut self.faces;
+        [
+            &mut faces.front,
+            &mut faces.right,
+            &mut faces.back,
+            &mut faces.left,
+            &mut faces.top,
+            &mut faces.bottom,
+        ]
+    }
+
+    /// Areas of the faces, in the same order.
+    fn face_areas(&self) -> [f64; 6] {
+        let size = Vec3::from(self.a) - Vec3::from(self.b);
+        let (x, y, z) = (size.x().abs(), size.y().abs(), size.z().abs());
+        [x * y, z * y, x * y, z * y, x * z, x * z]
+    }
+}
+
+/// Box between corners `a` and `b` with its edges and corners rounded off by `radius`.
+#[derive(Debug, Clone, Deserialize)]
+struct RoundedCuboidConfig {
+    a: [f64; 3],
+    b: [f64; 3],
+    radius: f64,
+}
+
+impl RoundedCuboidConfig {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let smallest = (0..3)
+            .map(|axis| (self.a[axis] - self.b[axis]).abs())
+            .fold(f64::INFINITY, f64::min);
+        if self.radius > 0.0 && self.radius <= smallest / 2.0 {
+            Ok(())
+        } else {
+            Err(format!(
+                "A rounded cuboid's radius must be positive and at most half its smallest \
+                 side, {}, but is {}",
+                smallest / 2.0,
+                self.radius
+            )
+            .into())
+        }
+    }
+}
+
+/// Air around a planet of `radius`, which is left to an entity of its own; the atmosphere's
+/// material is unused.
+#[derive(Debug, Clone, Deserialize)]
+struct AtmosphereConfig {
+    center: [f64; 3],
+    radius: f64,
+    /// Towards the sun lighting the air.
+    sun_direction: [f64; 3],
+    /// Sunlight falling on the air; only the air is lit by it, so a matching sun or light
+    /// is needed for the ground.
+    #[serde(default = "default_atmosphere_sun_irradiance")]
+    sun_irradiance: [f64; 3],
+    /// Density of the air relative to Earth's, at the same scale.
+    #[serde(default = "default_atmosphere_density")]
+    density: f64,
+    /// Amount of haze relative to Earth's, which whitens the sky and glows around the sun.
+    #[serde(default = "default_atmosphere_density")]
+    haze: f64,
+    /// How strongly the haze scatters forwards, from 0 to below 1.
+    #[serde(default = "default_mie_anisotropy")]
+    mie_anisotropy: f64,
+}
+
+fn default_atmosphere_sun_irradiance() -> [f64; 3] {
+    [20.0, 20.0, 20.0]
+}
+
+fn default_atmosphere_density() -> f64 {
+    1.0
+}
+
+fn default_mie_anisotropy() -> f64 {
+    0.76
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ConstantMediumConfig {
+    boundary: EntityConfig,
+    density: f64,
+    /// Where top-level media overlap, those of lower priority are left out of the higher ones;
+    /// media of equal priority add up.
+    #[serde(default)]
+    priority: i32,
+    /// World-space boundaries of the media that take precedence over this one.
+    #[serde(skip)]
+    excluded: Vec<Arc<dyn Entity>>,
+}
+
+/// Hollows out a closed `boundary`, leaving a wall `thickness` thick made of its material.
+#[derive(Debug, Clone, Deserialize)]
+struct ShellConfig {
+    boundary: EntityConfig,
+    thickness: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EntityClusterConfig {
+    children: Vec<EntityConfig>,
+}
+
+/// Alternative representations of one object, finest first; the first whose `min_size` the
+/// object's projected size reaches is built, the last otherwise.
+#[derive(Debug, Clone, Deserialize)]
+struct LodConfig {
+    levels: Vec<LodLevelConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LodLevelConfig {
+    /// Smallest on-screen diameter, in pixels, of the object's bounding sphere for which this
+    /// level is used.
+    #[serde(default)]
+    min_size: f64,
+    #[serde(flatten)]
+    entity: EntityConfig,
+}
+
+/// Where the camera looks from, for picking levels of detail.
+#[derive(Debug, Clone, Copy)]
+struct LodView {
+    eye: Point3,
+    /// Pixels spanned by one unit of length one unit away, along the image's height.
+    pixels_per_unit: f64,
+}
+
+impl LodView {
+    fn new(camera: &CameraConfig) -> Option<Self> {
+        let look_from = camera.look_from?;
+        let image_height = (camera.image_width as f64 / camera.aspect_ratio).max(1.0);
+        Some(Self {
+            eye: Point3::from(look_from),
+            pixels_per_unit: image_height / (2.0 * (camera.vertical_fov / 2.0).to_radians().tan()),
+        })
+    }
+
+    /// On-screen diameter in pixels of the sphere around `bounds`.
+    fn projected_size(&self, bounds: &Aabb) -> f64 {
+        let min = Point3::new(bounds.x().start, bounds.y().start, bounds.z().start);
+        let max = Point3::new(bounds.x().end, bounds.y().end, bounds.z().end);
+        let distance = (0.5 * (min + max) - self.eye).length();
+        let diameter = (max - min).length();
+        if distance <= 0.5 * diameter {
+            return f64::INFINITY;
+        }
+        self.pixels_per_unit * diameter / distance
+    }
+}
+
+/// Copy of an entity defined under `[entities.named]`; its own material is unused.
+#[derive(Debug, Clone, Deserialize)]
+struct InstanceConfig {
+    /// Key of the entity under `[entities.named]`.
+    entity: String,
+}
+
+/// Named entity, kept as its config until the first instance builds it.
+enum NamedEntity {
+    Config(Box<EntityConfig>),
+    Built(Arc<dyn Entity>),
+}
+
+thread_local! {
+    // set once the camera position is known, before entities are built
+    static LOD_VIEW: std::cell::Cell<Option<LodView>> = const { std::cell::Cell::new(None) };
+    // set from the scene's named entities, before entities are built
+    static NAMED_ENTITIES: RefCell<HashMap<String, NamedEntity>> = RefCell::new(HashMap::new());
+}
+
+/// Prepares the scene's named entities like top-level ones and makes them available to
+/// instances; none is built until an instance uses it, so unused meshes are never loaded.
+fn set_named_entities(
+    mut named: HashMap<String, EntityConfig>,
+    tags: &TagFilter,
+    material_override: Option<MaterialOverride>,
+    units: Unit,
+) -> Result<(), Box<dyn Error>> {
+    for entity in named.values_mut() {
+        entity.prepare(tags, material_override, units)?;
+    }
+    let mut checked = HashSet::new();
+    for name in named.keys() {
+        check_instances(name, &named, &mut Vec::new(), &mut checked)?;
+    }
+    NAMED_ENTITIES.set(
+        named
+            .into_iter()
+            .map(|(name, entity)| (name, NamedEntity::Config(Box::new(entity))))
+            .collect(),
+    );
+    Ok(())
+}
+
+/// Fails if the named entity `name`, or one it instances in turn, is missing or contains an
+/// instance of itself. `path` holds the named entities being checked that lead to `name`.
+fn check_instances<'a>(
+    name: &'a str,
+    named: &'a HashMap<String, EntityConfig>,
+    path: &mut Vec<&'a str>,
+    checked: &mut HashSet<&'a str>,
+) -> Result<(), Box<dyn Error>> {
+    if checked.contains(name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|&parent| parent == name) {
+        let cycle = path[start..].join("' -> '");
+        return Err(format!(
+            "Named entity '{name}' contains an instance of itself: '{cycle}' -> '{name}'"
+        )
+        .into());
+    }
+    let (name, entity) = named
+        .get_key_value(name)
+        .ok_or_else(|| format!("Named entity '{name}' does not exist"))?;
+    let mut instanced = Vec::new();
+    entity.collect_instances(&mut instanced);
+    path.push(name);
+    for instance in instanced {
+        check_instances(instance, named, path, checked)?;
+    }
+    path.pop();
+    checked.insert(name);
+    Ok(())
+}
+
+/// The named entity `name`, built on first use and shared by every later instance.
+fn named_entity(name: &str) -> Result<Arc<dyn Entity>, Box<dyn Error>> {
+    let entry = NAMED_ENTITIES.with_borrow_mut(|named| match named.get(name) {
+        Some(NamedEntity::Built(entity)) => Some(Ok(entity.clone())),
+        // taken out while it is built, which `check_instances` makes sure needs no other copy
+        Some(NamedEntity::Config(_)) => match named.remove(name) {
+            Some(NamedEntity::Config(config)) => Some(Err(config)),
+            _ => unreachable!("matched above"),
+        },
+        None => None,
+    });
+    match entry {
+        Some(Ok(entity)) => Ok(entity),
+        Some(Err(config)) => {
+            let entity = Arc::<dyn Entity>::try_from(*config)?;
+            NAMED_ENTITIES.with_borrow_mut(|named| {
+                named.insert(name.to_string(), NamedEntity::Built(entity.clone()))
+            });
+            Ok(entity)
+        }
+        None => Err(format!("Named entity '{name}' does not exist").into()),
+    }
+}
+
+impl LodConfig {
+    /// Builds the level matching the object's size as seen through `LOD_VIEW`, measuring it by
+    /// its coarsest level with the enclosing entity's `placement`; without a view the coarsest
+    /// level is used.
+    fn select(
+        self,
+        placement: Placement,
+        child: &mut ChildBuilder,
+    ) -> Result<Arc<dyn Entity>, Box<dyn Error>> {
+        let min_sizes: Vec<f64> = self.levels.iter().map(|level| level.min_size).collect();
+        let mut levels: Vec<_> = self
+            .levels
+            .into_iter()
+            .map(|level| Some(level.entity))
+            .collect();
+        let mut build = |index: usize| child(index, levels[index].take().expect("built once"));
+        let last = min_sizes.len().checked_sub(1).expect("checked by validate");
+        let coarsest = build(last)?;
+        let Some(view) = LOD_VIEW.get() else {
+            return Ok(coarsest);
+        };
+        let size = view.projected_size(&placed(coarsest.clone(), placement)?.bounding_box());
+        match min_sizes[..last]
+            .iter()
+            .position(|&min_size| size >= min_size)
+        {
+            Some(index) => build(index),
+            None => Ok(coarsest),
+        }
+    }
+}
+
+/// Hands each top-level medium the boundaries of the media with a higher priority.
+fn resolve_media_priorities(entities: &mut [EntityConfig]) -> Result<(), Box<dyn Error>> {
+    let priority = |entity: &EntityConfig| match &entity.variant {
+        EntityVariant::ConstantMedium(constant_medium) => Some(constant_medium.priority),
+        _ => None,
+    };
+    let lowest = entities.iter().filter_map(priority).min();
+    // boundaries are only built when some medium actually outranks another
+    let media = entities
+        .iter()
+        .filter_map(|entity| {
+            let EntityVariant::ConstantMedium(constant_medium) = &entity.variant else {
+                return None;
+            };
+            (Some(constant_medium.priority) > lowest).then(|| {
+                let boundary = constant_medium.boundary.clone().try_into()?;
+                Ok((
+                    constant_medium.priority,
+                    placed(boundary, entity.placement())?,
+                ))
+            })
+        })
+        .collect::<Result<Vec<(i32, Arc<dyn Entity>)>, Box<dyn Error>>>()?;
+    for entity in entities {
+        if let EntityVariant::ConstantMedium(constant_medium) = &mut entity.variant {
+            constant_medium.excluded = media
+                .iter()
+                .filter(|(priority, _)| *priority > constant_medium.priority)
+                .map(|(_, boundary)| boundary.clone())
+                .collect();
+        }
+    }
+    Ok(())
+}
+
+/// Gives each light with a link its bit, and every entity the bits of the linked lights that
+/// don't reach it. Named entities go by their key, as all their instances share one material.
+fn resolve_light_links(
+    entities: &mut [EntityConfig],
+    named: &mut HashMap<String, EntityConfig>,
+) -> Result<(), Box<dyn Error>> {
+    let mut links = Vec::new();
+    for entity in entities.iter_mut().chain(named.values_mut()) {
+        entity.collect_light_links(&mut links);
+    }
+    if links.len() > MAX_LINKED_LIGHTS {
+        return Err(format!("At most {MAX_LINKED_LIGHTS} lights can be linked").into());
+    }
+    if links.is_empty() {
+        return Ok(());
+    }
+    for entity in entities {
+        entity.unlink_lights(&links, &mut Vec::new());
+    }
+    for (name, entity) in named {
+        entity.unlink_lights(&links, &mut vec![name.as_str()]);
+    }
+    Ok(())
+}
+
+/// Wraps `entity` in the transform of its config, all of it applied in one step.
+fn placed(
+    entity: Arc<dyn Entity>,
+    placement: Placement,
+) -> Result<Arc<dyn Entity>, Box<dyn Error>> {
+    match placement.matrix() {
+        Some(matrix) => Ok(Arc::new(
+            Transformed::new(entity, matrix)
+                .ok_or("An entity's scale and matrix must not flatten it")?,
+        )),
+        None => Ok(entity),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "variant")]
+enum MaterialVariant {
+    Lambertian(LambertianConfig),
+    Metal(MetalConfig),
+    Dielectric(DielectricConfig),
+    DiffuseLight(DiffuseLightConfig),
+    Isotropic(IsotropicConfig),
+}
+
+impl MaterialVariant {
+    fn name(&self) -> &'static str {
+        match self {
+            MaterialVariant::Lambertian(_) => "Lambertian",
+            MaterialVariant::Metal(_) => "Metal",
+            MaterialVariant::Dielectric(_) => "Dielectric",
+            MaterialVariant::DiffuseLight(_) => "DiffuseLight",
+            MaterialVariant::Isotropic(_) => "Isotropic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LambertianConfig {
+    texture: TextureConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MetalConfig {
+    albedo: [f64; 3],
+    fuzz: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DielectricConfig {
+    refractive_index: f64,
+    /// Color shadow rays are tinted by as they pass through, so the glass casts a colored
+    /// shadow rather than a black one where direct light is sampled; opaque to them if unset.
+    shadow_tint: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DiffuseLightConfig {
+    /// Defaults to white, for lights colored only by `temperature`.
+    texture: Option<TextureConfig>,
+    /// Blackbody color temperature in kelvin, tinting the texture.
+    temperature: Option<f64>,
+    #[serde(default)]
+    one_sided: bool,
+    /// Opening angle of the emission cone in degrees.
+    spread: Option<f64>,
+    /// Multiplier on the emitted radiance, for lights brighter than the texture's colors;
+    /// scales a physical strength too.
+    #[serde(default = "default_light_intensity")]
+    intensity: f64,
+    /// Emitted flux in watts; at most one of `power`, `lumens` and `nits` may be given, and
+    /// the texture then only sets the color.
+    power: Option<f64>,
+    lumens: Option<f64>,
+    /// Luminance in candela per square meter.
+    nits: Option<f64>,
+    /// Radiance luminance the physical strength resolves to.
+    #[serde(skip)]
+    luminance: Option<f64>,
+    /// Entities the light reaches, by name; all of them if not given.
+    link: Option<LightLinkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LightLinkConfig {
+    /// If any are given, only entities going by one of these names are lit.
+    #[serde(default)]
+    include: Vec<String>,
+    /// Entities going by any of these names are left unlit, whatever `include` says.
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+impl LightLinkConfig {
+    /// Whether the light reaches an entity going by `names`, its own and its parents'.
+    fn reaches(&self, names: &[&str]) -> bool {
+        let listed = |list: &[String]| names.iter().any(|name| list.iter().any(|n| n == name));
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
+}
+
+// lights that can be linked, one bit each of `Material::light_link`
+const MAX_LINKED_LIGHTS: usize = 64;
+
+// luminous efficacy used to turn photometric quantities into radiometric ones
+const LUMENS_PER_WATT: f64 = 683.0;
+
+fn default_light_intensity() -> f64 {
+    1.0
+}
+
+impl DiffuseLightConfig {
+    /// Turns a physical strength into radiance, for an emitter of `area` square meters that
+    /// lights only its outside if `closed`.
+    fn resolve_units(&mut self, area: Option<f64>, closed: bool) -> Result<(), Box<dyn Error>> {
+        let flux = match (self.power, self.lumens, self.nits) {
+            (None, None, None) => return Ok(()),
+            (Some(watts), None, None) => watts,
+            (None, Some(lumens), None) => lumens / LUMENS_PER_WATT,
+            (None, None, Some(nits)) => {
+                self.luminance = Some(nits / LUMENS_PER_WATT);
+                return Ok(());
+            }
+            _ => return Err("Give a light only one of power, lumens and nits".into()),
+        };
+        let area = area.ok_or("Light power needs a sphere, quad or cuboid emitter")?;
+        // a diffuse emitter of radiance L sends out π·A·L from each side it lights
+        let sides = if self.one_sided || closed { 1.0 } else { 2.0 };
+        self.luminance = Some(flux / (PI * area * sides));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct IsotropicConfig {
+    texture: TextureConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MaterialConfig {
+    #[serde(flatten)]
+    material: MaterialVariant,
+    #[serde(default = "default_splitting")]
+    splitting: u16,
+    #[serde(default)]
+    extra_depth: u16,
+    /// Slot the material is built into when the scene is watched, see `Slotted`.
+    #[serde(skip)]
+    slot: Option<usize>,
+}
+
+fn default_splitting() -> u16 {
+    1
+}
+
+impl MaterialConfig {
+    /// Swaps the material for `material`, leaving lights as they are.
+    fn override_with(&mut self, material: MaterialOverride) {
+        let MaterialOverride::Clay = material;
+        let texture = TextureConfig {
+            variant: TextureVariant::SolidColor(SolidColorConfig { color: CLAY_ALBEDO }),
+        };
+        self.material = match self.material {
+            MaterialVariant::DiffuseLight(_) => return,
+            MaterialVariant::Isotropic(_) => {
+                MaterialVariant::Isotropic(IsotropicConfig { texture })
+            }
+            _ => MaterialVariant::Lambertian(LambertianConfig { texture }),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "variant")]
+enum TextureVariant {
+    SolidColor(SolidColorConfig),
+    Checker(Box<CheckerConfig>),
+    Image(ImageConfig),
+    Perlin(PerlinConfig),
+    Triplanar(Box<TriplanarConfig>),
+    Projected(Box<ProjectedConfig>),
+    VertexColor(Box<VertexColorConfig>),
+    Ramp(Box<RampConfig>),
+    Cavity(Box<CavityConfig>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SolidColorConfig {
+    color: [f64; 3],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct VertexColorConfig {
+    /// Used on surfaces without vertex colors; white if not given.
+    fallback: Option<TextureConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CheckerConfig {
+    odd: TextureConfig,
+    even: TextureConfig,
+    scale: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ImageConfig {
+    image_path: String,
+    /// Streams the image from a tiled copy on disk instead of decoding it all into memory.
+    cache: Option<TileCacheConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TileCacheConfig {
+    #[serde(default = "default_tile_size")]
+    tile_size: u32,
+    /// Megabytes of tiles kept in memory.
+    #[serde(default = "default_tile_budget")]
+    budget: f64,
+}
+
+fn default_tile_size() -> u32 {
+    128
+}
+
+fn default_tile_budget() -> f64 {
+    256.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PerlinConfig {
+    point_count: usize,
+    scale: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TriplanarConfig {
+    texture: TextureConfig,
+    scale: f64,
+    #[serde(default = "default_triplanar_sharpness")]
+    sharpness: f64,
+}
+
+fn default_triplanar_sharpness() -> f64 {
+    4.0
+}
+
+/// Blend from `low` to `high` as `input` goes from `start` to `end`.
+#[derive(Debug, Clone, Deserialize)]
+struct RampConfig {
+    input: RampInputVariant,
+    start: f64,
+    end: f64,
+    #[serde(default = "default_ramp_exponent")]
+    exponent: f64,
+    low: TextureConfig,
+    high: TextureConfig,
+}
+
+fn default_ramp_exponent() -> f64 {
+    1.0
+}
+
+/// Blend from `exposed` to `occluded` by how much geometry is within `radius` of the surface.
+#[derive(Debug, Clone, Deserialize)]
+struct CavityConfig {
+    radius: f64,
+    /// Probe rays per lookup.
+    #[serde(default = "default_cavity_samples")]
+    samples: u16,
+    /// Black if not given.
+    occluded: Option<TextureConfig>,
+    /// White if not given.
+    exposed: Option<TextureConfig>,
+}
+
+fn default_cavity_samples() -> u16 {
+    4
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum RampInputVariant {
+    Facing,
+    Distance,
+    NormalX,
+    NormalY,
+    NormalZ,
+}
+
+impl From<RampInputVariant> for RampInput {
+    fn from(value: RampInputVariant) -> Self {
+        match value {
+            RampInputVariant::Facing => RampInput::Facing,
+            RampInputVariant::Distance => RampInput::Distance,
+            RampInputVariant::NormalX => RampInput::NormalX,
+            RampInputVariant::NormalY => RampInput::NormalY,
+            RampInputVariant::NormalZ => RampInput::NormalZ,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProjectedConfig {
+    texture: TextureConfig,
+    outside: Option<TextureConfig>,
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    #[serde(default = "default_view_up")]
+    view_up: [f64; 3],
+    vertical_fov: f64,
+    #[serde(default = "default_projected_aspect_ratio")]
+    aspect_ratio: f64,
+}
+
+fn default_view_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_projected_aspect_ratio() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TextureConfig {
+    #[serde(flatten)]
+    variant: TextureVariant,
+}
+
+#[derive(Debug, Default, Deserialize)]
+enum RendererVariant {
+    #[default]
+    Recursive,
+    Wavefront,
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraConfig {
+    aspect_ratio: f64,
+    image_width: u32,
+    samples_per_pixel: u16,
+    max_depth: u16,
+    /// Optional with `auto_frame`, which keeps only the direction they give.
+    look_from: Option<[f64; 3]>,
+    look_at: Option<[f64; 3]>,
+    view_up: [f64; 3],
+    background: [f64; 3],
+    /// Replaces `background` with an image of the surroundings.
+    environment: Option<EnvironmentConfig>,
+    /// Disk light added to the background.
+    sun: Option<SunConfig>,
+    /// Procedural stars added to the background, for night and space scenes.
+    starfield: Option<StarfieldConfig>,
+    /// What camera rays see in place of the background, which keeps lighting the scene.
+    backplate: Option<BackplateVariant>,
+    vertical_fov: f64,
+    defocus_angle: f64,
+    /// Defaults to the distance from `look_from` to `look_at`.
+    focus_distance: Option<f64>,
+    /// Aims the camera at the whole scene, or at `frame_entity`, and backs it off until it fits.
+    #[serde(default)]
+    auto_frame: bool,
+    /// Name or tag of the top-level entities to frame, all of them together if several match.
+    frame_entity: Option<String>,
+    focus_map: Option<String>,
+    focus_range: Option<[f64; 2]>,
+    /// Grayscale image giving the aperture's shape, used when `defocus_angle` is non-zero.
+    bokeh_image: Option<String>,
+    /// Traces camera rays through a lens design rather than a thin lens; its f-stop takes
+    /// over from `defocus_angle`, and `vertical_fov` holds at infinity focus.
+    lens: Option<LensConfig>,
+    eye_separation: Option<f64>,
+    /// Stops of brightening applied to the image.
+    #[serde(default)]
+    exposure: f64,
+    /// Further stops for red, green and blue, compensating a color cast.
+    channel_exposure: Option<[f64; 3]>,
+    /// Makes sampling deterministic per pixel; `frame` and `decorrelate_frames` then control
+    /// how the noise changes along a frame sequence.
+    seed: Option<u64>,
+    #[serde(default)]
+    frame: u32,
+    /// Keyframed look_from and look_at, evaluated at `frame` in place of the fixed ones.
+    path: Option<CameraPathConfig>,
+    #[serde(default = "default_decorrelate_frames")]
+    decorrelate_frames: bool,
+    #[serde(default)]
+    renderer: RendererVariant,
+    tile_size: Option<u32>,
+    #[serde(default)]
+    tile_order: TileOrderVariant,
+    /// Splits tiles that turn out expensive into smaller ones between passes.
+    #[serde(default)]
+    adaptive_tiles: bool,
+    #[serde(default)]
+    filter: FilterVariant,
+    filter_radius: Option<f64>,
+    #[serde(default)]
+    backface_culling: bool,
+    near_clip: Option<f64>,
+    far_clip: Option<f64>,
+    wireframe: Option<WireframeVariant>,
+    wireframe_width: Option<f64>,
+    wireframe_color: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvironmentConfig {
+    /// Equirectangular image, preferably HDR or EXR so bright light sources keep their strength.
+    image_path: String,
+    #[serde(default = "default_environment_intensity")]
+    intensity: f64,
+}
+
+fn default_environment_intensity() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+struct StarfieldConfig {
+    /// Picks where the stars fall and how bright they are.
+    #[serde(default)]
+    seed: u64,
+    /// Stars over the whole sky, roughly.
+    #[serde(default = "default_star_count")]
+    count: u32,
+    /// Radiance of the faintest stars; the brightest are a hundred times that.
+    #[serde(default = "default_star_brightness")]
+    brightness: f64,
+    /// Exponent of the brightness distribution; larger values leave fewer bright stars.
+    #[serde(default = "default_star_falloff")]
+    falloff: f64,
+    /// Angular diameter of a star in degrees, best around a pixel's.
+    #[serde(default = "default_star_size")]
+    size: f64,
+    milky_way: Option<MilkyWayConfig>,
+}
+
+fn default_star_count() -> u32 {
+    8000
+}
+
+fn default_star_brightness() -> f64 {
+    0.5
+}
+
+fn default_star_falloff() -> f64 {
+    1.5
+}
+
+fn default_star_size() -> f64 {
+    0.1
+}
+
+#[derive(Debug, Deserialize)]
+struct MilkyWayConfig {
+    /// Pole of the great circle the band runs along.
+    #[serde(default = "default_milky_way_normal")]
+    normal: [f64; 3],
+    /// Half-width of the band in degrees.
+    #[serde(default = "default_milky_way_width")]
+    width: f64,
+    #[serde(default = "default_milky_way_intensity")]
+    intensity: f64,
+}
+
+fn default_milky_way_normal() -> [f64; 3] {
+    [0.4, 0.8, 0.45]
+}
+
+fn default_milky_way_width() -> f64 {
+    10.0
+}
+
+fn default_milky_way_intensity() -> f64 {
+    0.05
+}
+
+impl From<StarfieldConfig> for Starfield {
+    fn from(value: StarfieldConfig) -> Self {
+        Starfield::new(
+            value.seed,
+            value.count,
+            value.brightness,
+            value.falloff,
+            value.size,
+            value.milky_way.map(|milky_way| MilkyWay {
+                normal: Vec3::from(milky_way.normal),
+                width: milky_way.width,
+                intensity: milky_way.intensity,
+            }),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SunConfig {
+    /// Towards the sun.
+    direction: [f64; 3],
+    /// In degrees; wider suns cast softer shadows.
+    #[serde(default = "default_sun_angular_diameter")]
+    angular_diameter: f64,
+    /// Light falling on a surface facing the sun.
+    #[serde(default = "default_sun_irradiance")]
+    irradiance: [f64; 3],
+}
+
+fn default_sun_angular_diameter() -> f64 {
+    0.53
+}
+
+fn default_sun_irradiance() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Debug, Deserialize)]
+struct LensConfig {
+    #[serde(default)]
+    preset: LensPresetVariant,
+    focal_length: f64,
+    /// Wide open if not given.
+    f_stop: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum LensPresetVariant {
+    Biconvex,
+    #[default]
+    Doublet,
+}
+
+impl From<LensPresetVariant> for LensPreset {
+    fn from(value: LensPresetVariant) -> Self {
+        match value {
+            LensPresetVariant::Biconvex => LensPreset::Biconvex,
+            LensPresetVariant::Doublet => LensPreset::Doublet,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "variant")]
+enum BackplateVariant {
+    Color(BackplateColorConfig),
+    /// Written out as an alpha channel.
+    Transparent,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackplateColorConfig {
+    color: [f64; 3],
+}
+
+impl From<BackplateVariant> for Backplate {
+    fn from(value: BackplateVariant) -> Self {
+        match value {
+            BackplateVariant::Color(backplate) => Backplate::Color(Color::from(backplate.color)),
+            BackplateVariant::Transparent => Backplate::Transparent,
+        }
+    }
+}
+
+impl From<SunConfig> for Sun {
+    fn from(value: SunConfig) -> Self {
+        Sun::new(
+            Vec3::from(value.direction),
+            value.angular_diameter,
+            Color::from(value.irradiance),
+        )
+    }
+}
+
+fn default_decorrelate_frames() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+enum WireframeVariant {
+    Overlay,
+    Only,
+}
+
+#[derive(Debug, Deserialize)]
+struct BakeConfig {
+    /// Index of the top-level entity to bake, in file order.
+    entity: usize,
+    resolution: [u32; 2],
+    samples: Option<u16>,
+    #[serde(default)]
+    flip_normal: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpriteSheetConfig {
+    /// Frames evenly spaced around a full turn, usually 8 or 16.
+    directions: u32,
+    /// Frames per row; all of them in one row if not given.
+    columns: Option<u32>,
+    /// Point the camera orbits; the camera's `look_at` if not given.
+    pivot: Option<[f64; 3]>,
+    /// Axis the camera turns about; the camera's `view_up` if not given.
+    axis: Option<[f64; 3]>,
+}
+
+impl SpriteSheetConfig {
+    fn build(self, camera: &CameraConfig) -> Result<SpriteSheet, Box<dyn Error>> {
+        if self.directions == 0 || self.columns == Some(0) {
+            return Err("A sprite sheet needs at least one direction and column".into());
+        }
+        Ok(SpriteSheet {
+            directions: self.directions,
+            columns: self.columns.unwrap_or(self.directions),
+            pivot: Point3::from(
+                self.pivot
+                    .or(camera.look_at)
+                    .expect("resolved by resolve_framing"),
+            ),
+            axis: Vec3::from(self.axis.unwrap_or(camera.view_up)),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaSliceConfig {
+    point: [f64; 3],
+    normal: [f64; 3],
+    max_density: Option<f64>,
+}
+
+impl From<MediaSliceConfig> for MediaSlice {
+    fn from(value: MediaSliceConfig) -> Self {
+        MediaSlice {
+            point: Point3::from(value.point),
+            normal: Vec3::from(value.normal).unit(),
+            max_density: value.max_density,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BoundsConfig {
+    #[serde(default = "default_bounds_color")]
+    color: [f64; 3],
+    /// Levels of the scene's BVH below its root to outline too; none if left out.
+    bvh_depth: Option<u32>,
+    #[serde(default = "default_bvh_bounds_color")]
+    bvh_color: [f64; 3],
+    /// Line width in pixels.
+    #[serde(default = "default_bounds_width")]
+    width: f64,
+    #[serde(default = "default_bounds_opacity")]
+    opacity: f64,
+}
+
+fn default_bounds_color() -> [f64; 3] {
+    [1.0, 0.6, 0.1]
+}
+
+fn default_bvh_bounds_color() -> [f64; 3] {
+    [0.2, 0.6, 1.0]
+}
+
+fn default_bounds_width() -> f64 {
+    1.5
+}
+
+fn default_bounds_opacity() -> f64 {
+    0.5
+}
+
+impl BoundsConfig {
+    /// Outlines of each entity's box and of the BVH nodes that would be built over
+    /// `entities`, seen by camera rays alone so they leave the lighting untouched.
+    fn outlines(&self, entities: &mut [Arc<dyn Entity>]) -> Vec<Arc<dyn Entity>> {
+        let outline = |color: [f64; 3]| {
+            let [r, g, b] = color;
+            let material: Arc<dyn Material> =
+                Arc::new(DiffuseLight::new(Arc::new(Solid::new(r, g, b))));
+            move |bounding_box: Aabb| {
+                let outline =
+                    BoxOutline::new(bounding_box, self.width, self.opacity, material.clone());
+                let visibility = Visibility {
+                    camera: true,
+                    diffuse: false,
+                    specular: false,
+                    shadow: false,
+                };
+                Arc::new(Restricted::new(Arc::new(outline), visibility)) as Arc<dyn Entity>
+            }
+        };
+        let mut outlines: Vec<Arc<dyn Entity>> = entities
+            .iter()
+            .map(|entity| entity.bounding_box())
+            .map(outline(self.color))
+            .collect();
+        if let Some(depth) = self.bvh_depth {
+            outlines.extend(
+                BVHNode::node_bounds(entities, depth)
+                    .into_iter()
+                    .map(outline(self.bvh_color)),
+            );
+        }
+        outlines
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TextureBakeConfig {
+    /// Appended to the scene name to name the image.
+    name: String,
+    texture: TextureConfig,
+    resolution: [u32; 2],
+    #[serde(default)]
+    domain: BakeDomainVariant,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(tag = "variant")]
+enum BakeDomainVariant {
+    #[default]
+    Uv,
+    Plane(PlaneDomainConfig),
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaneDomainConfig {
+    origin: [f64; 3],
+    u: [f64; 3],
+    v: [f64; 3],
+}
+
+impl From<BakeDomainVariant> for BakeDomain {
+    fn from(value: BakeDomainVariant) -> Self {
+        match value {
+            BakeDomainVariant::Uv => BakeDomain::Uv,
+            BakeDomainVariant::Plane(plane) => BakeDomain::Plane {
+                origin: Point3::from(plane.origin),
+                u: Vec3::from(plane.u),
+                v: Vec3::from(plane.v),
+            },
+        }
+    }
+}
+
+impl TryFrom<TextureBakeConfig> for TextureBake {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: TextureBakeConfig) -> Result<Self, Self::Error> {
+        Ok(TextureBake {
+            name: value.name,
+            texture: value.texture.try_into()?,
+            width: value.resolution[0],
+            height: value.resolution[1],
+            domain: value.domain.into(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SectionConfig {
+    point: [f64; 3],
+    normal: [f64; 3],
+    cap: Option<MaterialConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+enum FilterVariant {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    BlackmanHarris,
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraPathConfig {
+    keyframes: Vec<KeyframeConfig>,
+    #[serde(default)]
+    interpolation: InterpolationVariant,
+    #[serde(default)]
+    easing: EasingVariant,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyframeConfig {
+    frame: f64,
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+}
+
+#[derive(Debug, Default, Deserialize)]
+enum InterpolationVariant {
+    #[default]
+    Linear,
+    CatmullRom,
+    Bezier,
+}
+
+#[derive(Debug, Default, Deserialize)]
+enum EasingVariant {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+#[derive(Debug, Default, Deserialize)]
+enum TileOrderVariant {
+    #[default]
+    Scanline,
+    Hilbert,
+    Morton,
+    Spiral,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OutputConfig {
+    #[serde(default)]
+    variance_heatmap: bool,
+    #[serde(default)]
+    preview: bool,
+    #[serde(default)]
+    motion_vectors: bool,
+    #[serde(default)]
+    cost_heatmap: bool,
+    #[serde(default)]
+    exr: bool,
+    #[serde(default)]
+    anaglyph: bool,
+    #[serde(default)]
+    bit_depth: BitDepthVariant,
+    #[serde(default)]
+    color_space: ColorSpaceVariant,
+    #[serde(default)]
+    highlight_rolloff: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum BitDepthVariant {
+    #[default]
+    #[serde(rename = "8")]
+    Eight,
+    #[serde(rename = "16")]
+    Sixteen,
+    #[serde(rename = "float")]
+    Float,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum ColorSpaceVariant {
+    #[default]
+    Srgb,
+    Linear,
+    DisplayP3,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct IntegratorConfig {
+    rr_start_depth: Option<u16>,
+    max_radiance: Option<f64>,
+    #[serde(default)]
+    min_bounces: u16,
+    irradiance_cache: Option<IrradianceCacheConfig>,
+    restir: Option<RestirConfig>,
+    #[serde(default)]
+    path_guiding: bool,
+    toon: Option<ToonConfig>,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(tag = "variant")]
-enum MaterialVariant {
-    Lambertian(LambertianConfig),
-    Metal(MetalConfig),
-    Dielectric(DielectricConfig),
-    DiffuseLight(DiffuseLightConfig),
-    Isotropic(IsotropicConfig),
+struct RestirConfig {
+    #[serde(default = "default_restir_candidates")]
+    candidates: u16,
+    #[serde(default = "default_restir_spatial_neighbors")]
+    spatial_neighbors: u16,
+    #[serde(default = "default_restir_spatial_radius")]
+    spatial_radius: u32,
+    #[serde(default = "default_restir_max_history")]
+    max_history: f64,
 }
 
-#[derive(Debug, Deserialize)]
-struct LambertianConfig {
-    texture: TextureConfig,
+fn default_restir_candidates() -> u16 {
+    8
 }
 
-#[derive(Debug, Deserialize)]
-struct MetalConfig {
-    albedo: [f64; 3],
-    fuzz: f64,
+fn default_restir_spatial_neighbors() -> u16 {
+    3
 }
 
-#[derive(Debug, Deserialize)]
-struct DielectricConfig {
-    refractive_index: f64,
+fn default_restir_spatial_radius() -> u32 {
+    8
 }
 
-#[derive(Debug, Deserialize)]
-struct DiffuseLightConfig {
-    texture: TextureConfig,
+fn default_restir_max_history() -> f64 {
+    20.0
 }
 
+/// Cel-shaded look in place of path tracing: flat bands of color and inked outlines.
 #[derive(Debug, Deserialize)]
-struct IsotropicConfig {
-    texture: TextureConfig,
+struct ToonConfig {
+    /// Evenly spaced gray shades, when no palette is given.
+    #[serde(default = "default_toon_bands")]
+    bands: u32,
+    /// Shades multiplying surface colors, from unlit to fully lit.
+    palette: Option<Vec<[f64; 3]>>,
+    /// Towards the key light; otherwise the brightest light is used, then the sun.
+    light_direction: Option<[f64; 3]>,
+    /// Outline width in pixels; 0 turns outlines off.
+    #[serde(default = "default_outline_width")]
+    outline_width: f64,
+    #[serde(default)]
+    outline_color: [f64; 3],
+    /// Smallest angle in degrees between neighboring normals inked as a crease.
+    #[serde(default = "default_crease_angle")]
+    crease_angle: f64,
+    /// Gap between neighboring surfaces inked as an edge, relative to their distance.
+    #[serde(default = "default_depth_threshold")]
+    depth_threshold: f64,
 }
 
-#[derive(Debug, Deserialize)]
-struct MaterialConfig {
-    #[serde(flatten)]
-    material: MaterialVariant,
+fn default_toon_bands() -> u32 {
+    3
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(tag = "variant")]
-enum TextureVariant {
-    SolidColor(SolidColorConfig),
-    Checker(Box<CheckerConfig>),
-    Image(ImageConfig),
-    Perlin(PerlinConfig),
+fn default_outline_width() -> f64 {
+    1.0
 }
 
-#[derive(Debug, Deserialize)]
-struct SolidColorConfig {
-    color: [f64; 3],
+fn default_crease_angle() -> f64 {
+    45.0
 }
 
-#[derive(Debug, Deserialize)]
-struct CheckerConfig {
-    odd: TextureConfig,
-    even: TextureConfig,
-    scale: f64,
+fn default_depth_threshold() -> f64 {
+    0.01
 }
 
-#[derive(Debug, Deserialize)]
-struct ImageConfig {
-    image_path: String,
+impl ToonConfig {
+    /// Lit by the light direction if one is given, else by the brightest of `lights`, else by
+    /// the sun, else from the camera.
+    fn build(self, lights: &[Arc<dyn Entity>], sun: Option<Sun>) -> Result<Toon, Box<dyn Error>> {
+        let palette = match self.palette {
+            Some(palette) => palette.into_iter().map(Color::from).collect(),
+            None => (1..=self.bands)
+                .map(|band| {
+                    let shade = band as f64 / self.bands as f64;
+                    Color::new(shade, shade, shade)
+                })
+                .collect::<Vec<_>>(),
+        };
+        if palette.is_empty() {
+            return Err("Toon shading needs at least one band".into());
+        }
+        let key_light = match (self.light_direction, lights.first(), sun) {
+            (Some(direction), _, _) => KeyLight::Direction(Vec3::from(direction).unit()),
+            (None, Some(light), _) => KeyLight::Entity(light.clone()),
+            (None, None, Some(sun)) => KeyLight::Sun(sun),
+            (None, None, None) => KeyLight::Headlight,
+        };
+        let outline = (self.outline_width > 0.0).then(|| Outline {
+            width: self.outline_width,
+            color: Color::from(self.outline_color),
+            crease_cos: self.crease_angle.to_radians().cos(),
+            depth_threshold: self.depth_threshold,
+        });
+        Ok(Toon {
+            palette,
+            outline,
+            key_light,
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
-struct PerlinConfig {
-    point_count: usize,
-    scale: f64,
+struct IrradianceCacheConfig {
+    #[serde(default = "default_cache_error")]
+    max_error: f64,
+    #[serde(default = "default_cache_samples")]
+    samples: u16,
 }
 
-#[derive(Debug, Deserialize)]
-struct TextureConfig {
-    #[serde(flatten)]
-    variant: TextureVariant,
+fn default_cache_error() -> f64 {
+    0.2
 }
 
-#[derive(Debug, Deserialize)]
-struct CameraConfig {
-    aspect_ratio: f64,
-    image_width: u32,
-    samples_per_pixel: u16,
-    max_depth: u16,
-    look_from: [f64; 3],
-    look_at: [f64; 3],
-    view_up: [f64; 3],
-    background: [f64; 3],
-    vertical_fov: f64,
-    defocus_angle: f64,
-    focus_distance: f64,
+fn default_cache_samples() -> u16 {
+    64
 }
 
-impl From<TextureConfig> for Arc<dyn Texture> {
-    fn from(value: TextureConfig) -> Self {
-        match value.variant {
+impl TryFrom<TextureConfig> for Arc<dyn Texture> {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: TextureConfig) -> Result<Self, Self::Error> {
+        let texture: Arc<dyn Texture> = match value.variant {
             TextureVariant::SolidColor(solid_color) => {
                 Arc::new(Solid::from(Color::from(solid_color.color)))
             }
+            TextureVariant::Ramp(ramp) => Arc::new(Ramp::new(
+                ramp.input.into(),
+                ramp.start,
+                ramp.end,
+                ramp.exponent,
+                ramp.low.try_into()?,
+                ramp.high.try_into()?,
+            )),
+            TextureVariant::Cavity(cavity) => Arc::new(Cavity::new(
+                cavity.radius,
+                cavity.samples,
+                cavity.occluded.map_or_else(
+                    || Ok(Arc::new(Solid::default()) as Arc<dyn Texture>),
+                    TryInto::try_into,
+                )?,
+                cavity.exposed.map_or_else(
+                    || Ok(Arc::new(Solid::new(1.0, 1.0, 1.0)) as Arc<dyn Texture>),
+                    TryInto::try_into,
+                )?,
+            )),
             TextureVariant::Checker(checker) => Arc::new(Checker::new(
-                checker.odd.into(),
-                checker.even.into(),
+                checker.odd.try_into()?,
+                checker.even.try_into()?,
                 checker.scale,
             )),
-            TextureVariant::Image(image) => Arc::new(ImageTex::new(&image.image_path)),
+            TextureVariant::Image(image) => match image.cache {
+                Some(cache) => Arc::new(TiledImageTex::new(
+                    &image.image_path,
+                    cache.tile_size.max(1),
+                    (cache.budget * 1024.0 * 1024.0) as usize,
+                )?),
+                None => Arc::new(ImageTex::new(&image.image_path)?),
+            },
             TextureVariant::Perlin(perlin) => {
                 Arc::new(PerlinTex::new(perlin.point_count, perlin.scale))
             }
-        }
+            TextureVariant::Triplanar(triplanar) => Arc::new(Triplanar::new(
+                triplanar.texture.try_into()?,
+                triplanar.scale,
+                triplanar.sharpness,
+            )),
+            TextureVariant::Projected(projected) => Arc::new(Projected::new(
+                projected.texture.try_into()?,
+                projected.outside.map_or_else(
+                    || Ok(Arc::new(Solid::default()) as Arc<dyn Texture>),
+                    TryInto::try_into,
+                )?,
+                Point3::from(projected.look_from),
+                Point3::from(projected.look_at),
+                Vec3::from(projected.view_up),
+                projected.vertical_fov,
+                projected.aspect_ratio,
+            )),
+            TextureVariant::VertexColor(vertex_color) => {
+                Arc::new(VertexColor::new(vertex_color.fallback.map_or_else(
+                    || Ok(Arc::new(Solid::new(1.0, 1.0, 1.0)) as Arc<dyn Texture>),
+                    TryInto::try_into,
+                )?))
+            }
+        };
+        Ok(texture)
     }
 }
 
-impl From<MaterialConfig> for Arc<dyn Material> {
-    fn from(value: MaterialConfig) -> Self {
-        match value.material {
+impl TryFrom<MaterialConfig> for Arc<dyn Material> {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: MaterialConfig) -> Result<Self, Self::Error> {
+        let slot = value.slot;
+        let material: Arc<dyn Material> = match value.material {
             MaterialVariant::Lambertian(lambertian) => {
-                Arc::new(Lambertian::new(lambertian.texture.into()))
+                Arc::new(Lambertian::new(lambertian.texture.try_into()?))
             }
             MaterialVariant::Metal(metal) => {
                 Arc::new(Metal::new(Color::from(metal.albedo), metal.fuzz))
             }
-            MaterialVariant::Dielectric(dielectric) => {
-                Arc::new(Dielectric::new(dielectric.refractive_index))
-            }
+            MaterialVariant::Dielectric(dielectric) => Arc::new(
+                Dielectric::new(dielectric.refractive_index)
+                    .with_shadow_tint(dielectric.shadow_tint.map(Color::from)),
+            ),
             MaterialVariant::DiffuseLight(diffuse_light) => {
-                Arc::new(DiffuseLight::new(diffuse_light.texture.into()))
+                let spread = EmissionSpread {
+                    one_sided: diffuse_light.one_sided,
+                    angle: diffuse_light.spread.map_or(PI, f64::to_radians),
+                };
+                let texture: Arc<dyn Texture> = diffuse_light.texture.map_or_else(
+                    || Ok(Arc::new(Solid::new(1.0, 1.0, 1.0)) as Arc<dyn Texture>),
+                    TryInto::try_into,
+                )?;
+                let tint = diffuse_light
+                    .temperature
+                    .map_or(Color::new(1.0, 1.0, 1.0), blackbody);
+                let average = (tint * texture.average_color()).luminance();
+                let strength = match diffuse_light.luminance {
+                    Some(luminance) if average > 0.0 => luminance / average,
+                    _ => 1.0,
+                };
+                Arc::new(
+                    DiffuseLight::new(texture)
+                        .with_spread(spread)
+                        .with_tint(tint)
+                        .with_intensity(strength * diffuse_light.intensity),
+                )
             }
             MaterialVariant::Isotropic(isotropic) => {
-                Arc::new(Isotropic::new(isotropic.texture.into()))
+                Arc::new(Isotropic::new(isotropic.texture.try_into()?))
+            }
+        };
+        let material: Arc<dyn Material> = if value.splitting > 1 || value.extra_depth > 0 {
+            Arc::new(Budgeted::new(material, value.splitting, value.extra_depth))
+        } else {
+            material
+        };
+        Ok(match slot {
+            Some(slot) => {
+                material::set_slot(slot, material);
+                Arc::new(Slotted::new(slot))
+            }
+            None => material,
+        })
+    }
+}
+
+/// An entity's shape, its built material and the wrapping its other materials get.
+type EntityParts = (EntityVariant, Arc<dyn Material>, MaterialWrapping);
+
+/// Builds a container's child from its config and its index among the children, a boundary
+/// being the only one.
+type ChildBuilder<'a> =
+    dyn FnMut(usize, EntityConfig) -> Result<Arc<dyn Entity>, Box<dyn Error>> + 'a;
+
+impl EntityConfig {
+    /// Number of entities `into_entities` builds.
+    fn entity_count(&self) -> usize {
+        match &self.variant {
+            EntityVariant::QuadGrid(grid) => grid.rows * grid.columns,
+            _ => 1,
+        }
+    }
+
+    /// Builds the entity, splitting a quad grid into its quads so each is sampled as a light.
+    fn into_entities(self) -> Result<Vec<Arc<dyn Entity>>, Box<dyn Error>> {
+        if !matches!(self.variant, EntityVariant::QuadGrid(_)) {
+            return Ok(vec![self.try_into()?]);
+        }
+        let placement = self.placement();
+        let (variant, material, _) = self.into_parts()?;
+        let EntityVariant::QuadGrid(grid) = variant else {
+            unreachable!("checked above");
+        };
+        grid.quads(material)
+            .into_iter()
+            .map(|quad| placed(quad, placement))
+            .collect()
+    }
+
+    fn placement(&self) -> Placement {
+        Placement {
+            scale: self.scale,
+            rotation: self.rotation,
+            translation: self.translation,
+            matrix: self.matrix,
+        }
+    }
+
+    /// Seed for a jitter that gives none, hashed from the entity's shape and placement so that
+    /// copies placed apart differ; the same on every platform and Rust release.
+    fn jitter_seed(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(self.variant.name().as_bytes());
+        hash.write(self.name.as_deref().unwrap_or_default().as_bytes());
+        let point = |point: &Option<[f64; 3]>| point.unwrap_or_default();
+        let scale = self.scale.map_or(Vec3::default(), Vec3::from);
+        hash.write_f64s(&point(&self.translation));
+        hash.write_f64s(&point(&self.rotation));
+        hash.write_f64s(&[scale.x(), scale.y(), scale.z()]);
+        hash.write_f64s(self.matrix.unwrap_or_default().as_flattened());
+        match &self.variant {
+            EntityVariant::Sphere(sphere) => {
+                hash.write_f64s(&sphere.center);
+                hash.write_f64s(&[sphere.radius]);
+            }
+            EntityVariant::MovingSphere(moving_sphere) => {
+                hash.write_f64s(&moving_sphere.center1);
+                hash.write_f64s(&moving_sphere.center2);
+                hash.write_f64s(&[moving_sphere.radius]);
+            }
+            EntityVariant::Ellipsoid(ellipsoid) => {
+                hash.write_f64s(&ellipsoid.center);
+                hash.write_f64s(&ellipsoid.radii);
+            }
+            EntityVariant::Quad(quad) | EntityVariant::Billboard(BillboardConfig { quad, .. }) => {
+                hash.write_f64s(&[quad.q, quad.u, quad.v].concat());
+            }
+            EntityVariant::QuadGrid(grid) => hash.write_f64s(&[grid.q, grid.u, grid.v].concat()),
+            EntityVariant::Triangle(triangle) => {
+                hash.write_f64s(&[triangle.a, triangle.b, triangle.c].concat());
+            }
+            EntityVariant::Polygon(polygon) => hash.write_f64s(polygon.points.as_flattened()),
+            EntityVariant::RegularPolygon(polygon) => {
+                hash.write_f64s(&polygon.center);
+                hash.write_f64s(&[polygon.radius]);
+            }
+            EntityVariant::BezierPatch(patch) => hash.write_f64s(patch.points.as_flattened()),
+            EntityVariant::Curves(curves) => {
+                hash.write_f64s(curves.curves.as_flattened().as_flattened());
+            }
+            EntityVariant::Metaballs(metaballs) => {
+                for ball in &metaballs.balls {
+                    hash.write_f64s(&ball.center);
+                    hash.write_f64s(&[ball.radius]);
+                }
+            }
+            EntityVariant::Cuboid(cuboid) => hash.write_f64s(&[cuboid.a, cuboid.b].concat()),
+            EntityVariant::RoundedCuboid(rounded_cuboid) => {
+                hash.write_f64s(&[rounded_cuboid.a, rounded_cuboid.b].concat());
+            }
+            EntityVariant::Atmosphere(atmosphere) => hash.write_f64s(&atmosphere.center),
+            EntityVariant::Mesh(MeshConfig { path, .. })
+            | EntityVariant::BezierPatches(BezierPatchesConfig { path, .. })
+            | EntityVariant::Heightfield(HeightfieldConfig { path, .. })
+            | EntityVariant::PointCloud(PointCloudConfig { path, .. })
+            | EntityVariant::Voxels(VoxelsConfig { path, .. })
+            | EntityVariant::Instance(InstanceConfig { entity: path }) => {
+                hash.write(path.as_bytes());
+            }
+            // containers are told apart by their placement
+            EntityVariant::Sdf(_)
+            | EntityVariant::ConstantMedium(_)
+            | EntityVariant::Shell(_)
+            | EntityVariant::EntityCluster(_)
+            | EntityVariant::Lod(_) => {}
+        }
+        hash.finish()
+    }
+
+    /// The shape, the material with any jitter applied, and the wrapping any other material
+    /// the entity is built with gets.
+    fn into_parts(mut self) -> Result<EntityParts, Box<dyn Error>> {
+        let mut hue_shift = 0.0;
+        if let Some(jitter) = self.jitter.take() {
+            let seed = jitter.seed.unwrap_or_else(|| self.jitter_seed());
+            let mut rng = fastrand::Rng::with_seed(seed);
+            hue_shift = jitter.hue * (2.0 * rng.f64() - 1.0);
+            if let MaterialVariant::Metal(metal) = &mut self.material.material {
+                let offset = jitter.roughness * (2.0 * rng.f64() - 1.0);
+                metal.fuzz = (metal.fuzz + offset).clamp(0.0, 1.0);
             }
         }
+        let wrapping = MaterialWrapping {
+            hue_shift,
+            light_link: self.light_link,
+            unlinked_lights: self.unlinked_lights,
+        };
+        let material = wrapping.apply(self.material.try_into()?);
+        Ok((self.variant, material, wrapping))
+    }
+}
+
+/// Wrappers every material of an entity is built with: its jitter's hue shift and its light
+/// links.
+#[derive(Debug, Clone, Copy)]
+struct MaterialWrapping {
+    hue_shift: f64,
+    light_link: u64,
+    unlinked_lights: u64,
+}
+
+impl MaterialWrapping {
+    fn apply(&self, mut material: Arc<dyn Material>) -> Arc<dyn Material> {
+        if self.hue_shift != 0.0 {
+            material = Arc::new(HueShifted::new(material, self.hue_shift));
+        }
+        if self.light_link != 0 || self.unlinked_lights != 0 {
+            material = Arc::new(LightLinked::new(
+                material,
+                self.light_link,
+                self.unlinked_lights,
+            ));
+        }
+        material
     }
 }
 
-impl From<EntityConfig> for Arc<dyn Entity> {
-    fn from(config: EntityConfig) -> Self {
-        let material = config.material.into();
-        let mut entity: Arc<dyn Entity> = match config.variant {
-            EntityVariant::Sphere(sphere) => Arc::new(Sphere::stationary(
+impl TryFrom<EntityConfig> for Arc<dyn Entity> {
+    type Error = Box<dyn Error>;
+
+    fn try_from(config: EntityConfig) -> Result<Self, Self::Error> {
+        config.build(&mut |_, child| child.try_into())
+    }
+}
+
+impl EntityConfig {
+    /// Builds the entity, leaving the children of containers to `child`.
+    fn build(self, child: &mut ChildBuilder) -> Result<Arc<dyn Entity>, Box<dyn Error>> {
+        let placement = self.placement();
+        let visibility = self.visibility;
+        let (variant, material, wrapping) = self.into_parts()?;
+        let entity: Arc<dyn Entity> = match variant {
+            EntityVariant::Sphere(sphere) => Arc::new(sphere.range.apply(Sphere::stationary(
                 Point3::from(sphere.center),
                 sphere.radius,
                 material,
+            ))),
+            EntityVariant::MovingSphere(moving_sphere) => {
+                Arc::new(moving_sphere.range.apply(Sphere::moving(
+                    Point3::from(moving_sphere.center1),
+                    Point3::from(moving_sphere.center2),
+                    moving_sphere.radius,
+                    material,
+                )))
+            }
+            EntityVariant::Ellipsoid(ellipsoid) => Arc::new(Translated::new(
+                Arc::new(
+                    Scaled::new(
+                        Arc::new(Sphere::stationary(Point3::default(), 1.0, material)),
+                        Vec3::from(ellipsoid.radii),
+                    )
+                    .ok_or("An ellipsoid's radii must not be zero")?,
+                ),
+                Vec3::from(ellipsoid.center),
+            )),
+            EntityVariant::Quad(quad) => Arc::new(quad.build(material)),
+            EntityVariant::Billboard(billboard) => {
+                let mask = AlphaMask::load(&billboard.alpha_mask)?;
+                Arc::new(Billboard::new(billboard.quad.build(material), mask))
+            }
+            EntityVariant::QuadGrid(grid) => {
+                let mut cluster = EntityCluster::new();
+                for quad in grid.quads(material) {
+                    cluster.push(quad);
+                }
+                Arc::new(cluster)
+            }
+            EntityVariant::Triangle(triangle) => Arc::new(Triangle::new(
+                Point3::from(triangle.a),
+                Point3::from(triangle.b),
+                Point3::from(triangle.c),
+                triangle
+                    .uvs
+                    .map_or(Triangle::DEFAULT_UVS, |uvs| uvs.map(|[u, v]| (u, v))),
+                material,
+            )),
+            EntityVariant::Polygon(polygon) => Arc::new(Polygon::new(
+                &polygon
+                    .points
+                    .into_iter()
+                    .map(Point3::from)
+                    .collect::<Vec<_>>(),
+                material,
+            )?),
+            EntityVariant::RegularPolygon(polygon) => {
+                Arc::new(Polygon::new(&polygon.corners(), material)?)
+            }
+            EntityVariant::Mesh(mesh) => {
+                let mut data =
+                    subdivision::subdivide(MeshData::load(&mesh.path)?, mesh.subdivision_levels)?;
+                if mesh.smooth_normals && data.normals.is_empty() {
+                    data.compute_normals();
+                }
+                Arc::new(Mesh::new(data, mesh.scale.unwrap_or(1.0), material)?)
+            }
+            EntityVariant::Heightfield(heightfield) => Arc::new(Mesh::new(
+                heightfield::load(
+                    &heightfield.path,
+                    heightfield.horizontal_scale,
+                    heightfield.height_scale,
+                )?,
+                1.0,
+                material,
+            )?),
+            EntityVariant::BezierPatch(patch) => {
+                let points: [[f64; 3]; 16] = patch
+                    .points
+                    .try_into()
+                    .map_err(|_| "A Bezier patch needs 16 control points")?;
+                Arc::new(BezierPatch::new(points.map(Point3::from), material))
+            }
+            EntityVariant::BezierPatches(patches) => {
+                let scale = patches.scale.unwrap_or(1.0);
+                let mut entities: Vec<Arc<dyn Entity>> = bezier::load(&patches.path)?
+                    .into_iter()
+                    .map(|points| {
+                        Arc::new(BezierPatch::new(
+                            points.map(|p| scale * p),
+                            material.clone(),
+                        )) as Arc<dyn Entity>
+                    })
+                    .collect();
+                Arc::new(BVHNode::new(&mut entities))
+            }
+            EntityVariant::Curves(curves) => Arc::new(Curves::new(
+                &curves
+                    .curves
+                    .iter()
+                    .map(|curve| curve.map(Point3::from))
+                    .collect::<Vec<_>>(),
+                CurveWidth {
+                    root: curves.root_width,
+                    tip: curves.tip_width,
+                    falloff: curves.width_falloff,
+                },
+                curves.segments,
+                material,
             )),
-            EntityVariant::MovingSphere(moving_sphere) => Arc::new(Sphere::moving(
-                Point3::from(moving_sphere.center1),
-                Point3::from(moving_sphere.center2),
-                moving_sphere.radius,
+            EntityVariant::Sdf(sdf) => Arc::new(Sdf::new(sdf.shape.into(), material)),
+            EntityVariant::Metaballs(metaballs) => Arc::new(Metaballs::new(
+                metaballs
+                    .balls
+                    .iter()
+                    .map(|ball| Metaball {
+                        center: Point3::from(ball.center),
+                        radius: ball.radius,
+                        weight: ball.weight,
+                    })
+                    .collect(),
+                metaballs.threshold,
                 material,
             )),
-            EntityVariant::Quad(quad) => Arc::new(Quad::new(
-                Point3::from(quad.q),
-                Vec3::from(quad.u),
-                Vec3::from(quad.v),
+            EntityVariant::PointCloud(point_cloud) => Arc::new(PointCloud::new(
+                point_cloud::load(&point_cloud.path)?,
+                point_cloud.scale.unwrap_or(1.0),
+                point_cloud.radius,
+                point_cloud.shape.into(),
                 material,
             )),
-            EntityVariant::Cuboid(cuboid) => Arc::new(Cuboid::new(
-                Point3::from(cuboid.a),
-                Point3::from(cuboid.b),
+            EntityVariant::Voxels(voxels) => Arc::new(VoxelGrid::new(
+                vox::load(&voxels.path)?,
+                voxels.voxel_size,
                 material,
             )),
-            EntityVariant::ConstantMedium(constant_medium) => Arc::new(ConstantMedium::new(
-                constant_medium.boundary.into(),
-                constant_medium.density,
+            EntityVariant::Cuboid(mut cuboid) => {
+                let (a, b) = (Point3::from(cuboid.a), Point3::from(cuboid.b));
+                if cuboid.faces().iter().all(|face| face.is_none()) {
+                    Arc::new(Cuboid::new(a, b, material))
+                } else {
+                    let mut faces = [(); 6].map(|()| material.clone());
+                    for (built, face) in faces.iter_mut().zip(cuboid.faces_mut()) {
+                        if let Some(face) = face.take() {
+                            *built = wrapping.apply(face.try_into()?);
+                        }
+                    }
+                    Arc::new(Cuboid::with_face_materials(a, b, faces))
+                }
+            }
+            EntityVariant::Atmosphere(atmosphere) => Arc::new(Atmosphere::new(
+                Point3::from(atmosphere.center),
+                atmosphere.radius,
+                atmosphere.density,
+                atmosphere.haze,
+                atmosphere.mie_anisotropy,
+                Vec3::from(atmosphere.sun_direction),
+                Color::from(atmosphere.sun_irradiance),
+            )),
+            EntityVariant::RoundedCuboid(rounded_cuboid) => Arc::new(RoundedCuboid::new(
+                Point3::from(rounded_cuboid.a),
+                Point3::from(rounded_cuboid.b),
+                rounded_cuboid.radius,
                 material,
             )),
+            EntityVariant::ConstantMedium(constant_medium) => {
+                // a medium has no orientation, so placing its boundary places it, and keeps
+                // the boundary in the same space as the excluded ones
+                let boundary = placed(child(0, constant_medium.boundary)?, placement)?;
+                return Ok(restricted(
+                    Arc::new(
+                        ConstantMedium::new(boundary, constant_medium.density, material)
+                            .with_excluded(constant_medium.excluded),
+                    ),
+                    visibility,
+                ));
+            }
+            EntityVariant::Shell(shell) => {
+                Arc::new(Shell::new(child(0, shell.boundary)?, shell.thickness)?)
+            }
             EntityVariant::EntityCluster(entity_cluster) => {
                 let mut cluster = EntityCluster::new();
-                for entity in entity_cluster.children {
-                    cluster.push(entity.into());
+                for (index, entity) in entity_cluster.children.into_iter().enumerate() {
+                    cluster.push(child(index, entity)?);
                 }
                 Arc::new(cluster)
             }
+            EntityVariant::Lod(lod) => lod.select(placement, child)?,
+            EntityVariant::Instance(instance) => named_entity(&instance.entity)?,
         };
 
-        if let Some(rotation) = config.rotation {
-            entity = Arc::new(Rotated::new(entity, Vec3::from(rotation)));
+        Ok(restricted(placed(entity, placement)?, visibility))
+    }
+}
+
+/// Hides `entity` from the kinds of rays `visibility` leaves out.
+fn restricted(entity: Arc<dyn Entity>, visibility: Option<VisibilityConfig>) -> Arc<dyn Entity> {
+    match visibility.map(Visibility::from) {
+        Some(visibility) if visibility != Visibility::default() => {
+            Arc::new(Restricted::new(entity, visibility))
+        }
+        _ => entity,
+    }
+}
+
+impl From<UnitVariant> for Unit {
+    fn from(value: UnitVariant) -> Self {
+        match value {
+            UnitVariant::Meters => Unit::Meters,
+            UnitVariant::Centimeters => Unit::Centimeters,
+            UnitVariant::Millimeters => Unit::Millimeters,
+            UnitVariant::Kilometers => Unit::Kilometers,
+            UnitVariant::Inches => Unit::Inches,
+            UnitVariant::Feet => Unit::Feet,
+        }
+    }
+}
+
+impl From<RendererVariant> for Renderer {
+    fn from(value: RendererVariant) -> Self {
+        match value {
+            RendererVariant::Recursive => Renderer::Recursive,
+            RendererVariant::Wavefront => Renderer::Wavefront,
+        }
+    }
+}
+
+impl From<InterpolationVariant> for Interpolation {
+    fn from(value: InterpolationVariant) -> Self {
+        match value {
+            InterpolationVariant::Linear => Interpolation::Linear,
+            InterpolationVariant::CatmullRom => Interpolation::CatmullRom,
+            InterpolationVariant::Bezier => Interpolation::Bezier,
+        }
+    }
+}
+
+impl From<EasingVariant> for Easing {
+    fn from(value: EasingVariant) -> Self {
+        match value {
+            EasingVariant::Linear => Easing::Linear,
+            EasingVariant::EaseIn => Easing::EaseIn,
+            EasingVariant::EaseOut => Easing::EaseOut,
+            EasingVariant::EaseInOut => Easing::EaseInOut,
+        }
+    }
+}
+
+impl From<KeyframeConfig> for Keyframe {
+    fn from(value: KeyframeConfig) -> Self {
+        Keyframe {
+            frame: value.frame,
+            look_from: Point3::from(value.look_from),
+            look_at: Point3::from(value.look_at),
+        }
+    }
+}
+
+impl From<TileOrderVariant> for TileOrder {
+    fn from(value: TileOrderVariant) -> Self {
+        match value {
+            TileOrderVariant::Scanline => TileOrder::Scanline,
+            TileOrderVariant::Hilbert => TileOrder::Hilbert,
+            TileOrderVariant::Morton => TileOrder::Morton,
+            TileOrderVariant::Spiral => TileOrder::Spiral,
         }
+    }
+}
 
-        if let Some(translation) = config.translation {
-            entity = Arc::new(Translated::new(entity, Vec3::from(translation)));
+impl From<WireframeVariant> for WireframeMode {
+    fn from(value: WireframeVariant) -> Self {
+        match value {
+            WireframeVariant::Overlay => WireframeMode::Overlay,
+            WireframeVariant::Only => WireframeMode::Only,
         }
+    }
+}
 
-        entity
+impl From<FilterVariant> for FilterKind {
+    fn from(value: FilterVariant) -> Self {
+        match value {
+            FilterVariant::Box => FilterKind::Box,
+            FilterVariant::Tent => FilterKind::Tent,
+            FilterVariant::Gaussian => FilterKind::Gaussian,
+            FilterVariant::Mitchell => FilterKind::Mitchell,
+            FilterVariant::BlackmanHarris => FilterKind::BlackmanHarris,
+        }
     }
 }
 
@@ -273,16 +3384,150 @@ impl From<CameraConfig> for Camera {
             value.samples_per_pixel,
             value.max_depth,
             value.vertical_fov,
-            Vec3::from(value.look_from),
-            Vec3::from(value.look_at),
+            Vec3::from(value.look_from.expect("resolved by resolve_framing")),
+            Vec3::from(value.look_at.expect("resolved by resolve_framing")),
             Vec3::from(value.view_up),
             Color::from(value.background),
             value.defocus_angle,
-            value.focus_distance,
+            value.focus_distance.expect("resolved by resolve_framing"),
+            value.renderer.into(),
         )
+        .with_tiling(Tiling {
+            size: value.tile_size.unwrap_or(Tiling::default().size),
+            order: value.tile_order.into(),
+            adaptive: value.adaptive_tiles,
+        })
+        .with_filter(PixelFilter::new(value.filter.into(), value.filter_radius))
+        .with_backface_culling(value.backface_culling)
+        .with_stereo(value.eye_separation)
+        .with_exposure(
+            value.channel_exposure.map_or(Color::default(), Color::from)
+                + Color::new(value.exposure, value.exposure, value.exposure),
+        )
+        .with_backplate(value.backplate.map(Into::into))
+        .with_starfield(value.starfield.map(Into::into))
+        .with_seeding(value.seed.map(|seed| Seeding {
+            seed,
+            frame: value.frame,
+            decorrelate_frames: value.decorrelate_frames,
+        }))
+        .with_wireframe(value.wireframe.map(|mode| {
+            Wireframe {
+                mode: mode.into(),
+                width: value.wireframe_width.unwrap_or(1.0),
+                color: value
+                    .wireframe_color
+                    .map_or(Color::new(1.0, 0.6, 0.0), Color::from),
+            }
+        }))
+    }
+}
+
+impl TryFrom<SectionConfig> for SectionPlane {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: SectionConfig) -> Result<Self, Self::Error> {
+        Ok(SectionPlane::new(
+            Point3::from(value.point),
+            Vec3::from(value.normal),
+            value.cap.map(TryInto::try_into).transpose()?,
+        ))
+    }
+}
+
+impl From<OutputConfig> for Output {
+    fn from(value: OutputConfig) -> Self {
+        Output {
+            variance_heatmap: value.variance_heatmap,
+            preview: value.preview,
+            motion_vectors: value.motion_vectors,
+            cost_heatmap: value.cost_heatmap,
+            exr: value.exr,
+            anaglyph: value.anaglyph,
+            bit_depth: value.bit_depth.into(),
+            color_space: value.color_space.into(),
+            highlight_rolloff: value.highlight_rolloff,
+            // follows the camera's backplate
+            alpha: false,
+        }
+    }
+}
+
+impl From<BitDepthVariant> for BitDepth {
+    fn from(value: BitDepthVariant) -> Self {
+        match value {
+            BitDepthVariant::Eight => BitDepth::Eight,
+            BitDepthVariant::Sixteen => BitDepth::Sixteen,
+            BitDepthVariant::Float => BitDepth::Float,
+        }
     }
 }
 
+impl From<ColorSpaceVariant> for ColorSpace {
+    fn from(value: ColorSpaceVariant) -> Self {
+        match value {
+            ColorSpaceVariant::Srgb => ColorSpace::Srgb,
+            ColorSpaceVariant::Linear => ColorSpace::Linear,
+            ColorSpaceVariant::DisplayP3 => ColorSpace::DisplayP3,
+        }
+    }
+}
+
+impl From<RestirConfig> for RestirSettings {
+    fn from(value: RestirConfig) -> Self {
+        RestirSettings {
+            candidates: value.candidates,
+            spatial_neighbors: value.spatial_neighbors,
+            spatial_radius: value.spatial_radius,
+            max_history: value.max_history,
+        }
+    }
+}
+
+impl From<IrradianceCacheConfig> for IrradianceCache {
+    fn from(value: IrradianceCacheConfig) -> Self {
+        IrradianceCache::new(value.max_error, value.samples)
+    }
+}
+
+impl From<IntegratorConfig> for Integrator {
+    fn from(value: IntegratorConfig) -> Self {
+        Integrator {
+            rr_start_depth: value.rr_start_depth,
+            max_radiance: value.max_radiance,
+            min_bounces: value.min_bounces,
+        }
+    }
+}
+
+/// Lists the scene's entities as they would be built, one per line with its material, whether
+/// it emits light and its bounding box, with the children of clusters and other containers
+/// indented below them. Entities the tag filter drops are listed as excluded.
+pub fn inspect(scene_path: &str, overrides: &Overrides) -> Result<String, Box<dyn Error>> {
+    let (data, _) = get_file_data_and_name(scene_path)?;
+    let scene: Config = toml::from_str(&data).map_err(|e| e.to_string())?;
+    let tags = scene.tags.clone().extended(&overrides.tags);
+    let units = Unit::from(scene.units);
+    let material_override = overrides.material.or(scene.override_material);
+    LOD_VIEW.set(
+        (!scene.camera.auto_frame)
+            .then(|| LodView::new(&scene.camera))
+            .flatten(),
+    );
+    set_named_entities(scene.entities.named, &tags, material_override, units)?;
+    let mut out = String::new();
+    for (index, mut entity) in scene.entity.into_iter().enumerate() {
+        let label = format!("{index}: ");
+        if !tags.allows(&entity.tags) {
+            writeln!(out, "{label}{} excluded by tags", entity.variant.name()).unwrap();
+            continue;
+        }
+        entity.prepare(&tags, material_override, units)?;
+        entity.describe(&label, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
 fn get_file_data_and_name(scene_path: &str) -> Result<(String, String), Box<dyn Error>> {
     let file_data = fs::read_to_string(scene_path)
         .map_err(|e| format!("Failed to read '{}' : {}", scene_path, e))?;
@@ -295,12 +3540,401 @@ fn get_file_data_and_name(scene_path: &str) -> Result<(String, String), Box<dyn
     Ok((file_data, name))
 }
 
-pub fn create(scene_path: &str) -> Result<(BVHNode, Camera, String), Box<dyn Error>> {
-    let (data, name) = get_file_data_and_name(scene_path)?;
-    let scene: Config = toml::from_str(&data).map_err(|e| e.to_string())?;
-    let mut entities: Vec<Arc<dyn Entity>> = scene.entity.into_iter().map(Into::into).collect();
-    let camera = scene.camera.into();
-    let world = BVHNode::new(&mut entities);
+pub struct Scene {
+    pub world: BVHNode,
+    /// Top-level entities that emit light, brightest first.
+    pub lights: Vec<Arc<dyn Entity>>,
+    pub camera: Camera,
+    pub name: String,
+    pub bake: Option<Bake>,
+    pub media_slice: Option<MediaSlice>,
+    pub sprite_sheet: Option<SpriteSheet>,
+    pub texture_bakes: Vec<TextureBake>,
+    /// Kept when watching, for a `reload` to go on from.
+    resident: Option<Resident>,
+}
+
+/// What a reload keeps from the scene before it when only the materials changed.
+struct Resident {
+    geometry: String,
+    /// Each material slot's config as it was last built, to tell which ones were edited.
+    materials: Vec<String>,
+    /// The top-level entities in the order they were built, before the BVH sorted them.
+    entities: Vec<Arc<dyn Entity>>,
+}
+
+// viewing direction used when auto-framing without a `look_from` and `look_at` to take it from
+const DEFAULT_VIEW: [f64; 3] = [0.0, 0.25, 1.0];
+
+/// Fills in the camera's position, target and focus distance, framing `entities` if asked to.
+fn resolve_framing(
+    camera: &mut CameraConfig,
+    entities: &[Arc<dyn Entity>],
+) -> Result<(), Box<dyn Error>> {
+    if camera.auto_frame {
+        let bounds = entities
+            .iter()
+            .map(|entity| entity.bounding_box())
+            .reduce(|a, b| Aabb::enclose(&a, &b))
+            .ok_or("Nothing to auto-frame")?;
+        let min = Point3::new(bounds.x().start, bounds.y().start, bounds.z().start);
+        let max = Point3::new(bounds.x().end, bounds.y().end, bounds.z().end);
+        let center = 0.5 * (min + max);
+        let radius = 0.5 * (max - min).length();
+
+        let view = match (camera.look_from, camera.look_at) {
+            (Some(from), Some(to)) if Vec3::from(from) != Vec3::from(to) => {
+                (Vec3::from(from) - Vec3::from(to)).unit()
+            }
+            _ => Vec3::from(DEFAULT_VIEW).unit(),
+        };
+        // fit the bounding sphere inside the narrower of the two fields of view
+        let half_vertical = (camera.vertical_fov / 2.0).to_radians();
+        let half_horizontal = (camera.aspect_ratio * half_vertical.tan()).atan();
+        let distance = radius / half_vertical.min(half_horizontal).sin();
+
+        let look_from = center + distance * view;
+        camera.look_from = Some([look_from.x(), look_from.y(), look_from.z()]);
+        camera.look_at = Some([center.x(), center.y(), center.z()]);
+        camera.focus_distance = Some(distance);
+        return Ok(());
+    }
+
+    let (Some(look_from), Some(look_at)) = (camera.look_from, camera.look_at) else {
+        return Err("Camera needs look_from and look_at unless auto_frame is set".into());
+    };
+    camera.focus_distance = Some(
+        camera
+            .focus_distance
+            .unwrap_or_else(|| (Vec3::from(look_from) - Vec3::from(look_at)).length()),
+    );
+    Ok(())
+}
+
+/// Loads the scene at `scene_path`, with `overrides` taking precedence over its settings.
+pub fn create(scene_path: &str, overrides: &Overrides) -> Result<Scene, Box<dyn Error>> {
+    build(scene_path, overrides, None)
+}
+
+/// Builds the scene file again after it changed. If only its materials did, the ones edited
+/// are swapped into their slots and `previous`'s entities and BVH are kept; anything else
+/// rebuilds the whole scene.
+pub fn reload(
+    scene_path: &str,
+    overrides: &Overrides,
+    previous: &Scene,
+) -> Result<Scene, Box<dyn Error>> {
+    build(scene_path, overrides, Some(previous))
+}
+
+fn build(
+    scene_path: &str,
+    overrides: &Overrides,
+    previous: Option<&Scene>,
+) -> Result<Scene, Box<dyn Error>> {
+    let (data, mut name) = get_file_data_and_name(scene_path)?;
+    let mut scene: Config = toml::from_str(&data).map_err(|e| e.to_string())?;
+    if overrides.reference {
+        scene.make_reference();
+        name.push_str("_reference");
+        status!("Reference render: outputs are written as '{name}'");
+    }
+    let tags = scene.tags.clone().extended(&overrides.tags);
+    // entities are dropped before they're built, so excluded props never load their textures
+    let (kept, entities): (Vec<usize>, Vec<EntityConfig>) = std::mem::take(&mut scene.entity)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, entity)| tags.allows(&entity.tags))
+        .unzip();
+    scene.entity = entities;
+    // entity indices in the file go to the span of built entities each kept one becomes
+    let spans: Vec<Range<usize>> = scene
+        .entity
+        .iter()
+        .scan(0, |start, entity| {
+            let span = *start..*start + entity.entity_count();
+            *start = span.end;
+            Some(span)
+        })
+        .collect();
+    let span = |index: usize| {
+        let kept_index = kept.iter().position(|&i| i == index)?;
+        Some(spans[kept_index].clone())
+    };
+    let frame_spans = match &scene.camera.frame_entity {
+        Some(target) => {
+            let spans: Vec<Range<usize>> = scene
+                .entity
+                .iter()
+                .zip(&spans)
+                .filter(|(entity, _)| {
+                    entity.name.as_ref() == Some(target) || entity.tags.contains(target)
+                })
+                .map(|(_, span)| span.clone())
+                .collect();
+            if spans.is_empty() {
+                return Err(format!(
+                    "No entity to frame is named or tagged '{target}', or all are excluded by their tags"
+                )
+                .into());
+            }
+            Some(spans)
+        }
+        None => None,
+    };
+    let bake_target = match &scene.bake {
+        Some(bake) => {
+            let index = bake.entity;
+            let span = span(index).ok_or_else(|| {
+                format!("Bake target {index} is not an entity index or is excluded by its tags")
+            })?;
+            if span.len() != 1 {
+                return Err(format!("Bake target {index} is a quad grid, not one entity").into());
+            }
+            Some(span.start)
+        }
+        None => None,
+    };
+    if let Some(path) = scene.camera.path.take() {
+        let path = CameraPath::new(
+            path.keyframes.into_iter().map(Into::into).collect(),
+            path.interpolation.into(),
+            path.easing.into(),
+        )
+        .ok_or("Camera path needs at least one keyframe")?;
+        let (look_from, look_at) = path.at(scene.camera.frame as f64);
+        scene.camera.look_from = Some([look_from.x(), look_from.y(), look_from.z()]);
+        scene.camera.look_at = Some([look_at.x(), look_at.y(), look_at.z()]);
+    }
+    let units = Unit::from(scene.units);
+    let material_override = overrides.material.or(scene.override_material);
+    for entity in &mut scene.entity {
+        entity.prepare(&tags, material_override, units)?;
+    }
+    resolve_light_links(&mut scene.entity, &mut scene.entities.named)?;
+    let mut materials = Vec::new();
+    if overrides.watch {
+        scene.for_each_material(&mut |material| {
+            material.slot = Some(materials.len());
+            materials.push(format!("{material:?}"));
+        });
+    }
+    let geometry = overrides.watch.then(|| scene.geometry_key());
+    let kept = previous.and_then(|previous| {
+        let resident = previous.resident.as_ref()?;
+        (Some(&resident.geometry) == geometry.as_ref()).then_some((resident, &previous.world))
+    });
+    if let Some((resident, _)) = kept {
+        // building an edited material puts it in its slot for the kept entities to pick up
+        let mut edited = 0;
+        let mut failed = None;
+        scene.for_each_material(&mut |material| {
+            let slot = material.slot.expect("watched scenes slot every material");
+            if resident.materials.get(slot) != Some(&materials[slot]) {
+                if let Err(e) = Arc::<dyn Material>::try_from(material.clone()) {
+                    failed.get_or_insert(e);
+                }
+                edited += 1;
+            }
+        });
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        status!("Reloaded {edited} edited materials, keeping the geometry");
+    }
+    set_named_entities(
+        std::mem::take(&mut scene.entities.named),
+        &tags,
+        material_override,
+        units,
+    )?;
+    resolve_media_priorities(&mut scene.entity)?;
+    // auto-framing moves the camera only once entities are built, too late to pick levels by
+    LOD_VIEW.set(
+        (!scene.camera.auto_frame)
+            .then(|| LodView::new(&scene.camera))
+            .flatten(),
+    );
+    let mut entities: Vec<Arc<dyn Entity>> = match kept {
+        Some((resident, _)) => resident.entities.clone(),
+        None => scene
+            .entity
+            .into_iter()
+            .map(EntityConfig::into_entities)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect(),
+    };
+    let resident = geometry.map(|geometry| Resident {
+        geometry,
+        materials,
+        entities: entities.clone(),
+    });
+    let clipping = Clipping {
+        near: scene.camera.near_clip,
+        far: scene.camera.far_clip,
+        sections: scene
+            .section
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+    };
+    let mut integrator = scene.integrator;
+    let irradiance_cache = integrator.irradiance_cache.take();
+    let restir = integrator.restir.take();
+    let toon = integrator.toon.take();
+    let path_guiding = integrator.path_guiding;
+    let framed = frame_spans.map(|spans| {
+        spans
+            .into_iter()
+            .flat_map(|span| entities[span].iter().cloned())
+            .collect::<Vec<_>>()
+    });
+    resolve_framing(&mut scene.camera, framed.as_deref().unwrap_or(&entities))?;
+    let sprite_sheet = scene
+        .sprite_sheet
+        .map(|sprite_sheet| sprite_sheet.build(&scene.camera))
+        .transpose()?;
+    let focus_map = match &scene.camera.focus_map {
+        Some(path) => {
+            let focus_distance = scene.camera.focus_distance.unwrap_or_default();
+            let [near, far] = scene
+                .camera
+                .focus_range
+                .unwrap_or([0.5 * focus_distance, 2.0 * focus_distance]);
+            Some(FocusMap::load(path, near, far)?)
+        }
+        None => None,
+    };
+    let mut output = Output::from(scene.output);
+    output.bit_depth = overrides.bit_depth.unwrap_or(output.bit_depth);
+    output.color_space = overrides.color_space.unwrap_or(output.color_space);
+    output.alpha = matches!(scene.camera.backplate, Some(BackplateVariant::Transparent));
+    let environment = match &scene.camera.environment {
+        Some(environment) => Some(Arc::new(Environment::load(
+            &environment.image_path,
+            environment.intensity,
+        )?)),
+        None => None,
+    };
+    let sun = scene.camera.sun.take().map(Sun::from);
+    let lens_settings = scene.camera.lens.take().map(|lens| {
+        let focus_distance = scene.camera.focus_distance.unwrap_or_default();
+        (lens, focus_distance, scene.camera.vertical_fov)
+    });
+    let aperture = match &scene.camera.bokeh_image {
+        Some(path) => Some(Aperture::load(path)?),
+        None => None,
+    };
+    let camera = Camera::from(scene.camera)
+        .with_focus_map(focus_map)
+        .with_aperture(aperture)
+        .with_environment(environment.clone())
+        .with_sun(sun)
+        .with_clipping(clipping)
+        .with_output(output)
+        .with_irradiance_cache(irradiance_cache.map(Into::into))
+        .with_integrator(integrator.into());
+    let lens = match lens_settings {
+        Some((lens, focus_distance, vertical_fov)) => Some(Lens::new(
+            lens.preset.into(),
+            lens.focal_length,
+            lens.f_stop,
+            focus_distance,
+            vertical_fov,
+            camera.image_width() as f64 / camera.image_height() as f64,
+        )?),
+        None => None,
+    };
+    let camera = camera.with_lens(lens);
+
+    let mut lights: Vec<Arc<dyn Entity>> = entities
+        .iter()
+        .filter(|entity| entity.emitted_power().luminance() > 0.0)
+        .cloned()
+        .collect();
+    lights.sort_by(|a, b| {
+        b.emitted_power()
+            .luminance()
+            .total_cmp(&a.emitted_power().luminance())
+    });
+
+    let light_sampler = LightSampler::new(&lights, environment, sun).map(Arc::new);
+    let restir = restir.zip(light_sampler.clone()).map(|(restir, sampler)| {
+        Restir::new(
+            restir.into(),
+            sampler,
+            camera.image_width(),
+            camera.image_height(),
+        )
+    });
+    let camera = camera.with_restir(restir).with_light_sampler(light_sampler);
+    let camera = match toon {
+        Some(toon) => camera.with_toon(Some(toon.build(&lights, sun)?)),
+        None => camera,
+    };
+
+    let bake = match (scene.bake, bake_target) {
+        (Some(bake), Some(target)) => {
+            let entity = &entities[target];
+            if entity.surface_point(0.5, 0.5).is_none() {
+                return Err(format!("Entity {} has no UV layout to bake into", bake.entity).into());
+            }
+            Some(Bake {
+                entity: entity.clone(),
+                width: bake.resolution[0],
+                height: bake.resolution[1],
+                samples: bake.samples.unwrap_or(camera.samples_per_pixel()),
+                flip_normal: bake.flip_normal,
+            })
+        }
+        _ => None,
+    };
+
+    let world = match kept {
+        Some((_, world)) => world.clone(),
+        None => {
+            let outlines = scene.bounds.map(|bounds| bounds.outlines(&mut entities));
+            let world = BVHNode::new(&mut entities);
+            // outlines get a hierarchy of their own, so the scene's is the one they show
+            match outlines {
+                Some(mut outlines) => BVHNode::new(&mut [
+                    Arc::new(world) as Arc<dyn Entity>,
+                    Arc::new(BVHNode::new(&mut outlines)),
+                ]),
+                None => world,
+            }
+        }
+    };
+    epsilon::set_scene_extent(&world.bounding_box());
+    texture::set_occluders(Arc::new(world.clone()));
+
+    let memory = MemoryUsage::current();
+    status!("Scene Memory: {memory}");
+    if let Some(budget) = scene.memory_budget {
+        if memory.total_mb() > budget {
+            eprintln!(
+                "Warning: scene uses {:.1} MB, over the {budget:.1} MB memory budget",
+                memory.total_mb()
+            );
+        }
+    }
+    let camera = camera.with_path_guide(path_guiding.then(|| PathGuide::new(world.bounding_box())));
 
-    Ok((world, camera, name))
+    Ok(Scene {
+        world,
+        lights,
+        camera,
+        name,
+        bake,
+        media_slice: scene.media_slice.map(Into::into),
+        sprite_sheet,
+        texture_bakes: scene
+            .texture_bake
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+        resident,
+    })
 }
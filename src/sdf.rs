@@ -0,0 +1,215 @@
+use crate::{
+    aabb::Aabb,
+    counters::{self, Test},
+    entity::{Entity, HitRecord},
+    epsilon,
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+use std::sync::Arc;
+
+// marching steps before a ray is taken to have missed, e.g. when grazing the surface
+const MAX_STEPS: u32 = 256;
+// distance counted as touching the surface, as a fraction of the scene epsilon
+const HIT_FRACTION: f64 = 0.1;
+
+/// Shape given by its signed distance function, negative inside. The combinations blend or
+/// carve their operands, which is awkward with explicit surfaces but just a `min` or `max` of
+/// distances here.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Sphere {
+        center: Point3,
+        radius: f64,
+    },
+    Box {
+        center: Point3,
+        half_size: Vec3,
+        /// Radius the edges and corners are rounded off with, taken out of `half_size`.
+        rounding: f64,
+    },
+    /// Ring around `center` in the xz plane.
+    Torus {
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Union(Box<Shape>, Box<Shape>),
+    /// Union whose seam is filleted over about `smoothness`.
+    SmoothUnion {
+        a: Box<Shape>,
+        b: Box<Shape>,
+        smoothness: f64,
+    },
+    Intersection(Box<Shape>, Box<Shape>),
+    /// `a` with `b` cut out of it.
+    Subtraction(Box<Shape>, Box<Shape>),
+}
+
+impl Shape {
+    /// Signed distance from `p` to the surface. For the combinations it is only a lower bound
+    /// on the true distance, which is all sphere tracing needs.
+    pub fn distance(&self, p: Point3) -> f64 {
+        match self {
+            Shape::Sphere { center, radius } => (p - *center).length() - radius,
+            Shape::Box {
+                center,
+                half_size,
+                rounding,
+            } => {
+                let d = p - *center;
+                let inset = |d: f64, half: f64| d.abs() - (half - rounding);
+                let q = Vec3::new(
+                    inset(d.x(), half_size.x()),
+                    inset(d.y(), half_size.y()),
+                    inset(d.z(), half_size.z()),
+                );
+                let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+                let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+                outside + inside - rounding
+            }
+            Shape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let d = p - *center;
+                let ring = (d.x().powi(2) + d.z().powi(2)).sqrt() - major_radius;
+                (ring.powi(2) + d.y().powi(2)).sqrt() - minor_radius
+            }
+            Shape::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Shape::SmoothUnion { a, b, smoothness } => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                // polynomial smooth minimum
+                let h = (smoothness - (da - db).abs()).max(0.0) / smoothness;
+                da.min(db) - 0.25 * h * h * smoothness
+            }
+            Shape::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Shape::Subtraction(a, b) => a.distance(p).max(-b.distance(p)),
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let around =
+            |center: Point3, extent: Vec3| Aabb::new_from_points(center - extent, center + extent);
+        match self {
+            Shape::Sphere { center, radius } => {
+                around(*center, Vec3::new(*radius, *radius, *radius))
+            }
+            Shape::Box {
+                center, half_size, ..
+            } => around(*center, *half_size),
+            Shape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                around(*center, Vec3::new(outer, *minor_radius, outer))
+            }
+            Shape::Union(a, b) => Aabb::enclose(&a.bounding_box(), &b.bounding_box()),
+            Shape::SmoothUnion { a, b, smoothness } => {
+                // the fillet bulges out by at most a quarter of the smoothness
+                let bulge = 0.25 * smoothness;
+                let b_box = Aabb::enclose(&a.bounding_box(), &b.bounding_box());
+                Aabb::new_from_points(
+                    Point3::new(b_box.x().start, b_box.y().start, b_box.z().start)
+                        - Vec3::new(bulge, bulge, bulge),
+                    Point3::new(b_box.x().end, b_box.y().end, b_box.z().end)
+                        + Vec3::new(bulge, bulge, bulge),
+                )
+            }
+            Shape::Intersection(a, b) => {
+                let (a, b) = (a.bounding_box(), b.bounding_box());
+                let overlap = |a: Interval, b: Interval| {
+                    Interval::new(a.start.max(b.start), a.end.min(b.end))
+                };
+                Aabb::new(
+                    overlap(a.x(), b.x()),
+                    overlap(a.y(), b.y()),
+                    overlap(a.z(), b.z()),
+                )
+            }
+            Shape::Subtraction(a, _) => a.bounding_box(),
+        }
+    }
+}
+
+/// Surface of a signed distance field, found by sphere tracing: stepping along the ray by the
+/// distance to the surface, which can never overshoot it.
+#[derive(Debug, Clone)]
+pub struct Sdf {
+    shape: Shape,
+    material: Arc<dyn Material>,
+    bounding_box: Aabb,
+}
+
+impl Sdf {
+    pub fn new(shape: Shape, material: Arc<dyn Material>) -> Self {
+        let bounding_box = shape.bounding_box();
+        Self {
+            shape,
+            material,
+            bounding_box,
+        }
+    }
+
+    /// Outward normal at `p`, the distance's gradient estimated by central differences.
+    fn normal(&self, p: Point3, step: f64) -> Vec3 {
+        let gradient = |axis: Vec3| {
+            self.shape.distance(p + step * axis) - self.shape.distance(p - step * axis)
+        };
+        Vec3::new(
+            gradient(Vec3::new(1.0, 0.0, 0.0)),
+            gradient(Vec3::new(0.0, 1.0, 0.0)),
+            gradient(Vec3::new(0.0, 0.0, 1.0)),
+        )
+        .unit()
+    }
+}
+
+impl Entity for Sdf {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        counters::count(Test::Sdf);
+        let (start, end) = self.bounding_box.hit(ray, time_interval)?;
+        let speed = ray.direction().length();
+        let epsilon = epsilon::scene_epsilon();
+        let threshold = HIT_FRACTION * epsilon;
+
+        // marching on the absolute distance finds the surface from inside as well, for rays
+        // refracted into a glass shape
+        let mut time = start;
+        if self.shape.distance(ray.at(time)).abs() < threshold {
+            // already on the surface the ray is leaving
+            time += epsilon / speed;
+        }
+        for _ in 0..MAX_STEPS {
+            if time > end {
+                return None;
+            }
+            let point = ray.at(time);
+            let distance = self.shape.distance(point).abs();
+            if distance < threshold {
+                let normal = self.normal(point, epsilon);
+                return Some(HitRecord::new(
+                    point,
+                    ray,
+                    normal,
+                    time,
+                    0.0,
+                    0.0,
+                    &*self.material,
+                ));
+            }
+            time += distance / speed;
+        }
+        None
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
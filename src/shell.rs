@@ -0,0 +1,79 @@
+use std::{error::Error, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    instance::{Scaled, Translated},
+    interval::Interval,
+    ray::Ray,
+    vec3::{Color, Point3, Vec3},
+};
+
+/// Thin-walled version of a closed entity, such as a glass or a bowl before it is cut open:
+/// the entity itself is the outer wall, and a copy shrunk about the center of its bounding box
+/// is the inner one, facing inwards so light crossing it leaves the wall. The shrinking takes
+/// `thickness` off each side of the bounding box, which is exact for spheres and boxes and an
+/// approximation for other shapes.
+#[derive(Debug, Clone)]
+pub struct Shell {
+    outer: Arc<dyn Entity>,
+    inner: Arc<dyn Entity>,
+}
+
+impl Shell {
+    /// Fails unless `thickness` is positive and less than half the entity's bounding box on
+    /// every axis, which also rules out flat entities.
+    pub fn new(entity: Arc<dyn Entity>, thickness: f64) -> Result<Self, Box<dyn Error>> {
+        if thickness <= 0.0 {
+            return Err("Shell thickness must be positive".into());
+        }
+        let b_box = entity.bounding_box();
+        let (x, y, z) = (b_box.x(), b_box.y(), b_box.z());
+        let center = 0.5 * Point3::new(x.start + x.end, y.start + y.end, z.start + z.end);
+        let shrink = |size: f64| (size - 2.0 * thickness) / size;
+        let scale = Vec3::new(shrink(x.size()), shrink(y.size()), shrink(z.size()));
+        // also false for the NaN of a flat side
+        if !(scale.x() > 0.0 && scale.y() > 0.0 && scale.z() > 0.0) {
+            return Err(format!(
+                "Shell thickness {thickness} is at least half the size of the entity it hollows out"
+            )
+            .into());
+        }
+        let scaled = Scaled::new(Arc::new(Translated::new(entity.clone(), -center)), scale)
+            .ok_or("Shell's inner wall is flat")?;
+        let inner = Arc::new(Translated::new(Arc::new(scaled), center));
+        Ok(Self {
+            outer: entity,
+            inner,
+        })
+    }
+}
+
+impl Entity for Shell {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let outer = self.outer.hit(ray, time_interval);
+        let closest = outer.as_ref().map_or(time_interval.end, |hit| hit.time);
+        let Some(mut inner) = self
+            .inner
+            .hit(ray, Interval::new(time_interval.start, closest))
+        else {
+            return outer;
+        };
+        // the wall lies outside the inner surface, so its sides swap
+        inner.front = !inner.front;
+        Some(inner)
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.outer.bounding_box()
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        self.outer.sample_surface()
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.outer.emitted_power()
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
     aabb::Aabb,
+    counters::{self, Test},
     entity::{Entity, HitRecord},
     interval::Interval,
     material::Material,
     ray::Ray,
-    vec3::{Point3, Vec3},
+    vec3::{Color, Point3, Vec3},
+};
+use std::{
+    f64::consts::{PI, TAU},
+    sync::Arc,
 };
-use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Sphere {
@@ -16,6 +20,11 @@ pub struct Sphere {
     is_moving: bool,
     center_vec: Vec3,
     bounding_box: Aabb,
+    /// Part of the surface kept, as `v` times pi, from the bottom pole.
+    theta_range: Interval,
+    /// Part of the surface kept, as `u` times 2 pi, possibly wrapping past 2 pi.
+    phi_range: Interval,
+    is_partial: bool,
 }
 
 impl Sphere {
@@ -29,6 +38,9 @@ impl Sphere {
             is_moving: false,
             center_vec: Vec3::default(),
             bounding_box: b_box,
+            theta_range: Interval::new(0.0, PI),
+            phi_range: Interval::new(0.0, TAU),
+            is_partial: false,
         }
     }
 
@@ -49,7 +61,39 @@ impl Sphere {
             is_moving: true,
             center_vec: center2 - center1,
             bounding_box: b_box,
+            theta_range: Interval::new(0.0, PI),
+            phi_range: Interval::new(0.0, TAU),
+            is_partial: false,
+        }
+    }
+
+    /// Keeps only the band of the surface between polar angles `theta`, measured from the top
+    /// pole, and the wedge between azimuths `phi`, measured like the `u` texture coordinate, so
+    /// caps, bowls and domes need no CSG. Angles are in radians, and `phi` may wrap past 2 pi.
+    /// The bounding box stays the whole sphere's.
+    pub fn with_angular_range(mut self, theta: Interval, phi: Interval) -> Self {
+        let theta = Interval::new(theta.start.max(0.0), theta.end.min(PI));
+        assert!(!theta.is_empty(), "Sphere's theta range is empty");
+        assert!(!phi.is_empty(), "Sphere's phi range is empty");
+        self.theta_range = Interval::new(PI - theta.end, PI - theta.start);
+        self.phi_range = Interval::new(phi.start, phi.start + phi.size().min(TAU));
+        self.is_partial = self.theta_range.size() < PI || self.phi_range.size() < TAU;
+        self
+    }
+
+    fn area(&self) -> f64 {
+        if !self.is_partial {
+            return 4.0 * PI * self.radius * self.radius;
         }
+        let band = self.theta_range.start.cos() - self.theta_range.end.cos();
+        self.radius * self.radius * self.phi_range.size() * band
+    }
+
+    /// Whether the point with texture coordinates (u, v) is on the kept part of the surface.
+    fn covers(&self, u: f64, v: f64) -> bool {
+        !self.is_partial
+            || (self.theta_range.contains(v * PI)
+                && (u * TAU - self.phi_range.start).rem_euclid(TAU) <= self.phi_range.size())
     }
 
     #[inline]
@@ -59,15 +103,27 @@ impl Sphere {
 
     fn get_uv(p: &Point3) -> (f64, f64) {
         let theta = (-p.y()).acos();
-        let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+        let phi = (-p.z()).atan2(p.x()) + PI;
         let u = phi * 0.5 * std::f64::consts::FRAC_1_PI;
         let v = theta * std::f64::consts::FRAC_1_PI;
         (u, v)
     }
+
+    /// Inverse of `get_uv`.
+    fn surface_normal(u: f64, v: f64) -> Vec3 {
+        let theta = v * PI;
+        let phi = u * TAU;
+        Vec3::new(
+            -theta.sin() * phi.cos(),
+            -theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
 }
 
 impl Entity for Sphere {
-    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        counters::count(Test::Sphere);
         let center = if self.is_moving {
             self.sphere_center(*ray.time())
         } else {
@@ -85,31 +141,67 @@ impl Entity for Sphere {
 
         let sqrt_d = discriminant.sqrt();
         let inv_a = 1.0 / a;
-        let mut root = (half_b - sqrt_d) * inv_a;
-
-        if !time_interval.surrounds(root) {
-            root = (half_b + sqrt_d) * inv_a;
-            if !time_interval.surrounds(root) {
-                return None;
-            }
-        }
+        // the far side shows through where the near one is cut away
+        let (root, outward_normal, (u, v)) = [half_b - sqrt_d, half_b + sqrt_d]
+            .into_iter()
+            .map(|root| root * inv_a)
+            .filter(|&root| time_interval.surrounds(root))
+            .map(|root| {
+                let outward_normal = (ray.at(root) - center) / self.radius;
+                (root, outward_normal, Self::get_uv(&outward_normal))
+            })
+            .find(|&(_, _, (u, v))| self.covers(u, v))?;
 
         let hit_point = ray.at(root);
-        let outward_normal = (hit_point - center) / self.radius;
-        let (u, v) = Self::get_uv(&outward_normal);
-        Some(HitRecord::new(
-            hit_point,
-            ray,
-            outward_normal,
-            root,
-            u,
-            v,
-            &*self.material,
-        ))
+        let mut hit_record =
+            HitRecord::new(hit_point, ray, outward_normal, root, u, v, &*self.material);
+        hit_record.velocity = self.center_vec;
+        // direction of increasing u (longitude) around the polar axis
+        hit_record.set_tangent(Vec3::new(outward_normal.z(), 0.0, -outward_normal.x()));
+        let offset = hit_point - center;
+        let rho = (offset.x().powi(2) + offset.z().powi(2)).sqrt();
+        hit_record.dp_du = 2.0 * PI * Vec3::new(offset.z(), 0.0, -offset.x());
+        if rho > 1e-9 {
+            let cot = -offset.y() / rho;
+            hit_record.dp_dv = PI * Vec3::new(cot * offset.x(), rho, cot * offset.z());
+        }
+        Some(hit_record)
     }
 
     #[inline]
     fn bounding_box(&self) -> Aabb {
         self.bounding_box
     }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let (normal, u, v) = if self.is_partial {
+            // area is uniform in the cosine of theta and in phi
+            let (low, high) = (self.theta_range.end.cos(), self.theta_range.start.cos());
+            let theta = (low + fastrand::f64() * (high - low))
+                .clamp(-1.0, 1.0)
+                .acos();
+            let phi = self.phi_range.start + fastrand::f64() * self.phi_range.size();
+            let (u, v) = (phi.rem_euclid(TAU) / TAU, theta / PI);
+            (Self::surface_normal(u, v), u, v)
+        } else {
+            let normal = Vec3::random_unit_vector();
+            let (u, v) = Self::get_uv(&normal);
+            (normal, u, v)
+        };
+        let point = self.center1 + self.radius * normal;
+        let hit_record = HitRecord::raw(point, normal, 0.0, true, u, v, &*self.material);
+        Some((hit_record, self.area()))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        if !self.covers(u, v) {
+            return None;
+        }
+        let normal = Self::surface_normal(u, v);
+        Some((self.center1 + self.radius * normal, normal))
+    }
+
+    fn emitted_power(&self) -> Color {
+        PI * self.area() * self.material.emitted_power()
+    }
 }
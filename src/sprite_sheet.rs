@@ -0,0 +1,65 @@
+use crate::{
+    bvh::BVHNode,
+    camera::Camera,
+    film::Film,
+    output,
+    progress::status,
+    vec3::{Point3, Vec3},
+};
+use std::{error::Error, time::Instant};
+
+/// Renders the scene from evenly spaced directions around a pivot, such as the 8 or 16 facings
+/// of a 2D game sprite, and tiles the frames into one image. The first frame is the camera's
+/// own view; each next one orbits the camera further counter-clockwise about `axis`, which
+/// turns the subject clockwise in the frames.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    pub directions: u32,
+    /// Frames per row of the sheet.
+    pub columns: u32,
+    pub pivot: Point3,
+    pub axis: Vec3,
+}
+
+impl SpriteSheet {
+    pub fn render(
+        &self,
+        camera: &Camera,
+        world: &BVHNode,
+        scene_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        // a time budget is shared out evenly, so the last frames aren't left without samples
+        let frame_time = camera
+            .budget()
+            .max_time
+            .map(|max_time| max_time / self.directions);
+        let frames = (0..self.directions)
+            .map(|direction| {
+                status!("Direction {} of {}", direction + 1, self.directions);
+                let angle = 360.0 * direction as f64 / self.directions as f64;
+                let deadline = frame_time.map(|frame_time| Instant::now() + frame_time);
+                camera.orbit(self.pivot, self.axis, angle).render_film(
+                    world,
+                    &format!("{scene_name}_{direction}"),
+                    deadline,
+                )
+            })
+            .collect::<Result<Vec<Film>, _>>()?;
+
+        let sheet = Film::tiled(&frames, self.columns);
+        let path = output::save_image(&sheet, &format!("{scene_name}_sheet"), camera.output())?;
+
+        status!("Finished");
+        status!("Render Time: {:.3}s", start.elapsed().as_secs_f64());
+        status!("Sprite Sheet: {path}");
+        status!(
+            "Frames: {} of {} x {} in {} columns",
+            self.directions,
+            camera.image_width(),
+            camera.image_height(),
+            self.columns
+        );
+        Ok(())
+    }
+}
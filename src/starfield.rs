@@ -0,0 +1,181 @@
+use crate::{
+    blackbody::blackbody,
+    camera::splitmix,
+    vec3::{Color, Vec3},
+};
+
+// star temperatures colors are precomputed for, from red dwarfs to blue giants
+const STAR_KELVIN: [f64; 2] = [3000.0, 12000.0];
+const STAR_COLORS: usize = 16;
+// brightest star as a multiple of `brightness`, cutting off the power law's tail
+const MAX_BRIGHTNESS: f64 = 100.0;
+// tint of the milky way's glow, unresolved stars on the warm side
+const MILKY_WAY_COLOR: [f64; 3] = [1.0, 0.92, 0.8];
+// noise cells across the sky, setting the size of the milky way's clumps
+const MILKY_WAY_SCALE: f64 = 6.0;
+
+/// Band of diffuse glow along a great circle, with a clumpy, uneven brightness.
+#[derive(Debug, Clone, Copy)]
+pub struct MilkyWay {
+    /// Pole of the band's great circle.
+    pub normal: Vec3,
+    /// Angle in degrees from the circle at which the glow has faded to a third.
+    pub width: f64,
+    pub intensity: f64,
+}
+
+/// Procedural night sky: stars scattered over the sky from a seed, looked up per direction, so
+/// they stay sharp at any resolution without an environment image.
+///
+/// The sky is split into cells over the six faces of a cube, each holding at most one star as
+/// a small disk. Star brightness follows a power law, like the real sky, with many faint stars
+/// for every bright one.
+#[derive(Debug, Clone)]
+pub struct Starfield {
+    seed: u64,
+    /// Cells along each edge of a cube face.
+    cells: u32,
+    /// Radiance of the faintest stars' disks.
+    brightness: f64,
+    /// Exponent of the brightness power law; larger leaves fewer bright stars.
+    falloff: f64,
+    /// Angular radius of a star's disk, in radians.
+    radius: f64,
+    cos_radius: f64,
+    colors: Vec<Color>,
+    milky_way: Option<MilkyWay>,
+}
+
+impl Starfield {
+    /// About `count` stars over the whole sky, each `size` degrees across.
+    pub fn new(
+        seed: u64,
+        count: u32,
+        brightness: f64,
+        falloff: f64,
+        size: f64,
+        milky_way: Option<MilkyWay>,
+    ) -> Self {
+        let radius = 0.5 * size.to_radians();
+        // half the cells hold a star
+        let cells = (count as f64 / 3.0).sqrt().ceil().max(1.0) as u32;
+        let colors = (0..STAR_COLORS)
+            .map(|index| {
+                let t = index as f64 / (STAR_COLORS - 1) as f64;
+                blackbody(STAR_KELVIN[0] + t * (STAR_KELVIN[1] - STAR_KELVIN[0]))
+            })
+            .collect();
+        Self {
+            seed,
+            cells,
+            brightness,
+            falloff,
+            radius,
+            cos_radius: radius.cos(),
+            colors,
+            milky_way: milky_way.map(|milky_way| MilkyWay {
+                normal: milky_way.normal.unit(),
+                ..milky_way
+            }),
+        }
+    }
+
+    /// Light arriving along `direction` from the stars and the milky way.
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        let direction = direction.unit();
+        let band = self.milky_way.map_or(0.0, |milky_way| {
+            let latitude = direction.dot(milky_way.normal).asin();
+            (-(latitude / milky_way.width.to_radians()).powi(2)).exp()
+        });
+        let glow = self.milky_way.map_or(Color::default(), |milky_way| {
+            let clumps = self.fbm(MILKY_WAY_SCALE * direction);
+            milky_way.intensity * band * (0.3 + 0.7 * clumps) * Color::from(MILKY_WAY_COLOR)
+        });
+        glow + self.star(direction, band)
+    }
+
+    /// Radiance of the star in the cell `direction` falls in, if it has one and `direction`
+    /// is within its disk. More cells hold a star where the milky way's `band` is strong.
+    fn star(&self, direction: Vec3, band: f64) -> Color {
+        let main = (0..3u8)
+            .max_by(|&i, &j| direction[i].abs().total_cmp(&direction[j].abs()))
+            .expect("three axes");
+        let sign = direction[main].signum();
+        let (s_axis, t_axis) = ((main + 1) % 3, (main + 2) % 3);
+        let face_point = |axis: u8| direction[axis] / direction[main].abs();
+        let cell =
+            |x: f64| (((x + 1.0) * 0.5 * self.cells as f64) as u32).min(self.cells - 1) as u64;
+        let (i, j) = (cell(face_point(s_axis)), cell(face_point(t_axis)));
+        let face = 2 * main as u64 + (sign < 0.0) as u64;
+
+        let mut state = [face, i, j]
+            .into_iter()
+            .fold(self.seed, |state, value| splitmix(state ^ value));
+        let mut random = || {
+            state = splitmix(state);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        if random() >= 0.5 * (1.0 + band) {
+            return Color::default();
+        }
+        // the disk is kept inside its cell, which it can't span more than three radii of
+        let margin = (1.5 * self.radius * self.cells as f64).min(0.45);
+        let mut center = [0.0; 3];
+        center[main as usize] = sign;
+        for (axis, index) in [(s_axis, i), (t_axis, j)] {
+            let within = margin + (1.0 - 2.0 * margin) * random();
+            center[axis as usize] = 2.0 * (index as f64 + within) / self.cells as f64 - 1.0;
+        }
+        if direction.dot(Vec3::from(center).unit()) < self.cos_radius {
+            return Color::default();
+        }
+        let scale = random()
+            .max(MAX_BRIGHTNESS.powf(-self.falloff))
+            .powf(-1.0 / self.falloff);
+        let color = self.colors[(random() * STAR_COLORS as f64) as usize % STAR_COLORS];
+        self.brightness * scale * color
+    }
+
+    /// Smooth noise in [0, 1] summed over a few octaves.
+    fn fbm(&self, point: Vec3) -> f64 {
+        let (mut sum, mut weight, mut total) = (0.0, 1.0, 0.0);
+        let mut point = point;
+        for _ in 0..4 {
+            sum += weight * self.value_noise(point);
+            total += weight;
+            weight *= 0.5;
+            point = 2.0 * point;
+        }
+        sum / total
+    }
+
+    /// Random values at integer lattice points, blended smoothly between them.
+    fn value_noise(&self, point: Vec3) -> f64 {
+        let base = [0, 1, 2].map(|axis: u8| point[axis].floor());
+        let fraction = [0, 1, 2].map(|axis: u8| {
+            let f = point[axis] - base[axis as usize];
+            f * f * (3.0 - 2.0 * f)
+        });
+        let lattice = |corner: [u64; 3]| {
+            let hash = (0..3).fold(!self.seed, |state, axis| {
+                splitmix(state ^ (base[axis] as i64 as u64).wrapping_add(corner[axis]))
+            });
+            (hash >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let mut sum = 0.0;
+        for corner in 0..8u64 {
+            let offset = [corner & 1, (corner >> 1) & 1, corner >> 2];
+            let weight: f64 = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1 {
+                        fraction[axis]
+                    } else {
+                        1.0 - fraction[axis]
+                    }
+                })
+                .product();
+            sum += weight * lattice(offset);
+        }
+        sum
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{mesh::MeshData, vec3::Point3};
+use std::{collections::HashMap, error::Error, fs};
+
+// 80 byte header followed by the triangle count
+const BINARY_HEADER: usize = 84;
+// normal, three vertices and an attribute byte count
+const BINARY_TRIANGLE: usize = 50;
+
+/// Reads an STL file, binary or ASCII. STL repeats shared vertices in every triangle, so
+/// identical positions are merged; the stored normals are ignored in favor of the winding.
+pub fn load(path: &str) -> Result<MeshData, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read '{}' : {}", path, e))?;
+    // ASCII files start with "solid", but so do plenty of binary ones, so trust the size first
+    let binary_count = bytes
+        .get(80..BINARY_HEADER)
+        .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    let mut vertices = Vertices::default();
+    match binary_count {
+        Some(count) if bytes.len() == BINARY_HEADER + count * BINARY_TRIANGLE => {
+            for triangle in bytes[BINARY_HEADER..].chunks_exact(BINARY_TRIANGLE) {
+                let float = |i: usize| {
+                    let offset = 12 + 4 * i;
+                    f32::from_le_bytes(triangle[offset..offset + 4].try_into().unwrap()) as f64
+                };
+                for corner in 0..3 {
+                    let i = 3 * corner;
+                    vertices.push(Point3::new(float(i), float(i + 1), float(i + 2)));
+                }
+            }
+        }
+        _ => {
+            let text = std::str::from_utf8(&bytes)
+                .map_err(|_| format!("'{path}' is neither binary nor ASCII STL"))?;
+            for (number, line) in text.lines().enumerate() {
+                let mut fields = line.split_whitespace();
+                if fields.next() != Some("vertex") {
+                    continue;
+                }
+                let mut coordinate = || fields.next().and_then(|field| field.parse().ok());
+                let (Some(x), Some(y), Some(z)) = (coordinate(), coordinate(), coordinate()) else {
+                    return Err(format!("{path}:{} : Invalid vertex", number + 1).into());
+                };
+                vertices.push(Point3::new(x, y, z));
+            }
+            if vertices.data.indices.len() % 3 != 0 {
+                return Err(format!("'{path}' has a facet without three vertices").into());
+            }
+        }
+    }
+    Ok(vertices.data)
+}
+
+/// Mesh being filled one corner at a time, reusing the index of positions seen before.
+#[derive(Debug, Default)]
+struct Vertices {
+    data: MeshData,
+    seen: HashMap<[u64; 3], u32>,
+}
+
+impl Vertices {
+    fn push(&mut self, position: Point3) {
+        let key = [position.x(), position.y(), position.z()].map(f64::to_bits);
+        let positions = &mut self.data.positions;
+        let index = *self.seen.entry(key).or_insert_with(|| {
+            positions.push(position);
+            positions.len() as u32 - 1
+        });
+        self.data.indices.push(index);
+    }
+}
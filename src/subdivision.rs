@@ -0,0 +1,174 @@
+use crate::{mesh::MeshData, vec3::Vec3};
+use std::{collections::HashMap, error::Error};
+
+/// Smooths a mesh with `levels` rounds of Loop subdivision. Each round splits every triangle
+/// into four at its edges' midpoints and moves the vertices towards a smooth limit surface, so
+/// a low-poly model's silhouette rounds off. Edges with only one face, and edges shared by more
+/// than two, are kept as creases that smooth along their own length; vertices where more than
+/// two of them meet stay put.
+///
+/// Vertex colors are smoothed like the positions, while texture coordinates are split
+/// linearly, keeping their seams. The file's vertex normals no longer fit the new surface and
+/// are dropped. Fails if the smoothed mesh has more vertices than its indices can address.
+pub fn subdivide(mut data: MeshData, levels: u32) -> Result<MeshData, Box<dyn Error>> {
+    for _ in 0..levels {
+        data = subdivide_once(&data)?;
+    }
+    Ok(data)
+}
+
+/// A side of one or more triangles, with the corner across from it in each.
+struct Edge {
+    ends: [u32; 2],
+    opposite: Vec<u32>,
+}
+
+impl Edge {
+    #[inline]
+    fn is_crease(&self) -> bool {
+        self.opposite.len() != 2
+    }
+}
+
+fn subdivide_once(data: &MeshData) -> Result<MeshData, Box<dyn Error>> {
+    let vertex_count = data.positions.len();
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut edge_ids = HashMap::new();
+    // each triangle's sides in turn, from its first corner to its second, second to third and
+    // third to first
+    let mut face_edges = Vec::with_capacity(data.indices.len());
+    for face in data.indices.chunks_exact(3) {
+        for side in 0..3 {
+            let (a, b) = (face[side], face[(side + 1) % 3]);
+            let id = *edge_ids.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                edges.push(Edge {
+                    ends: [a, b],
+                    opposite: Vec::new(),
+                });
+                edges.len() - 1
+            });
+            edges[id].opposite.push(face[(side + 2) % 3]);
+            face_edges.push(id);
+        }
+    }
+
+    if u32::try_from(vertex_count + edges.len()).is_err() {
+        return Err("Mesh has too many vertices after subdivision; use fewer levels".into());
+    }
+
+    let mut neighbors = vec![Vec::new(); vertex_count];
+    let mut crease_neighbors = vec![Vec::new(); vertex_count];
+    for edge in &edges {
+        let [a, b] = edge.ends;
+        neighbors[a as usize].push(b);
+        neighbors[b as usize].push(a);
+        if edge.is_crease() {
+            crease_neighbors[a as usize].push(b);
+            crease_neighbors[b as usize].push(a);
+        }
+    }
+
+    // every new vertex as a weighted sum of old ones: the old vertices moved, then one per edge
+    let moved = (0..vertex_count).map(|vertex| {
+        let creases = &crease_neighbors[vertex];
+        let ring = &neighbors[vertex];
+        match creases.len() {
+            0 if !ring.is_empty() => {
+                let n = ring.len() as f64;
+                // Warren's weights, which match Loop's own for the common valences
+                let beta = if ring.len() == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * n)
+                };
+                let mut stencil = vec![(vertex as u32, 1.0 - n * beta)];
+                stencil.extend(ring.iter().map(|&neighbor| (neighbor, beta)));
+                stencil
+            }
+            2 => vec![
+                (vertex as u32, 0.75),
+                (creases[0], 0.125),
+                (creases[1], 0.125),
+            ],
+            _ => vec![(vertex as u32, 1.0)],
+        }
+    });
+    let split = edges.iter().map(|edge| {
+        let [a, b] = edge.ends;
+        if edge.is_crease() {
+            vec![(a, 0.5), (b, 0.5)]
+        } else {
+            let [c, d] = [edge.opposite[0], edge.opposite[1]];
+            vec![(a, 0.375), (b, 0.375), (c, 0.125), (d, 0.125)]
+        }
+    });
+    let stencils: Vec<Vec<(u32, f64)>> = moved.chain(split).collect();
+    let apply = |values: &[Vec3]| -> Vec<Vec3> {
+        stencils
+            .iter()
+            .map(|stencil| {
+                stencil
+                    .iter()
+                    .map(|&(index, weight)| weight * values[index as usize])
+                    .sum()
+            })
+            .collect()
+    };
+
+    let midpoints: Vec<[u32; 3]> = face_edges
+        .chunks_exact(3)
+        .map(|sides| [0, 1, 2].map(|side| (vertex_count + sides[side]) as u32))
+        .collect();
+    let (uvs, uv_indices) = split_uvs(data);
+    Ok(MeshData {
+        positions: apply(&data.positions),
+        uvs,
+        indices: split_faces(&data.indices, &midpoints),
+        uv_indices,
+        colors: if data.colors.is_empty() {
+            Vec::new()
+        } else {
+            apply(&data.colors)
+        },
+        normals: Vec::new(),
+    })
+}
+
+/// Adds a texture coordinate halfway along each side of the triangles' UV layout, returning
+/// the coordinates and the split triangles' indices into them.
+fn split_uvs(data: &MeshData) -> (Vec<(f64, f64)>, Vec<u32>) {
+    if data.uv_indices.is_empty() {
+        return (data.uvs.clone(), Vec::new());
+    }
+    let mut uvs = data.uvs.clone();
+    let mut uv_ids = HashMap::new();
+    let midpoints: Vec<[u32; 3]> = data
+        .uv_indices
+        .chunks_exact(3)
+        .map(|face| {
+            [0, 1, 2].map(|side| {
+                let (a, b) = (face[side], face[(side + 1) % 3]);
+                *uv_ids.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let ((ua, va), (ub, vb)) = (data.uvs[a as usize], data.uvs[b as usize]);
+                    uvs.push((0.5 * (ua + ub), 0.5 * (va + vb)));
+                    (uvs.len() - 1) as u32
+                })
+            })
+        })
+        .collect();
+    let uv_indices = split_faces(&data.uv_indices, &midpoints);
+    (uvs, uv_indices)
+}
+
+/// Splits each triangle into its three corners' triangles and the one between its sides'
+/// `midpoints`, keeping the winding.
+fn split_faces(indices: &[u32], midpoints: &[[u32; 3]]) -> Vec<u32> {
+    indices
+        .chunks_exact(3)
+        .zip(midpoints)
+        .flat_map(|(face, &[ab, bc, ca])| {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            [a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]
+        })
+        .collect()
+}
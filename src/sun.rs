@@ -0,0 +1,59 @@
+use crate::vec3::{Color, Vec3};
+use std::f64::consts::TAU;
+
+// narrowest disk allowed, keeping the radiance of a near-point sun finite
+const MIN_ANGULAR_DIAMETER: f64 = 0.01;
+
+/// Distant disk light; its angular diameter sets how soft shadows are, the real sun's being
+/// about 0.53 degrees.
+#[derive(Debug, Clone, Copy)]
+pub struct Sun {
+    /// Towards the sun.
+    direction: Vec3,
+    /// Cosine of the disk's angular radius.
+    cos_radius: f64,
+    radiance: Color,
+}
+
+impl Sun {
+    /// `irradiance` is the light falling on a surface facing the sun, so changing the diameter
+    /// only changes the shadows, not the brightness.
+    pub fn new(direction: Vec3, angular_diameter: f64, irradiance: Color) -> Self {
+        let radius = 0.5 * angular_diameter.max(MIN_ANGULAR_DIAMETER).to_radians();
+        let cos_radius = radius.cos();
+        Self {
+            direction: direction.unit(),
+            cos_radius,
+            radiance: irradiance / Self::solid_angle(cos_radius),
+        }
+    }
+
+    fn solid_angle(cos_radius: f64) -> f64 {
+        TAU * (1.0 - cos_radius)
+    }
+
+    /// Radiance seen looking along `direction`, zero off the disk.
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        if direction.unit().dot(self.direction) >= self.cos_radius {
+            self.radiance
+        } else {
+            Color::default()
+        }
+    }
+
+    /// Direction picked uniformly over the disk, with its radiance and probability density over
+    /// solid angle.
+    pub fn sample(&self) -> (Vec3, Color, f64) {
+        let cos_theta = 1.0 - fastrand::f64() * (1.0 - self.cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = TAU * fastrand::f64();
+        let (tangent, bitangent) = self.direction.orthonormal_basis();
+        let direction =
+            cos_theta * self.direction + sin_theta * (phi.cos() * tangent + phi.sin() * bitangent);
+        (
+            direction,
+            self.radiance,
+            1.0 / Self::solid_angle(self.cos_radius),
+        )
+    }
+}
@@ -1,12 +1,106 @@
-use std::sync::Arc;
+use std::{
+    error::Error,
+    sync::{Arc, RwLock},
+};
 
 use crate::{
+    entity::{Entity, HitRecord},
+    epsilon,
+    interval::Interval,
+    memory::{self, Category},
     perlin::Perlin,
-    vec3::{Color, Point3},
+    ray::{Ray, RayKind},
+    vec3::{Color, Point3, Vec3},
 };
 
+/// Everything a texture may look at for one shading point.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadingContext {
+    pub u: f64,
+    pub v: f64,
+    /// World-space hit point.
+    pub point: Point3,
+    /// Hit point in the primitive's own space, before instance transforms.
+    pub object_point: Point3,
+    pub normal: Vec3,
+    /// Change in world-space point per pixel step; zero when the footprint is unknown.
+    pub dp_dx: Vec3,
+    pub dp_dy: Vec3,
+    /// Screen-space derivatives of (u, v); zero when the footprint is unknown.
+    pub du_dx: f64,
+    pub du_dy: f64,
+    pub dv_dx: f64,
+    pub dv_dy: f64,
+    pub vertex_color: Option<Color>,
+    /// Unit direction towards the viewer and the distance to it; zero when unknown.
+    pub view: Vec3,
+    pub distance: f64,
+}
+
+impl ShadingContext {
+    pub fn new(hit_record: &HitRecord) -> Self {
+        let (du_dx, dv_dx) = Self::uv_step(hit_record, hit_record.dp_dx);
+        let (du_dy, dv_dy) = Self::uv_step(hit_record, hit_record.dp_dy);
+        Self {
+            u: hit_record.u,
+            v: hit_record.v,
+            point: hit_record.hit_point,
+            object_point: hit_record.object_point,
+            normal: hit_record.normal,
+            dp_dx: hit_record.dp_dx,
+            dp_dy: hit_record.dp_dy,
+            du_dx,
+            du_dy,
+            dv_dx,
+            dv_dy,
+            vertex_color: hit_record.vertex_color,
+            view: hit_record.view,
+            distance: hit_record.distance,
+        }
+    }
+
+    /// Least-squares (du, dv) such that `du * dp_du + dv * dp_dv` best matches `dp`.
+    fn uv_step(hit_record: &HitRecord, dp: Vec3) -> (f64, f64) {
+        let (a, b) = (hit_record.dp_du, hit_record.dp_dv);
+        let (aa, ab, bb) = (a.dot(a), a.dot(b), b.dot(b));
+        let determinant = aa * bb - ab * ab;
+        if determinant.abs() < 1e-12 {
+            return (0.0, 0.0);
+        }
+        let (ap, bp) = (a.dot(dp), b.dot(dp));
+        (
+            (bb * ap - ab * bp) / determinant,
+            (aa * bp - ab * ap) / determinant,
+        )
+    }
+
+    /// Rough world-space width of the pixel footprint.
+    #[inline]
+    pub fn footprint(&self) -> f64 {
+        self.dp_dx.length().max(self.dp_dy.length())
+    }
+
+    /// Same context with different texture coordinates and no footprint.
+    fn with_uv(&self, u: f64, v: f64) -> Self {
+        Self {
+            u,
+            v,
+            dp_dx: Vec3::default(),
+            dp_dy: Vec3::default(),
+            du_dx: 0.0,
+            du_dy: 0.0,
+            dv_dx: 0.0,
+            dv_dy: 0.0,
+            ..*self
+        }
+    }
+}
+
 pub trait Texture: Send + Sync + std::fmt::Debug {
-    fn color_value(&self, u: f64, v: f64, hit_point: &Point3) -> Color;
+    fn color_value(&self, context: &ShadingContext) -> Color;
+
+    /// Mean color over the texture's domain.
+    fn average_color(&self) -> Color;
 }
 
 #[derive(Debug, Clone, Default)]
@@ -24,7 +118,12 @@ impl Solid {
 
 impl Texture for Solid {
     #[inline]
-    fn color_value(&self, _u: f64, _v: f64, _hit_point: &Point3) -> Color {
+    fn color_value(&self, _context: &ShadingContext) -> Color {
+        self.color
+    }
+
+    #[inline]
+    fn average_color(&self) -> Color {
         self.color
     }
 }
@@ -41,6 +140,31 @@ impl From<[f64; 3]> for Solid {
     }
 }
 
+/// Color stored on the vertices of the mesh being shaded, read from the model file.
+#[derive(Debug, Clone)]
+pub struct VertexColor {
+    /// Used where the surface has no vertex colors.
+    fallback: Arc<dyn Texture>,
+}
+
+impl VertexColor {
+    pub fn new(fallback: Arc<dyn Texture>) -> Self {
+        Self { fallback }
+    }
+}
+
+impl Texture for VertexColor {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        context
+            .vertex_color
+            .unwrap_or_else(|| self.fallback.color_value(context))
+    }
+
+    fn average_color(&self) -> Color {
+        self.fallback.average_color()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Checker {
     odd: Arc<dyn Texture>,
@@ -59,16 +183,29 @@ impl Checker {
 }
 
 impl Texture for Checker {
-    fn color_value(&self, u: f64, v: f64, hit_point: &Point3) -> Color {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        let hit_point = context.point;
         let x_int = (self.inv_scale * hit_point.x()).floor() as i32;
         let y_int = (self.inv_scale * hit_point.y()).floor() as i32;
         let z_int = (self.inv_scale * hit_point.z()).floor() as i32;
-        if (x_int + y_int + z_int) & 1 == 0 {
-            self.even.color_value(u, v, hit_point)
+        let pattern = if (x_int + y_int + z_int) & 1 == 0 {
+            self.even.color_value(context)
+        } else {
+            self.odd.color_value(context)
+        };
+
+        // fade towards the mean once a pixel spans about a whole check, instead of aliasing
+        let blend = (2.0 * context.footprint() * self.inv_scale - 1.0).clamp(0.0, 1.0);
+        if blend > 0.0 {
+            (1.0 - blend) * pattern + blend * self.average_color()
         } else {
-            self.odd.color_value(u, v, hit_point)
+            pattern
         }
     }
+
+    fn average_color(&self) -> Color {
+        0.5 * (self.odd.average_color() + self.even.average_color())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -77,25 +214,36 @@ pub struct ImageTex {
     width: u32,
     height: u32,
     bytes_per_pixel: u8,
+    average: Color,
 }
 
 impl ImageTex {
-    pub fn new(image_path: &str) -> Self {
+    pub fn new(image_path: &str) -> Result<Self, Box<dyn Error>> {
         let img = image::open(image_path)
-            .expect("Failed to open image")
+            .map_err(|e| format!("Failed to open image '{image_path}': {e}"))?
             .to_rgb8();
         let (width, height) = img.dimensions();
         let data = img.into_raw();
+        memory::track(Category::Textures, data.len());
         let bytes_per_pixel = 3;
-        Self {
+        let mut texture = Self {
             data,
             width,
             height,
             bytes_per_pixel,
-        }
+            average: Color::default(),
+        };
+        texture.average = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| texture.get_pixel(x as usize, y as usize))
+            .sum::<Color>()
+            / (width as f64 * height as f64);
+        Ok(texture)
     }
 
     fn get_pixel(&self, x: usize, y: usize) -> Color {
+        let x = x.min(self.width as usize - 1);
+        let y = y.min(self.height as usize - 1);
         let index = x * self.bytes_per_pixel as usize
             + y * self.width as usize * self.bytes_per_pixel as usize;
         let pixel = &self.data[index..index + 3];
@@ -107,11 +255,51 @@ impl ImageTex {
     }
 }
 
+/// Texel at (u, v) of a `width` x `height` image, box-filtered over the shading footprint
+/// when it covers more than one texel.
+pub fn filtered_texel(
+    width: u32,
+    height: u32,
+    context: &ShadingContext,
+    texel: impl Fn(usize, usize) -> Color,
+) -> Color {
+    // cap on texels averaged per axis when the footprint covers many of them
+    const MAX_TAPS: usize = 8;
+
+    let (width, height) = (width as f64, height as f64);
+    let x = context.u * width;
+    let y = (1.0 - context.v) * height;
+    let footprint_x = context.du_dx.abs().max(context.du_dy.abs()) * width;
+    let footprint_y = context.dv_dx.abs().max(context.dv_dy.abs()) * height;
+
+    if footprint_x <= 1.0 && footprint_y <= 1.0 {
+        return texel(x as usize, y as usize);
+    }
+
+    // box-filter the texels under the footprint
+    let taps_x = (footprint_x.ceil() as usize).clamp(1, MAX_TAPS);
+    let taps_y = (footprint_y.ceil() as usize).clamp(1, MAX_TAPS);
+    let mut sum = Color::default();
+    for ty in 0..taps_y {
+        for tx in 0..taps_x {
+            let sx = x + footprint_x * ((tx as f64 + 0.5) / taps_x as f64 - 0.5);
+            let sy = y + footprint_y * ((ty as f64 + 0.5) / taps_y as f64 - 0.5);
+            sum += texel(sx.max(0.0) as usize, sy.max(0.0) as usize);
+        }
+    }
+    sum / (taps_x * taps_y) as f64
+}
+
 impl Texture for ImageTex {
-    fn color_value(&self, u: f64, v: f64, _p: &Point3) -> Color {
-        let i = (u * self.width as f64) as usize;
-        let j = ((1.0 - v) * self.height as f64) as usize;
-        self.get_pixel(i, j)
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        filtered_texel(self.width, self.height, context, |x, y| {
+            self.get_pixel(x, y)
+        })
+    }
+
+    #[inline]
+    fn average_color(&self) -> Color {
+        self.average
     }
 }
 
@@ -129,9 +317,276 @@ impl PerlinTex {
 }
 
 impl Texture for PerlinTex {
-    fn color_value(&self, _u: f64, _v: f64, hit_point: &Point3) -> Color {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        let hit_point = context.point;
         Color::new(0.5, 0.5, 0.5)
             * (1.0
-                + (self.scale * hit_point.z() + 10.0 * self.source.turbulence(*hit_point, 7)).sin())
+                + (self.scale * hit_point.z() + 10.0 * self.source.turbulence(hit_point, 7)).sin())
+    }
+
+    #[inline]
+    fn average_color(&self) -> Color {
+        Color::new(0.5, 0.5, 0.5)
+    }
+}
+
+/// Shading quantity a `Ramp` is driven by.
+#[derive(Debug, Clone, Copy)]
+pub enum RampInput {
+    /// Cosine between the normal and the view direction, 1 facing the viewer and 0 at the
+    /// silhouette.
+    Facing,
+    /// Distance the ray travelled to the hit.
+    Distance,
+    /// World-space normal components, from -1 to 1.
+    NormalX,
+    NormalY,
+    NormalZ,
+}
+
+/// Blends between two textures by a shading quantity rather than by position, for stylized
+/// looks such as rim lighting (`Facing`), depth fades (`Distance`) or normal-tinted surfaces.
+#[derive(Debug, Clone)]
+pub struct Ramp {
+    input: RampInput,
+    /// Input values mapped to the `low` and `high` ends; either order works.
+    start: f64,
+    end: f64,
+    /// Bends the blend, e.g. 5 with `Facing` going from 1 to 0 for a Schlick-like Fresnel rim.
+    exponent: f64,
+    low: Arc<dyn Texture>,
+    high: Arc<dyn Texture>,
+}
+
+impl Ramp {
+    pub fn new(
+        input: RampInput,
+        start: f64,
+        end: f64,
+        exponent: f64,
+        low: Arc<dyn Texture>,
+        high: Arc<dyn Texture>,
+    ) -> Self {
+        Self {
+            input,
+            start,
+            end,
+            exponent,
+            low,
+            high,
+        }
+    }
+}
+
+impl Texture for Ramp {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        let value = match self.input {
+            RampInput::Facing => context.normal.dot(context.view).abs(),
+            RampInput::Distance => context.distance,
+            RampInput::NormalX => context.normal.x(),
+            RampInput::NormalY => context.normal.y(),
+            RampInput::NormalZ => context.normal.z(),
+        };
+        let t = if self.end == self.start {
+            if value >= self.end {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            ((value - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+        }
+        .powf(self.exponent);
+        (1.0 - t) * self.low.color_value(context) + t * self.high.color_value(context)
+    }
+
+    fn average_color(&self) -> Color {
+        0.5 * (self.low.average_color() + self.high.average_color())
+    }
+}
+
+/// Projects a texture along the three object-space axes and blends the projections by the
+/// surface normal, so unwrapped or UV-less geometry can still be image mapped.
+#[derive(Debug, Clone)]
+pub struct Triplanar {
+    texture: Arc<dyn Texture>,
+    inv_scale: f64,
+    sharpness: f64,
+}
+
+impl Triplanar {
+    pub fn new(texture: Arc<dyn Texture>, scale: f64, sharpness: f64) -> Self {
+        Self {
+            texture,
+            inv_scale: 1.0 / scale,
+            sharpness,
+        }
+    }
+}
+
+impl Texture for Triplanar {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        let p = self.inv_scale * context.object_point;
+        let n = context.normal;
+        let weights = Vec3::new(
+            n.x().abs().powf(self.sharpness),
+            n.y().abs().powf(self.sharpness),
+            n.z().abs().powf(self.sharpness),
+        );
+        let total = weights.x() + weights.y() + weights.z();
+        if total == 0.0 {
+            return self.texture.color_value(context);
+        }
+
+        let wrap = |t: f64| t - t.floor();
+        let x_proj = self
+            .texture
+            .color_value(&context.with_uv(wrap(p.z()), wrap(p.y())));
+        let y_proj = self
+            .texture
+            .color_value(&context.with_uv(wrap(p.x()), wrap(p.z())));
+        let z_proj = self
+            .texture
+            .color_value(&context.with_uv(wrap(p.x()), wrap(p.y())));
+
+        (weights.x() * x_proj + weights.y() * y_proj + weights.z() * z_proj) / total
+    }
+
+    #[inline]
+    fn average_color(&self) -> Color {
+        self.texture.average_color()
+    }
+}
+
+/// Projects a texture from a pinhole projector onto whatever it shines on, like a slide
+/// projector or a camera-mapped matte painting. Occlusion is ignored, so surfaces behind the
+/// first one are painted too.
+#[derive(Debug, Clone)]
+pub struct Projected {
+    texture: Arc<dyn Texture>,
+    /// Color of everything outside the projected frame.
+    outside: Arc<dyn Texture>,
+    origin: Point3,
+    forward: Vec3,
+    right: Vec3,
+    up: Vec3,
+    half_height: f64,
+    half_width: f64,
+}
+
+impl Projected {
+    /// `vertical_fov` is in degrees; `aspect_ratio` is the projected frame's width over height.
+    pub fn new(
+        texture: Arc<dyn Texture>,
+        outside: Arc<dyn Texture>,
+        look_from: Point3,
+        look_at: Point3,
+        view_up: Vec3,
+        vertical_fov: f64,
+        aspect_ratio: f64,
+    ) -> Self {
+        let forward = (look_at - look_from).unit();
+        let right = forward.cross(view_up).unit();
+        let up = right.cross(forward);
+        let half_height = (vertical_fov.to_radians() / 2.0).tan();
+        Self {
+            texture,
+            outside,
+            origin: look_from,
+            forward,
+            right,
+            up,
+            half_height,
+            half_width: half_height * aspect_ratio,
+        }
+    }
+}
+
+impl Texture for Projected {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        let offset = context.point - self.origin;
+        let depth = offset.dot(self.forward);
+        if depth <= 0.0 {
+            return self.outside.color_value(context);
+        }
+        let u = 0.5 + 0.5 * offset.dot(self.right) / (depth * self.half_width);
+        let v = 0.5 + 0.5 * offset.dot(self.up) / (depth * self.half_height);
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return self.outside.color_value(context);
+        }
+        self.texture.color_value(&context.with_uv(u, v))
+    }
+
+    #[inline]
+    fn average_color(&self) -> Color {
+        self.texture.average_color()
+    }
+}
+
+// geometry cavity textures probe for occluders, set once the scene is built
+static OCCLUDERS: RwLock<Option<Arc<dyn Entity>>> = RwLock::new(None);
+
+/// Makes `world` the geometry `Cavity` textures look for occluders in.
+pub fn set_occluders(world: Arc<dyn Entity>) {
+    *OCCLUDERS.write().unwrap() = Some(world);
+}
+
+/// Dirt or ambient occlusion mask traced at shading time: short rays from the shading point
+/// measure how much of the hemisphere above it is blocked within `radius`, so crevices and
+/// contact points turn `occluded` and open surfaces stay `exposed`. Each lookup fires only a
+/// few rays and the noise averages out over a pixel's samples.
+#[derive(Debug, Clone)]
+pub struct Cavity {
+    radius: f64,
+    samples: u16,
+    occluded: Arc<dyn Texture>,
+    exposed: Arc<dyn Texture>,
+}
+
+impl Cavity {
+    pub fn new(
+        radius: f64,
+        samples: u16,
+        occluded: Arc<dyn Texture>,
+        exposed: Arc<dyn Texture>,
+    ) -> Self {
+        Self {
+            radius,
+            samples: samples.max(1),
+            occluded,
+            exposed,
+        }
+    }
+
+    /// Fraction of cosine-weighted probes from the shading point blocked within `radius`.
+    fn occlusion(&self, world: &dyn Entity, context: &ShadingContext) -> f64 {
+        let blocked = (0..self.samples)
+            .filter(|_| {
+                let direction = context.normal + Vec3::random_unit_vector();
+                if direction.near_zero() {
+                    return false;
+                }
+                let ray = Ray::new(context.point, direction.unit(), 0.0).with_kind(RayKind::Shadow);
+                let start = epsilon::ray_interval(&ray).start;
+                world.hit(&ray, Interval::new(start, self.radius)).is_some()
+            })
+            .count();
+        blocked as f64 / self.samples as f64
+    }
+}
+
+impl Texture for Cavity {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        let world = OCCLUDERS.read().unwrap().clone();
+        let t = match world {
+            Some(world) => self.occlusion(&*world, context),
+            // nothing to probe before the scene is built
+            None => 0.0,
+        };
+        (1.0 - t) * self.exposed.color_value(context) + t * self.occluded.color_value(context)
+    }
+
+    fn average_color(&self) -> Color {
+        0.5 * (self.exposed.average_color() + self.occluded.average_color())
     }
 }
@@ -0,0 +1,84 @@
+use crate::{
+    output,
+    progress::status,
+    texture::{ShadingContext, Texture},
+    vec3::{Color, Point3, Vec3},
+};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::{error::Error, sync::Arc};
+
+/// Where the texels of a texture bake are looked up.
+#[derive(Debug, Clone, Copy)]
+pub enum BakeDomain {
+    /// The unit square of texture coordinates, for textures that read (u, v).
+    Uv,
+    /// The parallelogram `origin + s * u + t * v` for s, t in [0, 1], for textures that read the
+    /// world position; (s, t) also serve as the texture coordinates.
+    Plane { origin: Point3, u: Vec3, v: Vec3 },
+}
+
+/// Evaluates a texture over a domain and writes it out as an image, so procedural textures
+/// can be used elsewhere.
+#[derive(Debug, Clone)]
+pub struct TextureBake {
+    pub name: String,
+    pub texture: Arc<dyn Texture>,
+    pub width: u32,
+    pub height: u32,
+    pub domain: BakeDomain,
+}
+
+impl TextureBake {
+    /// Shading context at the center of texel (x, y), with a footprint of one texel so
+    /// filtered textures are averaged the way a camera would see them at this resolution.
+    fn context(&self, x: u32, y: u32) -> ShadingContext {
+        let (du, dv) = (1.0 / self.width as f64, -1.0 / self.height as f64);
+        let u = (x as f64 + 0.5) * du;
+        let v = 1.0 + (y as f64 + 0.5) * dv;
+        let (point, normal, dp_dx, dp_dy) = match self.domain {
+            BakeDomain::Uv => (
+                Point3::new(u, v, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(du, 0.0, 0.0),
+                Vec3::new(0.0, dv, 0.0),
+            ),
+            BakeDomain::Plane { origin, u: s, v: t } => {
+                (origin + u * s + v * t, s.cross(t).unit(), du * s, dv * t)
+            }
+        };
+        ShadingContext {
+            u,
+            v,
+            point,
+            object_point: point,
+            normal,
+            dp_dx,
+            dp_dy,
+            du_dx: du,
+            du_dy: 0.0,
+            dv_dx: 0.0,
+            dv_dy: dv,
+            vertex_color: None,
+            // seen head-on, as a flat scan of the texture would be
+            view: normal,
+            distance: 0.0,
+        }
+    }
+
+    /// Writes `<scene_name>_<name>.png`.
+    pub fn render(&self, scene_name: &str) -> Result<(), Box<dyn Error>> {
+        let texels: Vec<Color> = (0..self.width * self.height)
+            .into_par_iter()
+            .map(|index| {
+                let context = self.context(index % self.width, index / self.width);
+                self.texture.color_value(&context)
+            })
+            .collect();
+
+        let path = output::result_path(scene_name, &format!("_{}", self.name), "png")?;
+        let raw: Vec<u8> = texels.iter().copied().flat_map(Vec3::to_rgb8).collect();
+        output::write_png(&path, self.width, self.height, &raw)?;
+        status!("Texture '{}': {path}", self.name);
+        Ok(())
+    }
+}
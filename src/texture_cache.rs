@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use crate::{
+    fnv::Fnv1a,
+    memory::{self, Category},
+    output::OUTPUT_DIR,
+    texture::{filtered_texel, ShadingContext, Texture},
+    vec3::Color,
+};
+
+const MAGIC: &[u8; 8] = b"RTTILES1";
+// magic, width, height and tile size, then the average color
+const HEADER_BYTES: u64 = 8 + 3 * 4 + 3 * 8;
+// independently locked parts of the resident tiles, so threads sampling different tiles don't
+// wait on each other
+const SHARDS: usize = 16;
+
+#[derive(Debug)]
+struct Header {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    average: Color,
+}
+
+impl Header {
+    fn read(file: &mut File) -> Option<Self> {
+        let mut bytes = [0; HEADER_BYTES as usize];
+        file.read_exact(&mut bytes).ok()?;
+        if &bytes[..8] != MAGIC {
+            return None;
+        }
+        let word = |i: usize| u32::from_le_bytes(bytes[8 + 4 * i..12 + 4 * i].try_into().unwrap());
+        let float =
+            |i: usize| f64::from_le_bytes(bytes[20 + 8 * i..28 + 8 * i].try_into().unwrap());
+        Some(Self {
+            width: word(0),
+            height: word(1),
+            tile_size: word(2),
+            average: Color::new(float(0), float(1), float(2)),
+        })
+    }
+
+    fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(MAGIC)?;
+        for word in [self.width, self.height, self.tile_size] {
+            out.write_all(&word.to_le_bytes())?;
+        }
+        for channel in [self.average.x(), self.average.y(), self.average.z()] {
+            out.write_all(&channel.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn tiles_x(&self) -> u32 {
+        self.width.div_ceil(self.tile_size)
+    }
+
+    fn file_bytes(&self) -> u64 {
+        let tiles = self.tiles_x() * self.height.div_ceil(self.tile_size);
+        HEADER_BYTES + tiles as u64 * self.tile_bytes() as u64
+    }
+
+    fn tile_bytes(&self) -> usize {
+        (self.tile_size * self.tile_size * 3) as usize
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    index: usize,
+    tile: Arc<Vec<u8>>,
+    /// Set on every use and cleared as the clock hand passes, which evicts the tile if it's
+    /// still clear on the next pass.
+    used: AtomicBool,
+}
+
+/// Part of the resident tiles, evicted in clock order once `capacity` is reached.
+#[derive(Debug, Default)]
+struct Shard {
+    slots: Vec<Slot>,
+    /// Slot of each resident tile.
+    resident: HashMap<usize, usize>,
+    hand: usize,
+}
+
+impl Shard {
+    fn get(&self, index: usize) -> Option<Arc<Vec<u8>>> {
+        let slot = &self.slots[*self.resident.get(&index)?];
+        slot.used.store(true, Ordering::Relaxed);
+        Some(slot.tile.clone())
+    }
+
+    fn insert(&mut self, index: usize, tile: Arc<Vec<u8>>, capacity: usize) {
+        let slot = Slot {
+            index,
+            tile,
+            used: AtomicBool::new(true),
+        };
+        if self.slots.len() < capacity {
+            self.resident.insert(index, self.slots.len());
+            self.slots.push(slot);
+            return;
+        }
+        while self.slots[self.hand].used.swap(false, Ordering::Relaxed) {
+            self.hand = (self.hand + 1) % self.slots.len();
+        }
+        self.resident.remove(&self.slots[self.hand].index);
+        self.resident.insert(index, self.hand);
+        self.slots[self.hand] = slot;
+        self.hand = (self.hand + 1) % self.slots.len();
+    }
+}
+
+/// Image texture sampled from a tiled copy on disk, keeping only recently used tiles in memory.
+///
+/// The tiled copy is written to the `tiles` folder of the output directory the first time the
+/// image is used (or when the image is newer), which needs one full decode; later loads read
+/// only what they touch.
+#[derive(Debug)]
+pub struct TiledImageTex {
+    header: Header,
+    tiles_path: PathBuf,
+    /// Open handles to the tiled copy, each taken by one read at a time.
+    files: Mutex<Vec<File>>,
+    shards: Vec<RwLock<Shard>>,
+    /// Tiles each shard may keep resident.
+    shard_capacity: usize,
+}
+
+impl TiledImageTex {
+    /// `budget` is the number of bytes of tiles allowed to stay resident.
+    pub fn new(image_path: &str, tile_size: u32, budget: usize) -> Result<Self, Box<dyn Error>> {
+        let tiles_path = Self::tiles_path(image_path)?;
+        let mut file = match Self::open_current(image_path, &tiles_path, tile_size) {
+            Some(file) => file,
+            None => {
+                Self::write_tiles(image_path, &tiles_path, tile_size)
+                    .map_err(|e| format!("Failed to tile image '{image_path}': {e}"))?;
+                File::open(&tiles_path)?
+            }
+        };
+        file.rewind()?;
+        let header = Header::read(&mut file)
+            .ok_or_else(|| format!("Corrupt texture tiles '{}'", tiles_path.display()))?;
+        let capacity = (budget / header.tile_bytes()).max(1);
+        let shard_count = capacity.min(SHARDS);
+        let shard_capacity = capacity / shard_count;
+        memory::track(
+            Category::Textures,
+            shard_count * shard_capacity * header.tile_bytes(),
+        );
+        Ok(Self {
+            header,
+            tiles_path,
+            files: Mutex::new(vec![file]),
+            shards: (0..shard_count).map(|_| RwLock::default()).collect(),
+            shard_capacity,
+        })
+    }
+
+    /// `<output>/tiles/<image name>-<hash of its full path>.tiles`, creating the folder if needed.
+    fn tiles_path(image_path: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let image = Path::new(image_path);
+        let full_path = fs::canonicalize(image)
+            .map_err(|e| format!("Failed to open image '{image_path}': {e}"))?;
+        let mut hash = Fnv1a::new();
+        hash.write(full_path.as_os_str().as_encoded_bytes());
+        let name = image.file_name().unwrap_or_default().to_string_lossy();
+        let folder = Path::new(OUTPUT_DIR).join("tiles");
+        fs::create_dir_all(&folder)?;
+        Ok(folder.join(format!("{name}-{:016x}.tiles", hash.finish())))
+    }
+
+    /// The existing tiled copy, if it's complete, at least as new as the image and uses
+    /// `tile_size`.
+    fn open_current(image_path: &str, tiles_path: &Path, tile_size: u32) -> Option<File> {
+        let image_modified = fs::metadata(image_path).ok()?.modified().ok()?;
+        let tiles = fs::metadata(tiles_path).ok()?;
+        if tiles.modified().ok()? < image_modified {
+            return None;
+        }
+        let mut file = File::open(tiles_path).ok()?;
+        let header = Header::read(&mut file)?;
+        (header.tile_size == tile_size && tiles.len() == header.file_bytes()).then_some(file)
+    }
+
+    /// Writes the tiled copy beside its final path and moves it into place once complete, so a
+    /// concurrent render never opens a partial one.
+    fn write_tiles(
+        image_path: &str,
+        tiles_path: &Path,
+        tile_size: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        let img = image::open(image_path)?.to_rgb8();
+        let (width, height) = img.dimensions();
+        let average = img
+            .pixels()
+            .map(|p| Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .sum::<Color>()
+            / (255.0 * width as f64 * height as f64);
+        let header = Header {
+            width,
+            height,
+            tile_size,
+            average,
+        };
+
+        let partial_path = tiles_path.with_extension(format!("partial{}", std::process::id()));
+        let mut out = BufWriter::new(File::create(&partial_path)?);
+        header.write(&mut out)?;
+        // edge tiles are padded by repeating the last row and column
+        for tile_y in 0..height.div_ceil(tile_size) {
+            for tile_x in 0..header.tiles_x() {
+                for y in 0..tile_size {
+                    let y = (tile_y * tile_size + y).min(height - 1);
+                    for x in 0..tile_size {
+                        let x = (tile_x * tile_size + x).min(width - 1);
+                        out.write_all(&img.get_pixel(x, y).0)?;
+                    }
+                }
+            }
+        }
+        out.flush()?;
+        drop(out);
+        fs::rename(&partial_path, tiles_path)?;
+        Ok(())
+    }
+
+    /// Reads tile `index` from disk with a free file handle, opening another if all are busy.
+    fn read_tile(&self, index: usize) -> std::io::Result<Vec<u8>> {
+        let pooled = self.files.lock().unwrap().pop();
+        let mut file = match pooled {
+            Some(file) => file,
+            None => File::open(&self.tiles_path)?,
+        };
+        let tile_bytes = self.header.tile_bytes();
+        let mut tile = vec![0; tile_bytes];
+        file.seek(SeekFrom::Start(HEADER_BYTES + (index * tile_bytes) as u64))?;
+        file.read_exact(&mut tile)?;
+        self.files.lock().unwrap().push(file);
+        Ok(tile)
+    }
+
+    /// The tile, from memory or else read from disk without holding any lock; `None` if the
+    /// read fails.
+    fn tile(&self, index: usize) -> Option<Arc<Vec<u8>>> {
+        let shard = &self.shards[index % self.shards.len()];
+        if let Some(tile) = shard.read().unwrap().get(index) {
+            return Some(tile);
+        }
+        let tile = Arc::new(self.read_tile(index).ok()?);
+        let mut shard = shard.write().unwrap();
+        // another thread may have read the same tile meanwhile
+        if let Some(tile) = shard.get(index) {
+            return Some(tile);
+        }
+        shard.insert(index, tile.clone(), self.shard_capacity);
+        Some(tile)
+    }
+
+    /// The pixel's color, or the image's average if its tile can't be read.
+    fn get_pixel(&self, x: usize, y: usize) -> Color {
+        let x = x.min(self.header.width as usize - 1);
+        let y = y.min(self.header.height as usize - 1);
+        let size = self.header.tile_size as usize;
+        let Some(tile) = self.tile((y / size) * self.header.tiles_x() as usize + x / size) else {
+            return self.header.average;
+        };
+        let index = 3 * ((y % size) * size + x % size);
+        let pixel = &tile[index..index + 3];
+        Color::new(
+            pixel[0] as f64 / 255.0,
+            pixel[1] as f64 / 255.0,
+            pixel[2] as f64 / 255.0,
+        )
+    }
+}
+
+impl Texture for TiledImageTex {
+    fn color_value(&self, context: &ShadingContext) -> Color {
+        filtered_texel(self.header.width, self.header.height, context, |x, y| {
+            self.get_pixel(x, y)
+        })
+    }
+
+    #[inline]
+    fn average_color(&self) -> Color {
+        self.header.average
+    }
+}
@@ -0,0 +1,267 @@
+use crate::vec3::Color;
+use std::{
+    fmt::Write,
+    panic,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    #[inline]
+    pub fn pixel_count(&self) -> u32 {
+        self.width * self.height
+    }
+
+    /// The tile cut in half along each axis, leaving out empty parts of tiles one pixel wide
+    /// or tall.
+    fn quarters(&self) -> impl Iterator<Item = Tile> {
+        let (left, top) = (self.width / 2, self.height / 2);
+        let columns = [(self.x, left), (self.x + left, self.width - left)];
+        let rows = [(self.y, top), (self.y + top, self.height - top)];
+        rows.into_iter()
+            .flat_map(move |(y, height)| {
+                columns.map(|(x, width)| Tile {
+                    x,
+                    y,
+                    width,
+                    height,
+                })
+            })
+            .filter(|tile| tile.pixel_count() > 0)
+    }
+
+    /// Image coordinates of every pixel in the tile, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |j| (self.x..self.x + self.width).map(move |i| (i, j)))
+    }
+}
+
+/// A tile's pixels as they stand once a pass over it is done, sent out while the rest of the
+/// image is still rendering.
+#[derive(Debug, Clone)]
+pub struct FinishedTile {
+    pub tile: Tile,
+    /// Samples per pixel taken so far; a tile is sent again after each pass that adds to them.
+    pub samples: u32,
+    /// Exposed linear radiance of each pixel, row by row.
+    pub colors: Vec<Color>,
+    pub alpha: Vec<f64>,
+}
+
+impl FinishedTile {
+    /// The tile as one line of JSON: its place and size, its samples per pixel, and its pixels
+    /// row by row as linear RGBA.
+    pub fn to_json(&self) -> String {
+        let Tile {
+            x,
+            y,
+            width,
+            height,
+        } = self.tile;
+        let mut json = format!(
+            "{{\"x\":{x},\"y\":{y},\"width\":{width},\"height\":{height},\"samples\":{},\"pixels\":[",
+            self.samples
+        );
+        for (index, (color, alpha)) in self.colors.iter().zip(&self.alpha).enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "[{:.6},{:.6},{:.6},{alpha:.6}]",
+                color.x(),
+                color.y(),
+                color.z()
+            )
+            .unwrap();
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// Sending end of a stream of tiles, handed to a camera so it passes on each tile as soon as a
+/// worker finishes it, for a viewer, a GUI or an incremental encoder to consume.
+#[derive(Debug, Clone)]
+pub struct TileSink(Sender<FinishedTile>);
+
+impl TileSink {
+    /// A sink and the receiver iterating over the tiles sent into it, which ends once every
+    /// camera holding the sink is dropped.
+    pub fn channel() -> (Self, Receiver<FinishedTile>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self(sender), receiver)
+    }
+
+    /// Passes `tile` on, dropping it if nothing is receiving anymore.
+    pub fn send(&self, tile: FinishedTile) {
+        let _ = self.0.send(tile);
+    }
+}
+
+/// Runs `render` with a sink while `consume` iterates over the tiles sent into it on a thread
+/// of its own, each as soon as it is done, so a slow consumer doesn't hold up the render. The
+/// stream ends once `render` returns, as long as it drops the sink and every camera holding it.
+pub fn stream<R, T: Send>(
+    render: impl FnOnce(TileSink) -> R,
+    consume: impl FnOnce(mpsc::IntoIter<FinishedTile>) -> T + Send,
+) -> (R, T) {
+    let (sink, tiles) = TileSink::channel();
+    thread::scope(|scope| {
+        let consumer = scope.spawn(move || consume(tiles.into_iter()));
+        let rendered = render(sink);
+        match consumer.join() {
+            Ok(consumed) => (rendered, consumed),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum TileOrder {
+    #[default]
+    Scanline,
+    Hilbert,
+    Morton,
+    Spiral,
+}
+
+// tiles are split while they cost more than an even share of this many tiles per worker
+const TILES_PER_WORKER: f64 = 4.0;
+const MIN_ADAPTIVE_SIZE: u32 = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tiling {
+    pub size: u32,
+    pub order: TileOrder,
+    /// Whether `refine` splits expensive tiles between passes.
+    pub adaptive: bool,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Self {
+            size: 32,
+            order: TileOrder::default(),
+            adaptive: false,
+        }
+    }
+}
+
+impl Tiling {
+    /// Splits a `width` x `height` image into tiles listed in traversal order.
+    pub fn tiles(&self, width: u32, height: u32) -> Vec<Tile> {
+        let size = self.size.max(1);
+        let columns = width.div_ceil(size);
+        let rows = height.div_ceil(size);
+
+        let mut grid: Vec<(u32, u32)> = (0..rows)
+            .flat_map(|ty| (0..columns).map(move |tx| (tx, ty)))
+            .collect();
+
+        match self.order {
+            TileOrder::Scanline => {}
+            TileOrder::Hilbert => {
+                let side = columns.max(rows).next_power_of_two();
+                grid.sort_by_key(|&(tx, ty)| hilbert_index(side, tx, ty));
+            }
+            TileOrder::Morton => grid.sort_by_key(|&(tx, ty)| morton_index(tx, ty)),
+            TileOrder::Spiral => {
+                let cx = (columns as f64 - 1.0) / 2.0;
+                let cy = (rows as f64 - 1.0) / 2.0;
+                grid.sort_by(|&a, &b| spiral_key(a, cx, cy).total_cmp(&spiral_key(b, cx, cy)));
+            }
+        }
+
+        grid.into_iter()
+            .map(|(tx, ty)| {
+                let (x, y) = (tx * size, ty * size);
+                Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Tiling {
+    /// Splits tiles costing more than an even share of the whole pass among `workers` so a
+    /// slow tile (glass, volumes) doesn't keep a single worker busy at the end of the pass.
+    /// Split tiles keep their place in the traversal order.
+    pub fn refine(&self, tiles: &[Tile], workers: usize, cost: impl Fn(&Tile) -> f64) -> Vec<Tile> {
+        if !self.adaptive {
+            return tiles.to_vec();
+        }
+        let total: f64 = tiles.iter().map(&cost).sum();
+        let share = total / (workers as f64 * TILES_PER_WORKER);
+        if share <= 0.0 {
+            return tiles.to_vec();
+        }
+
+        let mut refined = Vec::with_capacity(tiles.len());
+        let mut pending: Vec<Tile> = tiles.iter().rev().copied().collect();
+        while let Some(tile) = pending.pop() {
+            let small = tile.width.max(tile.height) < 2 * MIN_ADAPTIVE_SIZE;
+            if small || cost(&tile) <= share {
+                refined.push(tile);
+            } else {
+                let quarters: Vec<Tile> = tile.quarters().collect();
+                pending.extend(quarters.into_iter().rev());
+            }
+        }
+        refined
+    }
+}
+
+/// Distance along a Hilbert curve filling a `side` x `side` grid (`side` a power of two).
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut d = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+fn morton_index(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+        v = (v | (v << 16)) & 0x0000_ffff_0000_ffff;
+        v = (v | (v << 8)) & 0x00ff_00ff_00ff_00ff;
+        v = (v | (v << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+        v
+    }
+    spread(x) | (spread(y) << 1)
+}
+
+/// Orders tiles ring by ring outwards from the center, sweeping each ring by angle.
+fn spiral_key((tx, ty): (u32, u32), cx: f64, cy: f64) -> f64 {
+    let (dx, dy) = (tx as f64 - cx, ty as f64 - cy);
+    let ring = dx.abs().max(dy.abs()).round();
+    let angle = dy.atan2(dx) + std::f64::consts::PI;
+    ring * 10.0 + angle
+}
@@ -0,0 +1,103 @@
+use crate::{
+    bvh::BVHNode,
+    entity::{Entity, HitRecord},
+    epsilon,
+    interval::Interval,
+    ray::{Ray, RayKind},
+    sun::Sun,
+    vec3::{Color, Vec3},
+};
+use std::sync::Arc;
+
+/// Light the cel shading is lit by.
+#[derive(Debug, Clone)]
+pub enum KeyLight {
+    /// Light from a fixed direction, towards the light.
+    Direction(Vec3),
+    Entity(Arc<dyn Entity>),
+    Sun(Sun),
+    /// Light from the viewer, for scenes with nothing else to light them.
+    Headlight,
+}
+
+/// Ink lines where the surfaces seen through neighboring points of a pixel are far apart or
+/// meet at a crease.
+#[derive(Debug, Clone, Copy)]
+pub struct Outline {
+    /// Line width in pixels.
+    pub width: f64,
+    pub color: Color,
+    /// Cosine of the angle between neighboring normals above which they are inked as a crease.
+    pub crease_cos: f64,
+    /// Distance of a neighbor off the surface's plane, relative to the surface's distance from
+    /// the camera, above which it counts as a different surface.
+    pub depth_threshold: f64,
+}
+
+impl Outline {
+    /// Whether the hits of a camera ray and of a ray beside it lie across an edge.
+    pub fn separates(&self, center: Option<&HitRecord>, neighbor: Option<&HitRecord>) -> bool {
+        match (center, neighbor) {
+            (None, None) => false,
+            (Some(center), Some(neighbor)) => {
+                let gap = (neighbor.hit_point - center.hit_point)
+                    .dot(center.normal)
+                    .abs();
+                gap > self.depth_threshold * center.distance
+                    || center.normal.dot(neighbor.normal) < self.crease_cos
+            }
+            // silhouettes
+            _ => true,
+        }
+    }
+}
+
+/// Non-photorealistic shading for camera rays: the key light's diffuse term is quantized to
+/// the shades of `palette`, flat bands of color instead of smooth falloff, and edges are
+/// inked. Nothing is traced beyond the camera ray, the key light's shadow ray and the outline
+/// rays, so there are no reflections or indirect light.
+#[derive(Debug, Clone)]
+pub struct Toon {
+    /// Shades multiplying the surface color from unlit to fully lit, one band each.
+    pub palette: Vec<Color>,
+    pub outline: Option<Outline>,
+    pub key_light: KeyLight,
+}
+
+impl Toon {
+    /// Band of the palette for a diffuse term in [0, 1].
+    pub fn shade(&self, lit: f64) -> Color {
+        let bands = self.palette.len();
+        let band = ((lit * bands as f64) as usize).min(bands - 1);
+        self.palette[band]
+    }
+
+    /// Unshadowed fraction of the key light falling on the hit, from 0 to 1.
+    pub fn lit(&self, world: &BVHNode, hit_record: &HitRecord) -> f64 {
+        let (direction, end) = match &self.key_light {
+            KeyLight::Direction(direction) => (*direction, f64::INFINITY),
+            // the light's center, not points spread over it, keeps bands and shadows crisp
+            KeyLight::Entity(light) => match light.surface_point(0.5, 0.5) {
+                Some((center, _)) => (center - hit_record.hit_point, 1.0),
+                None => match light.sample_surface() {
+                    Some((sample, _)) => (sample.hit_point - hit_record.hit_point, 1.0),
+                    None => return 0.0,
+                },
+            },
+            KeyLight::Sun(sun) => (sun.sample().0, f64::INFINITY),
+            KeyLight::Headlight => return hit_record.normal.dot(hit_record.view).max(0.0),
+        };
+        let cosine = hit_record.normal.dot(direction.unit());
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        let ray =
+            Ray::new(hit_record.hit_point, direction, fastrand::f64()).with_kind(RayKind::Shadow);
+        let start = epsilon::ray_interval(&ray).start;
+        // tinted glass lets some of the light through, for a lighter band in its shadow
+        cosine
+            * world
+                .transmittance(&ray, Interval::new(start, end - start))
+                .luminance()
+    }
+}
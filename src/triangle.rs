@@ -0,0 +1,185 @@
+use crate::{
+    aabb::Aabb,
+    counters::{self, Test},
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Color, Point3, Vec3},
+};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    a: Point3,
+    e1: Vec3,
+    e2: Vec3,
+    normal: Vec3,
+    area: f64,
+    uvs: [(f64, f64); 3],
+    dp_du: Vec3,
+    dp_dv: Vec3,
+    material: Arc<dyn Material>,
+    bounding_box: Aabb,
+}
+
+impl Triangle {
+    /// Texture coordinates used when none are given, mapping the triangle onto the lower left
+    /// half of the unit square.
+    pub const DEFAULT_UVS: [(f64, f64); 3] = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+
+    /// The front side is the one the vertices `a`, `b`, `c` wind counterclockwise around;
+    /// `uvs` are their texture coordinates in the same order.
+    pub fn new(
+        a: Point3,
+        b: Point3,
+        c: Point3,
+        uvs: [(f64, f64); 3],
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let bounding_box =
+            Aabb::enclose(&Aabb::new_from_points(a, b), &Aabb::new_from_points(a, c));
+        let (e1, e2) = (b - a, c - a);
+        let n = e1.cross(e2);
+        let (dp_du, dp_dv) = surface_derivatives(e1, e2, uvs);
+        Self {
+            a,
+            e1,
+            e2,
+            normal: n.unit(),
+            area: 0.5 * n.length(),
+            uvs,
+            dp_du,
+            dp_dv,
+            material,
+            bounding_box,
+        }
+    }
+}
+
+/// Möller–Trumbore test of `ray` against the triangle at `a` spanned by edges `e1` and `e2`,
+/// giving the ray time and the barycentric coordinates towards the ends of `e1` and `e2`.
+pub fn intersect(
+    a: Point3,
+    e1: Vec3,
+    e2: Vec3,
+    ray: &Ray,
+    time_interval: Interval,
+) -> Option<(f64, f64, f64)> {
+    counters::count(Test::Triangle);
+    let direction = *ray.direction();
+    let p = direction.cross(e2);
+    let determinant = e1.dot(p);
+    // compare against the lengths involved rather than a fixed bound so the test doesn't
+    // depend on scene scale
+    let scale = direction.length_sq() * e1.length_sq() * e2.length_sq();
+    if determinant * determinant < 1e-12 * scale {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = *ray.origin() - a;
+    let beta = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&beta) {
+        return None;
+    }
+    let q = s.cross(e1);
+    let gamma = direction.dot(q) * inverse;
+    if gamma < 0.0 || beta + gamma > 1.0 {
+        return None;
+    }
+    let time = e2.dot(q) * inverse;
+    time_interval.contains(time).then_some((time, beta, gamma))
+}
+
+/// Derivatives of the surface point with respect to u and v, from inverting the map from UV
+/// offsets to the edges; the edges themselves if the UVs are degenerate.
+pub fn surface_derivatives(e1: Vec3, e2: Vec3, uvs: [(f64, f64); 3]) -> (Vec3, Vec3) {
+    let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+    let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+    let determinant = du1 * dv2 - dv1 * du2;
+    if determinant.abs() < 1e-12 {
+        return (e1, e2);
+    }
+    (
+        (dv2 * e1 - dv1 * e2) / determinant,
+        (du1 * e2 - du2 * e1) / determinant,
+    )
+}
+
+/// Texture coordinates at barycentric coordinates (`beta`, `gamma`) towards the second and
+/// third vertex.
+pub fn interpolate_uv(uvs: [(f64, f64); 3], beta: f64, gamma: f64) -> (f64, f64) {
+    let alpha = 1.0 - beta - gamma;
+    let [uv0, uv1, uv2] = uvs;
+    (
+        alpha * uv0.0 + beta * uv1.0 + gamma * uv2.0,
+        alpha * uv0.1 + beta * uv1.1 + gamma * uv2.1,
+    )
+}
+
+/// Distance from the point at (`beta`, `gamma`) to the nearest edge, given twice the area.
+pub fn edge_distance(e1: Vec3, e2: Vec3, twice_area: f64, beta: f64, gamma: f64) -> f64 {
+    // each barycentric coordinate times the matching height is the distance to that edge
+    let alpha = 1.0 - beta - gamma;
+    (alpha * twice_area / (e2 - e1).length())
+        .min(beta * twice_area / e2.length())
+        .min(gamma * twice_area / e1.length())
+}
+
+/// Uniformly distributed barycentric coordinates.
+pub fn sample_barycentric() -> (f64, f64) {
+    let (beta, gamma) = (fastrand::f64(), fastrand::f64());
+    // fold the far half of the parallelogram back onto the triangle
+    if beta + gamma > 1.0 {
+        (1.0 - beta, 1.0 - gamma)
+    } else {
+        (beta, gamma)
+    }
+}
+
+impl Entity for Triangle {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        let (time, beta, gamma) = intersect(self.a, self.e1, self.e2, ray, time_interval)?;
+        let (u, v) = interpolate_uv(self.uvs, beta, gamma);
+        let mut hit_record =
+            HitRecord::new(ray.at(time), ray, self.normal, time, u, v, &*self.material);
+        hit_record.set_tangent(self.dp_du);
+        hit_record.dp_du = self.dp_du;
+        hit_record.dp_dv = self.dp_dv;
+        hit_record.edge_distance = edge_distance(self.e1, self.e2, 2.0 * self.area, beta, gamma);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        let (beta, gamma) = sample_barycentric();
+        let point = self.a + beta * self.e1 + gamma * self.e2;
+        let (u, v) = interpolate_uv(self.uvs, beta, gamma);
+        let hit_record = HitRecord::raw(point, self.normal, 0.0, true, u, v, &*self.material);
+        Some((hit_record, self.area))
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        let [uv0, uv1, uv2] = self.uvs;
+        let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+        let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+        let determinant = du1 * dv2 - dv1 * du2;
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let (du, dv) = (u - uv0.0, v - uv0.1);
+        let beta = (du * dv2 - dv * du2) / determinant;
+        let gamma = (du1 * dv - dv1 * du) / determinant;
+        if beta < 0.0 || gamma < 0.0 || beta + gamma > 1.0 {
+            return None;
+        }
+        Some((self.a + beta * self.e1 + gamma * self.e2, self.normal))
+    }
+
+    fn emitted_power(&self) -> Color {
+        std::f64::consts::PI * self.area * self.material.emitted_power()
+    }
+}
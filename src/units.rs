@@ -0,0 +1,30 @@
+/// Length unit that scene or asset coordinates are given in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Unit {
+    #[default]
+    Meters,
+    Centimeters,
+    Millimeters,
+    Kilometers,
+    Inches,
+    Feet,
+}
+
+impl Unit {
+    /// Length of one unit in meters.
+    pub fn meters(self) -> f64 {
+        match self {
+            Unit::Meters => 1.0,
+            Unit::Centimeters => 0.01,
+            Unit::Millimeters => 0.001,
+            Unit::Kilometers => 1000.0,
+            Unit::Inches => 0.0254,
+            Unit::Feet => 0.3048,
+        }
+    }
+
+    /// Factor converting lengths in `self` to lengths in `target`.
+    pub fn to(self, target: Unit) -> f64 {
+        self.meters() / target.meters()
+    }
+}
@@ -105,6 +105,17 @@ impl Vec3 {
         )
     }
 
+    /// Two unit vectors that complete this unit vector into a right-handed orthonormal frame.
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        // Duff et al., "Building an Orthonormal Basis, Revisited"
+        let sign = 1.0_f64.copysign(self.2);
+        let a = -1.0 / (sign + self.2);
+        let b = self.0 * self.1 * a;
+        let tangent = Vec3::new(1.0 + sign * self.0 * self.0 * a, sign * b, -sign * self.0);
+        let bitangent = Vec3::new(b, sign + self.1 * self.1 * a, -self.1);
+        (tangent, bitangent)
+    }
+
     #[inline]
     pub fn near_zero(&self) -> bool {
         const T: f64 = 1e-6;
@@ -125,6 +136,25 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
 
+    /// Rec. 709 luminance of a linear color.
+    #[inline]
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
+
+    /// Pulls a color whose brightest channel passes 1 towards white by as much as it overshoots,
+    /// so that highlights which would clip keep rising in brightness and lose their hue instead
+    /// of flattening into pure primaries.
+    pub fn desaturate_highlights(self) -> Self {
+        let peak = self.0.max(self.1).max(self.2);
+        if peak <= 1.0 {
+            return self;
+        }
+        let hue = self / peak;
+        let white = 1.0 - 1.0 / peak;
+        hue + white * (Self(1.0, 1.0, 1.0) - hue)
+    }
+
     #[inline]
     fn linear_to_gamma(component: f64) -> f64 {
         component.sqrt().max(0.0)
@@ -0,0 +1,84 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    ray::{Ray, RayKind},
+    vec3::{Color, Point3, Vec3},
+};
+use std::sync::Arc;
+
+/// Kinds of rays an entity shows up to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility {
+    pub camera: bool,
+    pub diffuse: bool,
+    pub specular: bool,
+    pub shadow: bool,
+}
+
+impl Visibility {
+    #[inline]
+    pub fn sees(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Diffuse => self.diffuse,
+            RayKind::Specular => self.specular,
+            RayKind::Shadow => self.shadow,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self {
+            camera: true,
+            diffuse: true,
+            specular: true,
+            shadow: true,
+        }
+    }
+}
+
+/// Entity that only some kinds of rays hit, such as a simplified stand-in seen by global
+/// illumination alone while the detailed original is seen by the camera.
+#[derive(Debug, Clone)]
+pub struct Restricted {
+    entity: Arc<dyn Entity>,
+    visibility: Visibility,
+}
+
+impl Restricted {
+    pub fn new(entity: Arc<dyn Entity>, visibility: Visibility) -> Self {
+        Self { entity, visibility }
+    }
+}
+
+impl Entity for Restricted {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        if !self.visibility.sees(ray.kind()) {
+            return None;
+        }
+        self.entity.hit(ray, time_interval)
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.entity.bounding_box()
+    }
+
+    fn sample_surface(&self) -> Option<(HitRecord<'_>, f64)> {
+        self.entity.sample_surface()
+    }
+
+    fn surface_point(&self, u: f64, v: f64) -> Option<(Point3, Vec3)> {
+        self.entity.surface_point(u, v)
+    }
+
+    fn emitted_power(&self) -> Color {
+        self.entity.emitted_power()
+    }
+
+    fn density(&self, point: Point3, time: f64) -> f64 {
+        self.entity.density(point, time)
+    }
+}
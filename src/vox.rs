@@ -0,0 +1,123 @@
+use crate::vec3::Color;
+use std::{error::Error, fs};
+
+/// Dense grid of palette indices, 0 for empty cells, with the palette they index.
+#[derive(Debug, Clone)]
+pub struct VoxelData {
+    /// Cells along x, y and z, with y up.
+    pub size: [usize; 3],
+    /// Palette index of each cell, x fastest, then z, then y.
+    pub cells: Vec<u8>,
+    /// Colors by palette index; index 0 is unused.
+    pub palette: [Color; 256],
+}
+
+impl VoxelData {
+    #[inline]
+    pub fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        x + self.size[0] * (z + self.size[2] * y)
+    }
+}
+
+/// Reads the first model of a MagicaVoxel file. MagicaVoxel counts z upwards, so its (x, y, z)
+/// cell becomes (x, z, -y) here, keeping the model upright without mirroring it.
+pub fn load(path: &str) -> Result<VoxelData, Box<dyn Error>> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read '{}' : {}", path, e))?;
+    if bytes.get(..4) != Some(b"VOX ") {
+        return Err(format!("'{path}' is not a MagicaVoxel file").into());
+    }
+    let invalid = || format!("'{path}' has a truncated chunk");
+    let word = |offset: usize| -> Result<usize, String> {
+        let word = bytes.get(offset..offset + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(word.try_into().unwrap()) as usize)
+    };
+
+    let mut size = None;
+    let mut cells = None;
+    let mut palette = None;
+    // the MAIN chunk's children follow its 12 byte header, one after another
+    let mut offset = 8 + 12;
+    while offset + 12 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let content_size = word(offset + 4)?;
+        let content = offset + 12;
+        let end = content + content_size;
+        if end > bytes.len() {
+            return Err(invalid().into());
+        }
+        match id {
+            b"SIZE" if size.is_none() => {
+                size = Some([word(content)?, word(content + 4)?, word(content + 8)?]);
+            }
+            b"XYZI" if cells.is_none() => {
+                let [sx, sy, sz] = size.ok_or(format!("'{path}' has voxels before a size"))?;
+                let count = word(content)?;
+                let voxels = bytes
+                    .get(content + 4..content + 4 + 4 * count)
+                    .ok_or_else(invalid)?;
+                let mut grid = vec![0; sx * sy * sz];
+                for voxel in voxels.chunks_exact(4) {
+                    let [x, y, z, color] =
+                        [voxel[0], voxel[1], voxel[2], voxel[3]].map(usize::from);
+                    if x >= sx || y >= sy || z >= sz {
+                        return Err(format!("'{path}' has a voxel outside its model").into());
+                    }
+                    // (x, y, z) -> (x, z, sy - 1 - y), laid out x fastest, then z, then y
+                    grid[x + sx * ((sy - 1 - y) + sy * z)] = color as u8;
+                }
+                cells = Some(grid);
+            }
+            b"RGBA" => {
+                let colors = bytes.get(content..content + 4 * 256).ok_or_else(invalid)?;
+                let mut table = [Color::default(); 256];
+                // the chunk's i-th color is palette index i + 1
+                for (index, color) in colors.chunks_exact(4).take(255).enumerate() {
+                    table[index + 1] = rgb(color[0], color[1], color[2]);
+                }
+                palette = Some(table);
+            }
+            _ => {}
+        }
+        offset = end;
+    }
+
+    let [sx, sy, sz] = size.ok_or(format!("'{path}' has no model"))?;
+    if sx * sy * sz == 0 {
+        return Err(format!("'{path}' has an empty model").into());
+    }
+    let cells = cells.ok_or(format!("'{path}' has no voxels"))?;
+    Ok(VoxelData {
+        size: [sx, sz, sy],
+        cells,
+        palette: palette.unwrap_or_else(default_palette),
+    })
+}
+
+#[inline]
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::new(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+}
+
+/// MagicaVoxel's palette for files without one of their own: a 6×6×6 color cube from white
+/// down, without black, then ramps of red, green, blue and gray.
+fn default_palette() -> [Color; 256] {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let cube = CUBE.iter().flat_map(|&r| {
+        CUBE.iter()
+            .flat_map(move |&g| CUBE.iter().map(move |&b| rgb(r, g, b)))
+    });
+    let ramps = [
+        |level| rgb(level, 0, 0),
+        |level| rgb(0, level, 0),
+        |level| rgb(0, 0, level),
+        |level| rgb(level, level, level),
+    ]
+    .into_iter()
+    .flat_map(|ramp: fn(u8) -> Color| RAMP.into_iter().map(ramp));
+    let mut palette = [Color::default(); 256];
+    for (entry, color) in palette[1..].iter_mut().zip(cube.take(215).chain(ramps)) {
+        *entry = color;
+    }
+    palette
+}
@@ -0,0 +1,144 @@
+use crate::{
+    aabb::Aabb,
+    entity::{Entity, HitRecord},
+    interval::Interval,
+    material::Material,
+    memory::{self, Category},
+    ray::Ray,
+    vec3::{Point3, Vec3},
+    vox::VoxelData,
+};
+use std::sync::Arc;
+
+/// Grid of solid cubes colored from a palette, such as a MagicaVoxel model, traced cell by
+/// cell along the ray with a 3D DDA rather than through a BVH. Each cube's palette color is
+/// passed on as the hit's vertex color. The grid stands on the origin, centered across x
+/// and z.
+#[derive(Debug)]
+pub struct VoxelGrid {
+    data: VoxelData,
+    /// Edge length of one cell.
+    voxel_size: f64,
+    min: Point3,
+    bounding_box: Aabb,
+    material: Arc<dyn Material>,
+}
+
+impl VoxelGrid {
+    /// Builds the grid with cells `voxel_size` across, which must be positive.
+    pub fn new(data: VoxelData, voxel_size: f64, material: Arc<dyn Material>) -> Self {
+        let [sx, sy, sz] = data.size.map(|cells| cells as f64 * voxel_size);
+        let min = Point3::new(-0.5 * sx, 0.0, -0.5 * sz);
+        let bounding_box = Aabb::new_from_points(min, min + Vec3::new(sx, sy, sz));
+        memory::track(Category::Meshes, data.cells.len());
+        Self {
+            data,
+            voxel_size,
+            min,
+            bounding_box,
+            material,
+        }
+    }
+
+    #[inline]
+    fn cell(&self, cell: [usize; 3]) -> u8 {
+        self.data.cells[self.data.index(cell)]
+    }
+}
+
+impl Entity for VoxelGrid {
+    fn hit(&self, ray: &Ray, time_interval: Interval) -> Option<HitRecord<'_>> {
+        // in cell units the grid spans [0, size] on each axis; times stay the same
+        let origin = (*ray.origin() - self.min) / self.voxel_size;
+        let direction = *ray.direction() / self.voxel_size;
+        let size = self.data.size;
+
+        // the face the ray enters the grid through, if it starts outside it
+        let (mut start, mut end) = (time_interval.start, time_interval.end);
+        let mut entry_axis = None;
+        for axis in 0..3 {
+            let inverse = 1.0 / direction[axis as u8];
+            let mut t0 = -origin[axis as u8] * inverse;
+            let mut t1 = (size[axis] as f64 - origin[axis as u8]) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > start {
+                start = t0;
+                entry_axis = Some(axis);
+            }
+            end = end.min(t1);
+            if end <= start {
+                return None;
+            }
+        }
+
+        let step = [0, 1, 2].map(|axis: u8| if direction[axis] < 0.0 { -1 } else { 1 });
+        let entry = origin + start * direction;
+        let mut cell = [0, 1, 2].map(|axis| {
+            let last = size[axis] - 1;
+            match entry_axis {
+                // rounding could put the entry point just outside the face it crossed
+                Some(entered) if entered == axis => {
+                    if step[axis] > 0 {
+                        0
+                    } else {
+                        last
+                    }
+                }
+                _ => (entry[axis as u8].floor().max(0.0) as usize).min(last),
+            }
+        });
+        let delta = [0, 1, 2].map(|axis: u8| direction[axis].abs().recip());
+        let mut next = [0, 1, 2].map(|axis| {
+            let boundary = cell[axis] as f64 + if step[axis] > 0 { 1.0 } else { 0.0 };
+            let t = (boundary - origin[axis as u8]) / direction[axis as u8];
+            if t.is_finite() {
+                t
+            } else {
+                f64::INFINITY
+            }
+        });
+
+        let mut time = start;
+        let mut axis = entry_axis;
+        loop {
+            let color = self.cell(cell);
+            // a ray starting inside a solid cell is leaving its surface, so passes out of it
+            if let Some(crossed) = axis.filter(|_| color != 0) {
+                let mut normal = [0.0; 3];
+                normal[crossed] = -step[crossed] as f64;
+                let point = ray.at(time);
+                let local = origin + time * direction;
+                let [a, b] = [(crossed + 1) % 3, (crossed + 2) % 3]
+                    .map(|other| local[other as u8] - cell[other] as f64);
+                let mut hit_record =
+                    HitRecord::new(point, ray, Vec3::from(normal), time, a, b, &*self.material);
+                hit_record.vertex_color = Some(self.data.palette[color as usize]);
+                hit_record.edge_distance =
+                    self.voxel_size * a.min(1.0 - a).min(b).min(1.0 - b).max(0.0);
+                return Some(hit_record);
+            }
+
+            let crossed = (0..3)
+                .min_by(|&i, &j| next[i].total_cmp(&next[j]))
+                .expect("three axes");
+            if next[crossed] > end {
+                return None;
+            }
+            time = next[crossed];
+            let moved = cell[crossed] as isize + step[crossed];
+            if moved < 0 || moved >= size[crossed] as isize {
+                return None;
+            }
+            cell[crossed] = moved as usize;
+            next[crossed] += delta[crossed];
+            axis = Some(crossed);
+        }
+    }
+
+    #[inline]
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+}
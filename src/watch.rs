@@ -0,0 +1,26 @@
+use std::{
+    fs, thread,
+    time::{Duration, SystemTime},
+};
+
+// how often a watched file's modification time is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// When the file at `path` was last modified, `None` if that can't be read.
+pub fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Blocks until the file at `path` has a modification time other than `since`, and returns
+/// the new one.
+pub fn wait_for_change(path: &str, since: Option<SystemTime>) -> Option<SystemTime> {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = modified(path);
+        if current.is_some() && current != since {
+            return current;
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use crate::{
+    bvh::BVHNode,
+    camera::{self, Camera},
+    entity::HitRecord,
+    film::Film,
+    progress,
+    ray::{MediumStack, Ray, RayDifferentials, RayKind},
+    tile::Tile,
+    vec3::{Color, Point3, Vec3},
+};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use std::time::Instant;
+
+// upper bound on the number of rays kept in flight per wave
+const BATCH_SIZE: usize = 1 << 18;
+
+/// Structure-of-arrays storage for a wave of rays, each lane tagged with the pixel it contributes to.
+#[derive(Debug, Clone, Default)]
+struct RayBatch {
+    origins: Vec<Point3>,
+    directions: Vec<Vec3>,
+    times: Vec<f64>,
+    throughputs: Vec<Color>,
+    differentials: Vec<Option<RayDifferentials>>,
+    media: Vec<MediumStack>,
+    kinds: Vec<RayKind>,
+    /// Linked lights the surface each ray left doesn't receive.
+    unlinked: Vec<u64>,
+    /// Bounces each path has left, counting the one its ray leads to.
+    depths: Vec<u16>,
+    /// Whether a material has already granted each path extra bounces.
+    extended: Vec<bool>,
+    pixels: Vec<u32>,
+}
+
+impl RayBatch {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            origins: Vec::with_capacity(capacity),
+            directions: Vec::with_capacity(capacity),
+            times: Vec::with_capacity(capacity),
+            throughputs: Vec::with_capacity(capacity),
+            differentials: Vec::with_capacity(capacity),
+            media: Vec::with_capacity(capacity),
+            kinds: Vec::with_capacity(capacity),
+            unlinked: Vec::with_capacity(capacity),
+            depths: Vec::with_capacity(capacity),
+            extended: Vec::with_capacity(capacity),
+            pixels: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.origins.clear();
+        self.directions.clear();
+        self.times.clear();
+        self.throughputs.clear();
+        self.differentials.clear();
+        self.media.clear();
+        self.kinds.clear();
+        self.unlinked.clear();
+        self.depths.clear();
+        self.extended.clear();
+        self.pixels.clear();
+    }
+
+    fn push(&mut self, path: &Path, pixel: u32) {
+        let ray = &path.ray;
+        self.origins.push(*ray.origin());
+        self.directions.push(*ray.direction());
+        self.times.push(*ray.time());
+        self.throughputs.push(path.throughput);
+        self.differentials.push(ray.differentials().copied());
+        self.media.push(ray.media());
+        self.kinds.push(ray.kind());
+        self.unlinked.push(path.unlinked);
+        self.depths.push(path.depth);
+        self.extended.push(path.extended);
+        self.pixels.push(pixel);
+    }
+
+    #[inline]
+    fn ray(&self, index: usize) -> Ray {
+        Ray::new(
+            self.origins[index],
+            self.directions[index],
+            self.times[index],
+        )
+        .with_differentials(self.differentials[index])
+        .with_media(self.media[index])
+        .with_kind(self.kinds[index])
+    }
+}
+
+/// A ray about to be traced, with the state of the path it continues; mirrors the recursive
+/// renderer's `PathState`.
+struct Path {
+    ray: Ray,
+    throughput: Color,
+    unlinked: u64,
+    depth: u16,
+    extended: bool,
+}
+
+/// Result of shading one lane: radiance to splat and the rays continuing the path, several
+/// where a camera ray hits a material that splits.
+struct Shaded {
+    radiance: Color,
+    next: Vec<Path>,
+}
+
+/// Fills `batch` with one camera ray per pixel and returns their filter weights.
+fn generate(camera: &Camera, pixels: std::ops::Range<u32>, batch: &mut RayBatch) -> Vec<f64> {
+    let width = camera.image_width();
+    pixels
+        .map(|pixel| {
+            let (ray, weight) = camera.get_ray(pixel % width, pixel / width);
+            if let Some((ray, transmission)) = ray.filter(|_| camera.max_depth() > 0) {
+                let path = Path {
+                    ray,
+                    throughput: Color::new(1.0, 1.0, 1.0) * transmission,
+                    unlinked: 0,
+                    depth: camera.max_depth(),
+                    extended: false,
+                };
+                batch.push(&path, pixel);
+            }
+            weight
+        })
+        .collect()
+}
+
+fn intersect<'a>(
+    camera: &'a Camera,
+    batch: &RayBatch,
+    world: &'a BVHNode,
+    primary: bool,
+) -> Vec<Option<HitRecord<'a>>> {
+    (0..batch.len())
+        .into_par_iter()
+        .map(|i| camera.trace(world, &batch.ray(i), primary))
+        .collect()
+}
+
+fn shade(
+    camera: &Camera,
+    world: &BVHNode,
+    batch: &RayBatch,
+    hits: Vec<Option<HitRecord>>,
+    interaction: u16,
+) -> Vec<Shaded> {
+    hits.into_par_iter()
+        .enumerate()
+        .map(|(i, mut hit)| {
+            let throughput = batch.throughputs[i];
+            let ray = batch.ray(i);
+            if let Some(hit_record) = hit.as_mut() {
+                hit_record.apply_differentials(&ray);
+                hit_record.set_view(&ray);
+            }
+            if interaction == 1 {
+                let color = camera
+                    .wireframe_color(world, &ray, hit.as_ref())
+                    .or_else(|| camera.toon_color(world, &ray, hit.as_ref()));
+                if let Some(color) = color {
+                    return Shaded {
+                        radiance: throughput * color,
+                        next: Vec::new(),
+                    };
+                }
+            }
+            match hit {
+                Some(hit_record) => {
+                    let emitted = if hit_record.material.light_link() & batch.unlinked[i] == 0 {
+                        hit_record
+                            .material
+                            .emit_towards(&hit_record, -ray.direction().unit())
+                    } else {
+                        Color::default()
+                    };
+                    if interaction == 1 {
+                        if let Some(reflected) = camera.cached_diffuse(world, &hit_record, None) {
+                            return Shaded {
+                                radiance: throughput * (emitted + reflected),
+                                next: Vec::new(),
+                            };
+                        }
+                    }
+                    let material = hit_record.material;
+                    // extra depth is granted once per path, splits only at camera hits
+                    let extra = if batch.extended[i] {
+                        0
+                    } else {
+                        material.extra_depth()
+                    };
+                    let depth = (batch.depths[i] - 1).saturating_add(extra);
+                    let splits = match interaction {
+                        _ if depth == 0 => 0,
+                        1 => material.splitting().max(1),
+                        _ => 1,
+                    };
+                    let next = (0..splits)
+                        .filter_map(|_| {
+                            let reflected = material.scatter(&ray, &hit_record)?;
+                            let throughput = throughput * reflected.attenuation / f64::from(splits);
+                            let scale = camera.integrator().roulette(interaction, throughput)?;
+                            Some(Path {
+                                ray: reflected.scattered,
+                                throughput: scale * throughput,
+                                unlinked: material.unlinked_lights(),
+                                depth,
+                                extended: batch.extended[i] || extra > 0,
+                            })
+                        })
+                        .collect();
+                    Shaded {
+                        radiance: throughput * emitted,
+                        next,
+                    }
+                }
+                None => {
+                    let background = if interaction == 1 {
+                        camera.primary_background(&ray).unwrap_or_default()
+                    } else {
+                        camera.background(&ray)
+                    };
+                    Shaded {
+                        radiance: throughput * background,
+                        next: Vec::new(),
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Adds one sample per pixel in `pixels` to `film` by advancing a whole wave of paths bounce by bounce.
+fn trace_wave(
+    camera: &Camera,
+    world: &BVHNode,
+    pixels: std::ops::Range<u32>,
+    film: &mut Film,
+    current: &mut RayBatch,
+    next: &mut RayBatch,
+) {
+    let first_pixel = pixels.start;
+    let mut path_radiance = vec![Color::default(); pixels.len()];
+    let mut coverage = vec![1.0; pixels.len()];
+
+    current.clear();
+    let weights = generate(camera, pixels, current);
+
+    // paths end on their own depth, which materials may extend past the camera's
+    let mut interaction = 0;
+    while !current.is_empty() {
+        interaction += 1;
+
+        let hits = intersect(camera, current, world, interaction == 1);
+        if interaction == 1 {
+            for (&pixel, hit) in current.pixels.iter().zip(&hits) {
+                coverage[(pixel - first_pixel) as usize] = camera.coverage(hit.is_some());
+            }
+        }
+        let shaded = shade(camera, world, current, hits, interaction);
+
+        next.clear();
+        for (i, lane) in shaded.into_iter().enumerate() {
+            let pixel = current.pixels[i];
+            // cost is counted in rays traced, since a wave has no per-pixel timing
+            film.add_cost(pixel as usize, 1.0);
+            path_radiance[(pixel - first_pixel) as usize] += lane.radiance;
+            for path in &lane.next {
+                next.push(path, pixel);
+            }
+        }
+        std::mem::swap(current, next);
+    }
+
+    let samples = path_radiance.into_iter().zip(coverage).zip(weights);
+    for (offset, ((radiance, coverage), weight)) in samples.enumerate() {
+        let radiance = camera.integrator().clamp(radiance);
+        film.add_sample(first_pixel as usize + offset, radiance, coverage, weight);
+    }
+}
+
+pub fn render_image(camera: &Camera, world: &BVHNode, film: &mut Film, deadline: Option<Instant>) {
+    let pixel_count = camera.image_width() * camera.image_height();
+    let samples_per_pixel = camera.samples_per_pixel();
+
+    let progress_bar = progress::render_progress_bar(samples_per_pixel as u64);
+
+    let batch_pixels = BATCH_SIZE.min(pixel_count as usize) as u32;
+    let mut current = RayBatch::with_capacity(batch_pixels as usize);
+    let mut next = RayBatch::with_capacity(batch_pixels as usize);
+
+    for sample in 0..samples_per_pixel {
+        // the first sample always completes so no pixel is left without samples
+        if sample > 0 && camera::past(deadline) {
+            break;
+        }
+        let mut start = 0;
+        while start < pixel_count {
+            let end = pixel_count.min(start + batch_pixels);
+            trace_wave(camera, world, start..end, film, &mut current, &mut next);
+            start = end;
+        }
+        progress::set_noise(
+            film.mean_std_error(),
+            sample as u32 + 1,
+            samples_per_pixel as u32,
+        );
+        // every pixel is sampled at once, so the whole image is the one tile there is
+        if let Some(sink) = camera.tile_sink() {
+            let image = Tile {
+                x: 0,
+                y: 0,
+                width: camera.image_width(),
+                height: camera.image_height(),
+            };
+            sink.send(film.finished_tile(&image, None, sample as u32 + 1));
+        }
+        progress_bar.inc(1);
+    }
+    progress_bar.finish();
+}
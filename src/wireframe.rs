@@ -0,0 +1,27 @@
+use crate::{entity::HitRecord, vec3::Color};
+
+#[derive(Debug, Clone, Copy)]
+pub enum WireframeMode {
+    /// Draws edges over the normally shaded image.
+    Overlay,
+    /// Draws only edges, seen through all surfaces, over the background.
+    Only,
+}
+
+/// Debug view of primitive edges, for checking geometry placement and topology.
+#[derive(Debug, Clone, Copy)]
+pub struct Wireframe {
+    pub mode: WireframeMode,
+    /// Line width in pixels.
+    pub width: f64,
+    pub color: Color,
+}
+
+impl Wireframe {
+    /// Whether the hit lies within half a line width of an edge, using the pixel footprint
+    /// from the ray's differentials.
+    pub fn is_edge(&self, hit_record: &HitRecord) -> bool {
+        let pixel = hit_record.dp_dx.length().max(hit_record.dp_dy.length());
+        hit_record.edge_distance <= 0.5 * self.width * pixel
+    }
+}
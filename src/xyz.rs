@@ -0,0 +1,65 @@
+use crate::{
+    mesh::MeshData,
+    vec3::{Color, Point3, Vec3},
+};
+use std::{error::Error, fs};
+
+/// Reads an XYZ point list, one point per line as `x y z`, optionally followed by `r g b` and
+/// then `nx ny nz`. Colors are taken as 0 to 255 if any channel in the file is above 1. Blank
+/// lines and lines starting with `#` or `//` are skipped.
+pub fn load(path: &str) -> Result<MeshData, Box<dyn Error>> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read '{}' : {}", path, e))?;
+    let mut data = MeshData::default();
+    // every point has the values the first one has
+    let mut columns = None;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let values = line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|value| !value.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| format!("'{path}' : Invalid number on line {}", number + 1))?;
+        let triple = |i: usize| [values[i], values[i + 1], values[i + 2]];
+        let (color, normal) = match values.len() {
+            3 => (None, None),
+            6 => (Some(triple(3)), None),
+            9 => (Some(triple(3)), Some(triple(6))),
+            count => {
+                return Err(format!(
+                    "'{path}' : Line {} has {count} values, expected 3, 6 or 9",
+                    number + 1
+                )
+                .into())
+            }
+        };
+        if *columns.get_or_insert(values.len()) != values.len() {
+            return Err(format!(
+                "'{path}' : Line {} differs from the first point",
+                number + 1
+            )
+            .into());
+        }
+        data.positions.push(Point3::from(triple(0)));
+        if let Some(color) = color {
+            data.colors.push(Color::from(color));
+        }
+        if let Some(normal) = normal {
+            data.normals.push(Vec3::from(normal));
+        }
+    }
+    let bytes = data
+        .colors
+        .iter()
+        .any(|color| color.x().max(color.y()).max(color.z()) > 1.0);
+    if bytes {
+        for color in &mut data.colors {
+            *color /= 255.0;
+        }
+    }
+    Ok(data)
+}
@@ -0,0 +1,83 @@
+//! Intersection test counts, which only change when the BVH or a primitive does more or less
+//! work. Counts are global, so every assertion on them lives in this one test.
+
+#![cfg(feature = "instrumentation")]
+
+use raytracer::{
+    bvh::BVHNode,
+    counters::{self, Test},
+    entity::{Entity, EntityCluster},
+    interval::Interval,
+    material::Lambertian,
+    ray::Ray,
+    sphere::Sphere,
+    texture::Solid,
+    vec3::{Point3, Vec3},
+};
+use std::sync::Arc;
+
+const GRID: usize = 16;
+
+/// A flat grid of small spheres facing +z, one unit apart.
+fn spheres() -> Vec<Arc<dyn Entity>> {
+    let material = Arc::new(Lambertian::new(Arc::new(Solid::new(0.5, 0.5, 0.5))));
+    (0..GRID * GRID)
+        .map(|i| {
+            let center = Point3::new((i % GRID) as f64, (i / GRID) as f64, 0.0);
+            Arc::new(Sphere::stationary(center, 0.25, material.clone())) as Arc<dyn Entity>
+        })
+        .collect()
+}
+
+/// Rays straight down -z at the center of every sphere, and as many between them.
+fn rays() -> Vec<Ray> {
+    (0..GRID * GRID)
+        .flat_map(|i| {
+            let (x, y) = ((i % GRID) as f64, (i / GRID) as f64);
+            [0.0, 0.5].map(|offset| {
+                Ray::new(
+                    Point3::new(x + offset, y + offset, 10.0),
+                    Vec3::new(0.0, 0.0, -1.0),
+                    0.0,
+                )
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn bvh_tests_fewer_spheres_than_brute_force() {
+    let mut entities = spheres();
+    let rays = rays();
+    let interval = Interval::new(0.001, f64::INFINITY);
+
+    // a cluster tests each of its entities in turn
+    let mut cluster = EntityCluster::new();
+    for entity in &entities {
+        cluster.push(entity.clone());
+    }
+    counters::reset();
+    let brute_force_hits = rays
+        .iter()
+        .filter(|ray| cluster.hit(ray, interval).is_some())
+        .count();
+    let brute_force = counters::total(Test::Sphere);
+    assert_eq!(brute_force, (rays.len() * entities.len()) as u64);
+
+    let bvh = BVHNode::new(&mut entities);
+    counters::reset();
+    let bvh_hits = rays
+        .iter()
+        .filter(|ray| bvh.hit(ray, interval).is_some())
+        .count();
+    let with_bvh = counters::total(Test::Sphere);
+
+    assert_eq!(bvh_hits, brute_force_hits);
+    assert_eq!(bvh_hits, GRID * GRID);
+    // each ray reaches at most a few leaves of the grid, where brute force tests every sphere
+    assert!(
+        with_bvh * 16 < brute_force,
+        "BVH ran {with_bvh} sphere tests, brute force {brute_force}"
+    );
+    assert!(counters::total(Test::Aabb) > 0);
+}
@@ -0,0 +1,59 @@
+//! Streaming a render's tiles through the library, as a viewer or encoder would.
+
+use raytracer::{progress, scene, tile};
+use std::{collections::HashSet, fs};
+
+const SCENE: &str = r#"
+[[entity]]
+variant = "Sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material.variant = "Lambertian"
+material.texture.variant = "SolidColor"
+material.texture.color = [0.5, 0.5, 0.5]
+
+[camera]
+aspect_ratio = 1.5
+image_width = 24
+samples_per_pixel = 3
+max_depth = 4
+vertical_fov = 40.0
+look_from = [0.0, 0.0, 5.0]
+look_at = [0.0, 0.0, 0.0]
+view_up = [0.0, 1.0, 0.0]
+defocus_angle = 0.0
+focus_distance = 5.0
+background = [0.7, 0.8, 1.0]
+"#;
+
+#[test]
+fn streams_every_pixel_of_a_tiny_render() {
+    progress::set_quiet(true);
+    let path = std::env::temp_dir().join(format!("stream_{}.toml", std::process::id()));
+    fs::write(&path, SCENE).unwrap();
+    let scene = scene::create(path.to_str().unwrap(), &scene::Overrides::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let (film, tiles) = tile::stream(
+        |sink| {
+            let camera = scene.camera.clone().with_tile_sink(Some(sink));
+            camera.render_film(&scene.world, &scene.name, None).unwrap()
+        },
+        |tiles| tiles.collect::<Vec<_>>(),
+    );
+
+    let samples = scene.camera.samples_per_pixel() as u32;
+    assert_eq!(film.min_samples(), samples);
+    let mut finished = HashSet::new();
+    for finished_tile in &tiles {
+        let pixels = finished_tile.tile.pixel_count() as usize;
+        assert_eq!(finished_tile.colors.len(), pixels);
+        assert_eq!(finished_tile.alpha.len(), pixels);
+        assert!((1..=samples).contains(&finished_tile.samples));
+        if finished_tile.samples == samples {
+            finished.extend(finished_tile.tile.pixels());
+        }
+    }
+    // every pixel is sent once all its samples are in
+    assert_eq!(finished.len(), (film.width() * film.height()) as usize);
+}